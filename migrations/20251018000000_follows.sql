@@ -0,0 +1,18 @@
+CREATE TABLE IF NOT EXISTS follows
+(
+    id          TEXT        NOT NULL PRIMARY KEY,
+    user_id     TEXT        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind        TEXT        NOT NULL,
+    value       TEXT        NOT NULL,
+    created_at  DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS follows_user_kind_value_idx ON follows (user_id, kind, value);
+
+CREATE TABLE IF NOT EXISTS follow_notification_settings
+(
+    user_id     TEXT        NOT NULL PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    webhook_url TEXT,
+    email       BOOLEAN     NOT NULL DEFAULT FALSE,
+    updated_at  DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,51 @@
+CREATE TABLE IF NOT EXISTS stream_categories
+(
+    slug        TEXT        NOT NULL PRIMARY KEY,
+    name        TEXT        NOT NULL,
+    icon        TEXT,
+    sort_order  INTEGER     NOT NULL DEFAULT 0
+);
+
+-- raw category names as each provider sends them, a null slug is one nobody has mapped yet
+CREATE TABLE IF NOT EXISTS stream_category_aliases
+(
+    provider        TEXT        NOT NULL,
+    raw_name        TEXT        NOT NULL,
+    category_slug   TEXT        REFERENCES stream_categories (slug) ON DELETE SET NULL,
+    first_seen_at   DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (provider, raw_name)
+);
+
+INSERT INTO stream_categories (slug, name, icon, sort_order)
+VALUES ('basketball', 'Basketball', '🏀', 10),
+       ('american-football', 'American Football', '🏈', 20),
+       ('football', 'Football', '⚽', 30),
+       ('ice-hockey', 'Ice Hockey', '🏒', 40),
+       ('baseball', 'Baseball', '⚾', 50),
+       ('combat-sports', 'Combat Sports', '🥊', 60),
+       ('wrestling', 'Wrestling', '🤼', 70),
+       ('motorsport', 'Motorsport', '🏎️', 80),
+       ('tennis', 'Tennis', '🎾', 90),
+       ('golf', 'Golf', '⛳', 100),
+       ('cricket', 'Cricket', '🏏', 110),
+       ('rugby', 'Rugby', '🏉', 120),
+       ('darts', 'Darts', '🎯', 130),
+       ('other', 'Other', '📺', 1000)
+ON CONFLICT DO NOTHING;
+
+INSERT INTO stream_category_aliases (provider, raw_name, category_slug)
+VALUES ('ppvsu', 'NBA', 'basketball'),
+       ('ppvsu', 'NFL', 'american-football'),
+       ('ppvsu', 'Soccer', 'football'),
+       ('ppvsu', 'Hockey', 'ice-hockey'),
+       ('ppvsu', 'NHL', 'ice-hockey'),
+       ('ppvsu', 'MLB', 'baseball'),
+       ('ppvsu', 'UFC', 'combat-sports'),
+       ('ppvsu', 'Boxing', 'combat-sports'),
+       ('ppvsu', 'MMA', 'combat-sports'),
+       ('ppvsu', 'WWE', 'wrestling'),
+       ('ppvsu', 'Motorsports', 'motorsport'),
+       ('ppvsu', 'Formula 1', 'motorsport'),
+       ('ppvsu', '24/7 Streams', 'other'),
+       ('ppvsu', 'Unknown', 'other')
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,20 @@
+-- every game a provider refresh has ever cached, kept after redis lets go of it
+CREATE TABLE IF NOT EXISTS games_archive
+(
+    provider        TEXT        NOT NULL,
+    game_id         BIGINT      NOT NULL,
+    name            TEXT        NOT NULL,
+    poster          TEXT        NOT NULL DEFAULT '',
+    category        TEXT        NOT NULL,
+    category_slug   TEXT        NOT NULL,
+    start_time      BIGINT      NOT NULL,
+    end_time        BIGINT      NOT NULL,
+    sources         TEXT        NOT NULL DEFAULT '[]',
+    status          TEXT        NOT NULL,
+    first_seen_at   BIGINT      NOT NULL,
+    last_seen_at    BIGINT      NOT NULL,
+    PRIMARY KEY (provider, game_id)
+);
+
+CREATE INDEX IF NOT EXISTS games_archive_start_time_idx ON games_archive (start_time);
+CREATE INDEX IF NOT EXISTS games_archive_category_slug_idx ON games_archive (category_slug, start_time);
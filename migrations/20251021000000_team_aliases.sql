@@ -0,0 +1,23 @@
+-- other names teams go by, `alias` is lowercase and `team` is the name we show
+CREATE TABLE IF NOT EXISTS team_aliases
+(
+    alias       TEXT        NOT NULL PRIMARY KEY,
+    team        TEXT        NOT NULL
+);
+
+INSERT INTO team_aliases (alias, team)
+VALUES ('la lakers', 'Los Angeles Lakers'),
+       ('lakers', 'Los Angeles Lakers'),
+       ('la clippers', 'Los Angeles Clippers'),
+       ('ny knicks', 'New York Knicks'),
+       ('gs warriors', 'Golden State Warriors'),
+       ('man utd', 'Manchester United'),
+       ('man united', 'Manchester United'),
+       ('man city', 'Manchester City'),
+       ('spurs', 'Tottenham Hotspur'),
+       ('psg', 'Paris Saint-Germain'),
+       ('inter', 'Inter Milan'),
+       ('barca', 'Barcelona'),
+       ('ny yankees', 'New York Yankees'),
+       ('la dodgers', 'Los Angeles Dodgers')
+ON CONFLICT DO NOTHING;
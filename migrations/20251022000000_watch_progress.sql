@@ -0,0 +1,14 @@
+-- where each user got to in a movie or an episode, `episode` is empty for movies
+CREATE TABLE IF NOT EXISTS watch_progress
+(
+    user_id         TEXT        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    media_id        TEXT        NOT NULL,
+    episode         TEXT        NOT NULL DEFAULT '',
+    position_secs   REAL        NOT NULL DEFAULT 0,
+    duration_secs   REAL,
+    watched         BOOLEAN     NOT NULL DEFAULT FALSE,
+    updated_at      DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, media_id, episode)
+);
+
+CREATE INDEX IF NOT EXISTS watch_progress_user_updated_idx ON watch_progress (user_id, updated_at);
@@ -0,0 +1,14 @@
+-- movies and shows a user wants to get around to, `position` is theirs to sort by
+CREATE TABLE IF NOT EXISTS watchlist
+(
+    id          TEXT        NOT NULL PRIMARY KEY,
+    user_id     TEXT        NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind        TEXT        NOT NULL,
+    media_id    TEXT        NOT NULL,
+    note        TEXT,
+    position    INTEGER     NOT NULL DEFAULT 0,
+    created_at  DATETIME    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, kind, media_id)
+);
+
+CREATE INDEX IF NOT EXISTS watchlist_user_position_idx ON watchlist (user_id, position);
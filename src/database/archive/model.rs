@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mockall::automock;
+use sqlx::FromRow;
+
+use crate::database::stream::{Game, GameSource};
+
+/// a game as it was the last time a refresh saw it. times are unix seconds, `sources` is the json
+/// of every source ever seen for it
+#[derive(FromRow, Debug, Clone)]
+pub struct ArchivedGame {
+    pub provider: String,
+    pub game_id: i64,
+    pub name: String,
+    pub poster: String,
+    pub category: String,
+    pub category_slug: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    pub sources: String,
+    /// upcoming, live or ended while upstream lists it, then ended or removed (pulled before it
+    /// was over) once it's gone
+    pub status: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+impl ArchivedGame {
+    pub fn sources(&self) -> Vec<GameSource> {
+        serde_json::from_str(&self.sources).unwrap_or_default()
+    }
+}
+
+/// what to pull out of the archive, everything is optional apart from the page
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArchiveFilter {
+    pub provider: Option<String>,
+    pub category_slug: Option<String>,
+    /// games starting at or after this unix time
+    pub from: Option<i64>,
+    /// games starting at or before this unix time
+    pub to: Option<i64>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(FromRow, Debug, Clone)]
+pub struct ArchivedCategoryCount {
+    pub category_slug: String,
+    pub category: String,
+    pub games: i64,
+}
+
+/// the fresh sources in their order, plus any older ones upstream stopped listing after them
+pub fn merge_sources(existing: Vec<GameSource>, fresh: &[GameSource]) -> Vec<GameSource> {
+    let mut merged = fresh.to_vec();
+    let mut next_priority = merged.iter().map(|s| s.priority + 1).max().unwrap_or(0);
+
+    for source in existing {
+        if merged.iter().all(|s| s.link != source.link) {
+            merged.push(GameSource {
+                priority: next_priority,
+                ..source
+            });
+            next_priority += 1;
+        }
+    }
+
+    merged
+}
+
+pub type DynGamesArchiveRepository = Arc<dyn GamesArchiveRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait GamesArchiveRepository {
+    /// upserts what a refresh fetched and settles the status of the provider's games it didn't
+    async fn archive_games(
+        &self,
+        provider: &str,
+        games: &[Game],
+        seen_at: i64,
+    ) -> anyhow::Result<()>;
+    /// newest first
+    async fn get_archived_games(&self, filter: &ArchiveFilter)
+    -> anyhow::Result<Vec<ArchivedGame>>;
+    /// ignores the page
+    async fn count_archived_games(&self, filter: &ArchiveFilter) -> anyhow::Result<i64>;
+    /// ignores the page and the category
+    async fn count_archived_games_by_category(
+        &self,
+        filter: &ArchiveFilter,
+    ) -> anyhow::Result<Vec<ArchivedCategoryCount>>;
+}
@@ -0,0 +1,305 @@
+// the games archive, written by provider refreshes and read by the history endpoint
+use anyhow::Context;
+use async_trait::async_trait;
+use sqlx::{query_as, query_scalar};
+
+use crate::database::stream::{Game, GameSource};
+use crate::database::{ConnectionPool, Database};
+use crate::server::dtos::stream_dto::GameStatus;
+
+use super::{
+    ArchiveFilter, ArchivedCategoryCount, ArchivedGame, GamesArchiveRepository, merge_sources,
+};
+
+fn status_name(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Upcoming => "upcoming",
+        GameStatus::Live => "live",
+        GameStatus::Ended => "ended",
+    }
+}
+
+fn sources_json(existing: Option<String>, game: &Game) -> String {
+    let existing: Vec<GameSource> = existing
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default();
+    let merged = merge_sources(existing, &game.playable_sources());
+
+    serde_json::to_string(&merged).unwrap_or_else(|_| String::from("[]"))
+}
+
+#[async_trait]
+impl GamesArchiveRepository for Database {
+    // one transaction per refresh, the sources already archived have to be read back so ones
+    // upstream dropped aren't lost
+    async fn archive_games(
+        &self,
+        provider: &str,
+        games: &[Game],
+        seen_at: i64,
+    ) -> anyhow::Result<()> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => {
+                let mut tx = pool.begin().await?;
+
+                for game in games {
+                    let existing: Option<String> = query_scalar(
+                        "select sources from games_archive where provider = $1 and game_id = $2",
+                    )
+                    .bind(provider)
+                    .bind(game.id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .context("unexpected error while querying for archived sources")?;
+
+                    sqlx::query(
+                        r#"
+                        insert into games_archive (provider, game_id, name, poster, category,
+                            category_slug, start_time, end_time, sources, status, first_seen_at,
+                            last_seen_at)
+                        values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $11)
+                        on conflict (provider, game_id) do update
+                        set name = excluded.name,
+                            poster = excluded.poster,
+                            category = excluded.category,
+                            category_slug = excluded.category_slug,
+                            start_time = excluded.start_time,
+                            end_time = excluded.end_time,
+                            sources = excluded.sources,
+                            status = excluded.status,
+                            last_seen_at = excluded.last_seen_at
+                            "#,
+                    )
+                    .bind(provider)
+                    .bind(game.id)
+                    .bind(&game.name)
+                    .bind(&game.poster)
+                    .bind(&game.category)
+                    .bind(&game.category_slug)
+                    .bind(game.start_time)
+                    .bind(game.end_time)
+                    .bind(sources_json(existing, game))
+                    .bind(status_name(game.status_at(seen_at)))
+                    .bind(seen_at)
+                    .execute(&mut *tx)
+                    .await
+                    .context("an unexpected error occured while archiving a game")?;
+                }
+
+                sqlx::query(
+                    r#"
+                    update games_archive
+                    set status = case when end_time <= $2 then 'ended' else 'removed' end
+                    where provider = $1
+                      and last_seen_at < $2
+                      and status not in ('ended', 'removed')
+                        "#,
+                )
+                .bind(provider)
+                .bind(seen_at)
+                .execute(&mut *tx)
+                .await
+                .context("an unexpected error occured while settling archived games")?;
+
+                tx.commit().await?;
+            }
+            ConnectionPool::Sqlite(pool) => {
+                let mut tx = pool.begin().await?;
+
+                for game in games {
+                    let existing: Option<String> = query_scalar(
+                        "select sources from games_archive where provider = ? and game_id = ?",
+                    )
+                    .bind(provider)
+                    .bind(game.id)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .context("unexpected error while querying for archived sources")?;
+
+                    sqlx::query(
+                        r#"
+                        insert into games_archive (provider, game_id, name, poster, category,
+                            category_slug, start_time, end_time, sources, status, first_seen_at,
+                            last_seen_at)
+                        values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?11)
+                        on conflict (provider, game_id) do update
+                        set name = excluded.name,
+                            poster = excluded.poster,
+                            category = excluded.category,
+                            category_slug = excluded.category_slug,
+                            start_time = excluded.start_time,
+                            end_time = excluded.end_time,
+                            sources = excluded.sources,
+                            status = excluded.status,
+                            last_seen_at = excluded.last_seen_at
+                            "#,
+                    )
+                    .bind(provider)
+                    .bind(game.id)
+                    .bind(&game.name)
+                    .bind(&game.poster)
+                    .bind(&game.category)
+                    .bind(&game.category_slug)
+                    .bind(game.start_time)
+                    .bind(game.end_time)
+                    .bind(sources_json(existing, game))
+                    .bind(status_name(game.status_at(seen_at)))
+                    .bind(seen_at)
+                    .execute(&mut *tx)
+                    .await
+                    .context("an unexpected error occured while archiving a game")?;
+                }
+
+                sqlx::query(
+                    r#"
+                    update games_archive
+                    set status = case when end_time <= ?2 then 'ended' else 'removed' end
+                    where provider = ?1
+                      and last_seen_at < ?2
+                      and status not in ('ended', 'removed')
+                        "#,
+                )
+                .bind(provider)
+                .bind(seen_at)
+                .execute(&mut *tx)
+                .await
+                .context("an unexpected error occured while settling archived games")?;
+
+                tx.commit().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_archived_games(
+        &self,
+        filter: &ArchiveFilter,
+    ) -> anyhow::Result<Vec<ArchivedGame>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, ArchivedGame>(
+                r#"
+                select *
+                from games_archive
+                where ($1::varchar is null or provider = $1)
+                  and ($2::varchar is null or category_slug = $2)
+                  and ($3::bigint is null or start_time >= $3)
+                  and ($4::bigint is null or start_time <= $4)
+                order by start_time desc, provider, game_id
+                limit $5 offset $6
+                    "#,
+            )
+            .bind(&filter.provider)
+            .bind(&filter.category_slug)
+            .bind(filter.from)
+            .bind(filter.to)
+            .bind(filter.limit)
+            .bind(filter.offset)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for archived games"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, ArchivedGame>(
+                r#"
+                select *
+                from games_archive
+                where (?1 is null or provider = ?1)
+                  and (?2 is null or category_slug = ?2)
+                  and (?3 is null or start_time >= ?3)
+                  and (?4 is null or start_time <= ?4)
+                order by start_time desc, provider, game_id
+                limit ?5 offset ?6
+                    "#,
+            )
+            .bind(&filter.provider)
+            .bind(&filter.category_slug)
+            .bind(filter.from)
+            .bind(filter.to)
+            .bind(filter.limit)
+            .bind(filter.offset)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for archived games"),
+        }
+    }
+
+    async fn count_archived_games(&self, filter: &ArchiveFilter) -> anyhow::Result<i64> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_scalar(
+                r#"
+                select count(*)
+                from games_archive
+                where ($1::varchar is null or provider = $1)
+                  and ($2::varchar is null or category_slug = $2)
+                  and ($3::bigint is null or start_time >= $3)
+                  and ($4::bigint is null or start_time <= $4)
+                    "#,
+            )
+            .bind(&filter.provider)
+            .bind(&filter.category_slug)
+            .bind(filter.from)
+            .bind(filter.to)
+            .fetch_one(pool)
+            .await
+            .context("unexpected error while counting archived games"),
+            ConnectionPool::Sqlite(pool) => query_scalar(
+                r#"
+                select count(*)
+                from games_archive
+                where (?1 is null or provider = ?1)
+                  and (?2 is null or category_slug = ?2)
+                  and (?3 is null or start_time >= ?3)
+                  and (?4 is null or start_time <= ?4)
+                    "#,
+            )
+            .bind(&filter.provider)
+            .bind(&filter.category_slug)
+            .bind(filter.from)
+            .bind(filter.to)
+            .fetch_one(pool)
+            .await
+            .context("unexpected error while counting archived games"),
+        }
+    }
+
+    async fn count_archived_games_by_category(
+        &self,
+        filter: &ArchiveFilter,
+    ) -> anyhow::Result<Vec<ArchivedCategoryCount>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, ArchivedCategoryCount>(
+                r#"
+                select category_slug, max(category) as category, count(*) as games
+                from games_archive
+                where ($1::varchar is null or provider = $1)
+                  and ($2::bigint is null or start_time >= $2)
+                  and ($3::bigint is null or start_time <= $3)
+                group by category_slug
+                order by games desc, category_slug
+                    "#,
+            )
+            .bind(&filter.provider)
+            .bind(filter.from)
+            .bind(filter.to)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while counting archived games by category"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, ArchivedCategoryCount>(
+                r#"
+                select category_slug, max(category) as category, count(*) as games
+                from games_archive
+                where (?1 is null or provider = ?1)
+                  and (?2 is null or start_time >= ?2)
+                  and (?3 is null or start_time <= ?3)
+                group by category_slug
+                order by games desc, category_slug
+                    "#,
+            )
+            .bind(&filter.provider)
+            .bind(filter.from)
+            .bind(filter.to)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while counting archived games by category"),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mockall::automock;
+use sqlx::FromRow;
+use sqlx::types::time::OffsetDateTime;
+
+/// one of our own categories, what every provider's names get folded into
+#[derive(FromRow, Debug, Clone, PartialEq)]
+pub struct Category {
+    pub slug: String,
+    pub name: String,
+    pub icon: Option<String>,
+    /// lower comes first in the listing
+    pub sort_order: i32,
+}
+
+/// a category name exactly as a provider sends it, `category_slug` is None until an admin says
+/// where it belongs
+#[derive(FromRow, Debug, Clone)]
+pub struct CategoryAlias {
+    pub provider: String,
+    pub raw_name: String,
+    pub category_slug: Option<String>,
+    pub first_seen_at: OffsetDateTime,
+}
+
+pub type DynCategoriesRepository = Arc<dyn CategoriesRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait CategoriesRepository {
+    async fn get_categories(&self) -> anyhow::Result<Vec<Category>>;
+    async fn upsert_category(&self, category: &Category) -> anyhow::Result<Category>;
+    async fn get_category_aliases(&self) -> anyhow::Result<Vec<CategoryAlias>>;
+    /// adds the names as unmapped aliases, ones that are already there are left alone
+    async fn record_unmapped_categories(
+        &self,
+        provider: &str,
+        raw_names: &[String],
+    ) -> anyhow::Result<()>;
+    async fn map_category_alias(
+        &self,
+        provider: &str,
+        raw_name: &str,
+        category_slug: &str,
+    ) -> anyhow::Result<CategoryAlias>;
+}
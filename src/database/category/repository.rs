@@ -0,0 +1,162 @@
+// our category taxonomy and the provider names that map onto it
+use anyhow::Context;
+use async_trait::async_trait;
+use sqlx::query_as;
+
+use crate::database::{ConnectionPool, Database};
+
+use super::{CategoriesRepository, Category, CategoryAlias};
+
+#[async_trait]
+impl CategoriesRepository for Database {
+    async fn get_categories(&self) -> anyhow::Result<Vec<Category>> {
+        let query = "select * from stream_categories order by sort_order, name";
+
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, Category>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for categories"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, Category>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for categories"),
+        }
+    }
+
+    async fn upsert_category(&self, category: &Category) -> anyhow::Result<Category> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, Category>(
+                r#"
+                insert into stream_categories (slug, name, icon, sort_order)
+                values ($1, $2, $3, $4)
+                on conflict (slug) do update
+                set name = excluded.name,
+                    icon = excluded.icon,
+                    sort_order = excluded.sort_order
+                returning *
+                    "#,
+            )
+            .bind(&category.slug)
+            .bind(&category.name)
+            .bind(&category.icon)
+            .bind(category.sort_order)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while saving the category"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, Category>(
+                r#"
+                insert into stream_categories (slug, name, icon, sort_order)
+                values (?, ?, ?, ?)
+                on conflict (slug) do update
+                set name = excluded.name,
+                    icon = excluded.icon,
+                    sort_order = excluded.sort_order
+                returning *
+                    "#,
+            )
+            .bind(&category.slug)
+            .bind(&category.name)
+            .bind(&category.icon)
+            .bind(category.sort_order)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while saving the category"),
+        }
+    }
+
+    async fn get_category_aliases(&self) -> anyhow::Result<Vec<CategoryAlias>> {
+        let query = "select * from stream_category_aliases order by provider, first_seen_at";
+
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, CategoryAlias>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for category aliases"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, CategoryAlias>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for category aliases"),
+        }
+    }
+
+    // only ever a handful of names per refresh so one insert each is fine
+    async fn record_unmapped_categories(
+        &self,
+        provider: &str,
+        raw_names: &[String],
+    ) -> anyhow::Result<()> {
+        for raw_name in raw_names {
+            match &self.pool {
+                ConnectionPool::Postgres(pool) => sqlx::query(
+                    r#"
+                    insert into stream_category_aliases (provider, raw_name)
+                    values ($1, $2)
+                    on conflict do nothing
+                        "#,
+                )
+                .bind(provider)
+                .bind(raw_name)
+                .execute(pool)
+                .await
+                .map(|_| ())
+                .context("an unexpected error occured while recording an unmapped category")?,
+                ConnectionPool::Sqlite(pool) => sqlx::query(
+                    r#"
+                    insert into stream_category_aliases (provider, raw_name)
+                    values (?, ?)
+                    on conflict do nothing
+                        "#,
+                )
+                .bind(provider)
+                .bind(raw_name)
+                .execute(pool)
+                .await
+                .map(|_| ())
+                .context("an unexpected error occured while recording an unmapped category")?,
+            };
+        }
+
+        Ok(())
+    }
+
+    async fn map_category_alias(
+        &self,
+        provider: &str,
+        raw_name: &str,
+        category_slug: &str,
+    ) -> anyhow::Result<CategoryAlias> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, CategoryAlias>(
+                r#"
+                insert into stream_category_aliases (provider, raw_name, category_slug)
+                values ($1, $2, $3)
+                on conflict (provider, raw_name) do update
+                set category_slug = excluded.category_slug
+                returning *
+                    "#,
+            )
+            .bind(provider)
+            .bind(raw_name)
+            .bind(category_slug)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while mapping the category"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, CategoryAlias>(
+                r#"
+                insert into stream_category_aliases (provider, raw_name, category_slug)
+                values (?, ?, ?)
+                on conflict (provider, raw_name) do update
+                set category_slug = excluded.category_slug
+                returning *
+                    "#,
+            )
+            .bind(provider)
+            .bind(raw_name)
+            .bind(category_slug)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while mapping the category"),
+        }
+    }
+}
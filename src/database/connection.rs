@@ -69,6 +69,252 @@ impl Database {
                 .await
                 .context("Failed to create sessions table")?;
 
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS follows
+                    (
+                        id          VARCHAR NOT NULL PRIMARY KEY,
+                        user_id     VARCHAR NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+                        kind        VARCHAR NOT NULL,
+                        value       VARCHAR NOT NULL,
+                        created_at  TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create follows table")?;
+
+                sqlx::query(
+                    "CREATE UNIQUE INDEX IF NOT EXISTS follows_user_kind_value_idx ON follows (user_id, kind, value);",
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create follows index")?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS follow_notification_settings
+                    (
+                        user_id     VARCHAR NOT NULL PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+                        webhook_url VARCHAR,
+                        email       BOOLEAN NOT NULL DEFAULT FALSE,
+                        updated_at  TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create follow notification settings table")?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS stream_categories
+                    (
+                        slug        VARCHAR NOT NULL PRIMARY KEY,
+                        name        VARCHAR NOT NULL,
+                        icon        VARCHAR,
+                        sort_order  INTEGER NOT NULL DEFAULT 0
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create stream categories table")?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS stream_category_aliases
+                    (
+                        provider        VARCHAR NOT NULL,
+                        raw_name        VARCHAR NOT NULL,
+                        category_slug   VARCHAR REFERENCES stream_categories (slug) ON DELETE SET NULL,
+                        first_seen_at   TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                        PRIMARY KEY (provider, raw_name)
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create stream category aliases table")?;
+
+                // same seed as the sqlite migration, existing rows are left alone so admin edits
+                // survive a restart
+                sqlx::query(
+                    r#"
+                    INSERT INTO stream_categories (slug, name, icon, sort_order)
+                    VALUES ('basketball', 'Basketball', '🏀', 10),
+                           ('american-football', 'American Football', '🏈', 20),
+                           ('football', 'Football', '⚽', 30),
+                           ('ice-hockey', 'Ice Hockey', '🏒', 40),
+                           ('baseball', 'Baseball', '⚾', 50),
+                           ('combat-sports', 'Combat Sports', '🥊', 60),
+                           ('wrestling', 'Wrestling', '🤼', 70),
+                           ('motorsport', 'Motorsport', '🏎️', 80),
+                           ('tennis', 'Tennis', '🎾', 90),
+                           ('golf', 'Golf', '⛳', 100),
+                           ('cricket', 'Cricket', '🏏', 110),
+                           ('rugby', 'Rugby', '🏉', 120),
+                           ('darts', 'Darts', '🎯', 130),
+                           ('other', 'Other', '📺', 1000)
+                    ON CONFLICT DO NOTHING;
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to seed stream categories")?;
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO stream_category_aliases (provider, raw_name, category_slug)
+                    VALUES ('ppvsu', 'NBA', 'basketball'),
+                           ('ppvsu', 'NFL', 'american-football'),
+                           ('ppvsu', 'Soccer', 'football'),
+                           ('ppvsu', 'Hockey', 'ice-hockey'),
+                           ('ppvsu', 'NHL', 'ice-hockey'),
+                           ('ppvsu', 'MLB', 'baseball'),
+                           ('ppvsu', 'UFC', 'combat-sports'),
+                           ('ppvsu', 'Boxing', 'combat-sports'),
+                           ('ppvsu', 'MMA', 'combat-sports'),
+                           ('ppvsu', 'WWE', 'wrestling'),
+                           ('ppvsu', 'Motorsports', 'motorsport'),
+                           ('ppvsu', 'Formula 1', 'motorsport'),
+                           ('ppvsu', '24/7 Streams', 'other'),
+                           ('ppvsu', 'Unknown', 'other')
+                    ON CONFLICT DO NOTHING;
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to seed stream category aliases")?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS games_archive
+                    (
+                        provider        VARCHAR NOT NULL,
+                        game_id         BIGINT NOT NULL,
+                        name            VARCHAR NOT NULL,
+                        poster          VARCHAR NOT NULL DEFAULT '',
+                        category        VARCHAR NOT NULL,
+                        category_slug   VARCHAR NOT NULL,
+                        start_time      BIGINT NOT NULL,
+                        end_time        BIGINT NOT NULL,
+                        sources         VARCHAR NOT NULL DEFAULT '[]',
+                        status          VARCHAR NOT NULL,
+                        first_seen_at   BIGINT NOT NULL,
+                        last_seen_at    BIGINT NOT NULL,
+                        PRIMARY KEY (provider, game_id)
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create games archive table")?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS games_archive_start_time_idx ON games_archive (start_time);",
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create games archive start time index")?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS games_archive_category_slug_idx ON games_archive (category_slug, start_time);",
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create games archive category index")?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS team_aliases
+                    (
+                        alias       VARCHAR NOT NULL PRIMARY KEY,
+                        team        VARCHAR NOT NULL
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create team aliases table")?;
+
+                sqlx::query(
+                    r#"
+                    INSERT INTO team_aliases (alias, team)
+                    VALUES ('la lakers', 'Los Angeles Lakers'),
+                           ('lakers', 'Los Angeles Lakers'),
+                           ('la clippers', 'Los Angeles Clippers'),
+                           ('ny knicks', 'New York Knicks'),
+                           ('gs warriors', 'Golden State Warriors'),
+                           ('man utd', 'Manchester United'),
+                           ('man united', 'Manchester United'),
+                           ('man city', 'Manchester City'),
+                           ('spurs', 'Tottenham Hotspur'),
+                           ('psg', 'Paris Saint-Germain'),
+                           ('inter', 'Inter Milan'),
+                           ('barca', 'Barcelona'),
+                           ('ny yankees', 'New York Yankees'),
+                           ('la dodgers', 'Los Angeles Dodgers')
+                    ON CONFLICT DO NOTHING;
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to seed team aliases")?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS watch_progress
+                    (
+                        user_id         VARCHAR NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+                        media_id        VARCHAR NOT NULL,
+                        episode         VARCHAR NOT NULL DEFAULT '',
+                        position_secs   DOUBLE PRECISION NOT NULL DEFAULT 0,
+                        duration_secs   DOUBLE PRECISION,
+                        watched         BOOLEAN NOT NULL DEFAULT FALSE,
+                        updated_at      TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                        PRIMARY KEY (user_id, media_id, episode)
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create watch progress table")?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS watch_progress_user_updated_idx ON watch_progress (user_id, updated_at);",
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create watch progress index")?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS watchlist
+                    (
+                        id          VARCHAR NOT NULL PRIMARY KEY,
+                        user_id     VARCHAR NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+                        kind        VARCHAR NOT NULL,
+                        media_id    VARCHAR NOT NULL,
+                        note        VARCHAR,
+                        position    BIGINT NOT NULL DEFAULT 0,
+                        created_at  TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                        UNIQUE (user_id, kind, media_id)
+                    );
+                    "#,
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create watchlist table")?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS watchlist_user_position_idx ON watchlist (user_id, position);",
+                )
+                .execute(&pg_pool)
+                .await
+                .context("Failed to create watchlist index")?;
+
                 info!("postgres migrations happy :)");
             }
 
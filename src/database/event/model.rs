@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamEventKind {
+    GameAdded,
+    GameRemoved,
+    GameLive,
+    CacheRefreshed,
+}
+
+impl StreamEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StreamEventKind::GameAdded => "game_added",
+            StreamEventKind::GameRemoved => "game_removed",
+            StreamEventKind::GameLive => "game_live",
+            StreamEventKind::CacheRefreshed => "cache_refreshed",
+        }
+    }
+}
+
+/// one entry of the sse feed, ids come from a redis counter so they're ordered across instances
+/// and can be resumed from with Last-Event-ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamEvent {
+    pub id: u64,
+    pub kind: StreamEventKind,
+    pub data: serde_json::Value,
+}
+
+pub type DynStreamEventsRepository = Arc<dyn StreamEventsRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait StreamEventsRepository {
+    /// give the events ids, add them to the replay buffer and publish them to every instance
+    async fn publish_events(
+        &self,
+        events: Vec<(StreamEventKind, serde_json::Value)>,
+    ) -> Result<Vec<StreamEvent>>;
+    /// whatever is still in the replay buffer after `last_event_id`, oldest first
+    async fn get_events_after(&self, last_event_id: u64) -> Result<Vec<StreamEvent>>;
+    /// everything published from now on, by any instance. ends when the connection drops
+    async fn subscribe_events(&self) -> Result<BoxStream<'static, StreamEvent>>;
+}
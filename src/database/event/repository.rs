@@ -0,0 +1,99 @@
+// sse events: a counter for ids, a capped list as the replay buffer and a pub/sub channel to fan
+// them out to every instance
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use redis::AsyncCommands;
+use tracing::error;
+
+use crate::database::RedisDatabase;
+
+use super::{StreamEvent, StreamEventKind, StreamEventsRepository};
+
+const EVENTS_CHANNEL: &str = "stream:events";
+const EVENTS_SEQ_KEY: &str = "stream:events:seq";
+const EVENTS_BUFFER_KEY: &str = "stream:events:buffer";
+
+// enough to cover a client reconnecting after a refresh or two
+const EVENTS_BUFFER_SIZE: isize = 500;
+
+#[async_trait]
+impl StreamEventsRepository for RedisDatabase {
+    async fn publish_events(
+        &self,
+        events: Vec<(StreamEventKind, serde_json::Value)>,
+    ) -> anyhow::Result<Vec<StreamEvent>> {
+        if events.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conn = self.connection.clone();
+
+        // reserve the whole block of ids at once so a batch stays contiguous
+        let last_id: u64 = conn.incr(EVENTS_SEQ_KEY, events.len() as u64).await?;
+        let first_id = last_id + 1 - events.len() as u64;
+
+        let events: Vec<StreamEvent> = events
+            .into_iter()
+            .enumerate()
+            .map(|(i, (kind, data))| StreamEvent {
+                id: first_id + i as u64,
+                kind,
+                data,
+            })
+            .collect();
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for event in &events {
+            let payload = serde_json::to_string(event)?;
+            pipe.lpush(EVENTS_BUFFER_KEY, &payload)
+                .ignore()
+                .publish(EVENTS_CHANNEL, &payload)
+                .ignore();
+        }
+        pipe.ltrim(EVENTS_BUFFER_KEY, 0, EVENTS_BUFFER_SIZE - 1)
+            .ignore();
+
+        let _: () = pipe.query_async(&mut conn).await?;
+
+        Ok(events)
+    }
+
+    async fn get_events_after(&self, last_event_id: u64) -> anyhow::Result<Vec<StreamEvent>> {
+        let mut conn = self.connection.clone();
+
+        // newest first in redis
+        let payloads: Vec<String> = conn.lrange(EVENTS_BUFFER_KEY, 0, -1).await?;
+
+        let mut events: Vec<StreamEvent> = payloads
+            .iter()
+            .filter_map(|p| serde_json::from_str::<StreamEvent>(p).ok())
+            .filter(|e| e.id > last_event_id)
+            .collect();
+        events.sort_by_key(|e| e.id);
+
+        Ok(events)
+    }
+
+    async fn subscribe_events(&self) -> anyhow::Result<BoxStream<'static, StreamEvent>> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(EVENTS_CHANNEL).await?;
+
+        let events = pubsub
+            .into_on_message()
+            .filter_map(|msg| async move {
+                let payload: String = msg.get_payload().ok()?;
+                match serde_json::from_str::<StreamEvent>(&payload) {
+                    Ok(event) => Some(event),
+                    Err(e) => {
+                        error!("dropping malformed stream event: {}", e);
+                        None
+                    }
+                }
+            })
+            .boxed();
+
+        Ok(events)
+    }
+}
@@ -0,0 +1,73 @@
+use std::{sync::Arc, time::SystemTime};
+
+use async_trait::async_trait;
+use mockall::automock;
+use sqlx::FromRow;
+use sqlx::types::time::OffsetDateTime;
+
+/// something a user wants go-live notifications for, `kind` is either "category" (matched against
+/// the whole category name) or "team" (matched against the teams read out of the game name)
+#[derive(FromRow, Debug, Clone)]
+pub struct Follow {
+    pub id: String,
+    pub user_id: String,
+    pub kind: String,
+    pub value: String,
+    pub created_at: OffsetDateTime,
+}
+
+impl Default for Follow {
+    fn default() -> Self {
+        Self {
+            id: String::from("V1StGXR8_Z5jdHi6B-myT"),
+            user_id: String::from("IRFa~VaY2b"),
+            kind: String::from("team"),
+            value: String::from("stub team"),
+            created_at: OffsetDateTime::from(SystemTime::now()),
+        }
+    }
+}
+
+/// where a user's notifications go, no row just means everything is off
+#[derive(FromRow, Debug, Clone, Default)]
+pub struct NotificationSettings {
+    pub user_id: String,
+    pub webhook_url: Option<String>,
+    pub email: bool,
+}
+
+/// settings joined with the user's email so the dispatchers don't need the users table
+#[derive(FromRow, Debug, Clone, Default)]
+pub struct NotificationTarget {
+    pub user_id: String,
+    pub email: String,
+    pub webhook_url: Option<String>,
+    pub email_enabled: bool,
+}
+
+pub type DynFollowsRepository = Arc<dyn FollowsRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait FollowsRepository {
+    async fn create_follow(&self, user_id: &str, kind: &str, value: &str)
+    -> anyhow::Result<Follow>;
+    async fn get_follows(&self, user_id: &str) -> anyhow::Result<Vec<Follow>>;
+    async fn get_all_follows(&self) -> anyhow::Result<Vec<Follow>>;
+    /// false if there was nothing of that user's to delete
+    async fn delete_follow(&self, user_id: &str, follow_id: &str) -> anyhow::Result<bool>;
+    async fn get_notification_settings(
+        &self,
+        user_id: &str,
+    ) -> anyhow::Result<NotificationSettings>;
+    async fn update_notification_settings(
+        &self,
+        user_id: &str,
+        webhook_url: Option<String>,
+        email: bool,
+    ) -> anyhow::Result<NotificationSettings>;
+    async fn get_notification_target(
+        &self,
+        user_id: &str,
+    ) -> anyhow::Result<Option<NotificationTarget>>;
+}
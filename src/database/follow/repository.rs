@@ -0,0 +1,247 @@
+// follows and the notification settings that go with them
+use anyhow::Context;
+use async_trait::async_trait;
+use nanoid::nanoid;
+use sqlx::query_as;
+
+use crate::database::{ConnectionPool, Database};
+
+use super::{Follow, FollowsRepository, NotificationSettings, NotificationTarget};
+
+#[async_trait]
+impl FollowsRepository for Database {
+    async fn create_follow(
+        &self,
+        user_id: &str,
+        kind: &str,
+        value: &str,
+    ) -> anyhow::Result<Follow> {
+        let follow_id: String = nanoid!();
+
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, Follow>(
+                r#"
+                insert into follows (id, user_id, kind, value)
+                values ($1, $2, $3, $4)
+                returning *
+                    "#,
+            )
+            .bind(&follow_id)
+            .bind(user_id)
+            .bind(kind)
+            .bind(value)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while creating the follow"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, Follow>(
+                r#"
+                insert into follows (id, user_id, kind, value)
+                values (?, ?, ?, ?)
+                returning *
+                    "#,
+            )
+            .bind(&follow_id)
+            .bind(user_id)
+            .bind(kind)
+            .bind(value)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while creating the follow"),
+        }
+    }
+
+    async fn get_follows(&self, user_id: &str) -> anyhow::Result<Vec<Follow>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, Follow>(
+                r#"
+                select *
+                from follows
+                where user_id = $1
+                order by created_at
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for follows"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, Follow>(
+                r#"
+                select *
+                from follows
+                where user_id = ?
+                order by created_at
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for follows"),
+        }
+    }
+
+    // only the watcher uses this, the table is small enough that pulling it all every tick is fine
+    async fn get_all_follows(&self) -> anyhow::Result<Vec<Follow>> {
+        let query = "select * from follows order by user_id, created_at";
+
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, Follow>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for all follows"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, Follow>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for all follows"),
+        }
+    }
+
+    async fn delete_follow(&self, user_id: &str, follow_id: &str) -> anyhow::Result<bool> {
+        let deleted = match &self.pool {
+            ConnectionPool::Postgres(pool) => sqlx::query(
+                r#"
+                delete from follows
+                where id = $1 and user_id = $2
+                    "#,
+            )
+            .bind(follow_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .context("an unexpected error occured while deleting the follow")?
+            .rows_affected(),
+            ConnectionPool::Sqlite(pool) => sqlx::query(
+                r#"
+                delete from follows
+                where id = ? and user_id = ?
+                    "#,
+            )
+            .bind(follow_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .context("an unexpected error occured while deleting the follow")?
+            .rows_affected(),
+        };
+
+        Ok(deleted > 0)
+    }
+
+    async fn get_notification_settings(
+        &self,
+        user_id: &str,
+    ) -> anyhow::Result<NotificationSettings> {
+        let settings = match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, NotificationSettings>(
+                r#"
+                select user_id, webhook_url, email
+                from follow_notification_settings
+                where user_id = $1
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for notification settings")?,
+            ConnectionPool::Sqlite(pool) => query_as::<_, NotificationSettings>(
+                r#"
+                select user_id, webhook_url, email
+                from follow_notification_settings
+                where user_id = ?
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for notification settings")?,
+        };
+
+        Ok(settings.unwrap_or_else(|| NotificationSettings {
+            user_id: user_id.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    async fn update_notification_settings(
+        &self,
+        user_id: &str,
+        webhook_url: Option<String>,
+        email: bool,
+    ) -> anyhow::Result<NotificationSettings> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, NotificationSettings>(
+                r#"
+                insert into follow_notification_settings (user_id, webhook_url, email)
+                values ($1, $2, $3)
+                on conflict (user_id) do update
+                set webhook_url = excluded.webhook_url,
+                    email = excluded.email,
+                    updated_at = current_timestamp
+                returning user_id, webhook_url, email
+                    "#,
+            )
+            .bind(user_id)
+            .bind(webhook_url)
+            .bind(email)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while updating notification settings"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, NotificationSettings>(
+                r#"
+                insert into follow_notification_settings (user_id, webhook_url, email)
+                values (?, ?, ?)
+                on conflict (user_id) do update
+                set webhook_url = excluded.webhook_url,
+                    email = excluded.email,
+                    updated_at = current_timestamp
+                returning user_id, webhook_url, email
+                    "#,
+            )
+            .bind(user_id)
+            .bind(webhook_url)
+            .bind(email)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while updating notification settings"),
+        }
+    }
+
+    async fn get_notification_target(
+        &self,
+        user_id: &str,
+    ) -> anyhow::Result<Option<NotificationTarget>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, NotificationTarget>(
+                r#"
+                select users.id as user_id,
+                       users.email,
+                       settings.webhook_url,
+                       coalesce(settings.email, false) as email_enabled
+                from users
+                left join follow_notification_settings settings
+                on settings.user_id = users.id
+                where users.id = $1
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for notification target"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, NotificationTarget>(
+                r#"
+                select users.id as user_id,
+                       users.email,
+                       settings.webhook_url,
+                       coalesce(settings.email, false) as email_enabled
+                from users
+                left join follow_notification_settings settings
+                on settings.user_id = users.id
+                where users.id = ?
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for notification target"),
+        }
+    }
+}
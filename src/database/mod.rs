@@ -1,9 +1,18 @@
 mod connection;
 mod redis_connection;
 
+pub mod archive;
+pub mod category;
+pub mod event;
+pub mod follow;
+pub mod notification;
+pub mod progress;
+pub mod room;
 pub mod session;
 pub mod stream;
+pub mod team;
 pub mod user;
+pub mod watchlist;
 
 pub use connection::*;
 pub use redis_connection::*;
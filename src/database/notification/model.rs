@@ -0,0 +1,24 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use mockall::automock;
+
+pub type DynNotificationsRepository = Arc<dyn NotificationsRepository + Send + Sync>;
+
+/// remembers which user/game pairs have already been notified so a game doesn't get announced
+/// every watcher tick, or once per instance
+#[automock]
+#[async_trait]
+pub trait NotificationsRepository {
+    /// true if this call claimed the pair, false if someone already notified it
+    async fn try_mark_notified(
+        &self,
+        user_id: &str,
+        provider: &str,
+        game_id: i64,
+        ttl_secs: u64,
+    ) -> Result<bool>;
+    /// undo the above so a failed send gets retried next tick
+    async fn clear_notified(&self, user_id: &str, provider: &str, game_id: i64) -> Result<()>;
+}
@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use redis::AsyncCommands;
+
+use crate::database::RedisDatabase;
+
+use super::NotificationsRepository;
+
+fn notified_key(user_id: &str, provider: &str, game_id: i64) -> String {
+    format!("notified:{}:{}:{}", user_id, provider, game_id)
+}
+
+#[async_trait]
+impl NotificationsRepository for RedisDatabase {
+    async fn try_mark_notified(
+        &self,
+        user_id: &str,
+        provider: &str,
+        game_id: i64,
+        ttl_secs: u64,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.connection.clone();
+
+        let marked: Option<String> = redis::cmd("SET")
+            .arg(notified_key(user_id, provider, game_id))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(marked.is_some())
+    }
+
+    async fn clear_notified(
+        &self,
+        user_id: &str,
+        provider: &str,
+        game_id: i64,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.connection.clone();
+
+        let _: () = conn.del(notified_key(user_id, provider, game_id)).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mockall::automock;
+use sqlx::FromRow;
+use sqlx::types::time::OffsetDateTime;
+
+/// how far a user got into a movie or an episode. `media_id` is the movie or tv id and `episode`
+/// is the `ep` the link was fetched with, empty for movies
+#[derive(FromRow, Debug, Clone, PartialEq)]
+pub struct WatchProgress {
+    pub user_id: String,
+    pub media_id: String,
+    pub episode: String,
+    pub position_secs: f64,
+    pub duration_secs: Option<f64>,
+    pub watched: bool,
+    pub updated_at: OffsetDateTime,
+}
+
+pub type DynWatchProgressRepository = Arc<dyn WatchProgressRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait WatchProgressRepository {
+    async fn get_progress(
+        &self,
+        user_id: &str,
+        media_id: &str,
+        episode: &str,
+    ) -> anyhow::Result<Option<WatchProgress>>;
+    /// `updated_at` is ignored, the database sets it
+    async fn upsert_progress(&self, progress: &WatchProgress) -> anyhow::Result<WatchProgress>;
+    /// leaves the position alone, or starts at 0 if there wasn't any progress yet
+    async fn set_watched(
+        &self,
+        user_id: &str,
+        media_id: &str,
+        episode: &str,
+        watched: bool,
+    ) -> anyhow::Result<WatchProgress>;
+    /// started but not finished, most recently watched first
+    async fn get_unfinished(&self, user_id: &str, limit: i64)
+    -> anyhow::Result<Vec<WatchProgress>>;
+}
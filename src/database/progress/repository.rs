@@ -0,0 +1,178 @@
+// movie and episode watch progress
+use anyhow::Context;
+use async_trait::async_trait;
+use sqlx::query_as;
+
+use crate::database::{ConnectionPool, Database};
+
+use super::{WatchProgress, WatchProgressRepository};
+
+#[async_trait]
+impl WatchProgressRepository for Database {
+    async fn get_progress(
+        &self,
+        user_id: &str,
+        media_id: &str,
+        episode: &str,
+    ) -> anyhow::Result<Option<WatchProgress>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchProgress>(
+                r#"
+                select *
+                from watch_progress
+                where user_id = $1 and media_id = $2 and episode = $3
+                    "#,
+            )
+            .bind(user_id)
+            .bind(media_id)
+            .bind(episode)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for watch progress"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchProgress>(
+                r#"
+                select *
+                from watch_progress
+                where user_id = ? and media_id = ? and episode = ?
+                    "#,
+            )
+            .bind(user_id)
+            .bind(media_id)
+            .bind(episode)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for watch progress"),
+        }
+    }
+
+    async fn upsert_progress(&self, progress: &WatchProgress) -> anyhow::Result<WatchProgress> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchProgress>(
+                r#"
+                insert into watch_progress (user_id, media_id, episode, position_secs,
+                    duration_secs, watched)
+                values ($1, $2, $3, $4, $5, $6)
+                on conflict (user_id, media_id, episode) do update
+                set position_secs = excluded.position_secs,
+                    duration_secs = coalesce(excluded.duration_secs, watch_progress.duration_secs),
+                    watched = watch_progress.watched or excluded.watched,
+                    updated_at = current_timestamp
+                returning *
+                    "#,
+            )
+            .bind(&progress.user_id)
+            .bind(&progress.media_id)
+            .bind(&progress.episode)
+            .bind(progress.position_secs)
+            .bind(progress.duration_secs)
+            .bind(progress.watched)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while saving watch progress"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchProgress>(
+                r#"
+                insert into watch_progress (user_id, media_id, episode, position_secs,
+                    duration_secs, watched)
+                values (?, ?, ?, ?, ?, ?)
+                on conflict (user_id, media_id, episode) do update
+                set position_secs = excluded.position_secs,
+                    duration_secs = coalesce(excluded.duration_secs, watch_progress.duration_secs),
+                    watched = watch_progress.watched or excluded.watched,
+                    updated_at = current_timestamp
+                returning *
+                    "#,
+            )
+            .bind(&progress.user_id)
+            .bind(&progress.media_id)
+            .bind(&progress.episode)
+            .bind(progress.position_secs)
+            .bind(progress.duration_secs)
+            .bind(progress.watched)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while saving watch progress"),
+        }
+    }
+
+    async fn set_watched(
+        &self,
+        user_id: &str,
+        media_id: &str,
+        episode: &str,
+        watched: bool,
+    ) -> anyhow::Result<WatchProgress> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchProgress>(
+                r#"
+                insert into watch_progress (user_id, media_id, episode, watched)
+                values ($1, $2, $3, $4)
+                on conflict (user_id, media_id, episode) do update
+                set watched = excluded.watched,
+                    updated_at = current_timestamp
+                returning *
+                    "#,
+            )
+            .bind(user_id)
+            .bind(media_id)
+            .bind(episode)
+            .bind(watched)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while marking as watched"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchProgress>(
+                r#"
+                insert into watch_progress (user_id, media_id, episode, watched)
+                values (?, ?, ?, ?)
+                on conflict (user_id, media_id, episode) do update
+                set watched = excluded.watched,
+                    updated_at = current_timestamp
+                returning *
+                    "#,
+            )
+            .bind(user_id)
+            .bind(media_id)
+            .bind(episode)
+            .bind(watched)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while marking as watched"),
+        }
+    }
+
+    async fn get_unfinished(
+        &self,
+        user_id: &str,
+        limit: i64,
+    ) -> anyhow::Result<Vec<WatchProgress>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchProgress>(
+                r#"
+                select *
+                from watch_progress
+                where user_id = $1 and not watched and position_secs > 0
+                order by updated_at desc
+                limit $2
+                    "#,
+            )
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for unfinished media"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchProgress>(
+                r#"
+                select *
+                from watch_progress
+                where user_id = ? and not watched and position_secs > 0
+                order by updated_at desc
+                limit ?
+                    "#,
+            )
+            .bind(user_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for unfinished media"),
+        }
+    }
+}
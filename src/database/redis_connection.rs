@@ -6,6 +6,8 @@ use tracing::info;
 
 #[derive(Debug, Clone)]
 pub struct RedisDatabase {
+    // kept around for pub/sub, subscribing needs a dedicated connection
+    pub client: Client,
     pub connection: MultiplexedConnection,
 }
 
@@ -22,7 +24,7 @@ impl RedisDatabase {
 
         info!("Redis connection established");
 
-        Ok(Self { connection })
+        Ok(Self { client, connection })
     }
 
     /// Performs a Redis PING health check
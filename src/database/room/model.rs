@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+
+/// a watch party. `position` is where playback was at `updated_at`, times are unix milliseconds
+/// so clients can correct for drift against the server clock
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Room {
+    pub id: String,
+    /// user id of whoever made it, the only one allowed to control playback
+    pub host: String,
+    pub media_url: String,
+    pub playing: bool,
+    /// seconds
+    pub position: f64,
+    pub updated_at: i64,
+    pub created_at: i64,
+}
+
+impl Room {
+    /// where playback should be at `now` (unix ms) going by the last command
+    pub fn position_at(&self, now: i64) -> f64 {
+        if !self.playing {
+            return self.position;
+        }
+
+        self.position + (now - self.updated_at).max(0) as f64 / 1000.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub user_id: String,
+    pub message: String,
+    pub sent_at: i64,
+}
+
+/// what goes out to everyone in a room, on every instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomEvent {
+    /// the whole room after the host changed something
+    State {
+        room: Room,
+    },
+    Chat(ChatMessage),
+    Joined {
+        user_id: String,
+    },
+    Left {
+        user_id: String,
+    },
+}
+
+/// an event and the room it's for, rooms share one pub/sub channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoomBroadcast {
+    pub room_id: String,
+    pub event: RoomEvent,
+}
+
+pub type DynRoomsRepository = Arc<dyn RoomsRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait RoomsRepository {
+    async fn get_room(&self, room_id: &str) -> Result<Option<Room>>;
+    /// creates or overwrites the room and pushes its expiry back
+    async fn save_room(&self, room: &Room) -> Result<()>;
+    /// saves `room` like save_room but only if what's stored is still `previous`, false when
+    /// something else changed it first
+    async fn replace_room(&self, previous: &Room, room: &Room) -> Result<bool>;
+    /// the last few messages, oldest first
+    async fn get_chat(&self, room_id: &str) -> Result<Vec<ChatMessage>>;
+    async fn add_chat(&self, room_id: &str, message: &ChatMessage) -> Result<()>;
+    async fn publish_room_event(&self, broadcast: &RoomBroadcast) -> Result<()>;
+    /// everything published from now on, by any instance. ends when the connection drops
+    async fn subscribe_room_events(&self) -> Result<BoxStream<'static, RoomBroadcast>>;
+}
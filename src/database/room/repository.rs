@@ -0,0 +1,148 @@
+// watch party rooms: the room itself as json, a capped list of chat and one pub/sub channel every
+// instance listens on to fan room events out to its own sockets
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use redis::AsyncCommands;
+use tracing::error;
+
+use crate::database::RedisDatabase;
+
+use super::{ChatMessage, Room, RoomBroadcast, RoomsRepository};
+
+const ROOMS_CHANNEL: &str = "room:events";
+
+// a room nobody has touched in this long is gone
+const ROOM_TTL_SECS: i64 = 12 * 60 * 60;
+
+// enough for someone joining late to see what they missed
+const CHAT_HISTORY_SIZE: isize = 50;
+
+// compare and set, a plain GET then SET would let two host commands overwrite each other.
+// same expiry handling as save_room
+const REPLACE_ROOM_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[3])
+redis.call('EXPIRE', KEYS[2], ARGV[3])
+return 1
+"#;
+
+fn room_key(room_id: &str) -> String {
+    format!("room:{}", room_id)
+}
+
+fn chat_key(room_id: &str) -> String {
+    format!("room:{}:chat", room_id)
+}
+
+#[async_trait]
+impl RoomsRepository for RedisDatabase {
+    async fn get_room(&self, room_id: &str) -> anyhow::Result<Option<Room>> {
+        let mut conn = self.connection.clone();
+
+        let room: Option<String> = conn.get(room_key(room_id)).await?;
+
+        Ok(room.map(|room| serde_json::from_str(&room)).transpose()?)
+    }
+
+    async fn save_room(&self, room: &Room) -> anyhow::Result<()> {
+        let mut conn = self.connection.clone();
+
+        // chat lives as long as the room does
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(
+                room_key(&room.id),
+                serde_json::to_string(room)?,
+                ROOM_TTL_SECS as u64,
+            )
+            .ignore()
+            .expire(chat_key(&room.id), ROOM_TTL_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn replace_room(&self, previous: &Room, room: &Room) -> anyhow::Result<bool> {
+        let mut conn = self.connection.clone();
+
+        // rooms are only ever written by serde_json so the stored json is what `previous`
+        // serializes to
+        let replaced: i32 = redis::Script::new(REPLACE_ROOM_SCRIPT)
+            .key(room_key(&room.id))
+            .key(chat_key(&room.id))
+            .arg(serde_json::to_string(previous)?)
+            .arg(serde_json::to_string(room)?)
+            .arg(ROOM_TTL_SECS)
+            .invoke_async(&mut conn)
+            .await?;
+
+        Ok(replaced == 1)
+    }
+
+    async fn get_chat(&self, room_id: &str) -> anyhow::Result<Vec<ChatMessage>> {
+        let mut conn = self.connection.clone();
+
+        let messages: Vec<String> = conn.lrange(chat_key(room_id), 0, -1).await?;
+
+        // newest are pushed to the front
+        Ok(messages
+            .iter()
+            .rev()
+            .filter_map(|message| serde_json::from_str(message).ok())
+            .collect())
+    }
+
+    async fn add_chat(&self, room_id: &str, message: &ChatMessage) -> anyhow::Result<()> {
+        let mut conn = self.connection.clone();
+        let key = chat_key(room_id);
+
+        let _: () = redis::pipe()
+            .atomic()
+            .lpush(&key, serde_json::to_string(message)?)
+            .ignore()
+            .ltrim(&key, 0, CHAT_HISTORY_SIZE - 1)
+            .ignore()
+            .expire(&key, ROOM_TTL_SECS)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn publish_room_event(&self, broadcast: &RoomBroadcast) -> anyhow::Result<()> {
+        let mut conn = self.connection.clone();
+
+        let _: () = conn
+            .publish(ROOMS_CHANNEL, serde_json::to_string(broadcast)?)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn subscribe_room_events(&self) -> anyhow::Result<BoxStream<'static, RoomBroadcast>> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(ROOMS_CHANNEL).await?;
+
+        let events = pubsub
+            .into_on_message()
+            .filter_map(|msg| async move {
+                let payload: String = msg.get_payload().ok()?;
+                match serde_json::from_str::<RoomBroadcast>(&payload) {
+                    Ok(broadcast) => Some(broadcast),
+                    Err(e) => {
+                        error!("dropping malformed room event: {}", e);
+                        None
+                    }
+                }
+            })
+            .boxed();
+
+        Ok(events)
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use mockall::automock;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -21,8 +22,67 @@ pub struct Game {
     pub start_time: i64,
     pub end_time: i64,
     pub cache_time: i64,
+    /// the preferred source's link, same as `sources[0].link`
     pub video_link: String,
+    /// our name for the category once the taxonomy has had a look at it, the raw name before
     pub category: String,
+    /// providers fill in `slugify(category)`, the taxonomy swaps it for ours. see category_utils
+    #[serde(default)]
+    pub category_slug: String,
+    /// every embed upstream knows about for the game, best first
+    #[serde(default)]
+    pub sources: Vec<GameSource>,
+    /// providers leave these empty, they're read out of `name` along with the category
+    /// normalisation. see team_utils
+    #[serde(default)]
+    pub league: Option<String>,
+    #[serde(default)]
+    pub home_team: Option<String>,
+    #[serde(default)]
+    pub away_team: Option<String>,
+}
+
+impl Game {
+    /// sources best first, falls back to `video_link` for providers that don't fill them in
+    pub fn playable_sources(&self) -> Vec<GameSource> {
+        if self.sources.is_empty() {
+            return vec![GameSource::primary(&self.video_link)];
+        }
+
+        let mut sources = self.sources.clone();
+        sources.sort_by_key(|s| s.priority);
+        sources
+    }
+
+    /// whether `team` (already lowercased) is part of either team's name. games nobody could
+    /// find teams in fall back to their name
+    pub fn plays(&self, team: &str) -> bool {
+        let teams: Vec<&String> = self.home_team.iter().chain(&self.away_team).collect();
+        if teams.is_empty() {
+            return self.name.to_lowercase().contains(team);
+        }
+
+        teams.iter().any(|t| t.to_lowercase().contains(team))
+    }
+}
+
+/// one upstream embed for a game. lower priority gets tried first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameSource {
+    pub label: String,
+    pub priority: i32,
+    pub link: String,
+}
+
+impl GameSource {
+    /// for games where upstream only gives one link
+    pub fn primary(link: &str) -> Self {
+        Self {
+            label: String::from("Main"),
+            priority: 0,
+            link: link.to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -67,10 +127,22 @@ pub struct PpvsuStreamDetail {
 #[derive(Debug, Clone, Deserialize)]
 pub struct PpvsuSource {
     pub data: String,
+    #[serde(default, alias = "name")]
+    pub label: Option<String>,
+}
+
+/// what came out of the last attempt at resolving a source's embed link
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolvedLink {
+    Playable(String),
+    /// the error, kept so a cached failure still says why
+    Failed(String),
 }
 
 pub type DynStreamsRepository = Arc<dyn StreamsRepository + Send + Sync>;
 
+#[automock]
 #[async_trait::async_trait]
 pub trait StreamsRepository {
     async fn get_stream(&self, provider: &str) -> Result<Option<Stream>>;
@@ -78,8 +150,34 @@ pub trait StreamsRepository {
     async fn store_game(&self, provider: &str, game: &Game) -> Result<()>;
     async fn get_game(&self, provider: &str, game_id: i64) -> Result<Option<Game>>;
     async fn get_games(&self, provider: &str) -> Result<Vec<Game>>;
+    async fn get_games_in_window(&self, provider: &str, from: i64, to: i64) -> Result<Vec<Game>>;
+    async fn get_games_by_category(&self, provider: &str, category_slug: &str)
+    -> Result<Vec<Game>>;
     async fn delete_game(&self, provider: &str, game_id: i64) -> Result<()>;
     async fn clear_cache(&self, provider: &str) -> Result<()>;
+    async fn replace_games(&self, provider: &str, games: &[Game], fetched_at: i64) -> Result<()>;
+    async fn try_acquire_refresh_lock(
+        &self,
+        provider: &str,
+        token: &str,
+        ttl_secs: u64,
+    ) -> Result<bool>;
+    async fn release_refresh_lock(&self, provider: &str, token: &str) -> Result<()>;
     async fn set_last_fetch_time(&self, provider: &str, timestamp: i64) -> Result<()>;
     async fn get_last_fetch_time(&self, provider: &str) -> Result<Option<i64>>;
+    async fn get_resolved_link(
+        &self,
+        provider: &str,
+        source_link: &str,
+    ) -> Result<Option<ResolvedLink>>;
+    async fn set_resolved_link(
+        &self,
+        provider: &str,
+        source_link: &str,
+        resolved: &ResolvedLink,
+        ttl_secs: u64,
+    ) -> Result<()>;
+    /// returns how many were actually cached
+    async fn delete_resolved_links(&self, provider: &str, source_links: &[String])
+    -> Result<usize>;
 }
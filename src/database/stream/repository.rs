@@ -1,52 +1,262 @@
-// FIXME: i dont know if it's here or in the actual api implementation but old games aren't being
-// dropped...
+// redis layout for the stream cache, everything for a provider lives under stream:{provider}:
+//
+//   stream:providers                    set of providers that have been refreshed at least once
+//   stream:{provider}:game:{id}         hash per game, expires a while after the game ends
+//   stream:{provider}:by_start          sorted set of game ids scored by start_time
+//   stream:{provider}:categories        set of category slugs currently indexed
+//   stream:{provider}:category:{slug}   set of game ids in that category
+//   stream:{provider}:last_fetch        unix time of the last successful refresh
+//   stream:{provider}:resolved:{hash}   what an embed link last resolved to (or why it failed),
+//                                       keyed by a hash of the embed url
+//
+// games expire on their own so nothing has to go looking for old ones, the indexes just get ids
+// pruned whenever a read finds the hash behind them is gone. the indexes expire along with the
+// last game in them and every refresh rebuilds them from scratch, so a category nobody reads
+// doesn't hang on to games that are long gone
+use std::collections::{HashMap, HashSet};
+
 use async_trait::async_trait;
 use chrono::Utc;
 use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use sha2::{Digest, Sha256};
 
 use crate::database::RedisDatabase;
+use crate::server::utils::category_utils::slugify;
+
+use super::{Game, GameSource, ResolvedLink, Stream, StreamsRepository};
+
+// how long a game sticks around after it ends, replays and games running late are a thing
+const GAME_EXPIRY_GRACE_SECS: i64 = 6 * 60 * 60;
+
+const PROVIDERS_KEY: &str = "stream:providers";
+
+fn game_key(provider: &str, game_id: &str) -> String {
+    format!("stream:{}:game:{}", provider, game_id)
+}
+
+fn by_start_key(provider: &str) -> String {
+    format!("stream:{}:by_start", provider)
+}
+
+fn categories_key(provider: &str) -> String {
+    format!("stream:{}:categories", provider)
+}
+
+fn category_key(provider: &str, slug: &str) -> String {
+    format!("stream:{}:category:{}", provider, slug)
+}
+
+fn last_fetch_key(provider: &str) -> String {
+    format!("stream:{}:last_fetch", provider)
+}
+
+// embed urls can be long and full of characters nobody wants in a key
+fn resolved_key(provider: &str, source_link: &str) -> String {
+    let digest = Sha256::digest(source_link.as_bytes());
+    format!("stream:{}:resolved:{}", provider, hex::encode(digest))
+}
+
+fn game_expiry(game: &Game) -> i64 {
+    game.end_time.max(Utc::now().timestamp()) + GAME_EXPIRY_GRACE_SECS
+}
+
+fn game_to_fields(game: &Game) -> Vec<(&'static str, String)> {
+    vec![
+        ("id", game.id.to_string()),
+        ("name", game.name.clone()),
+        ("poster", game.poster.clone()),
+        ("start_time", game.start_time.to_string()),
+        ("end_time", game.end_time.to_string()),
+        ("cache_time", game.cache_time.to_string()),
+        ("video_link", game.video_link.clone()),
+        ("category", game.category.clone()),
+        ("category_slug", game.category_slug.clone()),
+        (
+            "sources",
+            serde_json::to_string(&game.sources).unwrap_or_default(),
+        ),
+        ("league", game.league.clone().unwrap_or_default()),
+        ("home_team", game.home_team.clone().unwrap_or_default()),
+        ("away_team", game.away_team.clone().unwrap_or_default()),
+    ]
+}
+
+// redis hands back an empty map for a key that expired so None here is expected, not an error
+fn game_from_fields(mut fields: HashMap<String, String>) -> Option<Game> {
+    let video_link = fields.remove("video_link")?;
+
+    // games cached before sources were stored only have the one link
+    let sources = fields
+        .get("sources")
+        .and_then(|sources| serde_json::from_str::<Vec<GameSource>>(sources).ok())
+        .filter(|sources| !sources.is_empty())
+        .unwrap_or_else(|| vec![GameSource::primary(&video_link)]);
+
+    let category = fields.remove("category").unwrap_or_default();
+    // games cached before the taxonomy existed only have the raw name
+    let category_slug = fields
+        .remove("category_slug")
+        .filter(|slug| !slug.is_empty())
+        .unwrap_or_else(|| slugify(&category));
+
+    Some(Game {
+        id: fields.get("id")?.parse().ok()?,
+        name: fields.remove("name")?,
+        poster: fields.remove("poster").unwrap_or_default(),
+        start_time: fields.get("start_time")?.parse().ok()?,
+        end_time: fields.get("end_time")?.parse().ok()?,
+        cache_time: fields.get("cache_time")?.parse().ok()?,
+        video_link,
+        category,
+        category_slug,
+        sources,
+        // empty for games without them, and games cached before they were parsed out
+        league: fields.remove("league").filter(|l| !l.is_empty()),
+        home_team: fields.remove("home_team").filter(|t| !t.is_empty()),
+        away_team: fields.remove("away_team").filter(|t| !t.is_empty()),
+    })
+}
+
+// EXPIREAT with GT only ever pushes an expiry out but leaves keys without one alone, NX gives
+// those one first. needs redis 7
+fn extend_expiry(pipe: &mut redis::Pipeline, key: &str, at: i64) {
+    pipe.cmd("EXPIREAT")
+        .arg(key)
+        .arg(at)
+        .arg("NX")
+        .ignore()
+        .cmd("EXPIREAT")
+        .arg(key)
+        .arg(at)
+        .arg("GT")
+        .ignore();
+}
+
+// queue everything needed to write one game and index it
+fn queue_game(pipe: &mut redis::Pipeline, provider: &str, game: &Game) {
+    let id = game.id.to_string();
+    let key = game_key(provider, &id);
+    let expiry = game_expiry(game);
+
+    pipe.del(&key)
+        .ignore()
+        .hset_multiple(&key, &game_to_fields(game))
+        .ignore()
+        .expire_at(&key, expiry)
+        .ignore()
+        .zadd(by_start_key(provider), &id, game.start_time)
+        .ignore()
+        .sadd(categories_key(provider), &game.category_slug)
+        .ignore()
+        .sadd(category_key(provider, &game.category_slug), &id)
+        .ignore();
+
+    // an index lives as long as the last game in it
+    extend_expiry(pipe, &by_start_key(provider), expiry);
+    extend_expiry(pipe, &categories_key(provider), expiry);
+    extend_expiry(pipe, &category_key(provider, &game.category_slug), expiry);
+}
 
-use super::{Game, Stream, StreamsRepository};
+// the category a stored game is indexed under, games from before the taxonomy only have the name
+async fn stored_category_slug(
+    conn: &mut MultiplexedConnection,
+    key: &str,
+) -> anyhow::Result<Option<String>> {
+    let (category, category_slug): (Option<String>, Option<String>) =
+        conn.hget(key, &["category", "category_slug"]).await?;
+
+    Ok(category_slug
+        .filter(|slug| !slug.is_empty())
+        .or_else(|| category.map(|c| slugify(&c))))
+}
+
+impl RedisDatabase {
+    // load the games behind a list of ids in one round trip, ids whose hash already expired get
+    // dropped from the time index and the index they were read from
+    async fn load_games(
+        &self,
+        conn: &mut MultiplexedConnection,
+        provider: &str,
+        index_key: &str,
+        ids: Vec<String>,
+    ) -> anyhow::Result<Vec<Game>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut pipe = redis::pipe();
+        for id in &ids {
+            pipe.hgetall(game_key(provider, id));
+        }
+        let rows: Vec<HashMap<String, String>> = pipe.query_async(conn).await?;
+
+        let mut games = Vec::with_capacity(rows.len());
+        let mut expired = Vec::new();
+        for (id, fields) in ids.into_iter().zip(rows) {
+            match game_from_fields(fields) {
+                Some(game) => games.push(game),
+                None => expired.push(id),
+            }
+        }
+
+        if !expired.is_empty() {
+            let by_start = by_start_key(provider);
+            let mut pipe = redis::pipe();
+            pipe.zrem(&by_start, &expired).ignore();
+            if index_key != by_start {
+                pipe.srem(index_key, &expired).ignore();
+            }
+            let _: () = pipe.query_async(conn).await?;
+        }
+
+        Ok(games)
+    }
+
+    // every game hash and index key a provider currently has, last_fetch isn't included
+    async fn provider_keys(
+        &self,
+        conn: &mut MultiplexedConnection,
+        provider: &str,
+    ) -> anyhow::Result<Vec<String>> {
+        let ids: Vec<String> = conn.zrange(by_start_key(provider), 0, -1).await?;
+        let categories: HashSet<String> = conn.smembers(categories_key(provider)).await?;
+
+        let mut keys: Vec<String> = ids.iter().map(|id| game_key(provider, id)).collect();
+        keys.extend(categories.iter().map(|c| category_key(provider, c)));
+        keys.push(by_start_key(provider));
+        keys.push(categories_key(provider));
+
+        Ok(keys)
+    }
+}
 
 #[async_trait]
 impl StreamsRepository for RedisDatabase {
-    // gets all streams from a provider
+    // all cached games of a provider as one json blob
     async fn get_stream(&self, provider: &str) -> anyhow::Result<Option<Stream>> {
-        let mut conn = self.connection.clone();
+        let games = self.get_games(provider).await?;
 
-        let data: Option<String> = conn.get(provider).await?;
+        if games.is_empty() {
+            return Ok(None);
+        }
 
-        Ok(data.map(|d| Stream {
+        Ok(Some(Stream {
             provider: provider.to_string(),
-            data: d,
+            data: serde_json::to_string(&games)?,
         }))
     }
 
-    // get all streams no matter the provider (i only have one)
+    // get all streams no matter the provider
     async fn get_all_streams(&self) -> anyhow::Result<Vec<Stream>> {
         let mut conn = self.connection.clone();
 
-        let keys: Vec<String> = conn.keys("*").await?;
-        let current_time = Utc::now().timestamp();
-        let twenty_four_hours = 24 * 60 * 60;
+        let mut providers: Vec<String> = conn.smembers(PROVIDERS_KEY).await?;
+        providers.sort();
 
         let mut streams = Vec::new();
-        for key in keys {
-            if key.contains(':') {
-                let parts: Vec<&str> = key.split(':').collect();
-                if parts.len() == 2 {
-                    if let Ok(game_id) = parts[1].parse::<i64>() {
-                        if let Some(game) = self.get_game(parts[0], game_id).await? {
-                            if current_time - game.start_time > twenty_four_hours {
-                                self.delete_game(parts[0], game_id).await?;
-                                continue;
-                            }
-                        }
-                    }
-                }
-            }
-
-            if let Some(stream) = self.get_stream(&key).await? {
+        for provider in providers {
+            if let Some(stream) = self.get_stream(&provider).await? {
                 streams.push(stream);
             }
         }
@@ -54,63 +264,81 @@ impl StreamsRepository for RedisDatabase {
         Ok(streams)
     }
 
-    // store a stream with provider and id
+    // store a game with provider and id, and index it
     async fn store_game(&self, provider: &str, game: &Game) -> anyhow::Result<()> {
         let mut conn = self.connection.clone();
 
-        let key = format!("{}:{}", provider, game.id);
-        let value = serde_json::to_string(game)?;
+        let id = game.id.to_string();
+        let previous_slug = stored_category_slug(&mut conn, &game_key(provider, &id)).await?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        // it'd show up under both categories otherwise
+        if let Some(previous_slug) = previous_slug
+            && previous_slug != game.category_slug
+        {
+            pipe.srem(category_key(provider, &previous_slug), &id)
+                .ignore();
+        }
+        queue_game(&mut pipe, provider, game);
+        pipe.sadd(PROVIDERS_KEY, provider).ignore();
 
-        let _: () = conn.set(&key, value).await?;
+        let _: () = pipe.query_async(&mut conn).await?;
 
         Ok(())
     }
+
     // get a game with provider and id
     async fn get_game(&self, provider: &str, game_id: i64) -> anyhow::Result<Option<Game>> {
         let mut conn = self.connection.clone();
 
-        let key = format!("{}:{}", provider, game_id);
-        let data: Option<String> = conn.get(&key).await?;
+        let fields: HashMap<String, String> = conn
+            .hgetall(game_key(provider, &game_id.to_string()))
+            .await?;
 
-        Ok(data.and_then(|json| serde_json::from_str::<Game>(&json).ok()))
+        Ok(game_from_fields(fields))
     }
 
-    // get all games from a matcher in format provider:*
+    // every cached game of a provider, ordered by start time
     async fn get_games(&self, provider: &str) -> anyhow::Result<Vec<Game>> {
         let mut conn = self.connection.clone();
 
-        let pattern = format!("{}:*", provider);
-        let mut keys = Vec::new();
-        let mut cursor = 0u64;
+        let index_key = by_start_key(provider);
+        let ids: Vec<String> = conn.zrange(&index_key, 0, -1).await?;
 
-        loop {
-            let (new_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
-                .arg(cursor)
-                .arg("MATCH")
-                .arg(&pattern)
-                .query_async(&mut conn)
-                .await?;
+        self.load_games(&mut conn, provider, &index_key, ids).await
+    }
 
-            keys.extend(batch);
-            cursor = new_cursor;
+    // games starting between from and to (both inclusive), ordered by start time
+    async fn get_games_in_window(
+        &self,
+        provider: &str,
+        from: i64,
+        to: i64,
+    ) -> anyhow::Result<Vec<Game>> {
+        let mut conn = self.connection.clone();
 
-            if cursor == 0 {
-                break;
-            }
-        }
+        let index_key = by_start_key(provider);
+        let ids: Vec<String> = conn.zrangebyscore(&index_key, from, to).await?;
 
-        if keys.is_empty() {
-            return Ok(Vec::new());
-        }
+        self.load_games(&mut conn, provider, &index_key, ids).await
+    }
 
-        let values: Vec<Option<String>> =
-            redis::cmd("MGET").arg(&keys).query_async(&mut conn).await?;
+    // games in one category, ordered by start time
+    async fn get_games_by_category(
+        &self,
+        provider: &str,
+        category_slug: &str,
+    ) -> anyhow::Result<Vec<Game>> {
+        let mut conn = self.connection.clone();
 
-        let games = values
-            .into_iter()
-            .flatten()
-            .filter_map(|json| serde_json::from_str::<Game>(&json).ok())
-            .collect();
+        let index_key = category_key(provider, category_slug);
+        let ids: Vec<String> = conn.smembers(&index_key).await?;
+
+        let mut games = self
+            .load_games(&mut conn, provider, &index_key, ids)
+            .await?;
+        games.sort_by_key(|g| g.start_time);
 
         Ok(games)
     }
@@ -119,42 +347,112 @@ impl StreamsRepository for RedisDatabase {
     async fn delete_game(&self, provider: &str, game_id: i64) -> anyhow::Result<()> {
         let mut conn = self.connection.clone();
 
-        let key = format!("{}:{}", provider, game_id);
-        let _: () = conn.del(&key).await?;
+        let id = game_id.to_string();
+        let key = game_key(provider, &id);
+        let category_slug = stored_category_slug(&mut conn, &key).await?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(&key)
+            .ignore()
+            .zrem(by_start_key(provider), &id)
+            .ignore();
+        if let Some(category_slug) = category_slug {
+            pipe.srem(category_key(provider, &category_slug), &id)
+                .ignore();
+        }
+
+        let _: () = pipe.query_async(&mut conn).await?;
 
         Ok(())
     }
 
-    // used mainly for debugging
+    // used mainly for debugging, the indexes already list every key so nothing gets scanned
     async fn clear_cache(&self, provider: &str) -> anyhow::Result<()> {
         let mut conn = self.connection.clone();
 
-        let pattern = format!("{}:*", provider);
-        let mut keys = Vec::new();
-        let mut cursor = 0u64;
+        let mut keys = self.provider_keys(&mut conn, provider).await?;
+        keys.push(last_fetch_key(provider));
 
-        // Use SCAN command to iterate through keys matching the pattern
-        loop {
-            let (new_cursor, batch): (u64, Vec<String>) = redis::cmd("SCAN")
-                .arg(cursor)
-                .arg("MATCH")
-                .arg(&pattern)
-                .arg("COUNT")
-                .arg(100)
-                .query_async(&mut conn)
-                .await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(keys)
+            .ignore()
+            .srem(PROVIDERS_KEY, provider)
+            .ignore();
 
-            keys.extend(batch);
-            cursor = new_cursor;
+        let _: () = pipe.query_async(&mut conn).await?;
 
-            if cursor == 0 {
-                break;
-            }
-        }
+        Ok(())
+    }
+
+    // swap a provider's whole game list in one MULTI so readers never see it half written or
+    // empty, the old hashes and indexes get dropped in the same transaction
+    async fn replace_games(
+        &self,
+        provider: &str,
+        games: &[Game],
+        fetched_at: i64,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.connection.clone();
+
+        let old_keys = self.provider_keys(&mut conn, provider).await?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
 
-        if !keys.is_empty() {
-            let _: () = conn.del(keys).await?;
+        pipe.del(old_keys).ignore();
+        for game in games {
+            queue_game(&mut pipe, provider, game);
         }
+        pipe.set(last_fetch_key(provider), fetched_at)
+            .ignore()
+            .sadd(PROVIDERS_KEY, provider)
+            .ignore();
+
+        let _: () = pipe.query_async(&mut conn).await?;
+
+        Ok(())
+    }
+
+    // cross instance lock so only one refresh hits upstream at a time, it's deliberately kept out
+    // of the stream: namespace so clearing a provider never touches it
+    async fn try_acquire_refresh_lock(
+        &self,
+        provider: &str,
+        token: &str,
+        ttl_secs: u64,
+    ) -> anyhow::Result<bool> {
+        let mut conn = self.connection.clone();
+
+        let key = format!("lock:refresh:{}", provider);
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(&key)
+            .arg(token)
+            .arg("NX")
+            .arg("EX")
+            .arg(ttl_secs)
+            .query_async(&mut conn)
+            .await?;
+
+        Ok(acquired.is_some())
+    }
+
+    // only delete the lock if it's still ours, it could have expired and been taken by someone
+    // else while a slow refresh was running
+    async fn release_refresh_lock(&self, provider: &str, token: &str) -> anyhow::Result<()> {
+        let mut conn = self.connection.clone();
+
+        let key = format!("lock:refresh:{}", provider);
+        let script = redis::Script::new(
+            r#"
+            if redis.call("GET", KEYS[1]) == ARGV[1] then
+                return redis.call("DEL", KEYS[1])
+            end
+            return 0
+            "#,
+        );
+        let _: i64 = script.key(&key).arg(token).invoke_async(&mut conn).await?;
 
         Ok(())
     }
@@ -163,8 +461,7 @@ impl StreamsRepository for RedisDatabase {
     async fn set_last_fetch_time(&self, provider: &str, timestamp: i64) -> anyhow::Result<()> {
         let mut conn = self.connection.clone();
 
-        let key = format!("{}:last_fetch", provider);
-        let _: () = conn.set(&key, timestamp).await?;
+        let _: () = conn.set(last_fetch_key(provider), timestamp).await?;
 
         Ok(())
     }
@@ -173,9 +470,61 @@ impl StreamsRepository for RedisDatabase {
     async fn get_last_fetch_time(&self, provider: &str) -> anyhow::Result<Option<i64>> {
         let mut conn = self.connection.clone();
 
-        let key = format!("{}:last_fetch", provider);
-        let timestamp: Option<i64> = conn.get(&key).await?;
+        let timestamp: Option<i64> = conn.get(last_fetch_key(provider)).await?;
 
         Ok(timestamp)
     }
+
+    async fn get_resolved_link(
+        &self,
+        provider: &str,
+        source_link: &str,
+    ) -> anyhow::Result<Option<ResolvedLink>> {
+        let mut conn = self.connection.clone();
+
+        let cached: Option<String> = conn.get(resolved_key(provider, source_link)).await?;
+
+        // anything unreadable is treated as a miss and gets overwritten
+        Ok(cached.and_then(|cached| serde_json::from_str(&cached).ok()))
+    }
+
+    async fn set_resolved_link(
+        &self,
+        provider: &str,
+        source_link: &str,
+        resolved: &ResolvedLink,
+        ttl_secs: u64,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.connection.clone();
+
+        let _: () = conn
+            .set_ex(
+                resolved_key(provider, source_link),
+                serde_json::to_string(resolved)?,
+                ttl_secs,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_resolved_links(
+        &self,
+        provider: &str,
+        source_links: &[String],
+    ) -> anyhow::Result<usize> {
+        if source_links.is_empty() {
+            return Ok(0);
+        }
+
+        let mut conn = self.connection.clone();
+
+        let keys: Vec<String> = source_links
+            .iter()
+            .map(|link| resolved_key(provider, link))
+            .collect();
+        let deleted: usize = conn.del(keys).await?;
+
+        Ok(deleted)
+    }
 }
@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mockall::automock;
+use sqlx::FromRow;
+
+/// another name a team goes by in game titles, `alias` is always lowercase
+#[derive(FromRow, Debug, Clone, PartialEq)]
+pub struct TeamAlias {
+    pub alias: String,
+    pub team: String,
+}
+
+pub type DynTeamsRepository = Arc<dyn TeamsRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait TeamsRepository {
+    async fn get_team_aliases(&self) -> anyhow::Result<Vec<TeamAlias>>;
+    async fn upsert_team_alias(&self, alias: &TeamAlias) -> anyhow::Result<TeamAlias>;
+    /// false if there wasn't one
+    async fn delete_team_alias(&self, alias: &str) -> anyhow::Result<bool>;
+}
@@ -0,0 +1,80 @@
+// the alias table team names parsed out of game titles get normalised through
+use anyhow::Context;
+use async_trait::async_trait;
+use sqlx::query_as;
+
+use crate::database::{ConnectionPool, Database};
+
+use super::{TeamAlias, TeamsRepository};
+
+#[async_trait]
+impl TeamsRepository for Database {
+    async fn get_team_aliases(&self) -> anyhow::Result<Vec<TeamAlias>> {
+        let query = "select * from team_aliases order by team, alias";
+
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, TeamAlias>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for team aliases"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, TeamAlias>(query)
+                .fetch_all(pool)
+                .await
+                .context("unexpected error while querying for team aliases"),
+        }
+    }
+
+    async fn upsert_team_alias(&self, alias: &TeamAlias) -> anyhow::Result<TeamAlias> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, TeamAlias>(
+                r#"
+                insert into team_aliases (alias, team)
+                values ($1, $2)
+                on conflict (alias) do update
+                set team = excluded.team
+                returning *
+                    "#,
+            )
+            .bind(&alias.alias)
+            .bind(&alias.team)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while saving the team alias"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, TeamAlias>(
+                r#"
+                insert into team_aliases (alias, team)
+                values (?, ?)
+                on conflict (alias) do update
+                set team = excluded.team
+                returning *
+                    "#,
+            )
+            .bind(&alias.alias)
+            .bind(&alias.team)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while saving the team alias"),
+        }
+    }
+
+    async fn delete_team_alias(&self, alias: &str) -> anyhow::Result<bool> {
+        let rows_affected = match &self.pool {
+            ConnectionPool::Postgres(pool) => {
+                sqlx::query("delete from team_aliases where alias = $1")
+                    .bind(alias)
+                    .execute(pool)
+                    .await
+                    .context("an unexpected error occured while deleting the team alias")?
+                    .rows_affected()
+            }
+            ConnectionPool::Sqlite(pool) => sqlx::query("delete from team_aliases where alias = ?")
+                .bind(alias)
+                .execute(pool)
+                .await
+                .context("an unexpected error occured while deleting the team alias")?
+                .rows_affected(),
+        };
+
+        Ok(rows_affected > 0)
+    }
+}
@@ -0,0 +1,61 @@
+use std::{sync::Arc, time::SystemTime};
+
+use async_trait::async_trait;
+use mockall::automock;
+use sqlx::FromRow;
+use sqlx::types::time::OffsetDateTime;
+
+/// a movie or show a user saved for later, `kind` is "movie" or "tv" and `media_id` is the same id
+/// the movie link endpoints take
+#[derive(FromRow, Debug, Clone)]
+pub struct WatchlistItem {
+    pub id: String,
+    pub user_id: String,
+    pub kind: String,
+    pub media_id: String,
+    pub note: Option<String>,
+    /// lower comes first, ties go to whatever was added first
+    pub position: i64,
+    pub created_at: OffsetDateTime,
+}
+
+impl Default for WatchlistItem {
+    fn default() -> Self {
+        Self {
+            id: String::from("V1StGXR8_Z5jdHi6B-myT"),
+            user_id: String::from("IRFa~VaY2b"),
+            kind: String::from("movie"),
+            media_id: String::from("1311031"),
+            note: None,
+            position: 0,
+            created_at: OffsetDateTime::from(SystemTime::now()),
+        }
+    }
+}
+
+pub type DynWatchlistRepository = Arc<dyn WatchlistRepository + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait WatchlistRepository {
+    /// sorted by position
+    async fn get_watchlist(&self, user_id: &str) -> anyhow::Result<Vec<WatchlistItem>>;
+    async fn get_watchlist_item(
+        &self,
+        user_id: &str,
+        item_id: &str,
+    ) -> anyhow::Result<Option<WatchlistItem>>;
+    /// None if it's already on the list
+    async fn add_watchlist_item(
+        &self,
+        user_id: &str,
+        kind: &str,
+        media_id: &str,
+        note: Option<String>,
+        position: i64,
+    ) -> anyhow::Result<Option<WatchlistItem>>;
+    /// only the note and position can change
+    async fn update_watchlist_item(&self, item: &WatchlistItem) -> anyhow::Result<WatchlistItem>;
+    /// false if there was nothing of that user's to delete
+    async fn delete_watchlist_item(&self, user_id: &str, item_id: &str) -> anyhow::Result<bool>;
+}
@@ -0,0 +1,188 @@
+// the per user watchlist
+use anyhow::Context;
+use async_trait::async_trait;
+use nanoid::nanoid;
+use sqlx::query_as;
+
+use crate::database::{ConnectionPool, Database};
+
+use super::{WatchlistItem, WatchlistRepository};
+
+#[async_trait]
+impl WatchlistRepository for Database {
+    async fn get_watchlist(&self, user_id: &str) -> anyhow::Result<Vec<WatchlistItem>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                select *
+                from watchlist
+                where user_id = $1
+                order by position, created_at
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for the watchlist"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                select *
+                from watchlist
+                where user_id = ?
+                order by position, created_at
+                    "#,
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+            .context("unexpected error while querying for the watchlist"),
+        }
+    }
+
+    async fn get_watchlist_item(
+        &self,
+        user_id: &str,
+        item_id: &str,
+    ) -> anyhow::Result<Option<WatchlistItem>> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                select *
+                from watchlist
+                where id = $1 and user_id = $2
+                    "#,
+            )
+            .bind(item_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for the watchlist item"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                select *
+                from watchlist
+                where id = ? and user_id = ?
+                    "#,
+            )
+            .bind(item_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .context("unexpected error while querying for the watchlist item"),
+        }
+    }
+
+    async fn add_watchlist_item(
+        &self,
+        user_id: &str,
+        kind: &str,
+        media_id: &str,
+        note: Option<String>,
+        position: i64,
+    ) -> anyhow::Result<Option<WatchlistItem>> {
+        let item_id: String = nanoid!();
+
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                insert into watchlist (id, user_id, kind, media_id, note, position)
+                values ($1, $2, $3, $4, $5, $6)
+                on conflict (user_id, kind, media_id) do nothing
+                returning *
+                    "#,
+            )
+            .bind(&item_id)
+            .bind(user_id)
+            .bind(kind)
+            .bind(media_id)
+            .bind(note)
+            .bind(position)
+            .fetch_optional(pool)
+            .await
+            .context("an unexpected error occured while adding to the watchlist"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                insert into watchlist (id, user_id, kind, media_id, note, position)
+                values (?, ?, ?, ?, ?, ?)
+                on conflict (user_id, kind, media_id) do nothing
+                returning *
+                    "#,
+            )
+            .bind(&item_id)
+            .bind(user_id)
+            .bind(kind)
+            .bind(media_id)
+            .bind(note)
+            .bind(position)
+            .fetch_optional(pool)
+            .await
+            .context("an unexpected error occured while adding to the watchlist"),
+        }
+    }
+
+    async fn update_watchlist_item(&self, item: &WatchlistItem) -> anyhow::Result<WatchlistItem> {
+        match &self.pool {
+            ConnectionPool::Postgres(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                update watchlist
+                set note = $1, position = $2
+                where id = $3 and user_id = $4
+                returning *
+                    "#,
+            )
+            .bind(&item.note)
+            .bind(item.position)
+            .bind(&item.id)
+            .bind(&item.user_id)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while updating the watchlist item"),
+            ConnectionPool::Sqlite(pool) => query_as::<_, WatchlistItem>(
+                r#"
+                update watchlist
+                set note = ?, position = ?
+                where id = ? and user_id = ?
+                returning *
+                    "#,
+            )
+            .bind(&item.note)
+            .bind(item.position)
+            .bind(&item.id)
+            .bind(&item.user_id)
+            .fetch_one(pool)
+            .await
+            .context("an unexpected error occured while updating the watchlist item"),
+        }
+    }
+
+    async fn delete_watchlist_item(&self, user_id: &str, item_id: &str) -> anyhow::Result<bool> {
+        let deleted = match &self.pool {
+            ConnectionPool::Postgres(pool) => sqlx::query(
+                r#"
+                delete from watchlist
+                where id = $1 and user_id = $2
+                    "#,
+            )
+            .bind(item_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .context("an unexpected error occured while deleting the watchlist item")?
+            .rows_affected(),
+            ConnectionPool::Sqlite(pool) => sqlx::query(
+                r#"
+                delete from watchlist
+                where id = ? and user_id = ?
+                    "#,
+            )
+            .bind(item_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .context("an unexpected error occured while deleting the watchlist item")?
+            .rows_affected(),
+        };
+
+        Ok(deleted > 0)
+    }
+}
@@ -0,0 +1,103 @@
+use axum::Router;
+use axum::extract::{Json, Path};
+use axum::http::StatusCode;
+use axum::routing::{delete, get};
+use tracing::info;
+
+use crate::server::dtos::follow_dto::{
+    CreateFollowDto, FollowDto, FollowListResponse, NotificationSettingsDto,
+    UpdateNotificationSettingsDto,
+};
+use crate::server::error::AppResult;
+use crate::server::extractors::{RequiredAuthentication, ValidationExtractor};
+
+pub struct FollowController;
+/*
+*    api/v1/users/me/follows - GET - everything the current user follows
+*
+*    api/v1/users/me/follows - POST - takes in json with kind: "category" | "team" and value: string,
+*    "category" matches a whole category name and "team" matches a keyword anywhere in a game name.
+*    both ignore case
+*
+*    api/v1/users/me/follows/{id} - DELETE - stop following
+*
+*    api/v1/users/me/follows/notifications - GET/PUT - json with webhook_url: string | null and
+*    email: bool, where go-live notifications get sent
+* */
+impl FollowController {
+    pub fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(Self::get_follows_endpoint).post(Self::create_follow_endpoint),
+            )
+            .route(
+                "/notifications",
+                get(Self::get_notification_settings_endpoint)
+                    .put(Self::update_notification_settings_endpoint),
+            )
+            .route("/{id}", delete(Self::delete_follow_endpoint))
+    }
+
+    pub async fn get_follows_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+    ) -> AppResult<Json<FollowListResponse>> {
+        info!("recieved request to retrieve follows for {:?}", user_id);
+
+        let follows = services.follows.get_follows(user_id).await?;
+
+        Ok(Json(FollowListResponse { follows }))
+    }
+
+    pub async fn create_follow_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        ValidationExtractor(request): ValidationExtractor<CreateFollowDto>,
+    ) -> AppResult<(StatusCode, Json<FollowDto>)> {
+        info!("recieved request to create follow for {:?}", user_id);
+
+        let follow = services.follows.create_follow(user_id, request).await?;
+
+        Ok((StatusCode::CREATED, Json(follow)))
+    }
+
+    pub async fn delete_follow_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        Path(id): Path<String>,
+    ) -> AppResult<StatusCode> {
+        info!("recieved request to delete follow {:?}", id);
+
+        services.follows.delete_follow(user_id, id).await?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    pub async fn get_notification_settings_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+    ) -> AppResult<Json<NotificationSettingsDto>> {
+        info!(
+            "recieved request for notification settings of {:?}",
+            user_id
+        );
+
+        let settings = services.follows.get_notification_settings(user_id).await?;
+
+        Ok(Json(settings))
+    }
+
+    pub async fn update_notification_settings_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        ValidationExtractor(request): ValidationExtractor<UpdateNotificationSettingsDto>,
+    ) -> AppResult<Json<NotificationSettingsDto>> {
+        info!(
+            "recieved request to update notification settings of {:?}",
+            user_id
+        );
+
+        let settings = services
+            .follows
+            .update_notification_settings(user_id, request)
+            .await?;
+
+        Ok(Json(settings))
+    }
+}
@@ -17,8 +17,19 @@ pub async fn health_endpoint(
     let db_health = check_database_health(&services).await;
     let redis_health = check_redis_health(&services).await;
 
+    let upstreams = services.upstreams.health();
+
     // Determine overall status
-    let overall_status = determine_overall_status(&db_health, &redis_health);
+    let mut overall_status = determine_overall_status(&db_health, &redis_health);
+
+    // a scraped host being down breaks some endpoints but the api itself is fine
+    if overall_status == HealthStatus::Healthy
+        && upstreams
+            .values()
+            .any(|u| u.status != HealthStatus::Healthy)
+    {
+        overall_status = HealthStatus::Degraded;
+    }
 
     // Build response
     let response = HealthResponse {
@@ -30,6 +41,7 @@ pub async fn health_endpoint(
         services: ServiceHealthDetails {
             database: db_health,
             redis: redis_health,
+            upstreams,
         },
     };
 
@@ -0,0 +1,67 @@
+use axum::extract::Query;
+use axum::http::{HeaderMap, HeaderValue, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Extension, Router};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::server::error::AppResult;
+use crate::server::services::Services;
+use crate::server::utils::image_utils::ImageFormat;
+
+// proxied images never change for a given src and width so browsers can hang on to them
+const IMAGE_CACHE_CONTROL: &str = "public, max-age=604800, immutable";
+
+#[derive(Deserialize, Debug)]
+pub struct ImageQuery {
+    pub src: String,
+    pub w: Option<u32>,
+}
+
+pub struct ImageController;
+
+impl ImageController {
+    pub fn app() -> Router {
+        Router::new().route("/", get(Self::get_image_endpoint))
+    }
+
+    /// no auth here, <img> tags can't send it. the host allowlist is what stops this from being
+    /// an open proxy
+    pub async fn get_image_endpoint(
+        Extension(services): Extension<Services>,
+        headers: HeaderMap,
+        Query(query): Query<ImageQuery>,
+    ) -> AppResult<Response> {
+        info!(
+            "recieved request for image {} at width {:?}",
+            query.src, query.w
+        );
+
+        let format = ImageFormat::from_accept(
+            headers
+                .get(header::ACCEPT)
+                .and_then(|value| value.to_str().ok()),
+        );
+
+        let image = services
+            .images
+            .get_image(query.src, query.w, format)
+            .await?;
+
+        let mut response = image.bytes.into_response();
+        let response_headers = response.headers_mut();
+        response_headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(image.format.content_type()),
+        );
+        response_headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(IMAGE_CACHE_CONTROL),
+        );
+        // the format depends on what the browser accepts
+        response_headers.insert(header::VARY, HeaderValue::from_static("Accept"));
+
+        Ok(response)
+    }
+}
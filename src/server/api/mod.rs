@@ -1,5 +1,9 @@
+pub mod follow_controller;
 pub mod health_controller;
+pub mod image_controller;
 pub mod movie_controller;
 pub mod proxy_controller;
+pub mod room_controller;
 pub mod stream_controller;
 pub mod user_controller;
+pub mod watchlist_controller;
@@ -1,37 +1,29 @@
 use axum::Router;
-use axum::extract::{Json, Query};
-use axum::http::header;
-use axum::routing::{get, post};
+use axum::extract::{Json, Path, Query};
+use axum::http::{StatusCode, header};
+use axum::routing::{get, post, put};
 use base64::{Engine, engine::general_purpose::URL_SAFE};
-use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 use validator::Validate;
 
+use crate::server::api::proxy_controller::ProxyController;
+use crate::server::dtos::hls_dto::QualityQuery;
 use crate::server::dtos::movie_dto::{
     CaptionInfo, DecryptMovieRequest, DecryptMovieResponse, EncryptMovieRequest,
-    EncryptMovieResponse, GetMovieLinkResponse, VerifyKeyResponse, VidLinkResponse,
+    EncryptMovieResponse, GetMovieLinkQuery, GetMovieLinkResponse, NextEpisodeQuery,
+    NextEpisodeResponse, SignedMovieLinkResponse, TvEpisode, VerifyKeyResponse, VidLinkResponse,
+};
+use crate::server::dtos::progress_dto::{
+    ContinueWatchingResponse, MarkWatchedDto, ProgressSavedResponse, UpdateProgressDto,
+    WatchProgressDto,
 };
 use crate::server::error::{AppResult, Error};
-use crate::server::extractors::RequiredAuthentication;
-use crate::server::utils::signature_utils::SignatureUtil;
+use crate::server::extractors::{RequiredAuthentication, ValidationExtractor};
+use crate::server::services::Services;
+use crate::server::services::upstream_services::send_upstream;
 
 pub struct MovieController;
 
-#[derive(Debug, Deserialize)]
-pub struct GetMovieLinkQuery {
-    /// plain movie tv id
-    pub id: String,
-    /// option for episode number for tv shows
-    pub ep: Option<String>,
-}
-
-#[derive(Serialize)]
-pub struct SignedMovieLinkResponse {
-    pub signed_url: String,
-    pub expires_at: i64,
-    pub captions: Vec<CaptionInfo>,
-}
-
 impl MovieController {
     pub fn app() -> Router {
         Router::new()
@@ -43,6 +35,86 @@ impl MovieController {
                 get(Self::get_signed_movie_link_endpoint),
             )
             .route("/verify-key", get(Self::verify_key_endpoint))
+            .route("/progress", put(Self::update_progress_endpoint))
+            .route("/continue-watching", get(Self::continue_watching_endpoint))
+            .route("/watched", put(Self::mark_watched_endpoint))
+            .route("/tv/{id}/next", get(Self::get_next_episode_endpoint))
+    }
+
+    /// PUT /api/v1/movies/progress
+    ///
+    /// players call this every few seconds, writes closer together than 15s are skipped
+    /// (`saved: false`) unless the position is in the last 10% of the duration, which also marks
+    /// it as watched
+    ///
+    /// request body:
+    /// ```json
+    /// { "id": "1311031", "ep": null, "position": 1234.5, "duration": 6120 }
+    /// ```
+    pub async fn update_progress_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        ValidationExtractor(request): ValidationExtractor<UpdateProgressDto>,
+    ) -> AppResult<Json<ProgressSavedResponse>> {
+        debug!("received progress for {} {:?}", request.id, request.ep);
+
+        let saved = services.progress.update_progress(user_id, request).await?;
+
+        Ok(Json(saved))
+    }
+
+    /// GET /api/v1/movies/continue-watching
+    ///
+    /// started but unfinished, newest first, one entry per show
+    pub async fn continue_watching_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+    ) -> AppResult<Json<ContinueWatchingResponse>> {
+        info!(
+            "received request to retrieve continue watching for {}",
+            user_id
+        );
+
+        let items = services.progress.continue_watching(user_id).await?;
+
+        Ok(Json(ContinueWatchingResponse { items }))
+    }
+
+    /// PUT /api/v1/movies/watched
+    ///
+    /// request body:
+    /// ```json
+    /// { "id": "1311031", "ep": null, "watched": true }
+    /// ```
+    pub async fn mark_watched_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        ValidationExtractor(request): ValidationExtractor<MarkWatchedDto>,
+    ) -> AppResult<Json<WatchProgressDto>> {
+        info!(
+            "received request to mark {} {:?} as watched",
+            request.id, request.ep
+        );
+
+        let progress = services.progress.mark_watched(user_id, request).await?;
+
+        Ok(Json(progress))
+    }
+
+    // a link is still worth handing out if progress can't be read
+    async fn resume_at(
+        services: &Services,
+        user_id: &str,
+        id: &str,
+        episode: Option<TvEpisode>,
+    ) -> Option<f64> {
+        let episode = episode.map(|ep| ep.to_string()).unwrap_or_default();
+
+        services
+            .progress
+            .resume_at(user_id, id, &episode)
+            .await
+            .unwrap_or_else(|e| {
+                error!("failed to read progress for {}: {}", id, e);
+                None
+            })
     }
 
     /// these were confusing asf so they're like the only ones docs other than users
@@ -140,25 +212,23 @@ impl MovieController {
     /// get video stream and caption URLs from a certain website that is named elsewhere
     /// requires auth
     ///
-    /// FIXME:
-    /// same defn as the getmovielinkquery (why did i not put this with the
-    /// other dtos??????)
-    ///
     /// params:
     /// - `id`
-    /// - `ep`
+    /// - `season`, `episode`: both or neither, neither means it's a movie
+    /// - `ep`: older `1/5` form of the two above
     ///
     /// response:
     /// ```json
     /// {
     ///   "playlist_url": "/api/v1/proxy?url=...&movie=true",
     ///   "captions": [
-    ///     {"language": "English", "url": "https://..."}
-    ///   ]
+    ///     {"language": "English", "url": "/api/v1/proxy/captions?url=...&sig=...&exp=...&user=..."}
+    ///   ],
+    ///   "resume_at": 1234.5
     /// }
     /// ```
     pub async fn get_movie_link_endpoint(
-        RequiredAuthentication(_user_id, services): RequiredAuthentication,
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
         Query(params): Query<GetMovieLinkQuery>,
     ) -> AppResult<Json<GetMovieLinkResponse>> {
         info!(
@@ -166,129 +236,14 @@ impl MovieController {
             params.id
         );
 
-        // encrypt for 2 mins in the future
-        // this should be enough time....
-        // i could also sync this with the scraped key time time but no need
-        let future_timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map_err(|_| {
-                Error::InternalServerErrorWithContext("System time before UNIX epoch".to_string())
-            })?
-            .as_secs() as u32
-            + 240; // 4 mins
-
-        let encrypt_result = services
-            .movies
-            .encrypt_movie_id(&params.id, Some(future_timestamp))
-            .await?;
-        let encrypted_id = encrypt_result.encrypted_id;
-
-        debug!(
-            "encrypted movie ID: {} with timestamp: {}",
-            encrypted_id, future_timestamp
-        );
-
-        let movie_id = encrypted_id;
-
-        let (content_type, api_url) = if let Some(ref ep) = params.ep {
-            // uhhhhh no api is being called here there is no movies being scraped
-            let url = format!(
-                "https://vidlink.pro/api/b/tv/{}/{}?multilang=0",
-                movie_id, ep
-            );
-            ("tv", url)
-        } else {
-            let url = format!("https://vidlink.pro/api/b/movie/{}?multilang=0", movie_id);
-            ("movie", url)
-        };
-
-        info!("calling some API: {}", api_url);
+        let episode = params.tv_episode()?;
 
-        // i don't think it actually has to be this way but whatever i know it has to be
-        // there
-        let referer = if let Some(ref ep) = params.ep {
-            format!("https://vidlink.pro/tv/{}/{}", movie_id, ep)
-        } else {
-            format!("https://vidlink.pro/movie/{}", movie_id)
-        };
+        let (mut movie_link_response, _) =
+            Self::get_movie_link_internal(&services, &user_id, &params.id, episode).await?;
+        movie_link_response.resume_at =
+            Self::resume_at(&services, &user_id, &params.id, episode).await;
 
-        let client = reqwest::Client::new();
-        let response = client
-            .get(&api_url)
-            // 90% of these are really not needed but i also don't want to be randomly ip
-            // banned because of some user agent check (or cloudflare you never know)
-            .header(header::HOST, "vidlink.pro")
-            .header(
-                header::USER_AGENT,
-                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:145.0) Gecko/20100101 Firefox/145.0",
-            )
-            .header(header::ACCEPT, "*/*")
-            .header(header::ACCEPT_LANGUAGE, "en-US,en;q=0.5")
-            .header(header::REFERER, referer)
-            .header("Sec-GPC", "1")
-            .header(header::CONNECTION, "keep-alive")
-            .header("Sec-Fetch-Dest", "empty")
-            .header("Sec-Fetch-Mode", "cors")
-            .header("Sec-Fetch-Site", "same-origin")
-            .header("Priority", "u=4")
-            .send()
-            .await
-            .map_err(|e| {
-                tracing::error!("failed to call vidlink.pro API: {}", e);
-                Error::InternalServerErrorWithContext(format!("Failed to fetch stream: {}", e))
-            })?;
-
-        if !response.status().is_success() {
-            tracing::error!("vidlink.pro API returned error: {}", response.status());
-            Error::NotFound(format!("Stream not found for movie ID: {}", movie_id));
-        }
-
-        // this can be empty for things that aren't out or released yet so i should
-        // probably throw a softer error so it isn't caught by sentry but it should be ok
-        let vidlink_response: VidLinkResponse = response.json().await.map_err(|e| {
-            info!("Unable to serialize vidlink response: {}", e,);
-            Error::NotFound("Unable to obtain suitable response from api".to_string())
-        })?;
-
-        debug!(
-            "received playlist URL: {}",
-            vidlink_response.stream.playlist
-        );
-
-        let encoded_playlist_url = URL_SAFE
-            .encode(vidlink_response.stream.playlist.as_bytes())
-            .trim_end_matches('=')
-            .to_string();
-        let playlist_url = format!("/api/v1/proxy?url={}&schema=movie", encoded_playlist_url);
-
-        // don't ask about these they're soooooo annoying
-        let captions: Vec<CaptionInfo> = vidlink_response
-            .stream
-            .captions
-            .into_iter()
-            .map(|c| {
-                let encoded_caption_url = URL_SAFE
-                    .encode(c.url.as_bytes())
-                    .trim_end_matches('=')
-                    .to_string();
-                let proxied_url = format!("/api/v1/proxy/captions?url={}", encoded_caption_url);
-
-                CaptionInfo {
-                    language: c.language,
-                    url: proxied_url,
-                }
-            })
-            .collect();
-
-        info!(
-            "successfully retrieved stream link for movie ID: {} ({})",
-            movie_id, content_type
-        );
-
-        Ok(Json(GetMovieLinkResponse {
-            playlist_url,
-            captions,
-        }))
+        Ok(Json(movie_link_response))
     }
 
     /// GET /api/v1/movies/verify-key
@@ -324,79 +279,141 @@ impl MovieController {
     ///
     /// returns the playlist URL with HMAC signature auth
     ///
-    /// params:
-    /// - `id`:
-    /// - `ep`:
+    /// params: same as /movies/link
     ///
     /// response:
     /// ```json
     /// {
     ///   "signed_url": "/api/v1/proxy?url=...&schema=movie&sig=...&exp=...&user=...",
     ///   "expires_at": 1234567890,
-    ///   "captions": [{"language": "English", "url": "..."}]
+    ///   "captions": [{"language": "English", "url": "..."}],
+    ///   "resume_at": 1234.5
     /// }
     /// ```
     pub async fn get_signed_movie_link_endpoint(
         RequiredAuthentication(user_id, services): RequiredAuthentication,
         Query(params): Query<GetMovieLinkQuery>,
+        Query(quality): Query<QualityQuery>,
     ) -> AppResult<Json<SignedMovieLinkResponse>> {
         info!(
             "received request to get signed movie link for movie ID: {}",
             params.id
         );
 
-        let movie_link_response = Self::get_movie_link_internal(&services, &params).await?;
+        let episode = params.tv_episode()?;
 
-        let encoded_url = movie_link_response
-            .playlist_url
-            .split("url=")
-            .nth(1)
-            .and_then(|s| s.split('&').next())
-            .ok_or_else(|| {
-                Error::InternalServerErrorWithContext("Failed to parse playlist URL".to_string())
-            })?;
+        let signed =
+            Self::signed_movie_link(&services, &user_id, &params.id, episode, quality.max_height)
+                .await?;
 
-        // sign just the encoded url param, not the full path
-        // i got issues with gen and verification before so
-        let expiry = SignatureUtil::generate_expiry(12);
-        let url_for_signature = encoded_url.to_string();
+        Ok(Json(signed))
+    }
 
-        debug!("Generating signature for movie:");
-        debug!("  URL for signature: {}", url_for_signature);
-        debug!("  User: {}", user_id);
-        debug!("  Expiry: {}", expiry);
+    /// GET /api/v1/movies/tv/{id}/next?season=&episode=
+    ///
+    /// signed link for whatever comes after the given episode, for binge watching. tries the
+    /// next episode of the same season first and then the start of the next season, 404 when
+    /// vidlink has neither
+    ///
+    /// response:
+    /// ```json
+    /// {
+    ///   "season": 1,
+    ///   "episode": 6,
+    ///   "signed_url": "/api/v1/proxy?url=...&schema=movie&sig=...&exp=...&user=...",
+    ///   "expires_at": 1234567890,
+    ///   "captions": [{"language": "English", "url": "..."}],
+    ///   "resume_at": null
+    /// }
+    /// ```
+    pub async fn get_next_episode_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        Path(id): Path<String>,
+        Query(params): Query<NextEpisodeQuery>,
+        Query(quality): Query<QualityQuery>,
+    ) -> AppResult<Json<NextEpisodeResponse>> {
+        let current = params.tv_episode()?;
+
+        info!(
+            "received request for the episode after {} of {}",
+            current, id
+        );
+
+        for next in [current.next(), current.first_of_next_season()] {
+            let link =
+                Self::signed_movie_link(&services, &user_id, &id, Some(next), quality.max_height);
+            match link.await {
+                Ok(link) => {
+                    return Ok(Json(NextEpisodeResponse {
+                        season: next.season,
+                        episode: next.episode,
+                        link,
+                    }));
+                }
+                Err(Error::NotFound(_)) => debug!("no episode {} of {}", next, id),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(Error::NotFound(format!(
+            "there's no episode of {} after {}",
+            id, current
+        )))
+    }
 
-        let signature =
+    /// the whole signed link flow, also used to pre-resolve watchlist entries
+    pub async fn signed_movie_link(
+        services: &Services,
+        user_id: &str,
+        id: &str,
+        episode: Option<TvEpisode>,
+        max_height: Option<u32>,
+    ) -> AppResult<SignedMovieLinkResponse> {
+        let (movie_link_response, upstream_playlist) =
+            Self::get_movie_link_internal(services, user_id, id, episode).await?;
+
+        let (mut signed_url, expiry) =
             services
                 .signature_util
-                .generate_signature(&user_id, expiry, &url_for_signature);
-
-        let signed_url = format!(
-            "/api/v1/proxy?url={}&schema=movie&sig={}&exp={}&user={}",
-            encoded_url,
-            signature,
-            expiry,
-            urlencoding::encode(&user_id)
-        );
+                .signed_proxy_url(user_id, &upstream_playlist, "movie", None);
+        // not part of the signature, it only ever takes qualities away
+        if let Some(max_height) = max_height {
+            signed_url.push_str(&format!("&max_height={}", max_height));
+        }
+
+        let qualities = ProxyController::discover_qualities(
+            services,
+            user_id,
+            "movie",
+            "",
+            &upstream_playlist,
+            max_height,
+        )
+        .await;
 
         info!(
             "generated signed movie URL for {} (expires: {})",
-            params.id, expiry
+            id, expiry
         );
         debug!("  Full signed URL: {}", signed_url);
 
-        Ok(Json(SignedMovieLinkResponse {
+        Ok(SignedMovieLinkResponse {
             signed_url,
             expires_at: expiry,
             captions: movie_link_response.captions,
-        }))
+            resume_at: Self::resume_at(services, user_id, id, episode).await,
+            qualities,
+        })
     }
 
-    // internal helper to get movie link without authentication wrapper
+    // internal helper to get movie link without authentication wrapper, also hands back the
+    // upstream playlist url before it got proxied. caption links are signed for `user_id`
     async fn get_movie_link_internal(
-        services: &crate::server::services::Services,
-        params: &GetMovieLinkQuery,
-    ) -> AppResult<GetMovieLinkResponse> {
+        services: &Services,
+        user_id: &str,
+        id: &str,
+        episode: Option<TvEpisode>,
+    ) -> AppResult<(GetMovieLinkResponse, String)> {
         // encrypt the movie ID with timestamp 4 minutes in the future
         // per their key making
         let future_timestamp = std::time::SystemTime::now()
@@ -409,7 +426,7 @@ impl MovieController {
 
         let encrypt_result = services
             .movies
-            .encrypt_movie_id(&params.id, Some(future_timestamp))
+            .encrypt_movie_id(id, Some(future_timestamp))
             .await?;
         let encrypted_id = encrypt_result.encrypted_id;
 
@@ -420,29 +437,26 @@ impl MovieController {
 
         let movie_id = encrypted_id;
 
-        let (content_type, api_url) = if let Some(ref ep) = params.ep {
-            let url = format!(
-                "https://vidlink.pro/api/b/tv/{}/{}?multilang=0",
-                movie_id, ep
-            );
+        let vidlink_url = services.config.vidlink_url.trim_end_matches('/');
+        let (content_type, api_url) = if let Some(ep) = episode {
+            let url = format!("{}/api/b/tv/{}/{}?multilang=0", vidlink_url, movie_id, ep);
             ("tv", url)
         } else {
-            let url = format!("https://vidlink.pro/api/b/movie/{}?multilang=0", movie_id);
+            let url = format!("{}/api/b/movie/{}?multilang=0", vidlink_url, movie_id);
             ("movie", url)
         };
 
         info!("calling vidlink.pro API: {}", api_url);
 
-        let referer = if let Some(ref ep) = params.ep {
-            format!("https://vidlink.pro/tv/{}/{}", movie_id, ep)
+        let referer = if let Some(ep) = episode {
+            format!("{}/tv/{}/{}", vidlink_url, movie_id, ep)
         } else {
-            format!("https://vidlink.pro/movie/{}", movie_id)
+            format!("{}/movie/{}", vidlink_url, movie_id)
         };
 
         let client = reqwest::Client::new();
-        let response = client
+        let request = client
             .get(&api_url)
-            .header(header::HOST, "vidlink.pro")
             .header(
                 header::USER_AGENT,
                 "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:145.0) Gecko/20100101 Firefox/145.0",
@@ -455,18 +469,33 @@ impl MovieController {
             .header("Sec-Fetch-Dest", "empty")
             .header("Sec-Fetch-Mode", "cors")
             .header("Sec-Fetch-Site", "same-origin")
-            .header("Priority", "u=4")
-            .send()
+            .header("Priority", "u=4");
+        let response = send_upstream(&services.upstreams, request)
             .await
             .map_err(|e| {
                 tracing::error!("failed to call vidlink.pro API: {}", e);
-                Error::InternalServerErrorWithContext(format!("Failed to fetch stream: {}", e))
+                Error::from_upstream(e, |e| {
+                    Error::InternalServerErrorWithContext(format!("Failed to fetch stream: {}", e))
+                })
             })?;
 
+        // vidlink answers episodes past the end of a season like this, so it's worth saying which
+        let missing = || match episode {
+            Some(ep) => Error::NotFound(format!("no episode {} of {}", ep, id)),
+            None => Error::NotFound(format!("no movie {}", id)),
+        };
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(missing());
+        }
+
+        // anything else is vidlink having problems, not the title missing. the next episode lookup
+        // relies on that to tell the end of a season from an outage
         if !response.status().is_success() {
             tracing::error!("vidlink.pro API returned error: {}", response.status());
-            return Err(Error::NotFound(format!(
-                "Stream not found for movie ID: {}",
+            return Err(Error::InternalServerErrorWithContext(format!(
+                "vidlink.pro returned {} for movie ID: {}",
+                response.status(),
                 movie_id
             )));
         }
@@ -490,43 +519,44 @@ impl MovieController {
         // literally anything else that is coming out soon #noticing
         //
         // FIXME:
-        let vidlink_response: VidLinkResponse = response.json().await.map_err(|e| {
-            // add a line here to check if the response was empty, in which case we say that
+        let body = response.bytes().await.map_err(|e| {
+            tracing::error!("failed to read vidlink.pro response: {}", e);
+            Error::InternalServerErrorWithContext("Failed to read stream response".to_string())
+        })?;
+
+        // an empty body or a null stream is vidlink's other way of saying it has nothing
+        if body.iter().all(u8::is_ascii_whitespace) {
+            return Err(missing());
+        }
+
+        let vidlink_response: VidLinkResponse = serde_json::from_slice(&body).map_err(|e| {
             tracing::error!("failed to parse vidlink.pro response: {}", e);
             Error::InternalServerErrorWithContext("Failed to parse stream response".to_string())
         })?;
+        let stream = vidlink_response
+            .stream
+            .filter(|s| !s.playlist.is_empty())
+            .ok_or_else(missing)?;
 
         // the problem could also be here because the stream.playlist should not exist. Instead i
         // would need to fetch stream.qualities.1080.url and make sure that the ../qualities.type
         // is mp4 because confirming the schema.
         //
         // note that this is weird because vidlink doesn't want to play the links anyways
-        debug!(
-            "received playlist URL: {}",
-            vidlink_response.stream.playlist
-        );
+        debug!("received playlist URL: {}", stream.playlist);
 
         let encoded_playlist_url = URL_SAFE
-            .encode(vidlink_response.stream.playlist.as_bytes())
+            .encode(stream.playlist.as_bytes())
             .trim_end_matches('=')
             .to_string();
         let playlist_url = format!("/api/v1/proxy?url={}&schema=movie", encoded_playlist_url);
 
-        let captions: Vec<CaptionInfo> = vidlink_response
-            .stream
+        let captions: Vec<CaptionInfo> = stream
             .captions
             .into_iter()
-            .map(|c| {
-                let encoded_caption_url = URL_SAFE
-                    .encode(c.url.as_bytes())
-                    .trim_end_matches('=')
-                    .to_string();
-                let proxied_url = format!("/api/v1/proxy/captions?url={}", encoded_caption_url);
-
-                CaptionInfo {
-                    language: c.language,
-                    url: proxied_url,
-                }
+            .map(|c| CaptionInfo {
+                language: c.language,
+                url: services.signature_util.signed_captions_url(user_id, &c.url),
             })
             .collect();
 
@@ -535,9 +565,14 @@ impl MovieController {
             movie_id, content_type
         );
 
-        Ok(GetMovieLinkResponse {
-            playlist_url,
-            captions,
-        })
+        // filled in by whoever knows the user
+        Ok((
+            GetMovieLinkResponse {
+                playlist_url,
+                captions,
+                resume_at: None,
+            },
+            stream.playlist,
+        ))
     }
 }
@@ -5,22 +5,44 @@
 use axum::{
     Router,
     extract::Query,
-    http::{HeaderMap, StatusCode, header},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
     response::{IntoResponse, Response},
     routing::get,
 };
 use base64::{Engine as _, engine::general_purpose::URL_SAFE};
 use serde::Deserialize;
+use std::time::Duration;
 use tracing::{debug, error, info};
 
 use crate::server::{
-    extractors::RequiredAuthentication, services::Services, utils::signature_utils::SignatureUtil,
+    dtos::hls_dto::QualityDto,
+    error::{AppResult, Error},
+    extractors::ProxyAuthentication,
+    services::{Services, ppvsu_services::PPVSU_PROVIDER, stream_provider::HeaderProfile},
+    utils::hls_utils::{filter_master_playlist, parse_master_playlist, resolve_playlist_uri},
 };
 
+// discovering qualities holds up handing out the link, a slow cdn shouldn't hold it up for long
+const QUALITIES_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Deserialize)]
 struct ProxyQuery {
     url: String,
     schema: Option<String>,
+    /// which stream provider's headers to use for the sports schema, urls signed before this
+    /// existed don't have it so it falls back to ppvsu
+    provider: Option<String>,
+    /// only does anything for master playlists, see hls_utils
+    max_height: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct CaptionsQuery {
+    url: String,
+    /// moves every cue later, or earlier when negative
+    offset_ms: Option<i64>,
+    /// for legacy encoded files the source doesn't label, e.g. `windows-1251`
+    charset: Option<String>,
 }
 
 pub struct ProxyController;
@@ -33,7 +55,7 @@ impl ProxyController {
     }
 
     async fn proxy_get(
-        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        ProxyAuthentication(user_id, services): ProxyAuthentication,
         Query(params): Query<ProxyQuery>,
         headers: HeaderMap,
     ) -> Result<Response, (StatusCode, String)> {
@@ -44,11 +66,21 @@ impl ProxyController {
         }
 
         let schema = params.schema.as_deref().unwrap_or("sports");
-        debug!("Proxying (schema={}): {}", schema, target_url);
+        let provider = params.provider.as_deref().unwrap_or(PPVSU_PROVIDER);
+        debug!(
+            "Proxying (schema={}, provider={}): {}",
+            schema, provider, target_url
+        );
 
         let client = reqwest::Client::new();
-        let request_builder =
-            Self::apply_schema_headers(client.get(&target_url), schema, &target_url, &headers);
+        let request_builder = Self::apply_schema_headers(
+            client.get(&target_url),
+            schema,
+            provider,
+            &target_url,
+            &headers,
+            &services,
+        );
         debug!("Sending request to target");
 
         let target_response = request_builder.send().await.map_err(|e| {
@@ -152,12 +184,18 @@ impl ProxyController {
             })?;
             debug!("M3U8 text length: {} chars", text.len());
 
+            let text = match params.max_height {
+                Some(max_height) => filter_master_playlist(&text, max_height),
+                None => text,
+            };
+
             let processed_body = Self::process_m3u8_by_schema_with_retry(
                 &text,
                 &target_url,
                 &user_id,
                 &services,
                 schema,
+                provider,
             )?;
             debug!(
                 "Processed M3U8, response length: {} bytes",
@@ -238,85 +276,121 @@ impl ProxyController {
         }
     }
 
+    /// the qualities a playlist offers, each with its own signed proxy url. empty when it's not a
+    /// master playlist or couldn't be fetched, the playlist's own url works either way. playlists
+    /// are cached so only the first link handed out for one waits on the upstream
+    pub async fn discover_qualities(
+        services: &Services,
+        user_id: &str,
+        schema: &str,
+        provider: &str,
+        playlist_url: &str,
+        max_height: Option<u32>,
+    ) -> Vec<QualityDto> {
+        let cached = match services.playlists.get(playlist_url) {
+            Some(cached) => cached,
+            None => {
+                let fetched = Self::fetch_playlist(services, schema, provider, playlist_url)
+                    .await
+                    .map_err(|e| error!("failed to fetch {} for qualities: {}", playlist_url, e))
+                    .ok();
+                services.playlists.put(playlist_url, fetched.clone());
+                fetched
+            }
+        };
+        let Some(text) = cached else {
+            return vec![];
+        };
+        let text = match max_height {
+            Some(max_height) => filter_master_playlist(&text, max_height),
+            None => text,
+        };
+
+        let mut variants = parse_master_playlist(&text);
+        variants.sort_by_key(|v| std::cmp::Reverse(v.bandwidth));
+
+        variants
+            .into_iter()
+            .filter_map(|variant| {
+                let url = resolve_playlist_uri(playlist_url, &variant.uri)?;
+                let provider = Some(provider).filter(|_| schema == "sports");
+                let (signed_url, _) = services
+                    .signature_util
+                    .signed_proxy_url(user_id, &url, schema, provider);
+                Some(variant.into_dto(signed_url))
+            })
+            .collect()
+    }
+
+    async fn fetch_playlist(
+        services: &Services,
+        schema: &str,
+        provider: &str,
+        playlist_url: &str,
+    ) -> anyhow::Result<String> {
+        let client = reqwest::Client::new();
+        let response = Self::apply_schema_headers(
+            client.get(playlist_url).timeout(QUALITIES_TIMEOUT),
+            schema,
+            provider,
+            playlist_url,
+            &HeaderMap::new(),
+            services,
+        )
+        .send()
+        .await?
+        .error_for_status()?;
+
+        let zstd = response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .is_some_and(|v| v.as_bytes() == b"zstd");
+        let bytes = response.bytes().await?;
+        let bytes = if zstd {
+            zstd::decode_all(&bytes[..])?
+        } else {
+            bytes.to_vec()
+        };
+
+        Ok(String::from_utf8(bytes)?)
+    }
+
     async fn proxy_options() -> impl IntoResponse {
         StatusCode::NO_CONTENT
     }
 
-    // this is unathenticated because of a previous track implementation but i think it's fetched
-    // with the auth header anyways now so it should be closed again - double check later TODO:
-    //
-    // ps: im not checking this i don't care steal my captions dude
+    // `<track>` elements can't send the auth header so the links handed out are signed. logged in
+    // users can still pass any url, the captions service only fetches ones on public addresses
     async fn proxy_captions(
-        Query(params): Query<ProxyQuery>,
-    ) -> Result<Response, (StatusCode, String)> {
-        let target_url = Self::decode_url(&params.url)?;
+        ProxyAuthentication(user_id, services): ProxyAuthentication,
+        Query(params): Query<CaptionsQuery>,
+    ) -> AppResult<Response> {
+        let target_url =
+            Self::decode_url(&params.url).map_err(|(_, message)| Error::BadRequest(message))?;
 
         if !target_url.starts_with("http://") && !target_url.starts_with("https://") {
-            return Err((StatusCode::BAD_REQUEST, "Invalid URL format".to_string()));
-        }
-
-        debug!("Proxying caption: {}", target_url);
-
-        let client = reqwest::Client::new();
-        let request_builder = client
-            .get(&target_url)
-            .header(
-                header::USER_AGENT,
-                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:145.0) Gecko/20100101 Firefox/145.0",
-            )
-            .header(header::ACCEPT, "*/*");
-
-        let target_response = request_builder.send().await.map_err(|e| {
-            error!("Caption request failed: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e))
-        })?;
-
-        if !target_response.status().is_success() {
-            error!("Caption fetch failed: {}", target_response.status());
-            return Err((
-                StatusCode::from_u16(target_response.status().as_u16())
-                    .unwrap_or(StatusCode::BAD_GATEWAY),
-                format!("Failed to fetch caption: {}", target_response.status()),
-            ));
+            return Err(Error::BadRequest("Invalid URL format".to_string()));
         }
 
-        let content_type = target_response
-            .headers()
-            .get(header::CONTENT_TYPE)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("text/vtt")
-            .to_string();
+        debug!("Proxying caption for {}: {}", user_id, target_url);
 
-        let bytes = target_response.bytes().await.map_err(|e| {
-            error!("Failed to read caption response: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, format!("Error: {}", e))
-        })?;
+        let vtt = services
+            .captions
+            .get_captions(target_url, params.charset, params.offset_ms.unwrap_or(0))
+            .await?;
 
-        let mut response_headers = HeaderMap::new();
+        let mut response = vtt.into_response();
+        let response_headers = response.headers_mut();
         response_headers.insert(
             header::CONTENT_TYPE,
-            content_type.parse().unwrap_or_else(|_| {
-                "text/vtt"
-                    .parse()
-                    .expect("Static header value should parse")
-            }),
+            HeaderValue::from_static("text/vtt; charset=utf-8"),
         );
         response_headers.insert(
             header::CACHE_CONTROL,
-            "public, max-age=86400"
-                .parse()
-                .expect("Static header value should parse"),
-        );
-        response_headers.insert(
-            header::CONTENT_LENGTH,
-            bytes
-                .len()
-                .to_string()
-                .parse()
-                .expect("Content length should parse"),
+            HeaderValue::from_static("private, max-age=86400"),
         );
 
-        Ok((StatusCode::OK, response_headers, bytes).into_response())
+        Ok(response)
     }
 
     fn decode_url(url_param: &str) -> Result<String, (StatusCode, String)> {
@@ -329,7 +403,7 @@ impl ProxyController {
                 })
         } else {
             let mut padded = url_param.to_string();
-            while padded.len() % 4 != 0 {
+            while !padded.len().is_multiple_of(4) {
                 padded.push('=');
             }
 
@@ -348,18 +422,42 @@ impl ProxyController {
         }
     }
 
+    fn apply_header_profile(
+        mut request_builder: reqwest::RequestBuilder,
+        profile: &HeaderProfile,
+    ) -> reqwest::RequestBuilder {
+        if let Some(origin) = &profile.origin {
+            request_builder = request_builder.header(header::ORIGIN, origin);
+        }
+        if let Some(referer) = &profile.referer {
+            request_builder = request_builder.header(header::REFERER, referer);
+        }
+
+        request_builder
+            .header(header::USER_AGENT, &profile.user_agent)
+            .header(header::ACCEPT, "*/*")
+    }
+
     fn apply_schema_headers(
         mut request_builder: reqwest::RequestBuilder,
         schema: &str,
+        provider: &str,
         target_url: &str,
         headers: &HeaderMap,
+        services: &Services,
     ) -> reqwest::RequestBuilder {
         match schema {
             "movie" => {
                 request_builder
                     .header(header::HOST, "storm.vodvidl.site")
-                    .header(header::ORIGIN, "https://vidlink.pro")
-                    .header(header::REFERER, "https://vidlink.pro/")
+                    .header(
+                        header::ORIGIN,
+                        services.config.vidlink_url.trim_end_matches('/'),
+                    )
+                    .header(
+                        header::REFERER,
+                        format!("{}/", services.config.vidlink_url.trim_end_matches('/')),
+                    )
                     .header(
                         header::USER_AGENT,
                         "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:145.0) Gecko/20100101 Firefox/145.0",
@@ -367,30 +465,22 @@ impl ProxyController {
                     .header(header::ACCEPT, "*/*")
             }
             "sports" => {
-                if !target_url.contains("gg.poocloud.in") {
-                    request_builder = request_builder
-                        .header(header::ORIGIN, "https://embednow.top")
-                        .header(header::REFERER, "https://embednow.top/")
-                        .header(
-                            header::USER_AGENT,
-                            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
-                        )
-                        .header(header::ACCEPT, "*/*");
-                } else {
-                    request_builder = request_builder
-                        .header(header::REFERER, "https://api.ppvs.su/api/streams/")
-                        .header(header::ORIGIN, "https://api.ppvs.su/api/streams")
-                        .header(
-                            header::USER_AGENT,
-                            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
-                        )
-                        .header(header::ACCEPT, "*/*");
+                // the provider knows which cdn wants which headers
+                let provider = services
+                    .providers
+                    .get(provider)
+                    .or_else(|| services.providers.get(PPVSU_PROVIDER));
+                if let Some(provider) = provider {
+                    request_builder = Self::apply_header_profile(
+                        request_builder,
+                        &provider.header_profile(target_url),
+                    );
                 }
 
-                if let Some(range_header) = headers.get(header::RANGE) {
-                    if let Ok(range_value) = range_header.to_str() {
-                        request_builder = request_builder.header(header::RANGE, range_value);
-                    }
+                if let Some(range_header) = headers.get(header::RANGE)
+                    && let Ok(range_value) = range_header.to_str()
+                {
+                    request_builder = request_builder.header(header::RANGE, range_value);
                 }
 
                 request_builder
@@ -407,8 +497,14 @@ impl ProxyController {
                 // default to sports if anything, but this ideally shouldn't happen
                 info!("Unknown schema, falling back to sports headers");
                 request_builder = request_builder
-                    .header(header::REFERER, "https://api.ppvs.su/api/streams/")
-                    .header(header::ORIGIN, "https://api.ppvs.su/api/streams")
+                    .header(
+                        header::REFERER,
+                        format!("{}/api/streams/", services.config.ppvsu_api_url.trim_end_matches('/')),
+                    )
+                    .header(
+                        header::ORIGIN,
+                        format!("{}/api/streams", services.config.ppvsu_api_url.trim_end_matches('/')),
+                    )
                     .header(
                         header::USER_AGENT,
                         "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
@@ -416,10 +512,10 @@ impl ProxyController {
                     .header(header::ACCEPT_ENCODING, "gzip, deflate, br, zstd")
                     .header(header::ACCEPT, "*/*");
 
-                if let Some(range_header) = headers.get(header::RANGE) {
-                    if let Ok(range_value) = range_header.to_str() {
-                        request_builder = request_builder.header(header::RANGE, range_value);
-                    }
+                if let Some(range_header) = headers.get(header::RANGE)
+                    && let Ok(range_value) = range_header.to_str()
+                {
+                    request_builder = request_builder.header(header::RANGE, range_value);
                 }
 
                 request_builder
@@ -433,6 +529,7 @@ impl ProxyController {
         user_id: &str,
         services: &Services,
         schema: &str,
+        provider: &str,
     ) -> Result<String, (StatusCode, String)> {
         match schema {
             "movie" => {
@@ -441,7 +538,7 @@ impl ProxyController {
             }
             _ => {
                 debug!("Processing with sports schema");
-                Self::process_m3u8(text, target_url, user_id, services)
+                Self::process_m3u8(text, target_url, user_id, services, provider)
             }
         }
     }
@@ -452,8 +549,10 @@ impl ProxyController {
         user_id: &str,
         services: &Services,
         schema: &str,
+        provider: &str,
     ) -> Result<String, (StatusCode, String)> {
-        let result = Self::process_m3u8_by_schema(text, target_url, user_id, services, schema);
+        let result =
+            Self::process_m3u8_by_schema(text, target_url, user_id, services, schema, provider);
 
         match result {
             Err((StatusCode::INTERNAL_SERVER_ERROR, ref err_msg)) => {
@@ -462,7 +561,7 @@ impl ProxyController {
                     err_msg
                 );
                 // Retry once on 500 error
-                Self::process_m3u8_by_schema(text, target_url, user_id, services, schema)
+                Self::process_m3u8_by_schema(text, target_url, user_id, services, schema, provider)
             }
             other => other,
         }
@@ -473,6 +572,7 @@ impl ProxyController {
         target_url: &str,
         user_id: &str,
         services: &Services,
+        provider: &str,
     ) -> Result<String, (StatusCode, String)> {
         let base_url = url::Url::parse(target_url).map_err(|e| {
             error!("Failed to parse base URL: {}", e);
@@ -512,25 +612,13 @@ impl ProxyController {
                     }
                 };
 
-                let encoded = URL_SAFE
-                    .encode(full_url.as_bytes())
-                    .trim_end_matches('=')
-                    .to_string();
-
-                // Generate signed URL parameters
-                let expiry = SignatureUtil::generate_expiry(12);
-                // Sign just the encoded URL to avoid path mismatch issues
-                let signature = services
-                    .signature_util
-                    .generate_signature(user_id, expiry, &encoded);
-
-                format!(
-                    "/api/v1/proxy?url={}&schema=sports&sig={}&exp={}&user={}",
-                    encoded,
-                    signature,
-                    expiry,
-                    urlencoding::encode(user_id)
-                )
+                let (signed_url, _) = services.signature_util.signed_proxy_url(
+                    user_id,
+                    &full_url,
+                    "sports",
+                    Some(provider),
+                );
+                signed_url
             })
             .collect();
 
@@ -588,25 +676,10 @@ impl ProxyController {
                     }
                 };
 
-                let encoded = URL_SAFE
-                    .encode(full_url.as_bytes())
-                    .trim_end_matches('=')
-                    .to_string();
-
-                // Generate signed URL parameters
-                let expiry = SignatureUtil::generate_expiry(12);
-                // Sign just the encoded URL to avoid path mismatch issues
-                let signature = services
+                let (signed_url, _) = services
                     .signature_util
-                    .generate_signature(user_id, expiry, &encoded);
-
-                format!(
-                    "/api/v1/proxy?url={}&schema=movie&sig={}&exp={}&user={}",
-                    encoded,
-                    signature,
-                    expiry,
-                    urlencoding::encode(user_id)
-                )
+                    .signed_proxy_url(user_id, &full_url, "movie", None);
+                signed_url
             })
             .collect();
 
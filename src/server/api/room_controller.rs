@@ -0,0 +1,178 @@
+use axum::Router;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Json, Path};
+use axum::http::StatusCode;
+use axum::response::Response;
+use axum::routing::{get, post};
+use chrono::Utc;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use std::time::Duration;
+use tracing::{debug, error, info};
+
+use crate::database::room::RoomEvent;
+use crate::server::dtos::room_dto::{
+    CreateRoomDto, RoomCommand, RoomFrame, RoomResponse, RoomSocketMessage,
+};
+use crate::server::error::AppResult;
+use crate::server::extractors::{RequiredAuthentication, ValidationExtractor};
+use crate::server::services::Services;
+
+pub struct RoomController;
+
+// proxies tend to drop connections that go quiet for a minute
+const SOCKET_HEARTBEAT: Duration = Duration::from_secs(15);
+
+impl RoomController {
+    pub fn app() -> Router {
+        Router::new()
+            .route("/", post(Self::create_room_endpoint))
+            .route("/{id}", get(Self::get_room_endpoint))
+            .route("/{id}/ws", get(Self::room_socket_endpoint))
+    }
+
+    pub async fn create_room_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        ValidationExtractor(request): ValidationExtractor<CreateRoomDto>,
+    ) -> AppResult<(StatusCode, Json<RoomResponse>)> {
+        info!("recieved request from {} to create a room", user_id);
+
+        let room = services.rooms.create_room(user_id, request).await?;
+
+        Ok((
+            StatusCode::CREATED,
+            Json(RoomResponse {
+                room,
+                server_time: Utc::now().timestamp_millis(),
+            }),
+        ))
+    }
+
+    pub async fn get_room_endpoint(
+        RequiredAuthentication(_user_id, services): RequiredAuthentication,
+        Path(id): Path<String>,
+    ) -> AppResult<Json<RoomResponse>> {
+        info!("recieved request to retrieve room {:?}", id);
+
+        let room = services.rooms.get_room(&id).await?;
+
+        Ok(Json(RoomResponse {
+            room,
+            server_time: Utc::now().timestamp_millis(),
+        }))
+    }
+
+    /// browsers can't set headers on websockets, `?token=` works for those
+    pub async fn room_socket_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        Path(id): Path<String>,
+        upgrade: WebSocketUpgrade,
+    ) -> AppResult<Response> {
+        info!("recieved request from {} to join room {:?}", user_id, id);
+
+        // a room that doesn't exist is a 404 rather than a socket that closes straight away
+        services.rooms.get_room(&id).await?;
+
+        Ok(upgrade.on_upgrade(move |socket| Self::run_socket(socket, services, id, user_id)))
+    }
+
+    async fn run_socket(socket: WebSocket, services: Services, room_id: String, user_id: String) {
+        let rooms = services.rooms;
+        let (mut sender, mut receiver) = socket.split();
+
+        // subscribe before reading the room so nothing the host does in between gets lost
+        let mut events = rooms.subscribe(&room_id);
+
+        let welcome = match (
+            rooms.get_room(&room_id).await,
+            rooms.get_chat(&room_id).await,
+        ) {
+            (Ok(room), Ok(chat)) => RoomSocketMessage::Welcome { room, chat },
+            (Err(e), _) | (_, Err(e)) => {
+                error!("failed to load room {} for {}: {}", room_id, user_id, e);
+                return;
+            }
+        };
+        if !Self::send(&mut sender, welcome).await {
+            return;
+        }
+
+        metrics::gauge!("room_sockets_open").increment(1);
+        rooms
+            .announce(
+                &room_id,
+                RoomEvent::Joined {
+                    user_id: user_id.clone(),
+                },
+            )
+            .await;
+
+        let mut heartbeat = tokio::time::interval(SOCKET_HEARTBEAT);
+        loop {
+            tokio::select! {
+                event = events.next() => {
+                    // the stream only ends when this socket fell behind, the client reconnects
+                    let Some(event) = event else { break };
+                    if !Self::send(&mut sender, RoomSocketMessage::Event(event)).await {
+                        break;
+                    }
+                }
+                message = receiver.next() => {
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        // pings are answered for us, nothing else means anything
+                        Some(Ok(_)) => continue,
+                    };
+
+                    let reply = match serde_json::from_str::<RoomCommand>(text.as_str()) {
+                        Ok(command) => {
+                            debug!("{} sent {:?} to room {}", user_id, command, room_id);
+                            rooms
+                                .handle_command(&room_id, &user_id, command)
+                                .await
+                                .unwrap_or_else(|e| {
+                                    Some(RoomSocketMessage::Error {
+                                        message: e.to_string(),
+                                    })
+                                })
+                        }
+                        Err(e) => Some(RoomSocketMessage::Error {
+                            message: format!("couldn't read that message: {}", e),
+                        }),
+                    };
+
+                    if let Some(reply) = reply
+                        && !Self::send(&mut sender, reply).await
+                    {
+                        break;
+                    }
+                }
+                _ = heartbeat.tick() => {
+                    if sender.send(Message::Ping(Default::default())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        metrics::gauge!("room_sockets_open").decrement(1);
+        rooms.announce(&room_id, RoomEvent::Left { user_id }).await;
+    }
+
+    // false once the socket is gone
+    async fn send(sender: &mut SplitSink<WebSocket, Message>, message: RoomSocketMessage) -> bool {
+        let frame = RoomFrame {
+            message,
+            server_time: Utc::now().timestamp_millis(),
+        };
+
+        match serde_json::to_string(&frame) {
+            Ok(json) => sender.send(Message::Text(json.into())).await.is_ok(),
+            Err(e) => {
+                error!("failed to serialize room message: {}", e);
+                true
+            }
+        }
+    }
+}
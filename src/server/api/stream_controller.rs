@@ -1,46 +1,464 @@
-use axum::Router;
-use axum::extract::{Json, Path};
-use axum::routing::{delete, get};
-use base64::{Engine as _, engine::general_purpose::URL_SAFE};
+use axum::extract::{Json, Path, Query};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::routing::{delete, get, put};
+use axum::{Extension, Router};
+use futures::{Stream, StreamExt};
 use serde::Serialize;
+use std::convert::Infallible;
+use std::time::Duration;
 use tracing::debug;
 use tracing::info;
 
-use crate::server::dtos::stream_dto::{GameDto, GameListResponse, ResponseStreamDto};
-use crate::server::error::AppResult;
-use crate::server::extractors::RequiredAuthentication;
-use crate::server::utils::signature_utils::SignatureUtil;
+use crate::server::api::proxy_controller::ProxyController;
+use crate::server::dtos::category_dto::{
+    CategoryAliasDto, CategoryInfoDto, CategoryListResponse, MapCategoryAliasDto,
+    UnmappedCategoryListResponse, UpsertCategoryDto,
+};
+use crate::server::dtos::history_dto::{HistoryQuery, HistoryResponse};
+use crate::server::dtos::hls_dto::{QualityDto, QualityQuery};
+use crate::server::dtos::stream_dto::{
+    CalendarFeedUrlResponse, FeedKeyQuery, GameListQuery, IptvFeedUrlResponse, ResponseStreamDto,
+    SourceQuery, SourceSelection, StreamEventsQuery,
+};
+use crate::server::dtos::team_dto::{TeamAliasDto, TeamAliasListResponse, UpsertTeamAliasDto};
+use crate::server::error::{AppResult, Error};
+use crate::server::extractors::{AdminAuthentication, RequiredAuthentication, ValidationExtractor};
+use crate::server::services::Services;
+use crate::server::services::stream_services::listing_clock;
+use crate::server::utils::etag_utils::{CACHE_PRIVATE_SHORT, ETag};
+use crate::server::utils::iptv_utils::IptvFeed;
 
 pub struct StreamController;
 
+const CALENDAR_FEED: &str = "calendar";
+const IPTV_FEED: &str = "iptv";
+
+// proxies tend to drop connections that go quiet for a minute
+const EVENTS_HEARTBEAT: Duration = Duration::from_secs(15);
+
 #[derive(Serialize)]
 pub struct SignedUrlResponse {
     pub signed_url: String,
     pub expires_at: i64,
+    /// which of the game's sources got signed
+    pub source: usize,
+    pub source_label: String,
+    /// empty unless the source's playlist is a master playlist
+    pub qualities: Vec<QualityDto>,
 }
 
 impl StreamController {
     pub fn app() -> Router {
         Router::new()
             .route("/", get(Self::get_all_streams_endpoint))
-            .route("/ppvsu/cache", delete(Self::clear_ppvsu_cache_endpoint))
-            .route("/ppvsu/{id}", get(Self::get_ppvsu_game_endpoint))
+            .route("/events", get(Self::get_events_endpoint))
+            .route("/calendar.ics", get(Self::get_calendar_endpoint))
+            .route("/calendar/feed", get(Self::get_calendar_feed_url_endpoint))
+            .route("/calendar/feed.ics", get(Self::get_calendar_feed_endpoint))
+            .route("/iptv/feed", get(Self::get_iptv_feed_urls_endpoint))
+            .route("/playlist.m3u", get(Self::get_playlist_endpoint))
+            .route("/guide.xml", get(Self::get_guide_endpoint))
+            .route("/history", get(Self::get_history_endpoint))
+            .route("/categories", get(Self::get_categories_endpoint))
             .route(
-                "/ppvsu/{id}/decode",
-                get(Self::get_ppvsu_decoded_game_endpoint),
+                "/categories/unmapped",
+                get(Self::get_unmapped_categories_endpoint),
+            )
+            .route(
+                "/categories/aliases",
+                put(Self::map_category_alias_endpoint),
+            )
+            .route("/categories/{slug}", put(Self::upsert_category_endpoint))
+            .route("/teams/aliases", get(Self::get_team_aliases_endpoint))
+            .route(
+                "/teams/aliases/{alias}",
+                put(Self::upsert_team_alias_endpoint).delete(Self::delete_team_alias_endpoint),
             )
-            .route("/ppvsu/{id}/signed-url", get(Self::get_signed_url_endpoint))
             .route("/{provider}", get(Self::get_stream_endpoint))
+            .route("/{provider}/cache", delete(Self::clear_cache_endpoint))
+            .route("/{provider}/{id}", get(Self::get_game_endpoint))
+            .route(
+                "/{provider}/{id}/decode",
+                get(Self::get_decoded_game_endpoint),
+            )
+            .route(
+                "/{provider}/{id}/signed-url",
+                get(Self::get_signed_url_endpoint),
+            )
+            .route("/{provider}/{id}/play", get(Self::play_endpoint))
+            .route(
+                "/{provider}/{id}/resolved",
+                delete(Self::invalidate_resolved_links_endpoint),
+            )
     }
 
     pub async fn get_all_streams_endpoint(
         RequiredAuthentication(_user_id, services): RequiredAuthentication,
-    ) -> AppResult<Json<GameListResponse>> {
-        info!("recieved request to retrieve all games with auto-fetch");
+        headers: HeaderMap,
+        Query(query): Query<GameListQuery>,
+    ) -> AppResult<Response> {
+        info!("recieved request to retrieve all games");
+
+        // versioned before the games are read, if a refresh lands in between the client just
+        // misses next time instead of getting a 304 for something it never saw
+        let etag = ETag::strong(
+            services.streams.get_listing_version().await?,
+            CACHE_PRIVATE_SHORT,
+        );
+        if etag.matches(&headers) {
+            return Ok(etag.not_modified());
+        }
+
+        let games = services.streams.get_all_games(query).await?;
+
+        Ok(etag.respond(&headers, Json(games)))
+    }
+
+    /// every game refreshes have cached, including ones long gone from the listing
+    pub async fn get_history_endpoint(
+        RequiredAuthentication(_user_id, services): RequiredAuthentication,
+        Query(query): Query<HistoryQuery>,
+    ) -> AppResult<Json<HistoryResponse>> {
+        info!("recieved request to retrieve game history");
+
+        let history = services.history.get_history(query).await?;
+
+        Ok(Json(history))
+    }
+
+    pub async fn get_categories_endpoint(
+        RequiredAuthentication(_user_id, services): RequiredAuthentication,
+    ) -> AppResult<Json<CategoryListResponse>> {
+        info!("recieved request to retrieve stream categories");
+
+        let categories = services.categories.get_categories().await?;
+
+        Ok(Json(CategoryListResponse { categories }))
+    }
+
+    /// provider category names nothing maps to yet, they show up in the listing under their raw
+    /// name until they're mapped
+    pub async fn get_unmapped_categories_endpoint(
+        AdminAuthentication(user_id, services): AdminAuthentication,
+    ) -> AppResult<Json<UnmappedCategoryListResponse>> {
+        info!(
+            "recieved request from {} to retrieve unmapped categories",
+            user_id
+        );
+
+        let unmapped = services.categories.get_unmapped().await?;
+
+        Ok(Json(UnmappedCategoryListResponse { unmapped }))
+    }
+
+    /// cached games pick the new mapping up on the next refresh
+    pub async fn map_category_alias_endpoint(
+        AdminAuthentication(user_id, services): AdminAuthentication,
+        ValidationExtractor(request): ValidationExtractor<MapCategoryAliasDto>,
+    ) -> AppResult<Json<CategoryAliasDto>> {
+        info!(
+            "recieved request from {} to map {} category {:?}",
+            user_id, request.provider, request.raw_name
+        );
 
-        let categories = services.streams.get_all_games().await?;
+        let alias = services.categories.map_alias(request).await?;
 
-        Ok(Json(GameListResponse { categories }))
+        Ok(Json(alias))
+    }
+
+    pub async fn upsert_category_endpoint(
+        AdminAuthentication(user_id, services): AdminAuthentication,
+        Path(slug): Path<String>,
+        ValidationExtractor(request): ValidationExtractor<UpsertCategoryDto>,
+    ) -> AppResult<Json<CategoryInfoDto>> {
+        info!(
+            "recieved request from {} to save category {:?}",
+            user_id, slug
+        );
+
+        let category = services.categories.upsert_category(slug, request).await?;
+
+        Ok(Json(category))
+    }
+
+    pub async fn get_team_aliases_endpoint(
+        RequiredAuthentication(_user_id, services): RequiredAuthentication,
+    ) -> AppResult<Json<TeamAliasListResponse>> {
+        info!("recieved request to retrieve team aliases");
+
+        let aliases = services.teams.get_aliases().await?;
+
+        Ok(Json(TeamAliasListResponse { aliases }))
+    }
+
+    /// cached games pick the new name up on the next refresh, the team filter uses it straight
+    /// away
+    pub async fn upsert_team_alias_endpoint(
+        AdminAuthentication(user_id, services): AdminAuthentication,
+        Path(alias): Path<String>,
+        ValidationExtractor(request): ValidationExtractor<UpsertTeamAliasDto>,
+    ) -> AppResult<Json<TeamAliasDto>> {
+        info!(
+            "recieved request from {} to alias {:?} to {:?}",
+            user_id, alias, request.team
+        );
+
+        let alias = services.teams.upsert_alias(alias, request).await?;
+
+        Ok(Json(alias))
+    }
+
+    pub async fn delete_team_alias_endpoint(
+        AdminAuthentication(user_id, services): AdminAuthentication,
+        Path(alias): Path<String>,
+    ) -> AppResult<StatusCode> {
+        info!(
+            "recieved request from {} to delete team alias {:?}",
+            user_id, alias
+        );
+
+        services.teams.delete_alias(alias).await?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    /// server-sent events for cache changes, reconnecting with Last-Event-ID replays whatever
+    /// was missed as long as it's still buffered
+    pub async fn get_events_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        headers: HeaderMap,
+        Query(query): Query<StreamEventsQuery>,
+    ) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+        info!("recieved request to subscribe to stream events");
+
+        // the header is what browsers send on reconnect so it wins over the query
+        let last_event_id = headers
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .or(query.last_event_id);
+
+        debug!(
+            "user {} subscribing to stream events after {:?}",
+            user_id, last_event_id
+        );
+
+        let events = services
+            .events
+            .subscribe(last_event_id)
+            .await?
+            .map(|event| {
+                let sse_event = Event::default()
+                    .id(event.id.to_string())
+                    .event(event.kind.as_str());
+
+                Ok(sse_event
+                    .json_data(&event.data)
+                    .unwrap_or_else(|_| Event::default().comment("unserializable event")))
+            });
+
+        Ok(Sse::new(events).keep_alive(
+            KeepAlive::new()
+                .interval(EVENTS_HEARTBEAT)
+                .text("heartbeat"),
+        ))
+    }
+
+    pub async fn get_calendar_endpoint(
+        RequiredAuthentication(_user_id, services): RequiredAuthentication,
+        Query(query): Query<GameListQuery>,
+    ) -> AppResult<impl IntoResponse> {
+        info!("recieved request for the game calendar");
+
+        Self::calendar_response(&services, query).await
+    }
+
+    /// the url a calendar app can subscribe to, any listing filters on this request get baked
+    /// into it. only the filters, not whatever else is on the query (a `?token=` would leak)
+    pub async fn get_calendar_feed_url_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        Query(query): Query<GameListQuery>,
+    ) -> AppResult<Json<CalendarFeedUrlResponse>> {
+        info!("recieved request for calendar feed url");
+
+        let key = services
+            .signature_util
+            .generate_feed_key(&user_id, CALENDAR_FEED);
+
+        let mut feed_url = format!(
+            "/api/v1/streams/calendar/feed.ics?user={}&key={}",
+            urlencoding::encode(&user_id),
+            key
+        );
+        let filters = query.filter_params();
+        if !filters.is_empty() {
+            feed_url.push('&');
+            feed_url.push_str(&filters);
+        }
+
+        Ok(Json(CalendarFeedUrlResponse { feed_url }))
+    }
+
+    pub async fn get_calendar_feed_endpoint(
+        Extension(services): Extension<Services>,
+        Query(feed): Query<FeedKeyQuery>,
+        Query(query): Query<GameListQuery>,
+    ) -> AppResult<impl IntoResponse> {
+        Self::verify_feed_key(&services, &feed, CALENDAR_FEED)?;
+
+        info!("serving calendar feed for user {}", feed.user);
+
+        Self::calendar_response(&services, query).await
+    }
+
+    async fn calendar_response(
+        services: &Services,
+        query: GameListQuery,
+    ) -> AppResult<impl IntoResponse + use<>> {
+        let calendar = services
+            .streams
+            .get_calendar(query, services.config.frontend_url.clone())
+            .await?;
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            calendar,
+        ))
+    }
+
+    pub async fn get_iptv_feed_urls_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        headers: HeaderMap,
+    ) -> AppResult<Json<IptvFeedUrlResponse>> {
+        info!("recieved request for iptv feed urls");
+
+        let feed = Self::iptv_feed(&services, &headers, user_id);
+
+        Ok(Json(IptvFeedUrlResponse {
+            playlist_url: feed.playlist_url(),
+            guide_url: feed.guide_url(),
+        }))
+    }
+
+    pub async fn get_playlist_endpoint(
+        Extension(services): Extension<Services>,
+        headers: HeaderMap,
+        Query(feed): Query<FeedKeyQuery>,
+        Query(query): Query<GameListQuery>,
+    ) -> AppResult<impl IntoResponse> {
+        Self::verify_feed_key(&services, &feed, IPTV_FEED)?;
+
+        info!("serving m3u playlist for user {}", feed.user);
+
+        let playlist = services
+            .streams
+            .get_playlist(query, Self::iptv_feed(&services, &headers, feed.user))
+            .await?;
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, "audio/x-mpegurl; charset=utf-8"),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            playlist,
+        ))
+    }
+
+    pub async fn get_guide_endpoint(
+        Extension(services): Extension<Services>,
+        Query(feed): Query<FeedKeyQuery>,
+        Query(query): Query<GameListQuery>,
+    ) -> AppResult<impl IntoResponse> {
+        Self::verify_feed_key(&services, &feed, IPTV_FEED)?;
+
+        info!("serving xmltv guide for user {}", feed.user);
+
+        let guide = services.streams.get_guide(query).await?;
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, "application/xml; charset=utf-8"),
+                (header::CACHE_CONTROL, "no-cache"),
+            ],
+            guide,
+        ))
+    }
+
+    /// playlist entries land here, the link only gets resolved and signed once a player actually
+    /// opens the game. signing everything up front would mean resolving every game on every
+    /// playlist fetch and the signatures would go stale in players that cache playlists
+    pub async fn play_endpoint(
+        Extension(services): Extension<Services>,
+        headers: HeaderMap,
+        Path((provider, id)): Path<(String, i64)>,
+        Query(feed): Query<FeedKeyQuery>,
+    ) -> AppResult<Redirect> {
+        Self::verify_feed_key(&services, &feed, IPTV_FEED)?;
+
+        info!("recieved play request for {} game {}", provider, id);
+
+        let resolved = services
+            .streams
+            .resolve_video_link(provider.clone(), id, SourceSelection::Auto)
+            .await?;
+        let (signed_url, _) = services.signature_util.signed_proxy_url(
+            &feed.user,
+            &resolved.link,
+            "sports",
+            Some(&provider),
+        );
+
+        Ok(Redirect::temporary(&format!(
+            "{}{}",
+            Self::base_url(&services, &headers),
+            signed_url
+        )))
+    }
+
+    fn verify_feed_key(services: &Services, feed: &FeedKeyQuery, name: &str) -> AppResult<()> {
+        if !services
+            .signature_util
+            .verify_feed_key(&feed.user, name, &feed.key)
+        {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(())
+    }
+
+    fn iptv_feed(services: &Services, headers: &HeaderMap, user_id: String) -> IptvFeed {
+        IptvFeed {
+            base_url: Self::base_url(services, headers),
+            key: services
+                .signature_util
+                .generate_feed_key(&user_id, IPTV_FEED),
+            user: user_id,
+        }
+    }
+
+    // players need absolute urls, PUBLIC_URL wins and otherwise we trust whatever host the
+    // request came in on
+    fn base_url(services: &Services, headers: &HeaderMap) -> String {
+        if let Some(public_url) = &services.config.public_url {
+            return public_url.trim_end_matches('/').to_string();
+        }
+
+        let host = headers
+            .get("x-forwarded-host")
+            .or_else(|| headers.get(header::HOST))
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("localhost");
+        let scheme = headers
+            .get("x-forwarded-proto")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("http");
+
+        format!("{}://{}", scheme, host)
     }
 
     pub async fn get_stream_endpoint(
@@ -57,76 +475,135 @@ impl StreamController {
         Ok(Json(stream))
     }
 
-    pub async fn get_ppvsu_game_endpoint(
+    pub async fn get_game_endpoint(
         RequiredAuthentication(_user_id, services): RequiredAuthentication,
-        Path(id): Path<i64>,
-    ) -> AppResult<Json<GameDto>> {
-        info!("recieved request to fetch ppvsu game with id {}", id);
+        headers: HeaderMap,
+        Path((provider, id)): Path<(String, i64)>,
+    ) -> AppResult<Response> {
+        info!("recieved request to fetch {} game with id {}", provider, id);
+
+        let version = services
+            .streams
+            .get_provider_version(provider.clone())
+            .await?;
+        let etag = ETag::strong(format!("game-{}-{}", id, version), CACHE_PRIVATE_SHORT);
+        if etag.matches(&headers) {
+            return Ok(etag.not_modified());
+        }
 
-        let game = services.ppvsu.get_game_by_id(id).await?;
+        let game = services.streams.get_game(provider, id).await?;
 
-        Ok(Json(game.into_dto()))
+        let mut game = game.into_dto_at(listing_clock());
+        game.poster = services.images.proxied_url(&game.poster);
+
+        Ok(etag.respond(&headers, Json(game)))
     }
 
-    pub async fn get_ppvsu_decoded_game_endpoint(
+    pub async fn get_decoded_game_endpoint(
         RequiredAuthentication(_user_id, services): RequiredAuthentication,
-        Path(id): Path<i64>,
+        Path((provider, id)): Path<(String, i64)>,
+        Query(query): Query<SourceQuery>,
     ) -> AppResult<Json<serde_json::Value>> {
-        debug!("recieved reques to decode ppvsu game with id {}", id);
-        let game = services.ppvsu.get_game_by_id(id).await?;
-        let link = services.ppvsu.fetch_video_link(&game.video_link).await?;
+        debug!("recieved reques to decode {} game with id {}", provider, id);
+        let resolved = services
+            .streams
+            .resolve_video_link(provider, id, query.source)
+            .await?;
         Ok(Json(serde_json::json!({
-            "decoded_link": link
+            "decoded_link": resolved.link,
+            "source": resolved.index,
+            "source_label": resolved.label
         })))
     }
 
-    pub async fn clear_ppvsu_cache_endpoint(
-        RequiredAuthentication(_user_id, services): RequiredAuthentication,
+    pub async fn clear_cache_endpoint(
+        AdminAuthentication(user_id, services): AdminAuthentication,
+        Path(provider): Path<String>,
     ) -> AppResult<Json<serde_json::Value>> {
-        info!("recieved request to clear ppvsu cache");
+        info!(
+            "recieved request from {} to clear {} cache",
+            user_id, provider
+        );
 
-        services.ppvsu.clear_cache().await?;
+        // clearing is a forced refresh now, an empty cache would just mean an empty listing until
+        // the next background run. admins only since every call hits the upstream
+        let games = services.streams.refresh_provider(provider).await?;
 
         Ok(Json(serde_json::json!({
             "success": true,
-            "message": "Cache cleared successfully"
+            "message": "Cache refreshed successfully",
+            "games": games
         })))
     }
 
+    /// drops the cached resolved links for a game so the next play scrapes its embeds again
+    pub async fn invalidate_resolved_links_endpoint(
+        AdminAuthentication(user_id, services): AdminAuthentication,
+        Path((provider, id)): Path<(String, i64)>,
+    ) -> AppResult<Json<serde_json::Value>> {
+        info!(
+            "recieved request from {} to invalidate resolved links for {} game {}",
+            user_id, provider, id
+        );
+
+        let invalidated = services
+            .streams
+            .invalidate_resolved_links(provider, id)
+            .await?;
+
+        Ok(Json(serde_json::json!({
+            "success": true,
+            "invalidated": invalidated
+        })))
+    }
+
+    /// `?source=` picks one of the game's sources by index, without it (or with `auto`) they're
+    /// tried best first until one resolves
     pub async fn get_signed_url_endpoint(
         RequiredAuthentication(user_id, services): RequiredAuthentication,
-        Path(id): Path<i64>,
+        Path((provider, id)): Path<(String, i64)>,
+        Query(query): Query<SourceQuery>,
+        Query(quality): Query<QualityQuery>,
     ) -> AppResult<Json<SignedUrlResponse>> {
-        info!("received request to generate signed URL for game {}", id);
-
-        let game = services.ppvsu.get_game_by_id(id).await?;
-        let link = services.ppvsu.fetch_video_link(&game.video_link).await?;
-
-        let encoded_url = URL_SAFE
-            .encode(link.as_bytes())
-            .trim_end_matches('=')
-            .to_string();
+        info!(
+            "received request to generate signed URL for {} game {} ({:?})",
+            provider, id, query.source
+        );
 
-        // gen expiry (12 hours from now)
-        let expiry = SignatureUtil::generate_expiry(12);
+        let resolved = services
+            .streams
+            .resolve_video_link(provider.clone(), id, query.source)
+            .await?;
 
-        let signature = services
-            .signature_util
-            .generate_signature(&user_id, expiry, &encoded_url);
-
-        let signed_url = format!(
-            "/api/v1/proxy?url={}&schema=sports&sig={}&exp={}&user={}",
-            encoded_url,
-            signature,
-            expiry,
-            urlencoding::encode(&user_id)
+        let (mut signed_url, expiry) = services.signature_util.signed_proxy_url(
+            &user_id,
+            &resolved.link,
+            "sports",
+            Some(&provider),
         );
+        // not part of the signature, it only ever takes qualities away
+        if let Some(max_height) = quality.max_height {
+            signed_url.push_str(&format!("&max_height={}", max_height));
+        }
+
+        let qualities = ProxyController::discover_qualities(
+            &services,
+            &user_id,
+            "sports",
+            &provider,
+            &resolved.link,
+            quality.max_height,
+        )
+        .await;
 
         info!("generated signed URL for game {} (expires: {})", id, expiry);
 
         Ok(Json(SignedUrlResponse {
             signed_url,
             expires_at: expiry,
+            source: resolved.index,
+            source_label: resolved.label,
+            qualities,
         }))
     }
 }
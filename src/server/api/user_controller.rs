@@ -31,17 +31,21 @@ pub struct UserController;
 *    api/v1/users/refresh - GET - takes in a cookie with the refresh_token and returns a new access token
 * */
 impl UserController {
-    pub fn app() -> Router {
-        Router::new()
-            // i comment out the signups because i don't have proper email sending or verification
-            // yet and i don't want my database being slammed
-            //
-            //.route("/signup", post(Self::signup_user_endpoint))
+    pub fn app(allow_signups: bool) -> Router {
+        let router = Router::new()
             .route("/signin", post(Self::signin_user_endpoint))
             .route("/signout", post(Self::signout_user_endpoint))
             .route("/whoami", get(Self::get_current_user_endpoint))
             .route("/refresh", get(Self::refresh_user_endpoint))
-            .route("/", put(Self::update_user_endpoint))
+            .route("/", put(Self::update_user_endpoint));
+
+        // signups stay off by default because i don't have proper email sending or verification
+        // yet and i don't want my database being slammed
+        if allow_signups {
+            router.route("/signup", post(Self::signup_user_endpoint))
+        } else {
+            router
+        }
     }
 
     pub async fn signup_user_endpoint(
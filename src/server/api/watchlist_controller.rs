@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use axum::Router;
+use axum::extract::{Json, Path, Query};
+use axum::http::{StatusCode, header};
+use axum::response::IntoResponse;
+use axum::routing::{get, patch, post};
+use futures::future::join_all;
+use tracing::{error, info};
+
+use crate::server::api::movie_controller::MovieController;
+use crate::server::dtos::movie_dto::TvEpisode;
+use crate::server::dtos::watchlist_dto::{
+    AddWatchlistItemDto, UpdateWatchlistItemDto, WatchlistImportResponse, WatchlistItemDto,
+    WatchlistQuery, WatchlistResponse,
+};
+use crate::server::error::AppResult;
+use crate::server::extractors::{RequiredAuthentication, ValidationExtractor};
+use crate::server::services::Services;
+
+// every resolved link is a vidlink call, so don't let one list request turn into dozens
+const MAX_RESOLVE: usize = 5;
+
+pub struct WatchlistController;
+/*
+*    api/v1/users/me/watchlist - GET - the current user's watchlist sorted by position,
+*    ?resolve=N adds a signed playback link to the first N (up to 5) entries
+*
+*    api/v1/users/me/watchlist - POST - takes in json with kind: "movie" | "tv", media_id: string,
+*    note: string | null and position: number | null (end of the list when left out)
+*
+*    api/v1/users/me/watchlist/{id} - PATCH - json with note and/or position
+*
+*    api/v1/users/me/watchlist/{id} - DELETE - take it off the list
+*
+*    api/v1/users/me/watchlist/import - POST - csv body of id,kind,note rows (kind and note are
+*    optional), anything already on the list is skipped
+*
+*    api/v1/users/me/watchlist/export - GET - the list as csv in the same format
+* */
+impl WatchlistController {
+    pub fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(Self::get_watchlist_endpoint).post(Self::add_item_endpoint),
+            )
+            .route("/import", post(Self::import_endpoint))
+            .route("/export", get(Self::export_endpoint))
+            .route(
+                "/{id}",
+                patch(Self::update_item_endpoint).delete(Self::delete_item_endpoint),
+            )
+    }
+
+    pub async fn get_watchlist_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        Query(query): Query<WatchlistQuery>,
+    ) -> AppResult<Json<WatchlistResponse>> {
+        info!("recieved request to retrieve watchlist for {:?}", user_id);
+
+        let mut items = services.watchlist.get_watchlist(user_id.clone()).await?;
+
+        let resolve = query
+            .resolve
+            .unwrap_or_default()
+            .min(MAX_RESOLVE)
+            .min(items.len());
+        if resolve > 0 {
+            Self::resolve_links(&services, &user_id, &mut items[..resolve]).await;
+        }
+
+        Ok(Json(WatchlistResponse { items }))
+    }
+
+    // a link that can't be resolved just leaves that entry without one, the list still loads
+    async fn resolve_links(services: &Services, user_id: &str, items: &mut [WatchlistItemDto]) {
+        // shows pick up on the episode the user was last watching
+        let episodes: HashMap<String, TvEpisode> = if items.iter().any(|i| i.kind == "tv") {
+            match services
+                .progress
+                .continue_watching(user_id.to_string())
+                .await
+            {
+                Ok(progress) => progress
+                    .into_iter()
+                    .filter_map(|p| Some((p.id, p.ep?.parse().ok()?)))
+                    .collect(),
+                Err(e) => {
+                    error!("failed to read progress for watchlist links: {}", e);
+                    HashMap::new()
+                }
+            }
+        } else {
+            HashMap::new()
+        };
+
+        let links = join_all(items.iter().map(|item| {
+            // shows nobody has started yet begin at the start
+            let episode = (item.kind == "tv").then(|| {
+                episodes
+                    .get(&item.media_id)
+                    .copied()
+                    .unwrap_or(TvEpisode::FIRST)
+            });
+
+            MovieController::signed_movie_link(services, user_id, &item.media_id, episode, None)
+        }))
+        .await;
+
+        for (item, link) in items.iter_mut().zip(links) {
+            match link {
+                Ok(link) => item.link = Some(link),
+                Err(e) => error!(
+                    "failed to resolve watchlist link for {} {}: {}",
+                    item.kind, item.media_id, e
+                ),
+            }
+        }
+    }
+
+    pub async fn add_item_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        ValidationExtractor(request): ValidationExtractor<AddWatchlistItemDto>,
+    ) -> AppResult<(StatusCode, Json<WatchlistItemDto>)> {
+        info!("recieved request to add to watchlist for {:?}", user_id);
+
+        let item = services.watchlist.add_item(user_id, request).await?;
+
+        Ok((StatusCode::CREATED, Json(item)))
+    }
+
+    pub async fn update_item_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        Path(id): Path<String>,
+        ValidationExtractor(request): ValidationExtractor<UpdateWatchlistItemDto>,
+    ) -> AppResult<Json<WatchlistItemDto>> {
+        info!("recieved request to update watchlist item {:?}", id);
+
+        let item = services.watchlist.update_item(user_id, id, request).await?;
+
+        Ok(Json(item))
+    }
+
+    pub async fn delete_item_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        Path(id): Path<String>,
+    ) -> AppResult<StatusCode> {
+        info!("recieved request to delete watchlist item {:?}", id);
+
+        services.watchlist.delete_item(user_id, id).await?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    pub async fn import_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+        body: String,
+    ) -> AppResult<Json<WatchlistImportResponse>> {
+        info!("recieved request to import watchlist for {:?}", user_id);
+
+        let imported = services.watchlist.import_csv(user_id, body).await?;
+
+        Ok(Json(imported))
+    }
+
+    pub async fn export_endpoint(
+        RequiredAuthentication(user_id, services): RequiredAuthentication,
+    ) -> AppResult<impl IntoResponse> {
+        info!("recieved request to export watchlist for {:?}", user_id);
+
+        let csv = services.watchlist.export_csv(user_id).await?;
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"watchlist.csv\"",
+                ),
+            ],
+            csv,
+        ))
+    }
+}
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::database::category::{Category, CategoryAlias};
+
+impl CategoryAlias {
+    pub fn into_dto(self) -> CategoryAliasDto {
+        CategoryAliasDto {
+            provider: self.provider,
+            raw_name: self.raw_name,
+            category_slug: self.category_slug,
+            first_seen_at: self.first_seen_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CategoryAliasDto {
+    pub provider: String,
+    pub raw_name: String,
+    /// null while it's waiting for an admin to map it
+    pub category_slug: Option<String>,
+    pub first_seen_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CategoryInfoDto {
+    pub slug: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub sort_order: i32,
+    /// the provider names that end up here
+    pub aliases: Vec<CategoryAliasDto>,
+}
+
+impl CategoryInfoDto {
+    pub fn new(category: Category, aliases: Vec<CategoryAliasDto>) -> Self {
+        Self {
+            slug: category.slug,
+            name: category.name,
+            icon: category.icon,
+            sort_order: category.sort_order,
+            aliases,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CategoryListResponse {
+    pub categories: Vec<CategoryInfoDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UnmappedCategoryListResponse {
+    pub unmapped: Vec<CategoryAliasDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct UpsertCategoryDto {
+    #[validate(length(min = 1, max = 100, message = "name must be 1-100 characters"))]
+    pub name: String,
+    #[validate(length(max = 16, message = "icon must be at most 16 characters"))]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub sort_order: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct MapCategoryAliasDto {
+    #[validate(length(min = 1, max = 50, message = "provider must be 1-50 characters"))]
+    pub provider: String,
+    #[validate(length(min = 1, max = 100, message = "raw_name must be 1-100 characters"))]
+    pub raw_name: String,
+    #[validate(length(min = 1, max = 100, message = "category_slug must be 1-100 characters"))]
+    pub category_slug: String,
+}
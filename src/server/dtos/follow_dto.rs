@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::database::follow::{Follow, NotificationSettings};
+
+impl Follow {
+    pub fn into_dto(self) -> FollowDto {
+        FollowDto {
+            id: self.id,
+            kind: self.kind,
+            value: self.value,
+            created_at: self.created_at.unix_timestamp(),
+        }
+    }
+}
+
+impl NotificationSettings {
+    pub fn into_dto(self) -> NotificationSettingsDto {
+        NotificationSettingsDto {
+            webhook_url: self.webhook_url,
+            email: self.email,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FollowKind {
+    /// a category slug or its whole name, e.g. "ice-hockey" or "Ice Hockey"
+    Category,
+    /// part of either team's name (or the game name if it has no teams), e.g. "Lakers"
+    Team,
+}
+
+impl FollowKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FollowKind::Category => "category",
+            FollowKind::Team => "team",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FollowDto {
+    pub id: String,
+    pub kind: String,
+    pub value: String,
+    pub created_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct FollowListResponse {
+    pub follows: Vec<FollowDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct CreateFollowDto {
+    pub kind: FollowKind,
+    #[validate(length(min = 1, max = 100, message = "value must be 1-100 characters"))]
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct NotificationSettingsDto {
+    /// discord/ntfy style endpoint that gets a json POST per notification
+    pub webhook_url: Option<String>,
+    /// send to the account's email as well
+    pub email: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct UpdateNotificationSettingsDto {
+    #[validate(url(message = "webhook_url is not a valid url"))]
+    pub webhook_url: Option<String>,
+    #[serde(default)]
+    pub email: bool,
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -23,6 +25,8 @@ pub struct HealthResponse {
 pub struct ServiceHealthDetails {
     pub database: DatabaseHealth,
     pub redis: RedisHealth,
+    /// keyed by host, only hosts that have been called since startup show up
+    pub upstreams: BTreeMap<String, UpstreamHealth>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -38,3 +42,23 @@ pub struct RedisHealth {
     pub status: HealthStatus,
     pub response_time_ms: f64,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UpstreamHealth {
+    pub status: HealthStatus,
+    pub state: BreakerState,
+    /// over the last `requests` calls
+    pub error_rate: f64,
+    pub avg_response_time_ms: f64,
+    pub requests: usize,
+    /// when an open breaker lets the next request through
+    pub retry_in_secs: Option<u64>,
+}
@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::database::archive::{ArchivedCategoryCount, ArchivedGame};
+use crate::database::stream::GameSource;
+
+impl ArchivedGame {
+    pub fn into_dto(self) -> ArchivedGameDto {
+        ArchivedGameDto {
+            sources: self.sources(),
+            provider: self.provider,
+            id: self.game_id,
+            name: self.name,
+            poster: self.poster,
+            category: self.category,
+            category_slug: self.category_slug,
+            start_time: self.start_time,
+            end_time: self.end_time,
+            status: self.status,
+            first_seen_at: self.first_seen_at,
+            last_seen_at: self.last_seen_at,
+        }
+    }
+}
+
+impl ArchivedCategoryCount {
+    pub fn into_dto(self) -> CategoryUsageDto {
+        CategoryUsageDto {
+            slug: self.category_slug,
+            category: self.category,
+            games: self.games,
+        }
+    }
+}
+
+/// query params for GET /api/v1/streams/history
+#[derive(Deserialize, Debug, Clone, Default, Validate)]
+pub struct HistoryQuery {
+    /// only games starting at or after this unix time
+    pub from: Option<i64>,
+    /// only games starting at or before this unix time
+    pub to: Option<i64>,
+    /// category slug, or its whole name
+    pub category: Option<String>,
+    pub provider: Option<String>,
+    #[validate(range(min = 1, max = 500, message = "limit must be between 1 and 500"))]
+    pub limit: Option<i64>,
+    #[validate(range(min = 0, message = "offset can't be negative"))]
+    pub offset: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ArchivedGameDto {
+    pub provider: String,
+    pub id: i64,
+    pub name: String,
+    pub poster: String,
+    pub category: String,
+    pub category_slug: String,
+    pub start_time: i64,
+    pub end_time: i64,
+    /// every source ever seen for the game, the latest ones first
+    pub sources: Vec<GameSource>,
+    /// `upcoming`, `live` or `ended` as of `last_seen_at`, `removed` if upstream dropped it
+    /// before it ended
+    pub status: String,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CategoryUsageDto {
+    pub slug: String,
+    pub category: String,
+    pub games: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HistoryResponse {
+    pub games: Vec<ArchivedGameDto>,
+    /// every game matching the filters, not just this page
+    pub total: i64,
+    /// games per category over the same range, the category filter isn't applied to these
+    pub categories: Vec<CategoryUsageDto>,
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+use crate::server::utils::hls_utils::HlsVariant;
+
+impl HlsVariant {
+    /// `signed_url` is the proxy url for this variant's own playlist
+    pub fn into_dto(self, signed_url: String) -> QualityDto {
+        QualityDto {
+            label: match (self.height, self.bandwidth) {
+                (Some(height), _) => format!("{}p", height),
+                (None, Some(bandwidth)) => format!("{} kbps", bandwidth / 1000),
+                (None, None) => String::from("unknown"),
+            },
+            width: self.width,
+            height: self.height,
+            bandwidth: self.bandwidth,
+            codecs: self.codecs,
+            signed_url,
+        }
+    }
+}
+
+/// one quality from the upstream master playlist, highest bandwidth comes first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QualityDto {
+    /// "720p", or the bitrate for variants without a resolution
+    pub label: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// peak bits per second
+    pub bandwidth: Option<u64>,
+    pub codecs: Option<String>,
+    pub signed_url: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct QualityQuery {
+    /// leaves taller variants out of the master playlist and `qualities`, for weak connections
+    pub max_height: Option<u32>,
+}
@@ -1,5 +1,13 @@
+pub mod category_dto;
+pub mod follow_dto;
 pub mod health_dto;
+pub mod history_dto;
+pub mod hls_dto;
 pub mod movie_dto;
+pub mod progress_dto;
+pub mod room_dto;
 pub mod session_dto;
 pub mod stream_dto;
+pub mod team_dto;
 pub mod user_dto;
+pub mod watchlist_dto;
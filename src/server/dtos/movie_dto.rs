@@ -1,8 +1,15 @@
 // ai generated docs for most of the dtos below, take them with caution and read the api
 // implementation instead of just the dtos
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
+use crate::server::dtos::hls_dto::QualityDto;
+use crate::server::error::{Error, ErrorMap};
+
 /// Request body for decrypting a movie ID
 #[derive(Debug, Deserialize, Validate)]
 pub struct DecryptMovieRequest {
@@ -72,6 +79,162 @@ pub struct EncryptMovieResponse {
     pub timestamp_readable: Option<String>,
 }
 
+/// a season and episode of a show, vidlink takes it as `/tv/{id}/{season}/{episode}`. season 0 is
+/// where specials usually live
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TvEpisode {
+    pub season: u32,
+    pub episode: u32,
+}
+
+impl TvEpisode {
+    pub const FIRST: TvEpisode = TvEpisode {
+        season: 1,
+        episode: 1,
+    };
+
+    pub const MAX_SEASON: u32 = 100;
+
+    pub const MAX_EPISODE: u32 = 5000;
+
+    pub fn next(self) -> Self {
+        Self {
+            episode: self.episode + 1,
+            ..self
+        }
+    }
+
+    pub fn first_of_next_season(self) -> Self {
+        Self {
+            season: self.season + 1,
+            episode: 1,
+        }
+    }
+
+    /// checks the ranges and that both halves are there, errors are keyed by query param
+    pub fn from_parts(season: Option<u32>, episode: Option<u32>) -> Result<Option<Self>, ErrorMap> {
+        let mut errors = ErrorMap::new();
+
+        match (season, episode) {
+            (None, None) => return Ok(None),
+            (Some(_), None) => {
+                push_error(&mut errors, "episode", "episode is required with season")
+            }
+            (None, Some(_)) => push_error(&mut errors, "season", "season is required with episode"),
+            (Some(_), Some(_)) => {}
+        }
+        if season.is_some_and(|s| s > Self::MAX_SEASON) {
+            push_error(&mut errors, "season", "season must be 0-100");
+        }
+        if episode.is_some_and(|e| e == 0 || e > Self::MAX_EPISODE) {
+            push_error(&mut errors, "episode", "episode must be 1-5000");
+        }
+
+        match (season, episode) {
+            (Some(season), Some(episode)) if errors.is_empty() => {
+                Ok(Some(Self { season, episode }))
+            }
+            _ => Err(errors),
+        }
+    }
+}
+
+fn push_error(errors: &mut ErrorMap, field: &'static str, message: &'static str) {
+    errors
+        .entry(Cow::from(field))
+        .or_default()
+        .push(Cow::from(message));
+}
+
+/// `1/5`, the same thing the older `ep` param and watch progress use
+impl fmt::Display for TvEpisode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.season, self.episode)
+    }
+}
+
+impl FromStr for TvEpisode {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (season, episode) = value.trim().split_once('/').ok_or(())?;
+        let season = season.parse().map_err(|_| ())?;
+        let episode = episode.parse().map_err(|_| ())?;
+
+        TvEpisode::from_parts(Some(season), Some(episode))
+            .ok()
+            .flatten()
+            .ok_or(())
+    }
+}
+
+/// query for the movie link endpoints, leave season and episode out for movies
+#[derive(Debug, Deserialize, Default)]
+pub struct GetMovieLinkQuery {
+    /// plain movie tv id
+    pub id: String,
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+    /// older way of asking for an episode, `1/5` is season 1 episode 5
+    pub ep: Option<String>,
+}
+
+impl GetMovieLinkQuery {
+    /// which episode was asked for, None for movies. bad input is a 422 here instead of an error
+    /// from vidlink later
+    pub fn tv_episode(&self) -> Result<Option<TvEpisode>, Error> {
+        let mut errors = ErrorMap::new();
+
+        let id = self.id.trim();
+        if id.is_empty() || id.len() > 64 {
+            push_error(&mut errors, "id", "id must be 1-64 characters");
+        }
+
+        let episode = match (&self.ep, self.season.or(self.episode)) {
+            (Some(_), Some(_)) => {
+                push_error(&mut errors, "ep", "use either ep or season and episode");
+                None
+            }
+            (Some(ep), None) => ep.parse::<TvEpisode>().map(Some).unwrap_or_else(|_| {
+                push_error(&mut errors, "ep", "ep must look like 1/5 (season/episode)");
+                None
+            }),
+            (None, _) => TvEpisode::from_parts(self.season, self.episode).unwrap_or_else(|e| {
+                errors.extend(e);
+                None
+            }),
+        };
+
+        if errors.is_empty() {
+            Ok(episode)
+        } else {
+            Err(Error::UnprocessableEntity { errors })
+        }
+    }
+}
+
+/// query for GET /api/v1/movies/tv/{id}/next, the episode that was just watched
+#[derive(Debug, Deserialize)]
+pub struct NextEpisodeQuery {
+    pub season: Option<u32>,
+    pub episode: Option<u32>,
+}
+
+impl NextEpisodeQuery {
+    pub fn tv_episode(&self) -> Result<TvEpisode, Error> {
+        match TvEpisode::from_parts(self.season, self.episode) {
+            Ok(Some(episode)) => Ok(episode),
+            Ok(None) => {
+                let mut errors = ErrorMap::new();
+                push_error(&mut errors, "season", "season is required");
+                push_error(&mut errors, "episode", "episode is required");
+                Err(Error::UnprocessableEntity { errors })
+            }
+            Err(errors) => Err(Error::UnprocessableEntity { errors }),
+        }
+    }
+}
+
 /// Caption information
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CaptionInfo {
@@ -90,12 +253,37 @@ pub struct GetMovieLinkResponse {
 
     /// Available captions
     pub captions: Vec<CaptionInfo>,
+
+    /// seconds to pick back up from, null if it hasn't been started or was already watched
+    pub resume_at: Option<f64>,
+}
+
+/// Response body for the signed movie link, what Safari/iOS native HLS gets
+#[derive(Debug, Serialize)]
+pub struct SignedMovieLinkResponse {
+    pub signed_url: String,
+    pub expires_at: i64,
+    pub captions: Vec<CaptionInfo>,
+    pub resume_at: Option<f64>,
+    /// empty unless the upstream playlist is a master playlist
+    pub qualities: Vec<QualityDto>,
+}
+
+/// Response body for the next episode, the signed link plus which episode it's for
+#[derive(Debug, Serialize)]
+pub struct NextEpisodeResponse {
+    pub season: u32,
+    pub episode: u32,
+    #[serde(flatten)]
+    pub link: SignedMovieLinkResponse,
 }
 
 /// vidlink.pro API response structure
 #[derive(Debug, Deserialize)]
 pub struct VidLinkResponse {
-    pub stream: StreamInfo,
+    /// missing or null when there's nothing for that id/episode
+    #[serde(default)]
+    pub stream: Option<StreamInfo>,
 }
 
 /// Stream information from vidlink.pro
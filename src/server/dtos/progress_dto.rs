@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::database::progress::WatchProgress;
+use crate::server::dtos::movie_dto::{GetMovieLinkQuery, TvEpisode};
+use crate::server::error::Error;
+
+impl WatchProgress {
+    pub fn into_dto(self) -> WatchProgressDto {
+        WatchProgressDto {
+            kind: if self.episode.is_empty() {
+                String::from("movie")
+            } else {
+                String::from("tv")
+            },
+            id: self.media_id,
+            ep: Some(self.episode).filter(|ep| !ep.is_empty()),
+            position: self.position_secs,
+            duration: self.duration_secs,
+            watched: self.watched,
+            updated_at: self.updated_at.unix_timestamp(),
+        }
+    }
+}
+
+/// body for PUT /api/v1/movies/progress, `id` and `ep` are the same as on /movies/link
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct UpdateProgressDto {
+    #[validate(length(min = 1, max = 64, message = "id must be 1-64 characters"))]
+    pub id: String,
+    #[validate(length(min = 1, max = 32, message = "ep must be 1-32 characters"))]
+    pub ep: Option<String>,
+    /// seconds into the movie or episode
+    #[validate(range(min = 0.0, message = "position can't be negative"))]
+    pub position: f64,
+    /// seconds, lets the server work out when something has been finished
+    #[validate(range(min = 0.0, message = "duration can't be negative"))]
+    pub duration: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct MarkWatchedDto {
+    #[validate(length(min = 1, max = 64, message = "id must be 1-64 characters"))]
+    pub id: String,
+    #[validate(length(min = 1, max = 32, message = "ep must be 1-32 characters"))]
+    pub ep: Option<String>,
+    /// false unmarks it
+    #[serde(default = "default_watched")]
+    pub watched: bool,
+}
+
+fn default_watched() -> bool {
+    true
+}
+
+// parsed the same way as on /movies/link so `1/05` and `1/5` are the same episode everywhere, a
+// 422 for anything that isn't an episode
+fn tv_episode(id: &str, ep: &Option<String>) -> Result<Option<TvEpisode>, Error> {
+    GetMovieLinkQuery {
+        id: id.to_string(),
+        ep: ep.clone(),
+        ..Default::default()
+    }
+    .tv_episode()
+}
+
+impl UpdateProgressDto {
+    pub fn tv_episode(&self) -> Result<Option<TvEpisode>, Error> {
+        tv_episode(&self.id, &self.ep)
+    }
+}
+
+impl MarkWatchedDto {
+    pub fn tv_episode(&self) -> Result<Option<TvEpisode>, Error> {
+        tv_episode(&self.id, &self.ep)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WatchProgressDto {
+    /// `movie` or `tv`
+    pub kind: String,
+    pub id: String,
+    pub ep: Option<String>,
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub watched: bool,
+    pub updated_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProgressSavedResponse {
+    /// false when the write was skipped because the last one was too recent
+    pub saved: bool,
+    /// set once the position gets close enough to the end
+    pub watched: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContinueWatchingResponse {
+    pub items: Vec<WatchProgressDto>,
+}
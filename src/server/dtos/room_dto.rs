@@ -0,0 +1,74 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::database::room::{ChatMessage, Room, RoomEvent};
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct CreateRoomDto {
+    #[validate(url(message = "media_url must be a url"))]
+    #[validate(length(max = 2048, message = "media_url must be at most 2048 characters"))]
+    pub media_url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RoomResponse {
+    pub room: Room,
+    /// unix ms, what `room.updated_at` should be compared against
+    pub server_time: i64,
+}
+
+/// what clients send over the socket, e.g. `{"type": "seek", "position": 93.5}`. everything but
+/// chat and ping is host only
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomCommand {
+    /// `position` is where the host's player is, the room's last known position if left out
+    Play {
+        position: Option<f64>,
+    },
+    Pause {
+        position: Option<f64>,
+    },
+    Seek {
+        position: f64,
+    },
+    /// swaps what's being watched, starts paused from the beginning
+    Media {
+        media_url: String,
+    },
+    Chat {
+        message: String,
+    },
+    /// answered with a pong to the sender only, for working out the clock offset
+    Ping {
+        client_time: i64,
+    },
+}
+
+/// what the server sends over the socket, room events go out as they are
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RoomSocketMessage {
+    /// first thing a new connection gets
+    Welcome {
+        room: Room,
+        chat: Vec<ChatMessage>,
+    },
+    Pong {
+        client_time: i64,
+    },
+    /// something the sender did was rejected, the connection stays open
+    Error {
+        message: String,
+    },
+    #[serde(untagged)]
+    Event(RoomEvent),
+}
+
+/// every message is stamped with the server time as it's sent
+#[derive(Serialize, Debug)]
+pub struct RoomFrame {
+    #[serde(flatten)]
+    pub message: RoomSocketMessage,
+    pub server_time: i64,
+}
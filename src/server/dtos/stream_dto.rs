@@ -1,6 +1,9 @@
-use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::str::FromStr;
+use validator::Validate;
 
-use crate::database::stream::{Game, Stream};
+use crate::database::stream::{Game, GameSource, Stream};
 
 impl Stream {
     pub fn into_dto(self) -> ResponseStreamDto {
@@ -12,8 +15,27 @@ impl Stream {
 }
 
 impl Game {
+    pub fn status_at(&self, now: i64) -> GameStatus {
+        if now < self.start_time {
+            GameStatus::Upcoming
+        } else if now < self.end_time {
+            GameStatus::Live
+        } else {
+            GameStatus::Ended
+        }
+    }
+
     pub fn into_dto(self) -> GameDto {
+        self.into_dto_at(Utc::now().timestamp())
+    }
+
+    // status and countdown are relative to `now`, listings pass one value in so every game in a
+    // response agrees on what time it is
+    pub fn into_dto_at(self, now: i64) -> GameDto {
         GameDto {
+            status: self.status_at(now),
+            sources: self.playable_sources(),
+            starts_in_seconds: self.start_time - now,
             id: self.id,
             name: self.name,
             poster: self.poster,
@@ -22,6 +44,10 @@ impl Game {
             cache_time: self.cache_time,
             video_link: self.video_link,
             category: self.category,
+            category_slug: self.category_slug,
+            league: self.league,
+            home_team: self.home_team,
+            away_team: self.away_team,
         }
     }
 }
@@ -47,15 +73,190 @@ pub struct GameDto {
     pub cache_time: i64,
     pub video_link: String,
     pub category: String,
+    pub category_slug: String,
+    pub league: Option<String>,
+    pub home_team: Option<String>,
+    pub away_team: Option<String>,
+    /// best first, the position here is what `source` takes on the signed-url endpoint
+    pub sources: Vec<GameSource>,
+    pub status: GameStatus,
+    /// negative once the game has started
+    pub starts_in_seconds: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GameStatus {
+    Live,
+    Upcoming,
+    Ended,
+}
+
+impl GameStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameStatus::Live => "live",
+            GameStatus::Upcoming => "upcoming",
+            GameStatus::Ended => "ended",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GameSort {
+    /// soonest first
+    #[default]
+    #[serde(rename = "start")]
+    Start,
+    #[serde(rename = "-start")]
+    StartDesc,
+    #[serde(rename = "name")]
+    Name,
+    #[serde(rename = "-name")]
+    NameDesc,
+}
+
+impl GameSort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameSort::Start => "start",
+            GameSort::StartDesc => "-start",
+            GameSort::Name => "name",
+            GameSort::NameDesc => "-name",
+        }
+    }
+}
+
+/// query params for GET /api/v1/streams, everything is optional and leaving it all out returns
+/// every cached game like before
+#[derive(Deserialize, Debug, Clone, Default, Validate)]
+pub struct GameListQuery {
+    /// category slug, or its whole name
+    pub category: Option<String>,
+    pub status: Option<GameStatus>,
+    /// only games starting at or after this unix time
+    pub from: Option<i64>,
+    /// only games starting at or before this unix time
+    pub to: Option<i64>,
+    /// free text match against name and category
+    pub q: Option<String>,
+    /// part of either team's name, or anything the team alias table knows it as
+    pub team: Option<String>,
+    #[serde(default)]
+    pub sort: GameSort,
+    /// page size, no limit means no pagination
+    #[validate(range(min = 1, max = 500, message = "limit must be between 1 and 500"))]
+    pub limit: Option<usize>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+}
+
+impl GameListQuery {
+    /// the listing filters as query params for a feed url. paging is left out, feeds always get
+    /// every game
+    pub fn filter_params(&self) -> String {
+        let mut params = url::form_urlencoded::Serializer::new(String::new());
+
+        if let Some(category) = &self.category {
+            params.append_pair("category", category);
+        }
+        if let Some(status) = self.status {
+            params.append_pair("status", status.as_str());
+        }
+        if let Some(from) = self.from {
+            params.append_pair("from", &from.to_string());
+        }
+        if let Some(to) = self.to {
+            params.append_pair("to", &to.to_string());
+        }
+        if let Some(q) = &self.q {
+            params.append_pair("q", q);
+        }
+        if let Some(team) = &self.team {
+            params.append_pair("team", team);
+        }
+        if self.sort != GameSort::default() {
+            params.append_pair("sort", self.sort.as_str());
+        }
+
+        params.finish()
+    }
+}
+
+/// auth for the subscribable feeds (calendar, m3u, xmltv), the apps reading them can't send an
+/// authorization header
+#[derive(Deserialize, Debug)]
+pub struct FeedKeyQuery {
+    pub user: String,
+    pub key: String,
+}
+
+/// which of a game's sources to play, `auto` goes through them best first until one resolves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SourceSelection {
+    #[default]
+    Auto,
+    Index(usize),
+}
+
+impl FromStr for SourceSelection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "" | "auto" => Ok(SourceSelection::Auto),
+            index => index
+                .parse()
+                .map(SourceSelection::Index)
+                .map_err(|_| format!("invalid source {:?}, expected an index or auto", index)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceSelection {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SourceQuery {
+    #[serde(default)]
+    pub source: SourceSelection,
+}
+
+/// `EventSource` can't set Last-Event-ID on the first connect, this lets a client resume anyway
+#[derive(Deserialize, Debug, Default)]
+pub struct StreamEventsQuery {
+    pub last_event_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CalendarFeedUrlResponse {
+    pub feed_url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IptvFeedUrlResponse {
+    pub playlist_url: String,
+    pub guide_url: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CategoryDto {
+    /// display name
     pub category: String,
+    pub slug: String,
+    pub icon: Option<String>,
     pub games: Vec<GameDto>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GameListResponse {
     pub categories: Vec<CategoryDto>,
+    /// set when there are more games after this page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
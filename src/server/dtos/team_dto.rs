@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::database::team::TeamAlias;
+
+impl TeamAlias {
+    pub fn into_dto(self) -> TeamAliasDto {
+        TeamAliasDto {
+            alias: self.alias,
+            team: self.team,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TeamAliasDto {
+    pub alias: String,
+    pub team: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TeamAliasListResponse {
+    pub aliases: Vec<TeamAliasDto>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct UpsertTeamAliasDto {
+    /// what the alias should show up as
+    #[validate(length(min = 1, max = 100, message = "team must be 1-100 characters"))]
+    pub team: String,
+}
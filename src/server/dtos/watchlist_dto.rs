@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::database::watchlist::WatchlistItem;
+use crate::server::dtos::movie_dto::SignedMovieLinkResponse;
+
+impl WatchlistItem {
+    pub fn into_dto(self) -> WatchlistItemDto {
+        WatchlistItemDto {
+            id: self.id,
+            kind: self.kind,
+            media_id: self.media_id,
+            note: self.note,
+            position: self.position,
+            created_at: self.created_at.unix_timestamp(),
+            link: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchlistKind {
+    Movie,
+    Tv,
+}
+
+impl WatchlistKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchlistKind::Movie => "movie",
+            WatchlistKind::Tv => "tv",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "movie" => Some(WatchlistKind::Movie),
+            "tv" => Some(WatchlistKind::Tv),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct WatchlistItemDto {
+    pub id: String,
+    /// `movie` or `tv`
+    pub kind: String,
+    /// the id the movie link endpoints take
+    pub media_id: String,
+    pub note: Option<String>,
+    pub position: i64,
+    pub created_at: i64,
+    /// only on the first `resolve` entries of the list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<SignedMovieLinkResponse>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WatchlistResponse {
+    pub items: Vec<WatchlistItemDto>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct WatchlistQuery {
+    /// how many entries from the top get a signed playback link, capped at 5
+    pub resolve: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct AddWatchlistItemDto {
+    pub kind: WatchlistKind,
+    #[validate(length(min = 1, max = 64, message = "media_id must be 1-64 characters"))]
+    pub media_id: String,
+    #[validate(length(max = 500, message = "note can't be over 500 characters"))]
+    pub note: Option<String>,
+    /// goes to the end of the list when left out
+    pub position: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Validate)]
+pub struct UpdateWatchlistItemDto {
+    /// an empty note removes it, leaving it out keeps the old one
+    #[validate(length(max = 500, message = "note can't be over 500 characters"))]
+    pub note: Option<String>,
+    pub position: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct WatchlistImportResponse {
+    pub added: usize,
+    /// already on the list
+    pub skipped: usize,
+    /// rows that couldn't be read, e.g. "line 3: unknown kind \"show\""
+    pub errors: Vec<String>,
+}
@@ -8,7 +8,6 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
 use tracing::info;
-use tracing::log::error;
 use validator::{ValidationErrors, ValidationErrorsKind};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -51,6 +50,8 @@ pub enum Error {
     InternalServerErrorWithContext(String),
     #[error("{0}")]
     ObjectConflict(String),
+    #[error("{0}")]
+    ServiceUnavailable(String),
     #[error("unprocessable request has occurred")]
     UnprocessableEntity { errors: ErrorMap },
     #[error(transparent)]
@@ -61,7 +62,30 @@ pub enum Error {
     AnyhowError(#[from] anyhow::Error),
 }
 
+/// an upstream's circuit breaker is open so the request was never sent, surfaces as a 503 even
+/// when it's been passed around inside an anyhow error
+#[derive(Error, Debug, Clone)]
+#[error("{host} is unavailable right now, try again in {retry_in_secs}s")]
+pub struct UpstreamUnavailable {
+    pub host: String,
+    pub retry_in_secs: u64,
+}
+
+impl From<UpstreamUnavailable> for Error {
+    fn from(e: UpstreamUnavailable) -> Self {
+        Self::ServiceUnavailable(e.to_string())
+    }
+}
+
 impl Error {
+    /// keeps an open breaker as a 503, anything else goes through `otherwise`
+    pub fn from_upstream(e: anyhow::Error, otherwise: impl FnOnce(anyhow::Error) -> Error) -> Self {
+        match e.downcast::<UpstreamUnavailable>() {
+            Ok(unavailable) => unavailable.into(),
+            Err(e) => otherwise(e),
+        }
+    }
+
     pub fn unprocessable_entity(errors: ValidationErrors) -> Response {
         let mut validation_errors = ErrorMap::new();
 
@@ -124,11 +148,21 @@ impl IntoResponse for Error {
         if let Self::ValidationError(e) = self {
             return Self::unprocessable_entity(e);
         }
+        if let Self::UnprocessableEntity { errors } = self {
+            let body = Json(json!({
+                "errors": errors,
+            }));
+            return (StatusCode::UNPROCESSABLE_ENTITY, body).into_response();
+        }
 
         let (status, error_message) = match self {
             Self::InternalServerErrorWithContext(err) => (StatusCode::INTERNAL_SERVER_ERROR, err),
             Self::NotFound(err) => (StatusCode::NOT_FOUND, err),
             Self::ObjectConflict(err) => (StatusCode::CONFLICT, err),
+            Self::ServiceUnavailable(err) => (StatusCode::SERVICE_UNAVAILABLE, err),
+            Self::AnyhowError(err) if err.is::<UpstreamUnavailable>() => {
+                (StatusCode::SERVICE_UNAVAILABLE, err.to_string())
+            }
             Self::InvalidLoginAttmpt => (
                 StatusCode::BAD_REQUEST,
                 Self::InvalidLoginAttmpt.to_string(),
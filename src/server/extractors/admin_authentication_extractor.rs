@@ -0,0 +1,36 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use tracing::warn;
+
+use crate::server::error::Error;
+use crate::server::services::Services;
+
+use super::RequiredAuthentication;
+
+pub struct AdminAuthentication(pub String, pub Services);
+
+/// same as required authentication but the user also has to be listed in ADMIN_USER_IDS
+impl<S> FromRequestParts<S> for AdminAuthentication
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let RequiredAuthentication(user_id, services) =
+            RequiredAuthentication::from_request_parts(parts, state).await?;
+
+        let is_admin = services
+            .config
+            .admin_user_ids
+            .split(',')
+            .map(str::trim)
+            .any(|admin| !admin.is_empty() && admin == user_id);
+
+        if !is_admin {
+            warn!("user {} tried to use an admin endpoint", user_id);
+            return Err(Error::Forbidden);
+        }
+
+        Ok(AdminAuthentication(user_id, services))
+    }
+}
@@ -1,9 +1,13 @@
+mod admin_authentication_extractor;
 mod session_extractor;
 mod user_agent_extractor;
 mod validation_extractor;
 mod required_authentication_extractor;
+mod proxy_authentication_extractor;
 
+pub use admin_authentication_extractor::*;
 pub use session_extractor::*;
 pub use user_agent_extractor::*;
 pub use validation_extractor::*;
 pub use required_authentication_extractor::*;
+pub use proxy_authentication_extractor::*;
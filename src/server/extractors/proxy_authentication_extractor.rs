@@ -0,0 +1,86 @@
+use axum::Extension;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use serde::Deserialize;
+use tracing::{debug, error};
+
+use crate::server::error::Error;
+use crate::server::services::Services;
+
+use super::RequiredAuthentication;
+
+#[derive(Deserialize)]
+struct SignedUrlQuery {
+    sig: Option<String>,
+    exp: Option<String>,
+    user: Option<String>,
+}
+
+pub struct ProxyAuthentication(pub String, pub Services);
+
+/// required authentication that also takes the signed urls we hand out for players, only for the
+/// proxy routes. a sig only vouches for fetching its url, anywhere else it'd be a free login
+impl<S> FromRequestParts<S> for ProxyAuthentication
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(query): Query<SignedUrlQuery> = Query::from_request_parts(parts, state)
+            .await
+            .map_err(|_| Error::Unauthorized)?;
+
+        // the auth header still wins if there is one, same as before signed urls moved here
+        let (sig, exp_str, user) = match (query.sig, query.exp, query.user) {
+            (Some(sig), Some(exp), Some(user)) if !parts.headers.contains_key(AUTHORIZATION) => {
+                (sig, exp, user)
+            }
+            _ => {
+                let RequiredAuthentication(user_id, services) =
+                    RequiredAuthentication::from_request_parts(parts, state).await?;
+                return Ok(ProxyAuthentication(user_id, services));
+            }
+        };
+
+        let Extension(services): Extension<Services> = Extension::from_request_parts(parts, state)
+            .await
+            .map_err(|err| Error::InternalServerErrorWithContext(err.to_string()))?;
+
+        let expiry = exp_str.parse::<i64>().map_err(|_| {
+            error!("invalid expiry timestamp");
+            Error::Unauthorized
+        })?;
+
+        let uri = &parts.uri;
+        debug!("Incoming request path: {}", uri.path());
+        debug!("Full URI: {}", uri);
+
+        // extract url parameter from the RAW query string (before URL decoding)
+        // needed because the signature was generated with the raw b64 string
+        let url_param = uri
+            .query()
+            .and_then(|q| {
+                q.split('&')
+                    .find(|param| param.starts_with("url="))
+                    .and_then(|param| param.strip_prefix("url="))
+            })
+            .ok_or_else(|| {
+                error!("missing url parameter in signed URL");
+                Error::Unauthorized
+            })?;
+
+        if !services
+            .signature_util
+            .verify_signature(&user, expiry, url_param, &sig)
+        {
+            error!(
+                "Signature was invalid, url: {}, user: {}, expiry: {}, sig: {}",
+                url_param, user, expiry, sig
+            );
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(ProxyAuthentication(user, services))
+    }
+}
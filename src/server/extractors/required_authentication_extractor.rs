@@ -3,7 +3,7 @@ use axum::extract::{FromRequestParts, Query};
 use axum::http::header::AUTHORIZATION;
 use axum::http::request::Parts;
 use serde::Deserialize;
-use tracing::{debug, error};
+use tracing::error;
 
 use crate::server::error::Error;
 use crate::server::services::Services;
@@ -11,16 +11,14 @@ use crate::server::services::Services;
 // FIXME: PUT THIS IS IN THE DTOS!!!!!!!!!!!
 // WHY IS THIS NOT IN THE DTOS WHY DID I PUT THIS HERE??????????
 #[derive(Deserialize)]
-struct SignedUrlQuery {
-    sig: Option<String>,
-    exp: Option<String>,
-    user: Option<String>,
+struct TokenQuery {
     token: Option<String>,
 }
 
 pub struct RequiredAuthentication(pub String, pub Services);
 
-/// extractor that just gives the user id to the called function or verifies the signed url
+/// extractor that just gives the user id to the called function, signed urls only work on the
+/// proxy routes so they're checked in ProxyAuthentication instead
 impl<S> FromRequestParts<S> for RequiredAuthentication
 where
     S: Send + Sync,
@@ -58,54 +56,11 @@ where
                     Error::Unauthorized
                 })?
         } else {
-            // try query params for the signature
-            let Query(query): Query<SignedUrlQuery> = Query::from_request_parts(parts, state)
+            let Query(query): Query<TokenQuery> = Query::from_request_parts(parts, state)
                 .await
                 .map_err(|_| Error::Unauthorized)?;
 
-            if let (Some(sig), Some(exp_str), Some(user)) = (query.sig, query.exp, query.user) {
-                let expiry = exp_str.parse::<i64>().map_err(|_| {
-                    error!("invalid expiry timestamp");
-                    Error::Unauthorized
-                })?;
-
-                let uri = &parts.uri;
-                let path = uri.path();
-
-                debug!("Incoming request path: {}", path);
-                debug!("Full URI: {}", uri);
-
-                // extract url parameter from the RAW query string (before URL decoding)
-                // needed because the signature was generated with the raw b64 string
-                let url_param = uri
-                    .query()
-                    .and_then(|q| {
-                        q.split('&')
-                            .find(|param| param.starts_with("url="))
-                            .and_then(|param| param.strip_prefix("url="))
-                    })
-                    .ok_or_else(|| {
-                        error!("missing url parameter in signed URL");
-                        Error::Unauthorized
-                    })?;
-
-                let url_for_verification = url_param.to_string();
-
-                if !services.signature_util.verify_signature(
-                    &user,
-                    expiry,
-                    &url_for_verification,
-                    &sig,
-                ) {
-                    error!(
-                        "Signature was invalid, url: {}, user: {}, expiry: {}, sig: {}",
-                        url_for_verification, user, expiry, sig
-                    );
-                    return Err(Error::Unauthorized);
-                }
-
-                user
-            } else if let Some(token) = query.token {
+            if let Some(token) = query.token {
                 // i mean like why not i used this like once i think but it shouldn't be called
                 services
                     .jwt_util
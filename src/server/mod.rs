@@ -30,13 +30,16 @@ use tower_http::{
     cors::{AllowOrigin, CorsLayer},
     trace::TraceLayer,
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::config::AppConfig;
 use crate::database::Database;
 use crate::database::RedisDatabase;
 use crate::server::services::Services;
+use crate::server::services::refresh_services::ProviderRefreshService;
 use crate::server::services::seed_services::SeedService;
+use crate::server::services::watcher_services::GoLiveWatcher;
+use crate::server::utils::fake_upstream_utils::FakeUpstream;
 lazy_static! {
     static ref HTTP_TIMEOUT: u64 = 30;
     static ref EXPONENTIAL_SECONDS: &'static [f64] = &[
@@ -119,6 +122,25 @@ impl ApplicationServer {
             .install_recorder()
             .context("i can't run the metric recorder yo")?;
 
+        // everything upstream comes from fixture files instead, posters included
+        let config = match &config.fake_upstream_dir {
+            Some(dir) => {
+                let fake = FakeUpstream::spawn(dir).await?;
+                warn!(
+                    "using the fake upstream at {}, nothing is scraped",
+                    fake.base_url()
+                );
+
+                let mut faked = (*config).clone();
+                faked.ppvsu_api_url = fake.ppvsu_api_url();
+                faked.ppvsu_site_url = fake.ppvsu_site_url();
+                faked.vidlink_url = fake.vidlink_url();
+                faked.image_proxy_hosts = format!("{},127.0.0.1", config.image_proxy_hosts);
+                Arc::new(faked)
+            }
+            None => config,
+        };
+
         let services = Services::new(db, redis_db, config.clone());
 
         if config.seed {
@@ -129,6 +151,18 @@ impl ApplicationServer {
                 .expect("couldn't seed the db");
         }
 
+        // stream caches are only ever written from here, requests just read them
+        tokio::spawn(ProviderRefreshService::new(services.clone()).run());
+        tokio::spawn(GoLiveWatcher::new(services.clone()).run());
+
+        // one redis subscription per instance feeding every open sse connection
+        let events = services.events.clone();
+        tokio::spawn(async move { events.listen().await });
+
+        // same again for watch party sockets
+        let rooms = services.rooms.clone();
+        tokio::spawn(async move { rooms.listen().await });
+
         // the cors configs are independent to the proxy and general api layers but they can really be combined
         // if needed and it's very easy to do so
         let cors_origins: Vec<String> = config
@@ -176,8 +210,21 @@ impl ApplicationServer {
         // then merging them with the proxy routes
         let api_routes = Router::new()
             .nest("/streams", api::stream_controller::StreamController::app())
-            .nest("/users", api::user_controller::UserController::app())
+            .nest(
+                "/users/me/follows",
+                api::follow_controller::FollowController::app(),
+            )
+            .nest(
+                "/users/me/watchlist",
+                api::watchlist_controller::WatchlistController::app(),
+            )
+            .nest(
+                "/users",
+                api::user_controller::UserController::app(config.allow_signups),
+            )
             .nest("/movies", api::movie_controller::MovieController::app())
+            .nest("/images", api::image_controller::ImageController::app())
+            .nest("/rooms", api::room_controller::RoomController::app())
             .route("/health", get(api::health_controller::health_endpoint))
             .layer(cors);
 
@@ -0,0 +1,164 @@
+// caption pipeline behind /proxy/captions. whatever the source serves gets turned into utf-8
+// webvtt once and cached, the offset is cheap so it's applied per request on top of that. caption
+// hosts aren't scraped like the others so they stay out of the upstream breakers. any logged in
+// user can pass a url so only public addresses are fetched, like webhooks
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mockall::automock;
+use reqwest::header;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::server::{
+    error::{AppResult, Error},
+    utils::{
+        caption_utils::{convert_to_vtt, decode_caption_text, offset_vtt},
+        disk_cache_utils::DiskCache,
+        network_utils::resolve_public_url,
+    },
+};
+
+pub type DynCaptionsService = Arc<dyn CaptionsServiceTrait + Send + Sync>;
+
+// a feature length srt is a couple hundred kb
+const MAX_CAPTION_BYTES: usize = 5 * 1024 * 1024;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[automock]
+#[async_trait]
+pub trait CaptionsServiceTrait {
+    /// the captions at `url` as utf-8 webvtt, moved later by `offset_ms` (earlier if negative).
+    /// `charset` is for legacy files the source doesn't label, e.g. `windows-1251`
+    async fn get_captions(
+        &self,
+        url: String,
+        charset: Option<String>,
+        offset_ms: i64,
+    ) -> AppResult<String>;
+}
+
+pub struct CaptionsService {
+    client: reqwest::Client,
+    cache: Option<DiskCache>,
+    /// only for the fake upstream, it serves captions from localhost
+    allow_private_hosts: bool,
+}
+
+impl CaptionsService {
+    pub fn new(cache: Option<DiskCache>, allow_private_hosts: bool) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            cache,
+            allow_private_hosts,
+        }
+    }
+
+    /// a client that only connects to the public addresses `url` resolved to and doesn't follow
+    /// redirects, so neither dns changing nor a redirect can point it somewhere internal
+    async fn client_for(&self, url: &str) -> anyhow::Result<reqwest::Client> {
+        if self.allow_private_hosts {
+            return Ok(self.client.clone());
+        }
+
+        let target = resolve_public_url(url).await?;
+        let client = reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .resolve_to_addrs(&target.host, &target.addrs)
+            .build()?;
+
+        Ok(client)
+    }
+
+    async fn fetch(&self, client: reqwest::Client, url: &str) -> anyhow::Result<Vec<u8>> {
+        let request = client
+            .get(url)
+            .header(
+                header::USER_AGENT,
+                "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:145.0) Gecko/20100101 Firefox/145.0",
+            )
+            .header(header::ACCEPT, "*/*");
+        let mut response = request.send().await?.error_for_status()?;
+
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > MAX_CAPTION_BYTES)
+        {
+            anyhow::bail!("captions are too large");
+        }
+
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > MAX_CAPTION_BYTES {
+                anyhow::bail!("captions are too large");
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        Ok(bytes)
+    }
+}
+
+fn cache_key(url: &str, charset: Option<&str>) -> String {
+    let digest = Sha256::digest(format!("{}|{}", url, charset.unwrap_or_default()).as_bytes());
+
+    format!("{}.vtt", hex::encode(digest))
+}
+
+#[async_trait]
+impl CaptionsServiceTrait for CaptionsService {
+    async fn get_captions(
+        &self,
+        url: String,
+        charset: Option<String>,
+        offset_ms: i64,
+    ) -> AppResult<String> {
+        let key = cache_key(&url, charset.as_deref());
+
+        if let Some(cache) = &self.cache
+            && let Some(bytes) = cache.get(&key).await
+        {
+            metrics::counter!("caption_cache_requests_total", "result" => "hit").increment(1);
+            let vtt = String::from_utf8_lossy(&bytes);
+            return Ok(offset_vtt(&vtt, offset_ms));
+        }
+        metrics::counter!("caption_cache_requests_total", "result" => "miss").increment(1);
+
+        let client = self.client_for(&url).await.map_err(|e| {
+            error!("refusing to fetch captions {}: {}", url, e);
+            Error::BadRequest(format!("captions url is not allowed: {}", e))
+        })?;
+
+        info!("fetching captions {}", url);
+        let bytes = self.fetch(client, &url).await.map_err(|e| {
+            error!("failed to fetch captions {}: {}", url, e);
+            Error::from_upstream(e, |_| {
+                Error::NotFound("captions could not be fetched".into())
+            })
+        })?;
+
+        // the charset sources send in content-type is usually just their server's default, so
+        // it's not used
+        let text = decode_caption_text(&bytes, charset.as_deref());
+        let vtt = convert_to_vtt(&text).ok_or_else(|| {
+            error!("captions {} are not srt, vtt or ass", url);
+            Error::BadRequest("captions are not in a supported format".into())
+        })?;
+
+        if let Some(cache) = &self.cache
+            && let Err(e) = cache.put(&key, vtt.as_bytes()).await
+        {
+            error!("failed to cache captions {}: {}", url, e);
+        }
+
+        Ok(offset_vtt(&vtt, offset_ms))
+    }
+}
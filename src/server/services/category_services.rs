@@ -0,0 +1,217 @@
+// the category taxonomy, games get their categories normalised through this on every refresh. it
+// lives in the database so admins can map new provider names without a deploy, and a copy is kept
+// in memory because every refresh and listing needs it
+use async_trait::async_trait;
+use mockall::automock;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::{
+    database::category::{Category, DynCategoriesRepository},
+    server::{
+        dtos::category_dto::{
+            CategoryAliasDto, CategoryInfoDto, MapCategoryAliasDto, UpsertCategoryDto,
+        },
+        error::{AppResult, Error},
+        utils::category_utils::{CategoryTaxonomy, slugify},
+    },
+};
+
+pub type DynCategoriesService = Arc<dyn CategoriesServiceTrait + Send + Sync>;
+
+// other instances pick up admin changes within this
+const TAXONOMY_TTL: Duration = Duration::from_secs(60);
+
+#[automock]
+#[async_trait]
+pub trait CategoriesServiceTrait {
+    async fn taxonomy(&self) -> Arc<CategoryTaxonomy>;
+    /// remembers raw names the taxonomy couldn't place so an admin can map them
+    async fn record_unmapped(&self, provider: &str, raw_names: Vec<String>);
+    async fn get_categories(&self) -> AppResult<Vec<CategoryInfoDto>>;
+    async fn get_unmapped(&self) -> AppResult<Vec<CategoryAliasDto>>;
+    async fn upsert_category(
+        &self,
+        slug: String,
+        request: UpsertCategoryDto,
+    ) -> AppResult<CategoryInfoDto>;
+    async fn map_alias(&self, request: MapCategoryAliasDto) -> AppResult<CategoryAliasDto>;
+}
+
+struct LoadedTaxonomy {
+    taxonomy: Arc<CategoryTaxonomy>,
+    loaded_at: Option<Instant>,
+}
+
+pub struct CategoriesService {
+    repository: DynCategoriesRepository,
+    loaded: RwLock<LoadedTaxonomy>,
+}
+
+impl CategoriesService {
+    pub fn new(repository: DynCategoriesRepository) -> Self {
+        Self {
+            repository,
+            loaded: RwLock::new(LoadedTaxonomy {
+                taxonomy: Arc::new(CategoryTaxonomy::default()),
+                loaded_at: None,
+            }),
+        }
+    }
+
+    // next read goes back to the database
+    fn invalidate(&self) {
+        self.loaded.write().unwrap().loaded_at = None;
+    }
+}
+
+#[async_trait]
+impl CategoriesServiceTrait for CategoriesService {
+    // a database that's down means the last taxonomy we had keeps being used
+    async fn taxonomy(&self) -> Arc<CategoryTaxonomy> {
+        {
+            let loaded = self.loaded.read().unwrap();
+            if loaded
+                .loaded_at
+                .is_some_and(|loaded_at| loaded_at.elapsed() < TAXONOMY_TTL)
+            {
+                return loaded.taxonomy.clone();
+            }
+        }
+
+        let fetched = async {
+            let categories = self.repository.get_categories().await?;
+            let aliases = self.repository.get_category_aliases().await?;
+            anyhow::Ok(CategoryTaxonomy::new(categories, &aliases))
+        }
+        .await;
+
+        let mut loaded = self.loaded.write().unwrap();
+        match fetched {
+            Ok(taxonomy) => loaded.taxonomy = Arc::new(taxonomy),
+            Err(e) => error!(
+                "failed to load categories, keeping the previous ones: {}",
+                e
+            ),
+        }
+        loaded.loaded_at = Some(Instant::now());
+
+        loaded.taxonomy.clone()
+    }
+
+    async fn record_unmapped(&self, provider: &str, raw_names: Vec<String>) {
+        if raw_names.is_empty() {
+            return;
+        }
+
+        warn!(
+            "{} sent categories nobody has mapped yet: {}",
+            provider,
+            raw_names.join(", ")
+        );
+        metrics::counter!("stream_category_unmapped_total", "provider" => provider.to_string())
+            .increment(raw_names.len() as u64);
+
+        if let Err(e) = self
+            .repository
+            .record_unmapped_categories(provider, &raw_names)
+            .await
+        {
+            error!("failed to record unmapped {} categories: {}", provider, e);
+        }
+    }
+
+    async fn get_categories(&self) -> AppResult<Vec<CategoryInfoDto>> {
+        let categories = self.repository.get_categories().await?;
+
+        let mut aliases: HashMap<String, Vec<CategoryAliasDto>> = HashMap::new();
+        for alias in self.repository.get_category_aliases().await? {
+            if let Some(slug) = alias.category_slug.clone() {
+                aliases.entry(slug).or_default().push(alias.into_dto());
+            }
+        }
+
+        let taxonomy = CategoryTaxonomy::new(categories, &[]);
+        let categories = taxonomy
+            .categories()
+            .iter()
+            .map(|category| {
+                let aliases = aliases.remove(&category.slug).unwrap_or_default();
+                CategoryInfoDto::new(category.clone(), aliases)
+            })
+            .collect();
+
+        Ok(categories)
+    }
+
+    async fn get_unmapped(&self) -> AppResult<Vec<CategoryAliasDto>> {
+        let unmapped = self
+            .repository
+            .get_category_aliases()
+            .await?
+            .into_iter()
+            .filter(|alias| alias.category_slug.is_none())
+            .map(|alias| alias.into_dto())
+            .collect();
+
+        Ok(unmapped)
+    }
+
+    async fn upsert_category(
+        &self,
+        slug: String,
+        request: UpsertCategoryDto,
+    ) -> AppResult<CategoryInfoDto> {
+        if slugify(&slug) != slug {
+            return Err(Error::BadRequest(format!(
+                "{:?} isn't a slug, try {:?}",
+                slug,
+                slugify(&slug)
+            )));
+        }
+
+        info!("saving category {:?}", slug);
+
+        let category = self
+            .repository
+            .upsert_category(&Category {
+                slug,
+                name: request.name.trim().to_string(),
+                icon: request.icon.filter(|icon| !icon.trim().is_empty()),
+                sort_order: request.sort_order,
+            })
+            .await?;
+        self.invalidate();
+
+        Ok(CategoryInfoDto::new(category, Vec::new()))
+    }
+
+    async fn map_alias(&self, request: MapCategoryAliasDto) -> AppResult<CategoryAliasDto> {
+        let categories = self.repository.get_categories().await?;
+        if !categories.iter().any(|c| c.slug == request.category_slug) {
+            return Err(Error::NotFound(format!(
+                "category {} not found",
+                request.category_slug
+            )));
+        }
+
+        info!(
+            "mapping {} category {:?} to {}",
+            request.provider, request.raw_name, request.category_slug
+        );
+
+        let alias = self
+            .repository
+            .map_category_alias(
+                &request.provider,
+                request.raw_name.trim(),
+                &request.category_slug,
+            )
+            .await?;
+        self.invalidate();
+
+        Ok(alias.into_dto())
+    }
+}
@@ -0,0 +1,119 @@
+// live feed of cache changes for the sse endpoint. refreshes publish through redis so every
+// instance sees them, each instance runs one listener that hands them to its own sse clients
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use mockall::automock;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::{
+    database::event::{DynStreamEventsRepository, StreamEvent, StreamEventKind},
+    server::error::AppResult,
+};
+
+pub type DynStreamEventsService = Arc<dyn StreamEventsServiceTrait + Send + Sync>;
+
+// how many events a slow client can fall behind before it gets cut off and has to resume
+const BROADCAST_CAPACITY: usize = 256;
+
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+#[automock]
+#[async_trait]
+pub trait StreamEventsServiceTrait {
+    async fn publish(&self, events: Vec<(StreamEventKind, serde_json::Value)>) -> AppResult<()>;
+
+    /// anything after `last_event_id` that's still buffered followed by live events. the stream
+    /// ends if the client falls too far behind, reconnecting with the last id picks it back up
+    async fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> AppResult<BoxStream<'static, StreamEvent>>;
+
+    /// forwards events from redis to local subscribers, never returns
+    async fn listen(&self);
+}
+
+pub struct StreamEventsService {
+    repository: DynStreamEventsRepository,
+    sender: broadcast::Sender<StreamEvent>,
+}
+
+impl StreamEventsService {
+    pub fn new(repository: DynStreamEventsRepository) -> Self {
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+
+        Self { repository, sender }
+    }
+}
+
+#[async_trait]
+impl StreamEventsServiceTrait for StreamEventsService {
+    async fn publish(&self, events: Vec<(StreamEventKind, serde_json::Value)>) -> AppResult<()> {
+        let published = self.repository.publish_events(events).await?;
+
+        for event in &published {
+            metrics::counter!("stream_events_published_total", "event" => event.kind.as_str())
+                .increment(1);
+        }
+
+        Ok(())
+    }
+
+    async fn subscribe(
+        &self,
+        last_event_id: Option<u64>,
+    ) -> AppResult<BoxStream<'static, StreamEvent>> {
+        // subscribe before reading the buffer so nothing published in between gets lost, the
+        // overlap is dropped by id below
+        let receiver = self.sender.subscribe();
+
+        let replay = match last_event_id {
+            Some(id) => self.repository.get_events_after(id).await?,
+            None => Vec::new(),
+        };
+        let mut seen = replay.last().map(|e| e.id).or(last_event_id).unwrap_or(0);
+
+        let live = stream::unfold(receiver, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(event) => Some((event, receiver)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("sse client fell {} events behind, disconnecting", skipped);
+                    None
+                }
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        })
+        .filter(move |event| {
+            let fresh = event.id > seen;
+            if fresh {
+                seen = event.id;
+            }
+            futures::future::ready(fresh)
+        });
+
+        Ok(stream::iter(replay).chain(live).boxed())
+    }
+
+    async fn listen(&self) {
+        loop {
+            match self.repository.subscribe_events().await {
+                Ok(mut events) => {
+                    info!("listening for stream events");
+                    while let Some(event) = events.next().await {
+                        // no receivers just means nobody has the feed open
+                        let _ = self.sender.send(event);
+                    }
+                    warn!("stream event subscription closed, resubscribing");
+                }
+                Err(e) => error!("failed to subscribe to stream events: {}", e),
+            }
+
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+}
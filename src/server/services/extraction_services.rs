@@ -0,0 +1,199 @@
+// finds the playable link in an embed page with the strategy chain from extraction_utils. the
+// chain can come from a rules file that gets picked up again whenever it changes on disk, and
+// pages nothing could be pulled out of are saved so the new layout can be looked at later
+use async_trait::async_trait;
+use mockall::automock;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info, warn};
+
+use crate::server::utils::extraction_utils::{ExtractionRules, extract_video_link};
+
+pub type DynExtractionService = Arc<dyn ExtractionServiceTrait + Send + Sync>;
+
+// enough to see a pattern without the folder growing forever
+const MAX_SAVED_FAILURES: usize = 50;
+
+#[automock]
+#[async_trait]
+pub trait ExtractionServiceTrait {
+    /// the video link hidden in an embed page, `page_url` is where the html came from
+    async fn extract(&self, page_url: &str, html: &str) -> anyhow::Result<String>;
+}
+
+struct LoadedRules {
+    rules: Arc<ExtractionRules>,
+    // modified time of the file they came from, None for the built in chain
+    modified: Option<SystemTime>,
+}
+
+pub struct ExtractionService {
+    rules_path: Option<PathBuf>,
+    failures_dir: Option<PathBuf>,
+    loaded: RwLock<LoadedRules>,
+}
+
+impl ExtractionService {
+    /// without a rules path the built in chain is used, without a failures dir nothing is saved
+    pub fn new(rules_path: Option<PathBuf>, failures_dir: Option<PathBuf>) -> Self {
+        Self {
+            rules_path,
+            failures_dir,
+            loaded: RwLock::new(LoadedRules {
+                rules: Arc::new(ExtractionRules::default()),
+                modified: None,
+            }),
+        }
+    }
+
+    // the lock is only ever held to swap an arc so a poisoned one still has usable rules in it
+    fn current_rules(&self) -> Arc<ExtractionRules> {
+        self.loaded
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .rules
+            .clone()
+    }
+
+    // reloads when the file's modified time moves. a broken file is logged once and the last
+    // good rules stay in use until it's fixed
+    async fn rules(&self) -> Arc<ExtractionRules> {
+        let Some(path) = &self.rules_path else {
+            return self.current_rules();
+        };
+
+        let modified = match tokio::fs::metadata(path).await.and_then(|m| m.modified()) {
+            Ok(modified) => modified,
+            Err(e) => {
+                warn!("can't read extraction rules {}: {}", path.display(), e);
+                return self.current_rules();
+            }
+        };
+
+        {
+            let loaded = self.loaded.read().unwrap_or_else(|e| e.into_inner());
+            if loaded.modified == Some(modified) {
+                return loaded.rules.clone();
+            }
+        }
+
+        let parsed = tokio::fs::read_to_string(path)
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|json| ExtractionRules::from_json(&json));
+
+        let mut loaded = self.loaded.write().unwrap_or_else(|e| e.into_inner());
+        loaded.modified = Some(modified);
+        match parsed {
+            Ok(rules) => {
+                info!(
+                    "loaded extraction rules from {}: {}",
+                    path.display(),
+                    rules.strategy_names().join(", ")
+                );
+                loaded.rules = Arc::new(rules);
+            }
+            Err(e) => error!(
+                "bad extraction rules in {}, keeping the previous ones: {}",
+                path.display(),
+                e
+            ),
+        }
+
+        loaded.rules.clone()
+    }
+
+    async fn save_failure(&self, page_url: &str, reason: &str, html: &str) {
+        let Some(dir) = &self.failures_dir else {
+            return;
+        };
+
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let host: String = url::Url::parse(page_url)
+            .ok()
+            .and_then(|u| u.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| "unknown".to_string())
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let path = dir.join(format!("{:013}-{}.html", millis, host));
+
+        let contents = format!(
+            "<!-- {}\n{} -->\n{}",
+            page_url.replace("--", "- -"),
+            reason.replace("--", "- -"),
+            html
+        );
+
+        let saved = async {
+            tokio::fs::create_dir_all(dir).await?;
+            tokio::fs::write(&path, contents).await?;
+            prune_failures(dir).await
+        };
+
+        match saved.await {
+            Ok(()) => info!("saved embed page to {}", path.display()),
+            Err(e) => error!("failed to save embed page for {}: {}", page_url, e),
+        }
+    }
+}
+
+// the names start with a zero padded timestamp so sorting them is oldest first
+async fn prune_failures(dir: &Path) -> std::io::Result<()> {
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.ends_with(".html") {
+            names.push(name);
+        }
+    }
+
+    names.sort();
+    let excess = names.len().saturating_sub(MAX_SAVED_FAILURES);
+    for name in &names[..excess] {
+        tokio::fs::remove_file(dir.join(name)).await?;
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl ExtractionServiceTrait for ExtractionService {
+    async fn extract(&self, page_url: &str, html: &str) -> anyhow::Result<String> {
+        let rules = self.rules().await;
+
+        match extract_video_link(&rules, page_url, html) {
+            Ok(found) => {
+                info!(
+                    "extracted video link with {}: {}",
+                    found.strategy, found.link
+                );
+                metrics::counter!("link_extraction_total", "strategy" => found.strategy, "result" => "ok")
+                    .increment(1);
+                Ok(found.link)
+            }
+            Err(failure) => {
+                error!(
+                    "failed to extract video link from {}: {}",
+                    page_url, failure
+                );
+                metrics::counter!("link_extraction_total", "strategy" => "none", "result" => "failed")
+                    .increment(1);
+                self.save_failure(page_url, &failure.to_string(), html)
+                    .await;
+                Err(anyhow::anyhow!(failure.to_string()))
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// follows and where their notifications go, the actual notifying is in notification_services
+use async_trait::async_trait;
+use mockall::automock;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::{
+    database::follow::DynFollowsRepository,
+    server::{
+        dtos::follow_dto::{
+            CreateFollowDto, FollowDto, NotificationSettingsDto, UpdateNotificationSettingsDto,
+        },
+        error::{AppResult, Error},
+        utils::network_utils::resolve_public_url,
+    },
+};
+
+pub type DynFollowsService = Arc<dyn FollowsServiceTrait + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait FollowsServiceTrait {
+    async fn get_follows(&self, user_id: String) -> AppResult<Vec<FollowDto>>;
+    async fn create_follow(
+        &self,
+        user_id: String,
+        request: CreateFollowDto,
+    ) -> AppResult<FollowDto>;
+    async fn delete_follow(&self, user_id: String, follow_id: String) -> AppResult<()>;
+    async fn get_notification_settings(
+        &self,
+        user_id: String,
+    ) -> AppResult<NotificationSettingsDto>;
+    async fn update_notification_settings(
+        &self,
+        user_id: String,
+        request: UpdateNotificationSettingsDto,
+    ) -> AppResult<NotificationSettingsDto>;
+}
+
+#[derive(Clone)]
+pub struct FollowsService {
+    repository: DynFollowsRepository,
+}
+
+impl FollowsService {
+    pub fn new(repository: DynFollowsRepository) -> Self {
+        Self { repository }
+    }
+}
+
+#[async_trait]
+impl FollowsServiceTrait for FollowsService {
+    async fn get_follows(&self, user_id: String) -> AppResult<Vec<FollowDto>> {
+        info!("retrieving follows for user {:?}", user_id);
+
+        let follows = self
+            .repository
+            .get_follows(&user_id)
+            .await?
+            .into_iter()
+            .map(|f| f.into_dto())
+            .collect();
+
+        Ok(follows)
+    }
+
+    async fn create_follow(
+        &self,
+        user_id: String,
+        request: CreateFollowDto,
+    ) -> AppResult<FollowDto> {
+        let kind = request.kind.as_str();
+        let value = request.value.trim();
+
+        if value.is_empty() {
+            return Err(Error::BadRequest("value cannot be blank".into()));
+        }
+
+        info!("user {:?} following {} {:?}", user_id, kind, value);
+
+        // matching ignores case so "lakers" and "Lakers" are the same follow
+        let existing = self.repository.get_follows(&user_id).await?;
+        if existing
+            .iter()
+            .any(|f| f.kind == kind && f.value.eq_ignore_ascii_case(value))
+        {
+            return Err(Error::ObjectConflict(format!(
+                "already following {} {}",
+                kind, value
+            )));
+        }
+
+        let follow = self.repository.create_follow(&user_id, kind, value).await?;
+
+        Ok(follow.into_dto())
+    }
+
+    async fn delete_follow(&self, user_id: String, follow_id: String) -> AppResult<()> {
+        info!("user {:?} unfollowing {:?}", user_id, follow_id);
+
+        if !self.repository.delete_follow(&user_id, &follow_id).await? {
+            return Err(Error::NotFound(format!("follow {} not found", follow_id)));
+        }
+
+        Ok(())
+    }
+
+    async fn get_notification_settings(
+        &self,
+        user_id: String,
+    ) -> AppResult<NotificationSettingsDto> {
+        let settings = self.repository.get_notification_settings(&user_id).await?;
+
+        Ok(settings.into_dto())
+    }
+
+    async fn update_notification_settings(
+        &self,
+        user_id: String,
+        request: UpdateNotificationSettingsDto,
+    ) -> AppResult<NotificationSettingsDto> {
+        info!("updating notification settings for user {:?}", user_id);
+
+        let webhook_url = request
+            .webhook_url
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty());
+
+        // checked again before every send, the name could point somewhere else by then
+        if let Some(url) = &webhook_url
+            && let Err(e) = resolve_public_url(url).await
+        {
+            return Err(Error::BadRequest(format!(
+                "webhook_url is not allowed: {}",
+                e
+            )));
+        }
+
+        let settings = self
+            .repository
+            .update_notification_settings(&user_id, webhook_url, request.email)
+            .await?;
+
+        Ok(settings.into_dto())
+    }
+}
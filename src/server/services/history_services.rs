@@ -0,0 +1,105 @@
+// what we've shown over time. refreshes copy every game they cache into the archive so it's still
+// around long after redis lets it expire
+use async_trait::async_trait;
+use mockall::automock;
+use std::sync::Arc;
+use tracing::{error, info};
+use validator::Validate;
+
+use crate::{
+    database::{
+        archive::{ArchiveFilter, DynGamesArchiveRepository},
+        stream::Game,
+    },
+    server::{
+        dtos::history_dto::{HistoryQuery, HistoryResponse},
+        error::{AppResult, Error},
+        utils::category_utils::slugify,
+    },
+};
+
+pub type DynHistoryService = Arc<dyn HistoryServiceTrait + Send + Sync>;
+
+const DEFAULT_HISTORY_LIMIT: i64 = 100;
+
+#[automock]
+#[async_trait]
+pub trait HistoryServiceTrait {
+    /// never fails, a refresh shouldn't be lost because the archive couldn't be written
+    async fn archive(&self, provider: &str, games: &[Game], seen_at: i64);
+    async fn get_history(&self, query: HistoryQuery) -> AppResult<HistoryResponse>;
+}
+
+#[derive(Clone)]
+pub struct HistoryService {
+    repository: DynGamesArchiveRepository,
+}
+
+impl HistoryService {
+    pub fn new(repository: DynGamesArchiveRepository) -> Self {
+        Self { repository }
+    }
+}
+
+#[async_trait]
+impl HistoryServiceTrait for HistoryService {
+    async fn archive(&self, provider: &str, games: &[Game], seen_at: i64) {
+        match self
+            .repository
+            .archive_games(provider, games, seen_at)
+            .await
+        {
+            Ok(()) => info!("archived {} {} games", games.len(), provider),
+            Err(e) => error!("failed to archive {} games: {}", provider, e),
+        }
+    }
+
+    async fn get_history(&self, query: HistoryQuery) -> AppResult<HistoryResponse> {
+        info!("retrieving game history with {:?}", query);
+
+        query
+            .validate()
+            .map_err(|e| Error::BadRequest(format!("Validation error: {}", e)))?;
+
+        if let (Some(from), Some(to)) = (query.from, query.to)
+            && from > to
+        {
+            return Err(Error::BadRequest("from must not be after to".into()));
+        }
+
+        // names and slugs both work, same as the listing
+        let filter = ArchiveFilter {
+            provider: query.provider.filter(|p| !p.is_empty()),
+            category_slug: query
+                .category
+                .map(|c| slugify(&c))
+                .filter(|c| !c.is_empty()),
+            from: query.from,
+            to: query.to,
+            limit: query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+            offset: query.offset.unwrap_or(0),
+        };
+
+        let games = self
+            .repository
+            .get_archived_games(&filter)
+            .await?
+            .into_iter()
+            .map(|g| g.into_dto())
+            .collect();
+        let total = self.repository.count_archived_games(&filter).await?;
+        let categories = self
+            .repository
+            .count_archived_games_by_category(&filter)
+            .await?
+            .into_iter()
+            .map(|c| c.into_dto())
+            .collect();
+
+        Ok(HistoryResponse {
+            games,
+            total,
+            categories,
+        })
+    }
+}
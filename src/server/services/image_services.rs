@@ -0,0 +1,192 @@
+// poster proxy. browsers load posters from us instead of the upstream host, which keeps their
+// ips to ourselves and gets around hotlink blocking. only allowlisted hosts get fetched, and
+// since anyone can call it the fetches stay out of the upstream breakers, otherwise a flood of bad
+// posters could open the breaker for the api the games come from
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use mockall::automock;
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+use url::Url;
+
+use crate::server::{
+    error::{AppResult, Error},
+    utils::{
+        disk_cache_utils::DiskCache,
+        image_utils::{DEFAULT_IMAGE_WIDTH, ImageFormat, resize_image, snap_width},
+    },
+};
+
+pub type DynImagesService = Arc<dyn ImagesServiceTrait + Send + Sync>;
+
+// posters are a few hundred kb at most, this is plenty
+const MAX_SOURCE_BYTES: usize = 10 * 1024 * 1024;
+
+pub struct ProxiedImage {
+    pub bytes: Vec<u8>,
+    pub format: ImageFormat,
+}
+
+#[automock]
+#[async_trait]
+pub trait ImagesServiceTrait {
+    /// where the proxied copy of `src` lives, or `src` untouched if its host isn't allowlisted
+    fn proxied_url(&self, src: &str) -> String;
+
+    /// `src` resized to the closest allowed width at or above `width`
+    async fn get_image(
+        &self,
+        src: String,
+        width: Option<u32>,
+        format: ImageFormat,
+    ) -> AppResult<ProxiedImage>;
+}
+
+pub struct ImagesService {
+    client: reqwest::Client,
+    allowed_hosts: Arc<Vec<String>>,
+    public_url: Option<String>,
+    cache: Option<DiskCache>,
+}
+
+impl ImagesService {
+    pub fn new(
+        allowed_hosts: Vec<String>,
+        public_url: Option<String>,
+        cache: Option<DiskCache>,
+    ) -> Self {
+        let allowed_hosts = Arc::new(allowed_hosts);
+
+        // a redirect could point anywhere, it has to pass the allowlist like the original url
+        let redirect_hosts = allowed_hosts.clone();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 3 {
+                    attempt.error("too many redirects")
+                } else if host_allowed(&redirect_hosts, attempt.url()) {
+                    attempt.follow()
+                } else {
+                    attempt.stop()
+                }
+            }))
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            allowed_hosts,
+            public_url,
+            cache,
+        }
+    }
+
+    fn allowed_source(&self, src: &str) -> Option<Url> {
+        let url = Url::parse(src).ok()?;
+        host_allowed(&self.allowed_hosts, &url).then_some(url)
+    }
+
+    async fn fetch(&self, url: Url) -> anyhow::Result<Vec<u8>> {
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+
+        if response
+            .content_length()
+            .is_some_and(|len| len as usize > MAX_SOURCE_BYTES)
+        {
+            anyhow::bail!("image is too large");
+        }
+
+        // content-length can lie or be missing so the limit is enforced while reading too
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > MAX_SOURCE_BYTES {
+                anyhow::bail!("image is too large");
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        Ok(bytes)
+    }
+}
+
+fn host_allowed(allowed_hosts: &[String], url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let host = host.to_lowercase();
+
+    // exact hosts only, a suffix match would let in every subdomain including the api
+    allowed_hosts.contains(&host)
+}
+
+fn cache_key(src: &str, width: u32, format: ImageFormat) -> String {
+    let digest = Sha256::digest(format!("{}|{}", src, width).as_bytes());
+
+    format!("{}.{}", hex::encode(digest), format.extension())
+}
+
+#[async_trait]
+impl ImagesServiceTrait for ImagesService {
+    fn proxied_url(&self, src: &str) -> String {
+        if self.allowed_source(src).is_none() {
+            return src.to_string();
+        }
+
+        format!(
+            "{}/api/v1/images?src={}&w={}",
+            self.public_url.as_deref().unwrap_or(""),
+            urlencoding::encode(src),
+            DEFAULT_IMAGE_WIDTH
+        )
+    }
+
+    async fn get_image(
+        &self,
+        src: String,
+        width: Option<u32>,
+        format: ImageFormat,
+    ) -> AppResult<ProxiedImage> {
+        let url = self
+            .allowed_source(&src)
+            .ok_or_else(|| Error::BadRequest("image source is not allowed".into()))?;
+        let width = snap_width(width);
+        let key = cache_key(url.as_str(), width, format);
+
+        if let Some(cache) = &self.cache
+            && let Some(bytes) = cache.get(&key).await
+        {
+            metrics::counter!("image_cache_requests_total", "result" => "hit").increment(1);
+            return Ok(ProxiedImage { bytes, format });
+        }
+        metrics::counter!("image_cache_requests_total", "result" => "miss").increment(1);
+
+        info!("fetching image {} at {}px", url, width);
+        let source = self.fetch(url).await.map_err(|e| {
+            error!("failed to fetch image {}: {}", src, e);
+            Error::from_upstream(e, |_| Error::NotFound("image could not be fetched".into()))
+        })?;
+
+        let bytes = tokio::task::spawn_blocking(move || resize_image(&source, width, format))
+            .await
+            .map_err(|e| Error::InternalServerErrorWithContext(e.to_string()))?
+            .map_err(|e| {
+                error!("failed to resize image {}: {}", src, e);
+                Error::BadRequest("source is not a supported image".into())
+            })?;
+
+        if let Some(cache) = &self.cache
+            && let Err(e) = cache.put(&key, &bytes).await
+        {
+            // still worth serving, it'll just get resized again next time
+            error!("failed to cache image {}: {}", src, e);
+        }
+
+        Ok(ProxiedImage { bytes, format })
+    }
+}
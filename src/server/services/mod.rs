@@ -1,18 +1,37 @@
+use std::path::PathBuf;
 use std::sync::Arc;
 
-use tracing::info;
+use tracing::{error, info};
 
 use crate::{
     config::AppConfig,
     database::{Database, RedisDatabase},
     server::{
         services::{
-            movie_services::MovieService, ppvsu_services::PpvsuService,
-            session_services::SessionsService, stream_services::StreamsService,
+            caption_services::CaptionsService,
+            category_services::CategoriesService,
+            event_services::StreamEventsService,
+            extraction_services::ExtractionService,
+            follow_services::FollowsService,
+            history_services::HistoryService,
+            image_services::ImagesService,
+            movie_services::MovieService,
+            notification_services::{EmailDispatcher, NotificationsService, WebhookDispatcher},
+            ppvsu_services::PpvsuService,
+            progress_services::WatchProgressService,
+            room_services::RoomsService,
+            session_services::SessionsService,
+            stream_provider::StreamProviderRegistry,
+            stream_services::StreamsService,
+            team_services::TeamsService,
+            upstream_services::{BreakerSettings, UpstreamsService},
             user_services::UsersService,
+            watchlist_services::WatchlistService,
         },
         utils::{
             argon_utils::{ArgonSecurityUtil, DynArgonUtil},
+            disk_cache_utils::DiskCache,
+            hls_utils::PlaylistCache,
             jwt_utils::JwtTokenUtil,
             signature_utils::SignatureUtil,
         },
@@ -20,30 +39,74 @@ use crate::{
 };
 
 use self::{
-    movie_services::DynMovieService, ppvsu_services::DynPpvsuService,
-    session_services::DynSessionsService, stream_services::DynStreamsService,
+    caption_services::DynCaptionsService,
+    category_services::DynCategoriesService,
+    event_services::DynStreamEventsService,
+    extraction_services::DynExtractionService,
+    follow_services::DynFollowsService,
+    history_services::DynHistoryService,
+    image_services::DynImagesService,
+    movie_services::DynMovieService,
+    notification_services::{DynNotificationDispatcher, DynNotificationsService},
+    progress_services::DynWatchProgressService,
+    room_services::DynRoomsService,
+    session_services::DynSessionsService,
+    stream_provider::DynStreamProvider,
+    stream_services::DynStreamsService,
+    team_services::DynTeamsService,
+    upstream_services::DynUpstreamsService,
     user_services::DynUsersService,
+    watchlist_services::DynWatchlistService,
 };
 
 use super::utils::jwt_utils::DynJwtUtil;
 
+pub mod caption_services;
+pub mod category_services;
+pub mod event_services;
+pub mod extraction_services;
+pub mod follow_services;
+pub mod history_services;
+pub mod image_services;
 pub mod movie_services;
+pub mod notification_services;
 pub mod ppvsu_services;
+pub mod progress_services;
+pub mod refresh_services;
+pub mod room_services;
 pub mod seed_services;
 pub mod session_services;
+pub mod stream_provider;
 pub mod stream_services;
+pub mod team_services;
+pub mod upstream_services;
 pub mod user_services;
+pub mod watcher_services;
+pub mod watchlist_services;
 
 // list of services that we are using
 #[derive(Clone)]
 pub struct Services {
     pub jwt_util: DynJwtUtil,
     pub signature_util: Arc<SignatureUtil>,
+    pub playlists: Arc<PlaylistCache>,
     pub users: DynUsersService,
     pub sessions: DynSessionsService,
     pub streams: DynStreamsService,
-    pub ppvsu: DynPpvsuService,
+    pub categories: DynCategoriesService,
+    pub teams: DynTeamsService,
+    pub history: DynHistoryService,
+    pub events: DynStreamEventsService,
+    pub images: DynImagesService,
+    pub captions: DynCaptionsService,
+    pub providers: Arc<StreamProviderRegistry>,
+    pub follows: DynFollowsService,
+    pub notifications: DynNotificationsService,
     pub movies: DynMovieService,
+    pub progress: DynWatchProgressService,
+    pub rooms: DynRoomsService,
+    pub watchlist: DynWatchlistService,
+    pub upstreams: DynUpstreamsService,
     pub database: Arc<Database>,
     pub redis: Arc<RedisDatabase>,
     pub config: Arc<AppConfig>,
@@ -78,20 +141,155 @@ impl Services {
             sessions.clone(),
         )) as DynUsersService;
 
-        let ppvsu = Arc::new(PpvsuService::new(redis_repository.clone())) as DynPpvsuService;
-        let streams =
-            Arc::new(StreamsService::new(redis_repository.clone(), ppvsu.clone())) as DynStreamsService;
+        // shared by everything that scrapes so a host that's down is down for all of them
+        let breaker_hosts = [
+            &config.ppvsu_api_url,
+            &config.ppvsu_site_url,
+            &config.vidlink_url,
+        ]
+        .into_iter()
+        .filter_map(|url| url::Url::parse(url).ok()?.host_str().map(String::from))
+        .chain(
+            config
+                .upstream_breaker_hosts
+                .split(",")
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty()),
+        )
+        .collect();
+        let upstreams = Arc::new(UpstreamsService::new(
+            BreakerSettings::default(),
+            breaker_hosts,
+        )) as DynUpstreamsService;
+
+        let extraction = Arc::new(ExtractionService::new(
+            config.extraction_rules_path.as_ref().map(PathBuf::from),
+            Some(config.extraction_failures_dir.as_str())
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        )) as DynExtractionService;
+
+        // new sports sources just need to be registered here to show up in /streams
+        let mut providers = StreamProviderRegistry::new();
+        providers.register(Arc::new(PpvsuService::new(
+            redis_repository.clone(),
+            extraction.clone(),
+            upstreams.clone(),
+            &config.ppvsu_api_url,
+            &config.ppvsu_site_url,
+        )) as DynStreamProvider);
+        let providers = Arc::new(providers);
+
+        let events =
+            Arc::new(StreamEventsService::new(redis_repository.clone())) as DynStreamEventsService;
+
+        // without a usable cache dir images still work, they just get resized on every request
+        let image_cache = match DiskCache::open(
+            &config.image_cache_dir,
+            config.image_cache_max_mb * 1024 * 1024,
+        ) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                error!("image cache disabled: {}", e);
+                None
+            }
+        };
+        let images = Arc::new(ImagesService::new(
+            config
+                .image_proxy_hosts
+                .split(",")
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect(),
+            config.public_url.clone(),
+            image_cache,
+        )) as DynImagesService;
+
+        // same deal for captions, they get converted again on every request without it
+        let caption_cache = match DiskCache::open(
+            &config.caption_cache_dir,
+            config.caption_cache_max_mb * 1024 * 1024,
+        ) {
+            Ok(cache) => Some(cache),
+            Err(e) => {
+                error!("caption cache disabled: {}", e);
+                None
+            }
+        };
+        let captions = Arc::new(CaptionsService::new(
+            caption_cache,
+            config.fake_upstream_dir.is_some(),
+        )) as DynCaptionsService;
+
+        let categories =
+            Arc::new(CategoriesService::new(repository.clone())) as DynCategoriesService;
+
+        let teams = Arc::new(TeamsService::new(repository.clone())) as DynTeamsService;
+
+        let history = Arc::new(HistoryService::new(repository.clone())) as DynHistoryService;
+
+        let streams = Arc::new(StreamsService::new(
+            redis_repository.clone(),
+            providers.clone(),
+            events.clone(),
+            images.clone(),
+            categories.clone(),
+            teams.clone(),
+            history.clone(),
+        )) as DynStreamsService;
+
+        let follows = Arc::new(FollowsService::new(repository.clone())) as DynFollowsService;
+
+        // webhooks always work, email needs smtp configured
+        let mut dispatchers = vec![Arc::new(WebhookDispatcher::new()) as DynNotificationDispatcher];
+        if let (Some(smtp_url), Some(smtp_from)) = (&config.smtp_url, &config.smtp_from) {
+            match EmailDispatcher::new(smtp_url, smtp_from) {
+                Ok(email) => dispatchers.push(Arc::new(email) as DynNotificationDispatcher),
+                Err(e) => error!("email notifications disabled, bad smtp config: {}", e),
+            }
+        }
+
+        let notifications = Arc::new(NotificationsService::new(
+            repository.clone(),
+            redis_repository.clone(),
+            redis_repository.clone(),
+            providers.clone(),
+            teams.clone(),
+            dispatchers,
+            config.follow_notify_lead_secs as i64,
+            config.frontend_url.clone(),
+        )) as DynNotificationsService;
 
         let movies = Arc::new(MovieService::new()) as DynMovieService;
 
+        let progress =
+            Arc::new(WatchProgressService::new(repository.clone())) as DynWatchProgressService;
+
+        let rooms = Arc::new(RoomsService::new(redis_repository.clone())) as DynRoomsService;
+
+        let watchlist = Arc::new(WatchlistService::new(repository.clone())) as DynWatchlistService;
+
         Self {
             jwt_util,
             signature_util,
+            playlists: Arc::new(PlaylistCache::default()),
             users,
             sessions,
             streams,
-            ppvsu,
+            categories,
+            teams,
+            history,
+            events,
+            images,
+            captions,
+            providers,
+            follows,
+            notifications,
             movies,
+            progress,
+            rooms,
+            watchlist,
+            upstreams,
             database: repository,
             redis: redis_repository,
             config,
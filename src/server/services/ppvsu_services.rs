@@ -1,26 +1,36 @@
 // all the stream related functions, im not commenting on all of them, they're pretty readable
 use async_trait::async_trait;
-use base64::Engine;
 use flate2::read::GzDecoder;
 use mockall::automock;
-use regex::Regex;
 use std::io::Read;
 use std::sync::Arc;
 use tracing::{error, info};
 
 use crate::{
-    database::stream::{DynStreamsRepository, Game, PpvsuApiResponse, PpvsuStreamDetailResponse},
-    server::error::{AppResult, Error},
+    database::stream::{
+        DynStreamsRepository, Game, GameSource, PpvsuApiResponse, PpvsuStreamDetailResponse,
+    },
+    server::{
+        error::{AppResult, Error},
+        services::{
+            extraction_services::DynExtractionService,
+            stream_provider::{HeaderProfile, StreamProvider},
+            upstream_services::{DynUpstreamsService, send_upstream},
+        },
+        utils::category_utils::slugify,
+    },
 };
 
+/// redis prefix and route name for this provider
+pub const PPVSU_PROVIDER: &str = "ppvsu";
+
 pub type DynPpvsuService = Arc<dyn PpvsuServiceTrait + Send + Sync>;
 
 #[automock]
 #[async_trait]
 pub trait PpvsuServiceTrait {
-    async fn fetch_and_cache_games(&self) -> AppResult<Vec<Game>>;
+    async fn fetch_games(&self) -> AppResult<Vec<Game>>;
     async fn fetch_video_link(&self, iframe_url: &str) -> anyhow::Result<String>;
-    async fn get_games_with_refresh(&self) -> AppResult<Vec<Game>>;
     async fn get_game_by_id(&self, game_id: i64) -> AppResult<Game>;
     async fn clear_cache(&self) -> AppResult<()>;
     async fn get_current_timestamp(&self) -> AppResult<i64>;
@@ -30,11 +40,22 @@ pub trait PpvsuServiceTrait {
 #[derive(Clone)]
 pub struct PpvsuService {
     repository: DynStreamsRepository,
+    extraction: DynExtractionService,
+    upstreams: DynUpstreamsService,
     http_client: reqwest::Client,
+    // no trailing slash, https://api.ppvs.su and https://ppvs.su outside of tests
+    api_url: String,
+    site_url: String,
 }
 
 impl PpvsuService {
-    pub fn new(repository: DynStreamsRepository) -> Self {
+    pub fn new(
+        repository: DynStreamsRepository,
+        extraction: DynExtractionService,
+        upstreams: DynUpstreamsService,
+        api_url: &str,
+        site_url: &str,
+    ) -> Self {
         // i like to make it look like a real browser but it's really not needed
         // if only there was a global function to do this for me .... FIXME:
         let http_client = reqwest::Client::builder()
@@ -46,25 +67,30 @@ impl PpvsuService {
 
         Self {
             repository,
+            extraction,
+            upstreams,
             http_client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            site_url: site_url.trim_end_matches('/').to_string(),
         }
     }
 
     async fn refetch_game(&self, game_id: i64) -> anyhow::Result<Game> {
         info!("refetching game {} from ppvs.su API", game_id);
 
-        let response = self
-            .http_client
-            .get(format!("https://api.ppvs.su/api/streams/{}", game_id))
-            .header("Accept", "application/json, text/plain, */*")
-            .header("Accept-Language", "en-US,en;q=0.9")
-            .header("Referer", "https://api.ppvs.su/api/streams/")
-            .header("Origin", "https://api.ppvs.su/api/streams")
-            .header("Sec-Fetch-Dest", "empty")
-            .header("Sec-Fetch-Mode", "cors")
-            .header("Sec-Fetch-Site", "same-origin")
-            .send()
-            .await?;
+        let response = send_upstream(
+            &self.upstreams,
+            self.http_client
+                .get(format!("{}/api/streams/{}", self.api_url, game_id))
+                .header("Accept", "application/json, text/plain, */*")
+                .header("Accept-Language", "en-US,en;q=0.9")
+                .header("Referer", format!("{}/api/streams/", self.api_url))
+                .header("Origin", format!("{}/api/streams", self.api_url))
+                .header("Sec-Fetch-Dest", "empty")
+                .header("Sec-Fetch-Mode", "cors")
+                .header("Sec-Fetch-Site", "same-origin"),
+        )
+        .await?;
 
         let detail_response: PpvsuStreamDetailResponse = response.json().await?;
 
@@ -74,10 +100,24 @@ impl PpvsuService {
 
         let data = detail_response.data;
 
-        let iframe = data
+        // upstream lists them best first so that order is the priority
+        let sources: Vec<GameSource> = data
             .sources
+            .into_iter()
+            .enumerate()
+            .map(|(i, source)| GameSource {
+                label: source
+                    .label
+                    .filter(|label| !label.trim().is_empty())
+                    .unwrap_or_else(|| format!("Source {}", i + 1)),
+                priority: i as i32,
+                link: source.data,
+            })
+            .collect();
+
+        let iframe = sources
             .first()
-            .map(|s| s.data.clone())
+            .map(|s| s.link.clone())
             .ok_or_else(|| anyhow::anyhow!("no sources found for stream"))?;
 
         // previous logic of storing the games that were already at the pure link, instead i need
@@ -90,6 +130,7 @@ impl PpvsuService {
             .map_err(|_| anyhow::anyhow!("System time before UNIX epoch"))?
             .as_secs() as i64;
 
+        let category = data.category_name.unwrap_or_else(|| "Unknown".to_string());
         let game = Game {
             id: data.id,
             name: data.name,
@@ -98,10 +139,15 @@ impl PpvsuService {
             end_time: data.end_timestamp,
             cache_time,
             video_link: iframe,
-            category: data.category_name.unwrap_or_else(|| "Unknown".to_string()),
+            category_slug: slugify(&category),
+            category,
+            sources,
+            league: None,
+            home_team: None,
+            away_team: None,
         };
 
-        self.repository.store_game("ppvsu", &game).await?;
+        self.repository.store_game(PPVSU_PROVIDER, &game).await?;
 
         Ok(game)
     }
@@ -112,47 +158,29 @@ impl PpvsuServiceTrait for PpvsuService {
     async fn fetch_video_link(&self, iframe_url: &str) -> anyhow::Result<String> {
         info!("fetching video link from iframe: {}", iframe_url);
 
-        let response = self
-            .http_client
-            .get(iframe_url)
-            .header(
-                "Accept",
-                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
-            )
-            .header("Accept-Language", "en-US,en;q=0.9")
-            // this is literally the only one of these that actually matters
-            .header("Referer", "https://api.ppvs.su/")
-            .header("Sec-Fetch-Dest", "iframe")
-            .header("Sec-Fetch-Mode", "navigate")
-            .header("Sec-Fetch-Site", "cross-site")
-            .send()
-            .await?;
+        let response = send_upstream(
+            &self.upstreams,
+            self.http_client
+                .get(iframe_url)
+                .header(
+                    "Accept",
+                    "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                )
+                .header("Accept-Language", "en-US,en;q=0.9")
+                // this is literally the only one of these that actually matters
+                .header("Referer", format!("{}/", self.api_url))
+                .header("Sec-Fetch-Dest", "iframe")
+                .header("Sec-Fetch-Mode", "navigate")
+                .header("Sec-Fetch-Site", "cross-site"),
+        )
+        .await?;
 
         let html = response.text().await?;
 
-        let re = Regex::new(r#"const src = atob\("([^"]+)"\)"#)?;
-
-        if let Some(caps) = re.captures(&html) {
-            let base64_encoded = caps
-                .get(1)
-                .map(|m| m.as_str())
-                .ok_or_else(|| anyhow::anyhow!("failed to extract base64 string"))?;
-
-            let decoded_bytes = base64::engine::general_purpose::STANDARD
-                .decode(base64_encoded)
-                .map_err(|e| anyhow::anyhow!("failed to decode base64: {}", e))?;
-
-            let video_link = String::from_utf8(decoded_bytes)
-                .map_err(|e| anyhow::anyhow!("failed to convert decoded bytes to UTF-8: {}", e))?;
-
-            info!("extracted and decoded video link: {}", video_link);
-            Ok(video_link)
-        } else {
-            error!("no video link found in iframe html");
-            Err(anyhow::anyhow!("video link not found in iframe"))
-        }
+        self.extraction.extract(iframe_url, &html).await
     }
-    async fn fetch_and_cache_games(&self) -> AppResult<Vec<Game>> {
+
+    async fn fetch_games(&self) -> AppResult<Vec<Game>> {
         // this is to maybe avoid the 403s that happen when cloudflare bans the ip
         //
         // i don't actually think this does anything because i think i'm hitting a rate limit but
@@ -160,36 +188,36 @@ impl PpvsuServiceTrait for PpvsuService {
         //
         // also just going to drop the future here because there is no point for me to actually
         // check it
-        let _ = self.http_client.get("https://api.ppvs.su/api/ping")
+        drop(self.http_client.get(format!("{}/api/ping", self.api_url))
             .header("User-Agent", "Mozilla/5.0 (Macintosh; Intel Mac OS X 10.15; rv:146.0) Gecko/20100101 Firefox/146.0")
             .header("Accept", "application/json")
             .header("Accept-Language", "en-US,en;q=0.5")
             .header("Accept-Encoding", "gzip, deflate, br, zstd")
-            .header("Referer", "https://ppvs.su/")
-            .header("Origin", "https://ppvs.su")
+            .header("Referer", format!("{}/", self.site_url))
+            .header("Origin", &self.site_url)
             .header("Sec-GPC", "1")
-            .send();
-        let response = self
-            .http_client
-            .get("https://api.ppvs.su/api/streams")
-            .header("Accept", "application/json, text/plain, */*")
-            .header("Accept-Language", "en-US,en;q=0.9")
-            .header("Accept-Encoding", "gzip, deflate, br")
-            .header("Referer", "https://api.ppvs.su/api/streams/")
-            .header("Origin", "https://api.ppvs.su/api/streams")
-            .header("DNT", "1")
-            .header("Sec-Fetch-Dest", "empty")
-            .header("Sec-Fetch-Mode", "cors")
-            .header("Sec-Fetch-Site", "same-origin")
-            .send()
-            .await
-            .map_err(|e| {
-                error!("failed to fetch ppvs.su API: {}", e);
-                crate::server::error::Error::InternalServerErrorWithContext(format!(
-                    "failed to fetch ppvs.su API: {}",
-                    e
-                ))
-            })?;
+            .send());
+        let response = send_upstream(
+            &self.upstreams,
+            self.http_client
+                .get(format!("{}/api/streams", self.api_url))
+                .header("Accept", "application/json, text/plain, */*")
+                .header("Accept-Language", "en-US,en;q=0.9")
+                .header("Accept-Encoding", "gzip, deflate, br")
+                .header("Referer", format!("{}/api/streams/", self.api_url))
+                .header("Origin", format!("{}/api/streams", self.api_url))
+                .header("DNT", "1")
+                .header("Sec-Fetch-Dest", "empty")
+                .header("Sec-Fetch-Mode", "cors")
+                .header("Sec-Fetch-Site", "same-origin"),
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to fetch ppvs.su API: {}", e);
+            Error::from_upstream(e, |e| {
+                Error::InternalServerErrorWithContext(format!("failed to fetch ppvs.su API: {}", e))
+            })
+        })?;
 
         info!(
             "received response from ppvs.su with status: {}",
@@ -245,24 +273,29 @@ impl PpvsuServiceTrait for PpvsuService {
             .map_err(|_| anyhow::anyhow!("System time before UNIX epoch"))?
             .as_secs() as i64;
 
+        // nothing gets stored here anymore, the refresher swaps the whole list in at once so a
+        // failed fetch halfway through doesn't leave the cache with half the games
         let mut games: Vec<Game> = Vec::new();
-        let mut game_mem: Game;
         for category in api_response.streams {
             for stream in category.streams {
-                if let Some(iframe) = stream.iframe.clone() {
-                    game_mem = Game {
+                // the listing only has the main embed, the rest come from the detail endpoint
+                // if that one turns out to be dead
+                if let Some(iframe) = stream.iframe {
+                    games.push(Game {
+                        sources: vec![GameSource::primary(&iframe)],
                         id: stream.id,
                         name: stream.name,
                         poster: stream.poster,
                         start_time: stream.starts_at,
                         end_time: stream.ends_at,
                         cache_time,
-                        video_link: iframe.clone(),
+                        video_link: iframe,
                         category: category.category.clone(),
-                    };
-                    games.push(game_mem.clone());
-
-                    self.repository.store_game("ppvsu", &game_mem).await?;
+                        category_slug: slugify(&category.category),
+                        league: None,
+                        home_team: None,
+                        away_team: None,
+                    });
                 }
             }
         }
@@ -346,159 +379,26 @@ impl PpvsuServiceTrait for PpvsuService {
         //     }
         // }
 
-        info!("cached {} games from ppvs.su", games.len());
+        info!("fetched {} games from ppvs.su", games.len());
         Ok(games)
     }
 
-    async fn get_games_with_refresh(&self) -> AppResult<Vec<Game>> {
-        info!("retrieving games with refresh logic");
-
-        let cache_time = self.repository.get_last_fetch_time("ppvsu").await?;
-        let current_time = self.get_current_timestamp().await?;
-
-        match cache_time {
-            Some(last_fetch) if !self.is_cache_stale(last_fetch, current_time).await => {
-                let cache_age = current_time - last_fetch;
-                info!(
-                    "overall cache is fresh (last fetch {} seconds ago)",
-                    cache_age
-                );
-                self.repository.get_games("ppvsu").await.map_err(|e| {
-                    error!("failed to get games from cache: {}", e);
-                    crate::server::error::Error::InternalServerErrorWithContext(format!(
-                        "failed to get games from cache: {}",
-                        e
-                    ))
-                })
-            }
-            _ => {
-                if let Some(last_fetch) = cache_time {
-                    let cache_age = current_time - last_fetch;
-                    info!(
-                        "overall cache is stale (last fetch {} seconds ago), refetching all games",
-                        cache_age
-                    );
-                } else {
-                    info!("no cache found, fetching all games");
-                }
-
-                self.repository.clear_cache("ppvsu").await?;
-                let games = self.fetch_and_cache_games().await?;
-                self.repository
-                    .set_last_fetch_time("ppvsu", current_time)
-                    .await?;
-                Ok(games)
-            }
-        }
-
-        // let one_hour = 3600;
-        // let games = self.repository.get_games("ppvsu").await?;
-
-        // let mut refresh_tasks = Vec::new();
-        // let mut fresh_games = Vec::new();
-
-        // for game in games {
-        //     let cache_age = current_time - game.cache_time;
-
-        //     if cache_age > one_hour {
-        //         info!(
-        //             "game {} is stale (cached {} seconds ago), queueing for refetch",
-        //             game.id, cache_age
-        //         );
-
-        //         let service_clone = self.clone();
-        //         let game_id = game.id;
-        //         let old_game = game.clone();
-
-        //         let task = tokio::spawn(async move {
-        //             match service_clone.refetch_game(game_id).await {
-        //                 Ok(new_game) => (Some(new_game), None, None),
-        //                 Err(e) => {
-        //                     error!("failed to refetch game {}: {}", game_id, e);
-
-        //                     if e.to_string().contains("404") || e.to_string().contains("not found")
-        //                     {
-        //                         info!("game {} no longer exists, marking for deletion", game_id);
-        //                         (None, Some(game_id), None)
-        //                     } else {
-        //                         info!("keeping old version of game {}", game_id);
-        //                         (None, None, Some(old_game))
-        //                     }
-        //                 }
-        //             }
-        //         });
-
-        //         refresh_tasks.push(task);
-        //     } else {
-        //         fresh_games.push(game);
-        //     }
-        // }
-
-        // info!(
-        //     "refetching {} stale games concurrently",
-        //     refresh_tasks.len()
-        // );
-
-        // let results = futures::future::join_all(refresh_tasks).await;
-
-        // let mut refreshed_games = fresh_games;
-        // for result in results {
-        //     match result {
-        //         Ok((Some(new_game), _, _)) => {
-        //             refreshed_games.push(new_game);
-        //         }
-        //         Ok((None, Some(game_id_to_delete), None)) => {
-        //             if let Err(del_err) = self
-        //                 .repository
-        //                 .delete_game("ppvsu", game_id_to_delete)
-        //                 .await
-        //             {
-        //                 error!("failed to delete game {}: {}", game_id_to_delete, del_err);
-        //             }
-        //         }
-        //         Ok((None, None, Some(old_game))) => {
-        //             refreshed_games.push(old_game);
-        //         }
-        //         Ok(_) => {}
-        //         Err(e) => {
-        //             error!("refresh task panicked: {}", e);
-        //         }
-        //     }
-        // }
-
-        // Ok(refreshed_games)
-    }
-
     async fn get_game_by_id(&self, game_id: i64) -> AppResult<Game> {
         info!("fetching game {} from cache or API", game_id);
 
-        if let Some(cached_game) = self.repository.get_game("ppvsu", game_id).await? {
-            let current_time = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .map_err(|_| anyhow::anyhow!("System time before UNIX epoch"))?
-                .as_secs() as i64;
-
-            let cache_age = current_time - cached_game.cache_time;
-            let one_hour = 3600;
-
-            if cache_age <= one_hour {
-                info!(
-                    "returning cached game {} (age: {} seconds)",
-                    game_id, cache_age
-                );
-                return Ok(cached_game);
-            }
-
-            info!(
-                "cached game {} is stale (age: {} seconds), refetching",
-                game_id, cache_age
-            );
-        } else {
-            info!("game {} not in cache, fetching from API", game_id);
+        // the background refresher keeps the cache fresh so anything in there is good to return,
+        // only games that showed up upstream since the last refresh get fetched here
+        if let Some(cached_game) = self.repository.get_game(PPVSU_PROVIDER, game_id).await? {
+            info!("returning cached game {}", game_id);
+            return Ok(cached_game);
         }
 
+        info!("game {} not in cache, fetching from API", game_id);
+
         let game = self.refetch_game(game_id).await.map_err(|e| {
-            crate::server::error::Error::NotFound(format!("game {} not found: {}", game_id, e))
+            Error::from_upstream(e, |e| {
+                Error::NotFound(format!("game {} not found: {}", game_id, e))
+            })
         })?;
 
         Ok(game)
@@ -507,13 +407,16 @@ impl PpvsuServiceTrait for PpvsuService {
     async fn clear_cache(&self) -> AppResult<()> {
         info!("clearing ppvsu cache");
 
-        self.repository.clear_cache("ppvsu").await.map_err(|e| {
-            error!("failed to clear ppvsu cache: {}", e);
-            crate::server::error::Error::InternalServerErrorWithContext(format!(
-                "failed to clear cache: {}",
-                e
-            ))
-        })?;
+        self.repository
+            .clear_cache(PPVSU_PROVIDER)
+            .await
+            .map_err(|e| {
+                error!("failed to clear ppvsu cache: {}", e);
+                crate::server::error::Error::InternalServerErrorWithContext(format!(
+                    "failed to clear cache: {}",
+                    e
+                ))
+            })?;
 
         info!("ppvsu cache cleared successfully");
         Ok(())
@@ -530,3 +433,50 @@ impl PpvsuServiceTrait for PpvsuService {
         current_time - cache_time > ONE_HOUR
     }
 }
+
+#[async_trait]
+impl StreamProvider for PpvsuService {
+    fn name(&self) -> &'static str {
+        PPVSU_PROVIDER
+    }
+
+    async fn fetch_games(&self) -> AppResult<Vec<Game>> {
+        PpvsuServiceTrait::fetch_games(self).await
+    }
+
+    async fn get_game_by_id(&self, game_id: i64) -> AppResult<Game> {
+        PpvsuServiceTrait::get_game_by_id(self, game_id).await
+    }
+
+    async fn fetch_game(&self, game_id: i64) -> AppResult<Game> {
+        self.refetch_game(game_id).await.map_err(|e| {
+            Error::from_upstream(e, |e| {
+                Error::NotFound(format!("game {} not found upstream: {}", game_id, e))
+            })
+        })
+    }
+
+    async fn fetch_video_link(&self, iframe_url: &str) -> anyhow::Result<String> {
+        PpvsuServiceTrait::fetch_video_link(self, iframe_url).await
+    }
+
+    // the poocloud cdn wants to look like it's being hit from ppvs.su itself, everything else
+    // is served through the embednow player
+    fn header_profile(&self, target_url: &str) -> HeaderProfile {
+        let user_agent = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36".to_string();
+
+        if target_url.contains("gg.poocloud.in") {
+            HeaderProfile {
+                origin: Some(format!("{}/api/streams", self.api_url)),
+                referer: Some(format!("{}/api/streams/", self.api_url)),
+                user_agent,
+            }
+        } else {
+            HeaderProfile {
+                origin: Some("https://embednow.top".to_string()),
+                referer: Some("https://embednow.top/".to_string()),
+                user_agent,
+            }
+        }
+    }
+}
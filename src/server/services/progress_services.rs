@@ -0,0 +1,210 @@
+// where users got to in movies and episodes. players report their position every few seconds so
+// writes are throttled per user and title, the last few seconds before someone stops watching
+// can get lost but nobody minds rewinding that far
+use async_trait::async_trait;
+use mockall::automock;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::{
+    database::progress::{DynWatchProgressRepository, WatchProgress},
+    server::{
+        dtos::movie_dto::TvEpisode,
+        dtos::progress_dto::{
+            MarkWatchedDto, ProgressSavedResponse, UpdateProgressDto, WatchProgressDto,
+        },
+        error::AppResult,
+    },
+};
+
+pub type DynWatchProgressService = Arc<dyn WatchProgressServiceTrait + Send + Sync>;
+
+// at most one write per user and title in this long, per instance
+const PROGRESS_WRITE_INTERVAL: Duration = Duration::from_secs(15);
+
+// past this much of the duration the credits are rolling, it counts as watched
+const WATCHED_FRACTION: f64 = 0.9;
+
+// how far back the continue watching list looks before shows are folded together
+const UNFINISHED_LOOKBACK: i64 = 200;
+
+const CONTINUE_WATCHING_LIMIT: usize = 20;
+
+// the throttle forgets anything older than the interval once it gets this big
+const THROTTLE_PRUNE_SIZE: usize = 10_000;
+
+#[automock]
+#[async_trait]
+pub trait WatchProgressServiceTrait {
+    async fn update_progress(
+        &self,
+        user_id: String,
+        request: UpdateProgressDto,
+    ) -> AppResult<ProgressSavedResponse>;
+    async fn mark_watched(
+        &self,
+        user_id: String,
+        request: MarkWatchedDto,
+    ) -> AppResult<WatchProgressDto>;
+    /// one entry per movie or show, the most recent episode for shows
+    async fn continue_watching(&self, user_id: String) -> AppResult<Vec<WatchProgressDto>>;
+    /// where to pick a movie or episode back up, None if it's not started or already watched.
+    /// `episode` is empty for movies
+    async fn resume_at(
+        &self,
+        user_id: &str,
+        media_id: &str,
+        episode: &str,
+    ) -> AppResult<Option<f64>>;
+}
+
+type ThrottleKey = (String, String, String);
+
+// how episodes are stored, the same `1/5` the link endpoints read progress back with. empty for
+// movies
+fn episode_key(episode: Option<TvEpisode>) -> String {
+    episode.map(|ep| ep.to_string()).unwrap_or_default()
+}
+
+pub struct WatchProgressService {
+    repository: DynWatchProgressRepository,
+    last_writes: Mutex<HashMap<ThrottleKey, Instant>>,
+}
+
+impl WatchProgressService {
+    pub fn new(repository: DynWatchProgressRepository) -> Self {
+        Self {
+            repository,
+            last_writes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // whether something was written for this title recently
+    fn written_recently(&self, key: &ThrottleKey) -> bool {
+        self.last_writes
+            .lock()
+            .unwrap()
+            .get(key)
+            .is_some_and(|at| at.elapsed() < PROGRESS_WRITE_INTERVAL)
+    }
+
+    // only called once a write went through, a failed one shouldn't hold the next one back
+    fn record_write(&self, key: ThrottleKey) {
+        let mut last_writes = self.last_writes.lock().unwrap();
+
+        if last_writes.len() >= THROTTLE_PRUNE_SIZE {
+            last_writes.retain(|_, at| at.elapsed() < PROGRESS_WRITE_INTERVAL);
+        }
+        last_writes.insert(key, Instant::now());
+    }
+}
+
+#[async_trait]
+impl WatchProgressServiceTrait for WatchProgressService {
+    async fn update_progress(
+        &self,
+        user_id: String,
+        request: UpdateProgressDto,
+    ) -> AppResult<ProgressSavedResponse> {
+        let episode = episode_key(request.tv_episode()?);
+        let watched = request
+            .duration
+            .is_some_and(|d| d > 0.0 && request.position >= d * WATCHED_FRACTION);
+
+        // finishing is always written so it doesn't get lost to the throttle
+        let key = (user_id.clone(), request.id.clone(), episode.clone());
+        if !watched && self.written_recently(&key) {
+            return Ok(ProgressSavedResponse {
+                saved: false,
+                watched,
+            });
+        }
+
+        let progress = self
+            .repository
+            .upsert_progress(&WatchProgress {
+                user_id,
+                media_id: request.id,
+                episode,
+                position_secs: request.position,
+                duration_secs: request.duration,
+                watched,
+                updated_at: sqlx::types::time::OffsetDateTime::now_utc(),
+            })
+            .await?;
+        self.record_write(key);
+
+        Ok(ProgressSavedResponse {
+            saved: true,
+            watched: progress.watched,
+        })
+    }
+
+    async fn mark_watched(
+        &self,
+        user_id: String,
+        request: MarkWatchedDto,
+    ) -> AppResult<WatchProgressDto> {
+        info!(
+            "marking {} {:?} as {} for {}",
+            request.id,
+            request.ep,
+            if request.watched {
+                "watched"
+            } else {
+                "unwatched"
+            },
+            user_id
+        );
+
+        let episode = episode_key(request.tv_episode()?);
+        let progress = self
+            .repository
+            .set_watched(&user_id, &request.id, &episode, request.watched)
+            .await?;
+
+        Ok(progress.into_dto())
+    }
+
+    async fn continue_watching(&self, user_id: String) -> AppResult<Vec<WatchProgressDto>> {
+        let unfinished = self
+            .repository
+            .get_unfinished(&user_id, UNFINISHED_LOOKBACK)
+            .await?;
+
+        // newest first, so the first episode seen of a show is the one to carry on with
+        let mut seen = Vec::new();
+        let items = unfinished
+            .into_iter()
+            .filter(|progress| {
+                let fresh = !seen.contains(&progress.media_id);
+                if fresh {
+                    seen.push(progress.media_id.clone());
+                }
+                fresh
+            })
+            .take(CONTINUE_WATCHING_LIMIT)
+            .map(|progress| progress.into_dto())
+            .collect();
+
+        Ok(items)
+    }
+
+    async fn resume_at(
+        &self,
+        user_id: &str,
+        media_id: &str,
+        episode: &str,
+    ) -> AppResult<Option<f64>> {
+        let progress = self
+            .repository
+            .get_progress(user_id, media_id, episode)
+            .await?;
+
+        Ok(progress
+            .filter(|p| !p.watched && p.position_secs > 0.0)
+            .map(|p| p.position_secs))
+    }
+}
@@ -0,0 +1,100 @@
+// keeps every stream provider's cache fresh in the background so requests never have to wait on
+// (or trigger) an upstream fetch
+use std::time::Duration;
+
+use rand::Rng;
+use tracing::{error, info};
+
+use crate::database::stream::DynStreamsRepository;
+
+use super::{Services, stream_services::DynStreamsService};
+
+// don't want to hammer upstream right after it failed, that's how we got banned last time
+const FAILED_REFRESH_RETRY_SECS: u64 = 300;
+
+pub struct ProviderRefreshService {
+    streams: DynStreamsService,
+    repository: DynStreamsRepository,
+    provider_names: Vec<&'static str>,
+    interval: Duration,
+    jitter: Duration,
+}
+
+impl ProviderRefreshService {
+    pub fn new(services: Services) -> Self {
+        Self {
+            streams: services.streams,
+            repository: services.redis as DynStreamsRepository,
+            provider_names: services.providers.names(),
+            interval: Duration::from_secs(services.config.provider_refresh_interval_secs),
+            jitter: Duration::from_secs(services.config.provider_refresh_jitter_secs),
+        }
+    }
+
+    /// runs forever, spawn it
+    pub async fn run(self) {
+        info!(
+            "provider refresher started for {:?} (every {:?} + up to {:?} jitter)",
+            self.provider_names, self.interval, self.jitter
+        );
+
+        loop {
+            let next_run = self.refresh_due_providers().await;
+            let jitter = self.random_jitter();
+
+            info!("next provider refresh check in {:?}", next_run + jitter);
+            tokio::time::sleep(next_run + jitter).await;
+        }
+    }
+
+    /// refreshes anything that's older than the interval and returns how long until the next one
+    /// is due, last_fetch lives in redis so restarts and other instances don't refetch early
+    async fn refresh_due_providers(&self) -> Duration {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        let interval_secs = self.interval.as_secs() as i64;
+
+        let mut next_run = self.interval;
+
+        for name in &self.provider_names {
+            let last_fetch = match self.repository.get_last_fetch_time(name).await {
+                Ok(last_fetch) => last_fetch,
+                Err(e) => {
+                    error!("failed to read last {} fetch time: {}", name, e);
+                    None
+                }
+            };
+
+            if let Some(last_fetch) = last_fetch {
+                let age = now - last_fetch;
+                if age < interval_secs {
+                    let remaining = Duration::from_secs((interval_secs - age) as u64);
+                    next_run = next_run.min(remaining);
+                    continue;
+                }
+            }
+
+            match self.streams.refresh_provider(name.to_string()).await {
+                Ok(count) => info!("background refresh of {} stored {} games", name, count),
+                Err(e) => {
+                    // old games stay in the cache, just try again a bit sooner
+                    error!("background refresh of {} failed: {}", name, e);
+                    next_run = next_run.min(Duration::from_secs(FAILED_REFRESH_RETRY_SECS));
+                }
+            }
+        }
+
+        next_run
+    }
+
+    fn random_jitter(&self) -> Duration {
+        let max = self.jitter.as_millis() as u64;
+        if max == 0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_millis(rand::rng().random_range(0..=max))
+    }
+}
@@ -0,0 +1,303 @@
+// watch parties. rooms live in redis so everyone can be connected to a different instance, the
+// host's commands update the room and get published to every instance, and each instance runs one
+// listener handing them to its own sockets. clients work out where playback should be from the
+// position, when it was set and the server time on every message
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+use mockall::automock;
+use nanoid::nanoid;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use crate::{
+    database::room::{ChatMessage, DynRoomsRepository, Room, RoomBroadcast, RoomEvent},
+    server::{
+        dtos::room_dto::{CreateRoomDto, RoomCommand, RoomSocketMessage},
+        error::{AppResult, Error},
+    },
+};
+
+pub type DynRoomsService = Arc<dyn RoomsServiceTrait + Send + Sync>;
+
+// how many events a slow socket can fall behind before it gets cut off and has to reconnect,
+// every room gets its own channel so only its own room's events count
+const BROADCAST_CAPACITY: usize = 256;
+
+// host commands that race each other get retried against the fresh room this many times
+const UPDATE_ATTEMPTS: usize = 5;
+
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(5);
+
+const MAX_CHAT_LENGTH: usize = 500;
+
+#[automock]
+#[async_trait]
+pub trait RoomsServiceTrait {
+    async fn create_room(&self, user_id: String, request: CreateRoomDto) -> AppResult<Room>;
+    async fn get_room(&self, room_id: &str) -> AppResult<Room>;
+    async fn get_chat(&self, room_id: &str) -> AppResult<Vec<ChatMessage>>;
+
+    /// the room's events from now on. ends if the socket falls too far behind, reconnecting gets
+    /// a fresh copy of the room
+    fn subscribe(&self, room_id: &str) -> BoxStream<'static, RoomEvent>;
+
+    /// tells everyone else in the room, never fails
+    async fn announce(&self, room_id: &str, event: RoomEvent);
+
+    /// anything that's only for the sender comes back here
+    async fn handle_command(
+        &self,
+        room_id: &str,
+        user_id: &str,
+        command: RoomCommand,
+    ) -> AppResult<Option<RoomSocketMessage>>;
+
+    /// forwards room events from redis to local sockets, never returns
+    async fn listen(&self);
+}
+
+type RoomSenders = HashMap<String, broadcast::Sender<RoomEvent>>;
+
+pub struct RoomsService {
+    repository: DynRoomsRepository,
+    /// one channel per room that has sockets on this instance
+    senders: Mutex<RoomSenders>,
+}
+
+impl RoomsService {
+    pub fn new(repository: DynRoomsRepository) -> Self {
+        Self {
+            repository,
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn senders(&self) -> MutexGuard<'_, RoomSenders> {
+        self.senders.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn deliver(&self, broadcast: RoomBroadcast) {
+        let mut senders = self.senders();
+        // nobody here is in that room
+        let Some(sender) = senders.get(&broadcast.room_id) else {
+            return;
+        };
+
+        // everyone left since the last event, the channel goes with them
+        if sender.send(broadcast.event).is_err() {
+            senders.remove(&broadcast.room_id);
+        }
+    }
+
+    async fn publish(&self, room_id: &str, event: RoomEvent) -> AppResult<()> {
+        self.repository
+            .publish_room_event(&RoomBroadcast {
+                room_id: room_id.to_string(),
+                event,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// only writes the room back if nobody else changed it in between, otherwise two host
+    /// commands landing at once would lose one of them
+    async fn update_room(
+        &self,
+        room_id: &str,
+        user_id: &str,
+        update: impl Fn(&mut Room, i64) + Send + Sync,
+    ) -> AppResult<()> {
+        for _ in 0..UPDATE_ATTEMPTS {
+            let previous = self.get_room(room_id).await?;
+            if previous.host != user_id {
+                return Err(Error::Forbidden);
+            }
+
+            let mut room = previous.clone();
+            let now = Utc::now().timestamp_millis();
+            update(&mut room, now);
+            room.updated_at = now;
+
+            if self.repository.replace_room(&previous, &room).await? {
+                return self.publish(room_id, RoomEvent::State { room }).await;
+            }
+        }
+
+        warn!(
+            "gave up updating room {} after {} attempts",
+            room_id, UPDATE_ATTEMPTS
+        );
+        Err(Error::ObjectConflict(format!(
+            "room {} is changing too quickly, try again",
+            room_id
+        )))
+    }
+}
+
+fn checked_position(position: f64) -> AppResult<f64> {
+    if !position.is_finite() || position < 0.0 {
+        return Err(Error::BadRequest(
+            "position must be a positive number of seconds".into(),
+        ));
+    }
+
+    Ok(position)
+}
+
+fn checked_media_url(media_url: &str) -> AppResult<String> {
+    match url::Url::parse(media_url.trim()) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(url.to_string()),
+        _ => Err(Error::BadRequest("media_url must be a url".into())),
+    }
+}
+
+#[async_trait]
+impl RoomsServiceTrait for RoomsService {
+    async fn create_room(&self, user_id: String, request: CreateRoomDto) -> AppResult<Room> {
+        let now = Utc::now().timestamp_millis();
+        let room = Room {
+            id: nanoid!(10),
+            host: user_id,
+            media_url: checked_media_url(&request.media_url)?,
+            playing: false,
+            position: 0.0,
+            updated_at: now,
+            created_at: now,
+        };
+
+        info!("{} opened room {}", room.host, room.id);
+        self.repository.save_room(&room).await?;
+
+        Ok(room)
+    }
+
+    async fn get_room(&self, room_id: &str) -> AppResult<Room> {
+        self.repository
+            .get_room(room_id)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("room {} not found", room_id)))
+    }
+
+    async fn get_chat(&self, room_id: &str) -> AppResult<Vec<ChatMessage>> {
+        Ok(self.repository.get_chat(room_id).await?)
+    }
+
+    fn subscribe(&self, room_id: &str) -> BoxStream<'static, RoomEvent> {
+        let receiver = {
+            let mut senders = self.senders();
+            // rooms everyone left that never saw another event to clean them up
+            senders.retain(|_, sender| sender.receiver_count() > 0);
+            senders
+                .entry(room_id.to_string())
+                .or_insert_with(|| broadcast::channel(BROADCAST_CAPACITY).0)
+                .subscribe()
+        };
+
+        stream::unfold(receiver, |mut receiver| async move {
+            match receiver.recv().await {
+                Ok(event) => Some((event, receiver)),
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("room socket fell {} events behind, disconnecting", skipped);
+                    None
+                }
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        })
+        .boxed()
+    }
+
+    async fn announce(&self, room_id: &str, event: RoomEvent) {
+        if let Err(e) = self.publish(room_id, event).await {
+            error!("failed to announce to room {}: {}", room_id, e);
+        }
+    }
+
+    async fn handle_command(
+        &self,
+        room_id: &str,
+        user_id: &str,
+        command: RoomCommand,
+    ) -> AppResult<Option<RoomSocketMessage>> {
+        match command {
+            RoomCommand::Play { position } => {
+                let position = position.map(checked_position).transpose()?;
+                self.update_room(room_id, user_id, |room, now| {
+                    room.position = position.unwrap_or_else(|| room.position_at(now));
+                    room.playing = true;
+                })
+                .await?;
+            }
+            RoomCommand::Pause { position } => {
+                let position = position.map(checked_position).transpose()?;
+                self.update_room(room_id, user_id, |room, now| {
+                    room.position = position.unwrap_or_else(|| room.position_at(now));
+                    room.playing = false;
+                })
+                .await?;
+            }
+            RoomCommand::Seek { position } => {
+                let position = checked_position(position)?;
+                self.update_room(room_id, user_id, |room, _| room.position = position)
+                    .await?;
+            }
+            RoomCommand::Media { media_url } => {
+                let media_url = checked_media_url(&media_url)?;
+                self.update_room(room_id, user_id, |room, _| {
+                    room.media_url = media_url.clone();
+                    room.position = 0.0;
+                    room.playing = false;
+                })
+                .await?;
+            }
+            RoomCommand::Chat { message } => {
+                let message = message.trim();
+                if message.is_empty() || message.chars().count() > MAX_CHAT_LENGTH {
+                    return Err(Error::BadRequest(format!(
+                        "chat messages must be 1-{} characters",
+                        MAX_CHAT_LENGTH
+                    )));
+                }
+
+                // the chat list would outlive a room that's already gone
+                self.get_room(room_id).await?;
+
+                let message = ChatMessage {
+                    user_id: user_id.to_string(),
+                    message: message.to_string(),
+                    sent_at: Utc::now().timestamp_millis(),
+                };
+                self.repository.add_chat(room_id, &message).await?;
+                self.publish(room_id, RoomEvent::Chat(message)).await?;
+            }
+            RoomCommand::Ping { client_time } => {
+                return Ok(Some(RoomSocketMessage::Pong { client_time }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn listen(&self) {
+        loop {
+            match self.repository.subscribe_room_events().await {
+                Ok(mut events) => {
+                    info!("listening for room events");
+                    while let Some(broadcast) = events.next().await {
+                        self.deliver(broadcast);
+                    }
+                    warn!("room event subscription closed, resubscribing");
+                }
+                Err(e) => error!("failed to subscribe to room events: {}", e),
+            }
+
+            tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// everything a sports source needs to plug into /streams, the ppvsu service is the reference
+// implementation so look there if something here isn't obvious
+use async_trait::async_trait;
+use mockall::automock;
+use std::sync::Arc;
+
+use crate::{database::stream::Game, server::error::AppResult};
+
+pub type DynStreamProvider = Arc<dyn StreamProvider + Send + Sync>;
+
+/// headers the proxy has to send upstream for a provider's playlists and segments, most cdns
+/// only care about the referer/origin pair but the user agent is kept here so it can be changed
+/// per provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderProfile {
+    pub origin: Option<String>,
+    pub referer: Option<String>,
+    pub user_agent: String,
+}
+
+#[automock]
+#[async_trait]
+pub trait StreamProvider {
+    /// key used for redis, routes and the `provider` param on proxied urls
+    fn name(&self) -> &'static str;
+
+    /// pull every game from upstream, this doesn't touch the cache since the refresher swaps the
+    /// whole set in once it has it
+    async fn fetch_games(&self) -> AppResult<Vec<Game>>;
+
+    /// one game, from the cache or from upstream if it isn't cached yet
+    async fn get_game_by_id(&self, game_id: i64) -> AppResult<Game>;
+
+    /// one game straight from upstream with every source it has, skipping the cache. used when
+    /// the cached sources are all dead
+    async fn fetch_game(&self, game_id: i64) -> AppResult<Game>;
+
+    /// turn the stored embed/iframe link into something that can actually be played
+    async fn fetch_video_link(&self, iframe_url: &str) -> anyhow::Result<String>;
+
+    /// headers for proxying anything resolved by this provider
+    fn header_profile(&self, target_url: &str) -> HeaderProfile;
+}
+
+/// all the providers that get merged into the stream listing, order of registration is the order
+/// they get fetched in
+#[derive(Clone, Default)]
+pub struct StreamProviderRegistry {
+    providers: Vec<DynStreamProvider>,
+}
+
+impl StreamProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// registering the same name twice replaces the old provider instead of listing it twice
+    pub fn register(&mut self, provider: DynStreamProvider) {
+        self.providers.retain(|p| p.name() != provider.name());
+        self.providers.push(provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<DynStreamProvider> {
+        self.providers.iter().find(|p| p.name() == name).cloned()
+    }
+
+    pub fn all(&self) -> &[DynStreamProvider] {
+        &self.providers
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+}
@@ -1,43 +1,625 @@
 // general stream services
 use async_trait::async_trait;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use futures::FutureExt;
+use futures::future::{BoxFuture, Shared};
 use mockall::automock;
-use std::sync::Arc;
-use tracing::info;
+use nanoid::nanoid;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+use validator::Validate;
 
 use std::collections::HashMap;
 
 use crate::{
-    database::stream::DynStreamsRepository,
+    database::{
+        event::StreamEventKind,
+        stream::{DynStreamsRepository, Game, ResolvedLink},
+    },
     server::{
-        dtos::stream_dto::{CategoryDto, GameDto, ResponseStreamDto},
-        error::AppResult,
+        dtos::stream_dto::{
+            CategoryDto, GameDto, GameListQuery, GameListResponse, GameSort, GameStatus,
+            ResponseStreamDto, SourceSelection,
+        },
+        error::{AppResult, Error, UpstreamUnavailable},
+        utils::{
+            calendar_utils::{CalendarEvent, render_calendar},
+            category_utils::{CategoryTaxonomy, slugify},
+            iptv_utils::{IptvEntry, IptvFeed, render_guide, render_playlist},
+            team_utils::TeamAliases,
+        },
     },
 };
 
-use super::ppvsu_services::DynPpvsuService;
+use super::{
+    category_services::DynCategoriesService,
+    event_services::DynStreamEventsService,
+    history_services::DynHistoryService,
+    image_services::{DynImagesService, ImagesServiceTrait},
+    stream_provider::{DynStreamProvider, StreamProvider, StreamProviderRegistry},
+    team_services::DynTeamsService,
+};
 
 pub type DynStreamsService = Arc<dyn StreamsServiceTrait + Send + Sync>;
 
+// errors get flattened to strings because every waiter needs its own copy of the result
+type SharedRefresh = Shared<BoxFuture<'static, Result<usize, String>>>;
+
+// upstream has a 30 second timeout so this leaves plenty of room before another instance can
+// take over a refresh that died without releasing
+const REFRESH_LOCK_TTL_SECS: u64 = 120;
+
+// listings are built against a clock that only ticks once a minute, otherwise the countdowns in
+// them would change every second and no etag would ever match
+const LISTING_CLOCK_SECS: i64 = 60;
+
+// scraping embeds is what gets us ip banned so a resolved link is reused for a while. upstream
+// links carry tokens that last a lot longer than this
+const RESOLVED_LINK_TTL_SECS: u64 = 10 * 60;
+
+// dead embeds are remembered for a bit too so a busy game doesn't hammer one on every request
+const FAILED_LINK_TTL_SECS: u64 = 60;
+
+// a game where nothing resolved, even after asking upstream for more sources, is remembered in
+// the same cache under this instead of a source link so the next request doesn't refetch it
+fn dead_game_key(game_id: i64) -> String {
+    format!("game:{}", game_id)
+}
+
+/// the time game statuses and countdowns in responses are worked out against
+pub fn listing_clock() -> i64 {
+    let now = Utc::now().timestamp();
+    now - now.rem_euclid(LISTING_CLOCK_SECS)
+}
+
+#[derive(Default)]
+struct SourceAttempts {
+    tried: HashSet<String>,
+    // set when a source was skipped because its host's breaker is open
+    unavailable: Option<UpstreamUnavailable>,
+}
+
+/// the playable link for a game and which of its sources it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedSource {
+    pub index: usize,
+    pub label: String,
+    pub link: String,
+}
+
+// position of the last game on a page, paging picks up at whatever sorts after it so a refresh
+// between pages doesn't shift everything by a few games like an offset would. ids are only
+// unique per provider so the provider is part of it, and the sort it was made for has to match
+#[derive(Serialize, Deserialize)]
+struct GameCursor {
+    start_time: i64,
+    name: String,
+    id: i64,
+    provider: String,
+    sort: GameSort,
+}
+
+impl GameCursor {
+    fn of(provider: &str, game: &Game, sort: GameSort) -> Self {
+        Self {
+            start_time: game.start_time,
+            name: game.name.clone(),
+            id: game.id,
+            provider: provider.to_string(),
+            sort,
+        }
+    }
+
+    fn encode(&self) -> AppResult<String> {
+        Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).map_err(anyhow::Error::from)?))
+    }
+
+    fn decode(cursor: &str) -> AppResult<Self> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| Error::BadRequest("invalid cursor".into()))
+    }
+
+    // ties always fall back to start time, id then provider so the order is total and paging is
+    // stable
+    fn compare(&self, other: &Self) -> Ordering {
+        let by_start = self
+            .start_time
+            .cmp(&other.start_time)
+            .then(self.id.cmp(&other.id))
+            .then(self.provider.cmp(&other.provider));
+        let by_name = self
+            .name
+            .to_lowercase()
+            .cmp(&other.name.to_lowercase())
+            .then(by_start);
+
+        match self.sort {
+            GameSort::Start => by_start,
+            GameSort::StartDesc => by_start.reverse(),
+            GameSort::Name => by_name,
+            GameSort::NameDesc => by_name.reverse(),
+        }
+    }
+}
+
+// `search` and `team` come in lowercased
+fn matches_query(
+    game: &Game,
+    query: &GameListQuery,
+    search: Option<&str>,
+    team: Option<&str>,
+    now: i64,
+) -> bool {
+    query
+        .category
+        .as_ref()
+        .is_none_or(|c| game.category_slug == *c || game.category.eq_ignore_ascii_case(c))
+        && query.status.is_none_or(|s| game.status_at(now) == s)
+        && query.from.is_none_or(|from| game.start_time >= from)
+        && query.to.is_none_or(|to| game.start_time <= to)
+        && team.is_none_or(|t| game.plays(t))
+        && search.is_none_or(|q| {
+            game.name.to_lowercase().contains(q) || game.category.to_lowercase().contains(q)
+        })
+}
+
+// swaps each game's category for ours and reads the teams out of its name, handing back the raw
+// category names the taxonomy didn't know. running it over games that already went through it
+// changes nothing
+fn normalise_games(
+    taxonomy: &CategoryTaxonomy,
+    teams: &TeamAliases,
+    provider: &str,
+    games: &mut [Game],
+) -> Vec<String> {
+    let mut unmapped = Vec::new();
+    for game in games {
+        let canonical = taxonomy.resolve(provider, &game.category);
+        if !canonical.known && !unmapped.contains(&game.category) {
+            unmapped.push(game.category.clone());
+        }
+        game.category = canonical.name;
+        game.category_slug = canonical.slug;
+
+        let matchup = teams.parse(&game.name);
+        game.league = matchup.league;
+        game.home_team = matchup.home_team;
+        game.away_team = matchup.away_team;
+    }
+
+    unmapped
+}
+
+// posters in responses point at the image proxy instead of the upstream host
+fn game_dto(game: Game, now: i64, images: &dyn ImagesServiceTrait) -> GameDto {
+    let mut dto = game.into_dto_at(now);
+    dto.poster = images.proxied_url(&dto.poster);
+    dto
+}
+
+// what a refresh changed, for the sse feed. games that started since the last fetch count as
+// going live, on the very first fetch there's nothing to compare to so everything is just added
+fn refresh_events(
+    images: &dyn ImagesServiceTrait,
+    provider: &str,
+    previous: Option<&[Game]>,
+    last_fetch: Option<i64>,
+    games: &[Game],
+    fetched_at: i64,
+) -> Vec<(StreamEventKind, serde_json::Value)> {
+    let mut events = Vec::new();
+
+    if let Some(previous) = previous {
+        let previous_ids: HashSet<i64> = previous.iter().map(|g| g.id).collect();
+        let current_ids: HashSet<i64> = games.iter().map(|g| g.id).collect();
+
+        for game in games.iter().filter(|g| !previous_ids.contains(&g.id)) {
+            events.push((
+                StreamEventKind::GameAdded,
+                json!({ "provider": provider, "game": game_dto(game.clone(), fetched_at, images) }),
+            ));
+        }
+
+        for game in previous.iter().filter(|g| !current_ids.contains(&g.id)) {
+            events.push((
+                StreamEventKind::GameRemoved,
+                json!({ "provider": provider, "id": game.id }),
+            ));
+        }
+
+        if let Some(last_fetch) = last_fetch {
+            for game in games.iter().filter(|g| {
+                g.start_time > last_fetch && g.status_at(fetched_at) == GameStatus::Live
+            }) {
+                events.push((
+                    StreamEventKind::GameLive,
+                    json!({ "provider": provider, "game": game_dto(game.clone(), fetched_at, images) }),
+                ));
+            }
+        }
+    }
+
+    events.push((
+        StreamEventKind::CacheRefreshed,
+        json!({ "provider": provider, "games": games.len(), "fetched_at": fetched_at }),
+    ));
+
+    events
+}
+
 #[automock]
 #[async_trait]
 pub trait StreamsServiceTrait {
     async fn get_stream(&self, provider: String) -> AppResult<ResponseStreamDto>;
     async fn get_all_streams(&self) -> AppResult<Vec<ResponseStreamDto>>;
-    async fn get_all_games(&self) -> AppResult<Vec<CategoryDto>>;
+    async fn get_all_games(&self, query: GameListQuery) -> AppResult<GameListResponse>;
+    /// changes whenever the game listing could, for etags
+    async fn get_listing_version(&self) -> AppResult<String>;
+    /// same as the listing version but only for one provider's games
+    async fn get_provider_version(&self, provider: String) -> AppResult<String>;
+    async fn get_calendar(
+        &self,
+        query: GameListQuery,
+        frontend_url: Option<String>,
+    ) -> AppResult<String>;
+    async fn get_playlist(&self, query: GameListQuery, feed: IptvFeed) -> AppResult<String>;
+    async fn get_guide(&self, query: GameListQuery) -> AppResult<String>;
+    async fn get_game(&self, provider: String, game_id: i64) -> AppResult<Game>;
+    async fn resolve_video_link(
+        &self,
+        provider: String,
+        game_id: i64,
+        source: SourceSelection,
+    ) -> AppResult<ResolvedSource>;
+    async fn refresh_provider(&self, provider: String) -> AppResult<usize>;
+    /// forget what a game's sources resolved to, returns how many were cached
+    async fn invalidate_resolved_links(&self, provider: String, game_id: i64) -> AppResult<usize>;
 }
 
 #[derive(Clone)]
 pub struct StreamsService {
     repository: DynStreamsRepository,
-    ppvsu_service: DynPpvsuService,
+    providers: Arc<StreamProviderRegistry>,
+    events: DynStreamEventsService,
+    images: DynImagesService,
+    categories: DynCategoriesService,
+    teams: DynTeamsService,
+    history: DynHistoryService,
+    // refreshes currently running in this process, keyed by provider name
+    in_flight: Arc<Mutex<HashMap<&'static str, SharedRefresh>>>,
 }
 
 impl StreamsService {
-    pub fn new(repository: DynStreamsRepository, ppvsu_service: DynPpvsuService) -> Self {
+    pub fn new(
+        repository: DynStreamsRepository,
+        providers: Arc<StreamProviderRegistry>,
+        events: DynStreamEventsService,
+        images: DynImagesService,
+        categories: DynCategoriesService,
+        teams: DynTeamsService,
+        history: DynHistoryService,
+    ) -> Self {
         Self {
             repository,
-            ppvsu_service,
+            providers,
+            events,
+            images,
+            categories,
+            teams,
+            history,
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // the actual refresh, only runs upstream if we can get the redis lock. if another instance
+    // has it we just report what's already cached and let them finish (stale-while-revalidate)
+    async fn locked_refresh(&self, provider: DynStreamProvider) -> AppResult<usize> {
+        let repository = &self.repository;
+        let name = provider.name();
+        let token = nanoid!();
+
+        if !repository
+            .try_acquire_refresh_lock(name, &token, REFRESH_LOCK_TTL_SECS)
+            .await?
+        {
+            info!(
+                "another instance is refreshing {}, serving cached games",
+                name
+            );
+            metrics::counter!("stream_refresh_coalesced_total", "provider" => name, "scope" => "redis")
+                .increment(1);
+            return Ok(repository.get_games(name).await?.len());
+        }
+
+        let result = self.fetch_and_swap(provider.as_ref()).await;
+
+        if let Err(e) = repository.release_refresh_lock(name, &token).await {
+            // not the end of the world, it expires on its own
+            error!("failed to release {} refresh lock: {}", name, e);
+        }
+
+        result
+    }
+
+    async fn fetch_and_swap(
+        &self,
+        provider: &(dyn StreamProvider + Send + Sync),
+    ) -> AppResult<usize> {
+        let repository = &self.repository;
+        let name = provider.name();
+
+        info!("refreshing {} games from upstream", name);
+        metrics::counter!("stream_refresh_upstream_total", "provider" => name).increment(1);
+
+        // fetch first and only swap on success so a dead upstream leaves the old games in place
+        let mut games = provider.fetch_games().await?;
+
+        let taxonomy = self.categories.taxonomy().await;
+        let teams = self.teams.aliases().await;
+        let unmapped = normalise_games(&taxonomy, &teams, name, &mut games);
+        self.categories.record_unmapped(name, unmapped).await;
+
+        let fetched_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| anyhow::anyhow!("System time before UNIX epoch"))?
+            .as_secs() as i64;
+
+        // what was cached before the swap, only needed to work out what changed. a failure here
+        // shouldn't stop the refresh, it just means no diff gets published
+        let previous = match repository.get_games(name).await {
+            Ok(previous) => Some(previous),
+            Err(e) => {
+                error!("failed to read {} games before refresh: {}", name, e);
+                None
+            }
+        };
+        let last_fetch = repository.get_last_fetch_time(name).await.ok().flatten();
+
+        repository
+            .replace_games(name, &games, fetched_at)
+            .await
+            .map_err(|e| {
+                error!("failed to swap in new {} games: {}", name, e);
+                Error::InternalServerErrorWithContext(format!("failed to update cache: {}", e))
+            })?;
+
+        info!("{} cache refreshed with {} games", name, games.len());
+
+        self.history.archive(name, &games, fetched_at).await;
+
+        let changes = refresh_events(
+            self.images.as_ref(),
+            name,
+            previous.as_deref(),
+            last_fetch,
+            &games,
+            fetched_at,
+        );
+        if let Err(e) = self.events.publish(changes).await {
+            error!("failed to publish {} refresh events: {}", name, e);
+        }
+
+        Ok(games.len())
+    }
+
+    // narrow things down in redis where there's an index for it, the rest of the query is
+    // applied in memory afterwards anyway
+    async fn cached_games(
+        &self,
+        provider: &str,
+        query: &GameListQuery,
+    ) -> anyhow::Result<Vec<Game>> {
+        if query.from.is_some() || query.to.is_some() {
+            let from = query.from.unwrap_or(i64::MIN);
+            let to = query.to.unwrap_or(i64::MAX);
+            return self
+                .repository
+                .get_games_in_window(provider, from, to)
+                .await;
+        }
+
+        // names and slugs both work, ours are always the slug of the name
+        if let Some(category) = &query.category {
+            return self
+                .repository
+                .get_games_by_category(provider, &slugify(category))
+                .await;
+        }
+
+        self.repository.get_games(provider).await
+    }
+
+    // everything matching the listing query in the requested order, tagged with the provider it
+    // came from, plus the cursor for the next page if there is one
+    async fn query_games(
+        &self,
+        query: &GameListQuery,
+        now: i64,
+    ) -> AppResult<(Vec<(&'static str, Game)>, Option<String>)> {
+        query
+            .validate()
+            .map_err(|e| Error::BadRequest(format!("Validation error: {}", e)))?;
+
+        if let (Some(from), Some(to)) = (query.from, query.to)
+            && from > to
+        {
+            return Err(Error::BadRequest("from must not be after to".into()));
+        }
+
+        let after = query
+            .cursor
+            .as_deref()
+            .map(GameCursor::decode)
+            .transpose()?;
+        // a cursor from another sort (or a provider that's gone) would quietly page through the
+        // wrong games
+        if let Some(after) = &after
+            && (after.sort != query.sort || self.providers.get(&after.provider).is_none())
+        {
+            return Err(Error::BadRequest("invalid cursor".into()));
+        }
+        let search = query.q.as_deref().map(|q| q.trim().to_lowercase());
+
+        // this only ever reads, keeping the cache fresh is the background refresher's job
+        // games fetched outside a refresh get cached with the raw category, this catches those
+        let taxonomy = self.categories.taxonomy().await;
+        let teams = self.teams.aliases().await;
+        let team = query
+            .team
+            .as_deref()
+            .map(|t| teams.canonical(t).to_lowercase())
+            .filter(|t| !t.is_empty());
+        let mut games = Vec::new();
+        for provider in self.providers.all() {
+            match self.cached_games(provider.name(), query).await {
+                Ok(mut provider_games) => {
+                    normalise_games(&taxonomy, &teams, provider.name(), &mut provider_games);
+                    games.extend(provider_games.into_iter().map(|g| (provider.name(), g)))
+                }
+                Err(e) => error!("failed to get cached {} games: {}", provider.name(), e),
+            }
+        }
+
+        games.retain(|(_, g)| matches_query(g, query, search.as_deref(), team.as_deref(), now));
+        games.sort_by(|(pa, a), (pb, b)| {
+            GameCursor::of(pa, a, query.sort).compare(&GameCursor::of(pb, b, query.sort))
+        });
+
+        if let Some(after) = after {
+            games.retain(|(p, g)| {
+                GameCursor::of(p, g, query.sort).compare(&after) == Ordering::Greater
+            });
+        }
+
+        let mut next_cursor = None;
+        if let Some(limit) = query.limit
+            && games.len() > limit
+        {
+            games.truncate(limit);
+            next_cursor = games
+                .last()
+                .map(|(p, g)| GameCursor::of(p, g, query.sort).encode())
+                .transpose()?;
+        }
+
+        Ok((games, next_cursor))
+    }
+
+    // what iptv players get, anything that's already over is useless as a channel
+    async fn current_games(
+        &self,
+        query: &GameListQuery,
+        now: i64,
+    ) -> AppResult<Vec<(&'static str, Game)>> {
+        let (mut games, _) = self.query_games(query, now).await?;
+        games.retain(|(_, g)| g.status_at(now) != GameStatus::Ended);
+
+        Ok(games)
+    }
+
+    // tries each source best first, skipping links that already failed
+    async fn first_resolvable(
+        &self,
+        provider: &(dyn StreamProvider + Send + Sync),
+        game: &Game,
+        attempts: &mut SourceAttempts,
+    ) -> Option<ResolvedSource> {
+        for (index, source) in game.playable_sources().into_iter().enumerate() {
+            if !attempts.tried.insert(source.link.clone()) {
+                continue;
+            }
+
+            match self.resolve_source(provider, &source.link).await {
+                Ok(link) => {
+                    return Some(ResolvedSource {
+                        index,
+                        label: source.label,
+                        link,
+                    });
+                }
+                Err(e) => {
+                    if let Some(unavailable) = e.downcast_ref::<UpstreamUnavailable>() {
+                        attempts.unavailable = Some(unavailable.clone());
+                    }
+                    error!(
+                        "{} source {} ({}) for game {} failed: {}",
+                        provider.name(),
+                        index,
+                        source.label,
+                        game.id,
+                        e
+                    );
+                    metrics::counter!("stream_source_failures_total", "provider" => provider.name())
+                        .increment(1);
+                }
+            }
         }
+
+        None
+    }
+
+    // fetch_video_link with a cache in front of it, failures are cached too. the cache being down
+    // only means going upstream every time
+    async fn resolve_source(
+        &self,
+        provider: &(dyn StreamProvider + Send + Sync),
+        source_link: &str,
+    ) -> anyhow::Result<String> {
+        let name = provider.name();
+
+        match self.repository.get_resolved_link(name, source_link).await {
+            Ok(Some(ResolvedLink::Playable(link))) => {
+                metrics::counter!("resolved_link_cache_total", "provider" => name, "result" => "hit")
+                    .increment(1);
+                return Ok(link);
+            }
+            Ok(Some(ResolvedLink::Failed(reason))) => {
+                metrics::counter!("resolved_link_cache_total", "provider" => name, "result" => "failed_hit")
+                    .increment(1);
+                return Err(anyhow::anyhow!("source failed recently: {}", reason));
+            }
+            Ok(None) => {}
+            Err(e) => error!("failed to read resolved {} link: {}", name, e),
+        }
+        metrics::counter!("resolved_link_cache_total", "provider" => name, "result" => "miss")
+            .increment(1);
+
+        let result = provider.fetch_video_link(source_link).await;
+
+        // an open breaker says nothing about the source itself, it's worth trying once it closes
+        if let Err(e) = &result
+            && e.is::<UpstreamUnavailable>()
+        {
+            return result;
+        }
+
+        let (resolved, ttl) = match &result {
+            Ok(link) => (ResolvedLink::Playable(link.clone()), RESOLVED_LINK_TTL_SECS),
+            Err(e) => (ResolvedLink::Failed(e.to_string()), FAILED_LINK_TTL_SECS),
+        };
+        if let Err(e) = self
+            .repository
+            .set_resolved_link(name, source_link, &resolved, ttl)
+            .await
+        {
+            error!("failed to cache resolved {} link: {}", name, e);
+        }
+
+        result
+    }
+
+    fn provider(&self, name: &str) -> AppResult<DynStreamProvider> {
+        self.providers
+            .get(name)
+            .ok_or_else(|| Error::NotFound(format!("stream provider {} not found", name)))
     }
 }
 
@@ -74,67 +656,301 @@ impl StreamsServiceTrait for StreamsService {
         Ok(streams)
     }
 
-    async fn get_all_games(&self) -> AppResult<Vec<CategoryDto>> {
-        info!("retrieving all games with auto-fetch");
+    async fn get_all_games(&self, query: GameListQuery) -> AppResult<GameListResponse> {
+        info!("retrieving games from cache with {:?}", query);
 
-        let last_fetch = self.repository.get_last_fetch_time("ppvsu").await?;
+        let now = listing_clock();
+        let (games, next_cursor) = self.query_games(&query, now).await?;
+        let taxonomy = self.categories.taxonomy().await;
 
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .map_err(|_| anyhow::anyhow!("System time before UNIX epoch"))?
-            .as_secs() as i64;
+        // games keep the requested order inside their category
+        let mut categories_map: HashMap<String, (String, Vec<GameDto>)> = HashMap::new();
 
-        let one_hour = 3600;
-        let should_fetch = match last_fetch {
-            None => {
-                info!("no previous fetch found, fetching all games from API");
-                true
+        for (_, game) in games {
+            let (_, category_games) = categories_map
+                .entry(game.category_slug.clone())
+                .or_insert_with(|| (game.category.clone(), Vec::new()));
+            category_games.push(game_dto(game, now, self.images.as_ref()));
+        }
+
+        // ours in their sort order, anything unmapped after them by name
+        let mut categories: Vec<(i32, CategoryDto)> = categories_map
+            .into_iter()
+            .map(|(slug, (name, games))| {
+                let canonical = taxonomy.for_slug(&slug, &name);
+                let category = CategoryDto {
+                    category: canonical.name,
+                    slug: canonical.slug,
+                    icon: canonical.icon,
+                    games,
+                };
+                (canonical.sort_order, category)
+            })
+            .collect();
+
+        categories.sort_by(|(a_order, a), (b_order, b)| {
+            a_order.cmp(b_order).then(a.category.cmp(&b.category))
+        });
+        let categories = categories
+            .into_iter()
+            .map(|(_, category)| category)
+            .collect();
+
+        Ok(GameListResponse {
+            categories,
+            next_cursor,
+        })
+    }
+
+    async fn get_listing_version(&self) -> AppResult<String> {
+        // cheap on purpose, this runs before anything gets built. a provider that has never been
+        // fetched counts as 0 so it still changes the version once it has
+        let mut fetches = Vec::new();
+        for provider in self.providers.all() {
+            let last_fetch = self
+                .repository
+                .get_last_fetch_time(provider.name())
+                .await?
+                .unwrap_or(0);
+            fetches.push(last_fetch.to_string());
+        }
+
+        Ok(format!("streams-{}-{}", fetches.join("-"), listing_clock()))
+    }
+
+    async fn get_provider_version(&self, provider: String) -> AppResult<String> {
+        let name = self.provider(&provider)?.name();
+        let last_fetch = self
+            .repository
+            .get_last_fetch_time(name)
+            .await?
+            .unwrap_or(0);
+
+        Ok(format!("{}-{}-{}", name, last_fetch, listing_clock()))
+    }
+
+    async fn get_calendar(
+        &self,
+        query: GameListQuery,
+        frontend_url: Option<String>,
+    ) -> AppResult<String> {
+        info!("rendering game calendar with {:?}", query);
+
+        let now = Utc::now().timestamp();
+        let (games, _) = self.query_games(&query, now).await?;
+
+        let events: Vec<CalendarEvent> = games
+            .iter()
+            .map(|(provider, game)| CalendarEvent { provider, game })
+            .collect();
+
+        Ok(render_calendar(&events, frontend_url.as_deref(), now))
+    }
+
+    async fn get_playlist(&self, query: GameListQuery, feed: IptvFeed) -> AppResult<String> {
+        info!("rendering m3u playlist with {:?}", query);
+
+        let games = self.current_games(&query, Utc::now().timestamp()).await?;
+
+        let entries: Vec<IptvEntry> = games
+            .iter()
+            .map(|(provider, game)| IptvEntry { provider, game })
+            .collect();
+
+        Ok(render_playlist(&entries, &feed))
+    }
+
+    async fn get_guide(&self, query: GameListQuery) -> AppResult<String> {
+        info!("rendering xmltv guide with {:?}", query);
+
+        let games = self.current_games(&query, Utc::now().timestamp()).await?;
+
+        let entries: Vec<IptvEntry> = games
+            .iter()
+            .map(|(provider, game)| IptvEntry { provider, game })
+            .collect();
+
+        Ok(render_guide(&entries))
+    }
+
+    async fn get_game(&self, provider: String, game_id: i64) -> AppResult<Game> {
+        info!("retrieving game {} from provider {}", game_id, provider);
+
+        let provider = self.provider(&provider)?;
+        let mut game = provider.get_game_by_id(game_id).await?;
+
+        let taxonomy = self.categories.taxonomy().await;
+        let teams = self.teams.aliases().await;
+        normalise_games(
+            &taxonomy,
+            &teams,
+            provider.name(),
+            std::slice::from_mut(&mut game),
+        );
+
+        Ok(game)
+    }
+
+    async fn resolve_video_link(
+        &self,
+        provider: String,
+        game_id: i64,
+        source: SourceSelection,
+    ) -> AppResult<ResolvedSource> {
+        let provider = self.provider(&provider)?;
+        let mut game = provider.get_game_by_id(game_id).await?;
+
+        match source {
+            SourceSelection::Index(index) => {
+                // cached games can be missing sources upstream has, the listing only gives one
+                if index >= game.playable_sources().len() {
+                    game = provider.fetch_game(game_id).await?;
+                }
+
+                let source = game
+                    .playable_sources()
+                    .into_iter()
+                    .nth(index)
+                    .ok_or_else(|| {
+                        Error::NotFound(format!("game {} has no source {}", game_id, index))
+                    })?;
+                let link = self.resolve_source(provider.as_ref(), &source.link).await?;
+
+                Ok(ResolvedSource {
+                    index,
+                    label: source.label,
+                    link,
+                })
             }
-            Some(last_time) => {
-                let age = current_time - last_time;
-                if age > one_hour {
-                    info!("last fetch was {} seconds ago (> 1 hour), refetching", age);
-                    true
-                } else {
-                    info!("last fetch was {} seconds ago, using cache", age);
-                    false
+            SourceSelection::Auto => {
+                let mut attempts = SourceAttempts::default();
+                if let Some(resolved) = self
+                    .first_resolvable(provider.as_ref(), &game, &mut attempts)
+                    .await
+                {
+                    return Ok(resolved);
+                }
+
+                let dead_key = dead_game_key(game_id);
+                if let Ok(Some(ResolvedLink::Failed(_))) = self
+                    .repository
+                    .get_resolved_link(provider.name(), &dead_key)
+                    .await
+                {
+                    return Err(Error::NotFound(format!(
+                        "no source for game {} could be resolved",
+                        game_id
+                    )));
+                }
+
+                // everything cached is dead, upstream might know about more
+                info!(
+                    "no cached source for {} game {} resolved, asking upstream",
+                    provider.name(),
+                    game_id
+                );
+                let fresh = provider.fetch_game(game_id).await?;
+                if let Some(resolved) = self
+                    .first_resolvable(provider.as_ref(), &fresh, &mut attempts)
+                    .await
+                {
+                    return Ok(resolved);
                 }
+
+                // nothing resolved because the host wouldn't be called, that's a 503 not a 404
+                if let Some(unavailable) = attempts.unavailable {
+                    return Err(unavailable.into());
+                }
+
+                let dead = ResolvedLink::Failed(String::from("no source resolved"));
+                if let Err(e) = self
+                    .repository
+                    .set_resolved_link(provider.name(), &dead_key, &dead, FAILED_LINK_TTL_SECS)
+                    .await
+                {
+                    error!(
+                        "failed to cache dead {} game {}: {}",
+                        provider.name(),
+                        game_id,
+                        e
+                    );
+                }
+
+                Err(Error::NotFound(format!(
+                    "no source for game {} could be resolved",
+                    game_id
+                )))
             }
-        };
+        }
+    }
+
+    async fn refresh_provider(&self, provider: String) -> AppResult<usize> {
+        let provider = self.provider(&provider)?;
+        let name = provider.name();
+
+        // anyone asking while a refresh is already running just waits on that one
+        let refresh = {
+            let mut in_flight = self.in_flight.lock().map_err(|_| {
+                Error::InternalServerErrorWithContext("refresh map poisoned".into())
+            })?;
+
+            if let Some(existing) = in_flight.get(name) {
+                info!("{} refresh already running, waiting on it", name);
+                metrics::counter!("stream_refresh_coalesced_total", "provider" => name, "scope" => "local")
+                    .increment(1);
+                existing.clone()
+            } else {
+                // spawned so the refresh finishes (and clears itself out of the map) even if the
+                // request that started it goes away
+                let service = self.clone();
+                let task = tokio::spawn(async move {
+                    let result = service
+                        .locked_refresh(provider)
+                        .await
+                        .map_err(|e| e.to_string());
+                    if let Ok(mut map) = service.in_flight.lock() {
+                        map.remove(name);
+                    }
+                    result
+                });
 
-        let games = if should_fetch {
-            // cache is old so we drop it
-            info!("Dumping cache of ppvsu:* matchers");
-            self.repository.clear_cache("ppvsu").await?;
-
-            info!("fetching all games from ppvs.su API");
-            let games = self.ppvsu_service.fetch_and_cache_games().await?;
-            self.repository
-                .set_last_fetch_time("ppvsu", current_time)
-                .await?;
-            games
-        } else {
-            self.repository.get_games("ppvsu").await?
+                let refresh = async move {
+                    task.await
+                        .unwrap_or_else(|e| Err(format!("refresh task panicked: {}", e)))
+                }
+                .boxed()
+                .shared();
+
+                in_flight.insert(name, refresh.clone());
+                refresh
+            }
         };
 
-        let mut categories_map: HashMap<String, Vec<GameDto>> = HashMap::new();
+        refresh.await.map_err(Error::InternalServerErrorWithContext)
+    }
 
-        for game in games {
-            let category = game.category.clone();
-            let game_dto = game.into_dto();
-            categories_map
-                .entry(category)
-                .or_insert_with(Vec::new)
-                .push(game_dto);
-        }
+    async fn invalidate_resolved_links(&self, provider: String, game_id: i64) -> AppResult<usize> {
+        let provider = self.provider(&provider)?;
+        let game = provider.get_game_by_id(game_id).await?;
 
-        let mut categories: Vec<CategoryDto> = categories_map
+        let links: Vec<String> = game
+            .playable_sources()
             .into_iter()
-            .map(|(category, games)| CategoryDto { category, games })
+            .map(|source| source.link)
+            .chain([dead_game_key(game_id)])
             .collect();
+        let deleted = self
+            .repository
+            .delete_resolved_links(provider.name(), &links)
+            .await?;
 
-        categories.sort_by(|a, b| a.category.cmp(&b.category));
+        info!(
+            "invalidated {} resolved links for {} game {}",
+            deleted,
+            provider.name(),
+            game_id
+        );
 
-        Ok(categories)
+        Ok(deleted)
     }
 }
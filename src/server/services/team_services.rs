@@ -0,0 +1,142 @@
+// team aliases, the teams parsed out of game names get normalised through these so one team
+// doesn't show up under three names. kept in the database so admins can add them as they come
+// across new ones, and in memory because every refresh and listing needs them
+use async_trait::async_trait;
+use mockall::automock;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+use crate::{
+    database::team::{DynTeamsRepository, TeamAlias},
+    server::{
+        dtos::team_dto::{TeamAliasDto, UpsertTeamAliasDto},
+        error::{AppResult, Error},
+        utils::team_utils::TeamAliases,
+    },
+};
+
+pub type DynTeamsService = Arc<dyn TeamsServiceTrait + Send + Sync>;
+
+// other instances pick up admin changes within this
+const ALIASES_TTL: Duration = Duration::from_secs(60);
+
+#[automock]
+#[async_trait]
+pub trait TeamsServiceTrait {
+    async fn aliases(&self) -> Arc<TeamAliases>;
+    async fn get_aliases(&self) -> AppResult<Vec<TeamAliasDto>>;
+    async fn upsert_alias(
+        &self,
+        alias: String,
+        request: UpsertTeamAliasDto,
+    ) -> AppResult<TeamAliasDto>;
+    async fn delete_alias(&self, alias: String) -> AppResult<()>;
+}
+
+struct LoadedAliases {
+    aliases: Arc<TeamAliases>,
+    loaded_at: Option<Instant>,
+}
+
+pub struct TeamsService {
+    repository: DynTeamsRepository,
+    loaded: RwLock<LoadedAliases>,
+}
+
+impl TeamsService {
+    pub fn new(repository: DynTeamsRepository) -> Self {
+        Self {
+            repository,
+            loaded: RwLock::new(LoadedAliases {
+                aliases: Arc::new(TeamAliases::default()),
+                loaded_at: None,
+            }),
+        }
+    }
+
+    // next read goes back to the database
+    fn invalidate(&self) {
+        self.loaded.write().unwrap().loaded_at = None;
+    }
+}
+
+#[async_trait]
+impl TeamsServiceTrait for TeamsService {
+    // a database that's down means the last aliases we had keep being used
+    async fn aliases(&self) -> Arc<TeamAliases> {
+        {
+            let loaded = self.loaded.read().unwrap();
+            if loaded
+                .loaded_at
+                .is_some_and(|loaded_at| loaded_at.elapsed() < ALIASES_TTL)
+            {
+                return loaded.aliases.clone();
+            }
+        }
+
+        let fetched = self.repository.get_team_aliases().await;
+
+        let mut loaded = self.loaded.write().unwrap();
+        match fetched {
+            Ok(aliases) => loaded.aliases = Arc::new(TeamAliases::new(&aliases)),
+            Err(e) => error!(
+                "failed to load team aliases, keeping the previous ones: {}",
+                e
+            ),
+        }
+        loaded.loaded_at = Some(Instant::now());
+
+        loaded.aliases.clone()
+    }
+
+    async fn get_aliases(&self) -> AppResult<Vec<TeamAliasDto>> {
+        let aliases = self
+            .repository
+            .get_team_aliases()
+            .await?
+            .into_iter()
+            .map(|alias| alias.into_dto())
+            .collect();
+
+        Ok(aliases)
+    }
+
+    async fn upsert_alias(
+        &self,
+        alias: String,
+        request: UpsertTeamAliasDto,
+    ) -> AppResult<TeamAliasDto> {
+        let alias = alias.trim().to_lowercase();
+        let team = request.team.trim().to_string();
+        if alias.is_empty() || team.is_empty() {
+            return Err(Error::BadRequest("alias and team can't be blank".into()));
+        }
+        if alias == team.to_lowercase() {
+            return Err(Error::BadRequest(format!(
+                "{:?} is already the team's name",
+                team
+            )));
+        }
+
+        info!("aliasing {:?} to {:?}", alias, team);
+
+        let alias = self
+            .repository
+            .upsert_team_alias(&TeamAlias { alias, team })
+            .await?;
+        self.invalidate();
+
+        Ok(alias.into_dto())
+    }
+
+    async fn delete_alias(&self, alias: String) -> AppResult<()> {
+        let alias = alias.trim().to_lowercase();
+        if !self.repository.delete_team_alias(&alias).await? {
+            return Err(Error::NotFound(format!("team alias {:?} not found", alias)));
+        }
+        self.invalidate();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+// circuit breakers for the hosts we scrape. once enough of the recent calls to a host fail (or
+// take forever) the breaker opens and requests get a 503 straight away instead of waiting on the
+// timeout. after a while one request is let through to see if the host is back. only the hosts
+// we were configured with get a breaker, anything else goes straight through so odd urls can't
+// grow the map, the health output or the metric labels
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use mockall::automock;
+use tracing::{info, warn};
+
+use crate::server::{
+    dtos::health_dto::{BreakerState, HealthStatus, UpstreamHealth},
+    error::UpstreamUnavailable,
+};
+
+pub type DynUpstreamsService = Arc<dyn UpstreamsServiceTrait + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub struct BreakerSettings {
+    /// how many of the latest calls the error rate is worked out over
+    pub window: usize,
+    /// fewer calls than this in the window never opens the breaker
+    pub min_requests: usize,
+    pub error_rate: f64,
+    pub open_for: Duration,
+    /// anything slower counts as a failure, cloudflare challenges tend to just hang
+    pub slow_call: Duration,
+}
+
+impl Default for BreakerSettings {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            min_requests: 5,
+            error_rate: 0.5,
+            open_for: Duration::from_secs(30),
+            slow_call: Duration::from_secs(10),
+        }
+    }
+}
+
+#[automock]
+pub trait UpstreamsServiceTrait {
+    /// whether `host` is one of the upstreams with a breaker
+    fn tracks(&self, host: &str) -> bool;
+    /// whether a request to `host` can go out right now
+    fn acquire(&self, host: &str) -> Result<(), UpstreamUnavailable>;
+    fn record(&self, host: &str, success: bool, latency: Duration);
+    fn health(&self) -> BTreeMap<String, UpstreamHealth>;
+}
+
+struct Outcome {
+    failed: bool,
+    latency: Duration,
+}
+
+#[derive(Default)]
+struct Breaker {
+    recent: VecDeque<Outcome>,
+    opened_at: Option<Instant>,
+    // set while the one half open request is out
+    probe_started: Option<Instant>,
+}
+
+impl Breaker {
+    fn state(&self, settings: &BreakerSettings, now: Instant) -> BreakerState {
+        match self.opened_at {
+            None => BreakerState::Closed,
+            Some(opened_at) if now.duration_since(opened_at) < settings.open_for => {
+                BreakerState::Open
+            }
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    fn retry_in(&self, settings: &BreakerSettings, now: Instant) -> Duration {
+        self.opened_at
+            .map(|opened_at| {
+                settings
+                    .open_for
+                    .saturating_sub(now.duration_since(opened_at))
+            })
+            .unwrap_or_default()
+    }
+
+    fn error_rate(&self) -> f64 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        self.recent.iter().filter(|o| o.failed).count() as f64 / self.recent.len() as f64
+    }
+}
+
+pub struct UpstreamsService {
+    settings: BreakerSettings,
+    hosts: HashSet<String>,
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+impl UpstreamsService {
+    pub fn new(settings: BreakerSettings, hosts: Vec<String>) -> Self {
+        Self {
+            settings,
+            hosts: hosts.into_iter().map(|host| host.to_lowercase()).collect(),
+            breakers: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl UpstreamsServiceTrait for UpstreamsService {
+    fn tracks(&self, host: &str) -> bool {
+        self.hosts.contains(host)
+    }
+
+    fn acquire(&self, host: &str) -> Result<(), UpstreamUnavailable> {
+        if !self.tracks(host) {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers.entry(host.to_string()).or_default();
+
+        let unavailable = |retry_in: Duration| {
+            metrics::counter!("upstream_breaker_rejected_total", "host" => host.to_string())
+                .increment(1);
+            UpstreamUnavailable {
+                host: host.to_string(),
+                retry_in_secs: retry_in.as_secs().max(1),
+            }
+        };
+
+        match breaker.state(&self.settings, now) {
+            BreakerState::Closed => Ok(()),
+            BreakerState::Open => Err(unavailable(breaker.retry_in(&self.settings, now))),
+            BreakerState::HalfOpen => {
+                // a probe that never came back (dropped future) stops counting after a while
+                let probing = breaker
+                    .probe_started
+                    .is_some_and(|started| now.duration_since(started) < self.settings.open_for);
+                if probing {
+                    return Err(unavailable(Duration::from_secs(1)));
+                }
+
+                info!("letting a request through to {} to see if it's back", host);
+                breaker.probe_started = Some(now);
+                Ok(())
+            }
+        }
+    }
+
+    fn record(&self, host: &str, success: bool, latency: Duration) {
+        if !self.tracks(host) {
+            return;
+        }
+
+        let now = Instant::now();
+        let failed = !success || latency > self.settings.slow_call;
+        metrics::counter!(
+            "upstream_requests_total",
+            "host" => host.to_string(),
+            "result" => if failed { "failed" } else { "ok" }
+        )
+        .increment(1);
+
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers.entry(host.to_string()).or_default();
+
+        breaker.recent.push_back(Outcome { failed, latency });
+        while breaker.recent.len() > self.settings.window {
+            breaker.recent.pop_front();
+        }
+
+        match breaker.state(&self.settings, now) {
+            BreakerState::HalfOpen if breaker.probe_started.is_some() => {
+                breaker.probe_started = None;
+                if failed {
+                    warn!("{} is still failing, keeping its breaker open", host);
+                    breaker.opened_at = Some(now);
+                } else {
+                    info!("{} is back, closing its breaker", host);
+                    breaker.opened_at = None;
+                    breaker.recent.clear();
+                }
+            }
+            BreakerState::Closed
+                if breaker.recent.len() >= self.settings.min_requests
+                    && breaker.error_rate() >= self.settings.error_rate =>
+            {
+                warn!(
+                    "{} failed {:.0}% of the last {} calls, opening its breaker for {}s",
+                    host,
+                    breaker.error_rate() * 100.0,
+                    breaker.recent.len(),
+                    self.settings.open_for.as_secs()
+                );
+                breaker.opened_at = Some(now);
+            }
+            // calls that went out before it opened don't change anything
+            _ => {}
+        }
+    }
+
+    fn health(&self) -> BTreeMap<String, UpstreamHealth> {
+        let now = Instant::now();
+        let breakers = self.breakers.lock().unwrap();
+
+        breakers
+            .iter()
+            .map(|(host, breaker)| {
+                let state = breaker.state(&self.settings, now);
+                let requests = breaker.recent.len();
+                let avg_response_time_ms = if requests == 0 {
+                    0.0
+                } else {
+                    breaker
+                        .recent
+                        .iter()
+                        .map(|o| o.latency.as_secs_f64() * 1000.0)
+                        .sum::<f64>()
+                        / requests as f64
+                };
+
+                let health = UpstreamHealth {
+                    status: match state {
+                        BreakerState::Closed => HealthStatus::Healthy,
+                        BreakerState::HalfOpen => HealthStatus::Degraded,
+                        BreakerState::Open => HealthStatus::Unhealthy,
+                    },
+                    state,
+                    error_rate: breaker.error_rate(),
+                    avg_response_time_ms,
+                    requests,
+                    retry_in_secs: (state == BreakerState::Open)
+                        .then(|| breaker.retry_in(&self.settings, now).as_secs()),
+                };
+
+                (host.clone(), health)
+            })
+            .collect()
+    }
+}
+
+/// send through the host's breaker. errors and 5xx/403/429 (what cloudflare blocks look like)
+/// count against the host, other statuses are handed back for the caller to deal with. hosts
+/// without a breaker are just sent
+pub async fn send_upstream(
+    upstreams: &DynUpstreamsService,
+    request: reqwest::RequestBuilder,
+) -> anyhow::Result<reqwest::Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let host = request.url().host_str().unwrap_or_default().to_lowercase();
+    if !upstreams.tracks(&host) {
+        return Ok(client.execute(request).await?);
+    }
+
+    upstreams.acquire(&host)?;
+
+    let started = Instant::now();
+    let result = client.execute(request).await;
+    let latency = started.elapsed();
+
+    let success = match &result {
+        Ok(response) => {
+            let status = response.status();
+            !(status.is_server_error()
+                || status == reqwest::StatusCode::FORBIDDEN
+                || status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+        }
+        Err(_) => false,
+    };
+    upstreams.record(&host, success, latency);
+
+    Ok(result?)
+}
@@ -176,20 +176,20 @@ impl UsersServiceTrait for UsersService {
         let mut salt = [0u8; 16];
 
         // if the password is included on the request, hash it and update the stored password
-        if let Some(ref password) = request.password {
-            if !password.is_empty() {
-                info!(
-                    "new password found for user {:?}, hashing password",
-                    user_id
-                );
-                OsRng.try_fill_bytes(&mut salt)
-                    .map_err(|e| Error::InternalServerErrorWithContext(
-                        format!("Failed to generate random salt: {}", e)
-                    ))?;
-                updated_hashed_password = self
-                    .argon_util
-                    .hash_password(password.as_str(), &salt)?;
-            }
+        if let Some(ref password) = request.password
+            && !password.is_empty()
+        {
+            info!(
+                "new password found for user {:?}, hashing password",
+                user_id
+            );
+            OsRng.try_fill_bytes(&mut salt)
+                .map_err(|e| Error::InternalServerErrorWithContext(
+                    format!("Failed to generate random salt: {}", e)
+                ))?;
+            updated_hashed_password = self
+                .argon_util
+                .hash_password(password.as_str(), &salt)?;
         }
 
         info!("updating user {:?}", user_id);
@@ -0,0 +1,37 @@
+// ticks the go-live notifications, the matching and sending all happens in notification_services
+use std::time::Duration;
+
+use tracing::{error, info};
+
+use super::{Services, notification_services::DynNotificationsService};
+
+pub struct GoLiveWatcher {
+    notifications: DynNotificationsService,
+    interval: Duration,
+}
+
+impl GoLiveWatcher {
+    pub fn new(services: Services) -> Self {
+        Self {
+            notifications: services.notifications,
+            interval: Duration::from_secs(services.config.follow_watch_interval_secs),
+        }
+    }
+
+    /// runs forever, spawn it
+    pub async fn run(self) {
+        info!("go-live watcher started (every {:?})", self.interval);
+
+        let mut ticker = tokio::time::interval(self.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+
+            let now = chrono::Utc::now().timestamp();
+            if let Err(e) = self.notifications.notify_due_games(now).await {
+                error!("go-live watcher tick failed: {}", e);
+            }
+        }
+    }
+}
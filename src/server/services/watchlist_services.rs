@@ -0,0 +1,287 @@
+// a user's watchlist plus the csv import/export, resolving playback links for the top of the
+// list is the controller's job since that's where the movie link flow lives
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mockall::automock;
+use tracing::info;
+
+use crate::{
+    database::watchlist::{DynWatchlistRepository, WatchlistItem},
+    server::{
+        dtos::watchlist_dto::{
+            AddWatchlistItemDto, UpdateWatchlistItemDto, WatchlistImportResponse, WatchlistItemDto,
+            WatchlistKind,
+        },
+        error::{AppResult, Error},
+        utils::csv_utils::{parse_csv, write_csv_row},
+    },
+};
+
+/// most entries one user can have, imports stop once they hit it
+pub const WATCHLIST_LIMIT: usize = 500;
+
+const MEDIA_ID_MAX_LENGTH: usize = 64;
+
+const NOTE_MAX_LENGTH: usize = 500;
+
+/// the first line of exports, imports skip it if it's there
+pub const WATCHLIST_CSV_HEADER: &str = "id,kind,note";
+
+pub type DynWatchlistService = Arc<dyn WatchlistServiceTrait + Send + Sync>;
+
+#[automock]
+#[async_trait]
+pub trait WatchlistServiceTrait {
+    async fn get_watchlist(&self, user_id: String) -> AppResult<Vec<WatchlistItemDto>>;
+    async fn add_item(
+        &self,
+        user_id: String,
+        request: AddWatchlistItemDto,
+    ) -> AppResult<WatchlistItemDto>;
+    async fn update_item(
+        &self,
+        user_id: String,
+        item_id: String,
+        request: UpdateWatchlistItemDto,
+    ) -> AppResult<WatchlistItemDto>;
+    async fn delete_item(&self, user_id: String, item_id: String) -> AppResult<()>;
+    /// rows are `id[,kind[,note]]`, kind defaults to movie so a plain list of ids works
+    async fn import_csv(&self, user_id: String, csv: String) -> AppResult<WatchlistImportResponse>;
+    /// same format the import takes, in list order
+    async fn export_csv(&self, user_id: String) -> AppResult<String>;
+}
+
+#[derive(Clone)]
+pub struct WatchlistService {
+    repository: DynWatchlistRepository,
+}
+
+impl WatchlistService {
+    pub fn new(repository: DynWatchlistRepository) -> Self {
+        Self { repository }
+    }
+}
+
+// where something new goes when no position is given
+fn next_position(items: &[WatchlistItem]) -> i64 {
+    items.iter().map(|i| i.position + 1).max().unwrap_or(0)
+}
+
+fn non_empty_note(note: Option<String>) -> Option<String> {
+    note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty())
+}
+
+#[async_trait]
+impl WatchlistServiceTrait for WatchlistService {
+    async fn get_watchlist(&self, user_id: String) -> AppResult<Vec<WatchlistItemDto>> {
+        info!("retrieving watchlist for user {:?}", user_id);
+
+        let items = self
+            .repository
+            .get_watchlist(&user_id)
+            .await?
+            .into_iter()
+            .map(|i| i.into_dto())
+            .collect();
+
+        Ok(items)
+    }
+
+    async fn add_item(
+        &self,
+        user_id: String,
+        request: AddWatchlistItemDto,
+    ) -> AppResult<WatchlistItemDto> {
+        let kind = request.kind.as_str();
+        let media_id = request.media_id.trim();
+
+        if media_id.is_empty() {
+            return Err(Error::BadRequest("media_id cannot be blank".into()));
+        }
+
+        info!(
+            "user {:?} adding {} {:?} to watchlist",
+            user_id, kind, media_id
+        );
+
+        let existing = self.repository.get_watchlist(&user_id).await?;
+        if existing.len() >= WATCHLIST_LIMIT {
+            return Err(Error::BadRequest(format!(
+                "the watchlist can't have more than {} entries",
+                WATCHLIST_LIMIT
+            )));
+        }
+
+        let position = request.position.unwrap_or_else(|| next_position(&existing));
+
+        self.repository
+            .add_watchlist_item(
+                &user_id,
+                kind,
+                media_id,
+                non_empty_note(request.note),
+                position,
+            )
+            .await?
+            .map(|i| i.into_dto())
+            .ok_or_else(|| {
+                Error::ObjectConflict(format!("{} {} is already on the watchlist", kind, media_id))
+            })
+    }
+
+    async fn update_item(
+        &self,
+        user_id: String,
+        item_id: String,
+        request: UpdateWatchlistItemDto,
+    ) -> AppResult<WatchlistItemDto> {
+        info!("user {:?} updating watchlist item {:?}", user_id, item_id);
+
+        let mut item = self
+            .repository
+            .get_watchlist_item(&user_id, &item_id)
+            .await?
+            .ok_or_else(|| Error::NotFound(format!("watchlist item {} not found", item_id)))?;
+
+        if let Some(note) = request.note {
+            item.note = non_empty_note(Some(note));
+        }
+        if let Some(position) = request.position {
+            item.position = position;
+        }
+
+        let item = self.repository.update_watchlist_item(&item).await?;
+
+        Ok(item.into_dto())
+    }
+
+    async fn delete_item(&self, user_id: String, item_id: String) -> AppResult<()> {
+        info!("user {:?} removing watchlist item {:?}", user_id, item_id);
+
+        if !self
+            .repository
+            .delete_watchlist_item(&user_id, &item_id)
+            .await?
+        {
+            return Err(Error::NotFound(format!(
+                "watchlist item {} not found",
+                item_id
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn import_csv(&self, user_id: String, csv: String) -> AppResult<WatchlistImportResponse> {
+        let existing = self.repository.get_watchlist(&user_id).await?;
+        let mut position = next_position(&existing);
+        let mut count = existing.len();
+        let mut seen: HashSet<(String, String)> =
+            existing.into_iter().map(|i| (i.kind, i.media_id)).collect();
+
+        let mut response = WatchlistImportResponse::default();
+
+        for (index, row) in parse_csv(&csv).into_iter().enumerate() {
+            // the line in the file, blank lines and notes spanning lines make it differ from index
+            let line = row.line;
+            let field = |i: usize| row.fields.get(i).map(|f| f.trim()).unwrap_or_default();
+
+            if index == 0 && field(0).eq_ignore_ascii_case("id") {
+                continue;
+            }
+
+            let media_id = field(0);
+            if media_id.is_empty() || media_id.len() > MEDIA_ID_MAX_LENGTH {
+                response.errors.push(format!(
+                    "line {}: id must be 1-{} characters",
+                    line, MEDIA_ID_MAX_LENGTH
+                ));
+                continue;
+            }
+
+            let kind = if field(1).is_empty() {
+                WatchlistKind::Movie
+            } else if let Some(kind) = WatchlistKind::parse(field(1)) {
+                kind
+            } else {
+                response
+                    .errors
+                    .push(format!("line {}: unknown kind {:?}", line, field(1)));
+                continue;
+            };
+
+            let note = field(2);
+            if note.chars().count() > NOTE_MAX_LENGTH {
+                response.errors.push(format!(
+                    "line {}: note can't be over {} characters",
+                    line, NOTE_MAX_LENGTH
+                ));
+                continue;
+            }
+
+            if !seen.insert((kind.as_str().to_string(), media_id.to_string())) {
+                response.skipped += 1;
+                continue;
+            }
+
+            if count >= WATCHLIST_LIMIT {
+                response.errors.push(format!(
+                    "line {}: the watchlist is full ({} entries), stopped here",
+                    line, WATCHLIST_LIMIT
+                ));
+                break;
+            }
+
+            let added = self
+                .repository
+                .add_watchlist_item(
+                    &user_id,
+                    kind.as_str(),
+                    media_id,
+                    non_empty_note(Some(note.to_string())),
+                    position,
+                )
+                .await?;
+
+            // someone else got it in between reading the list and now
+            if added.is_none() {
+                response.skipped += 1;
+                continue;
+            }
+
+            response.added += 1;
+            position += 1;
+            count += 1;
+        }
+
+        info!(
+            "imported watchlist for user {:?}: {} added, {} skipped, {} errors",
+            user_id,
+            response.added,
+            response.skipped,
+            response.errors.len()
+        );
+
+        Ok(response)
+    }
+
+    async fn export_csv(&self, user_id: String) -> AppResult<String> {
+        info!("exporting watchlist for user {:?}", user_id);
+
+        let items = self.repository.get_watchlist(&user_id).await?;
+
+        let mut csv = format!("{}\n", WATCHLIST_CSV_HEADER);
+        for item in items {
+            csv.push_str(&write_csv_row(&[
+                &item.media_id,
+                &item.kind,
+                item.note.as_deref().unwrap_or_default(),
+            ]));
+            csv.push('\n');
+        }
+
+        Ok(csv)
+    }
+}
@@ -25,6 +25,12 @@ impl ArgonSecurityUtil {
     }
 }
 
+impl Default for ArgonSecurityUtil {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ArgonUtil for ArgonSecurityUtil {
     fn hash_password(&self, raw_password: &str, salt: &[u8]) -> AppResult<String> {
         let password_bytes = raw_password.as_bytes();
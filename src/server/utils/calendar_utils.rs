@@ -0,0 +1,106 @@
+// bare minimum rfc 5545 writer for the game calendar, there's no crate for this in the tree and
+// the format is simple enough that it isn't worth adding one
+use chrono::{DateTime, Utc};
+
+use crate::database::stream::Game;
+
+const PRODID: &str = "-//personalrestapi//streams//EN";
+
+// content lines have to be folded at 75 octets
+const MAX_LINE_OCTETS: usize = 75;
+
+/// a game plus the provider it came from, the pair is what makes the uid stable
+pub struct CalendarEvent<'a> {
+    pub provider: &'a str,
+    pub game: &'a Game,
+}
+
+/// link to the game on the frontend, `None` leaves URL off the events
+pub fn game_link(frontend_url: Option<&str>, provider: &str, game_id: i64) -> Option<String> {
+    frontend_url.map(|base| {
+        format!(
+            "{}/streams/{}/{}",
+            base.trim_end_matches('/'),
+            urlencoding::encode(provider),
+            game_id
+        )
+    })
+}
+
+pub fn render_calendar(events: &[CalendarEvent], frontend_url: Option<&str>, now: i64) -> String {
+    let mut out = String::new();
+
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, &format!("PRODID:{}", PRODID));
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, "METHOD:PUBLISH");
+    push_line(&mut out, "X-WR-CALNAME:Games");
+
+    for event in events {
+        let game = event.game;
+
+        push_line(&mut out, "BEGIN:VEVENT");
+        push_line(
+            &mut out,
+            &format!("UID:{}-{}@streams", escape_text(event.provider), game.id),
+        );
+        push_line(&mut out, &format!("DTSTAMP:{}", format_time(now)));
+        push_line(
+            &mut out,
+            &format!("DTSTART:{}", format_time(game.start_time)),
+        );
+        // zero length or backwards games happen upstream, an event can't end before it starts
+        push_line(
+            &mut out,
+            &format!("DTEND:{}", format_time(game.end_time.max(game.start_time))),
+        );
+        push_line(&mut out, &format!("SUMMARY:{}", escape_text(&game.name)));
+        if !game.category.is_empty() {
+            push_line(
+                &mut out,
+                &format!("CATEGORIES:{}", escape_text(&game.category)),
+            );
+        }
+        if let Some(link) = game_link(frontend_url, event.provider, game.id) {
+            push_line(&mut out, &format!("URL:{}", link));
+            push_line(&mut out, &format!("DESCRIPTION:{}", escape_text(&link)));
+        }
+        push_line(&mut out, "END:VEVENT");
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+
+    out
+}
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y%m%dT%H%M%SZ")
+        .to_string()
+}
+
+fn escape_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+// fold long lines without splitting a utf-8 character, continuation lines start with a space
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        let len = c.len_utf8();
+        if width + len > MAX_LINE_OCTETS {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += len;
+    }
+    out.push_str("\r\n");
+}
@@ -0,0 +1,539 @@
+// caption handling for /proxy/captions. sources hand out srt, webvtt or ass, sometimes in a
+// windows codepage, while a <track> only plays utf-8 webvtt. everything gets parsed into cues
+// and written back out as clean vtt
+use encoding_rs::{Encoding, WINDOWS_1252};
+
+// the ass v4+ default, used when a file has no Format line of its own
+const DEFAULT_ASS_FORMAT: &str =
+    "Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+
+// cue settings vtt understands, regions are dropped along with their definitions
+const CUE_SETTINGS: [&str; 5] = ["vertical", "line", "position", "size", "align"];
+
+// tags a <track> renders, anything else (srt's <font> mostly) is dropped
+const CUE_TAGS: [&str; 8] = ["b", "i", "u", "c", "v", "lang", "ruby", "rt"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaptionFormat {
+    Vtt,
+    Srt,
+    Ass,
+}
+
+impl CaptionFormat {
+    /// sniffed from the text itself, content types and extensions are wrong too often to trust
+    pub fn detect(text: &str) -> Option<Self> {
+        let text = text.trim_start_matches('\u{feff}').trim_start();
+
+        if text.starts_with("WEBVTT") {
+            Some(Self::Vtt)
+        } else if text.starts_with("[Script Info]")
+            || text
+                .lines()
+                .any(|line| line.trim().eq_ignore_ascii_case("[events]"))
+        {
+            Some(Self::Ass)
+        } else if text.lines().any(|line| line.contains("-->")) {
+            // a vtt missing its header parses the same way
+            Some(Self::Srt)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cue {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    /// vtt positioning like `line:0 align:start`, already filtered
+    pub settings: Option<String>,
+    pub text: String,
+}
+
+/// utf-8 text out of whatever the file was saved as. a bom wins, then `charset`, and anything
+/// that isn't valid utf-8 is assumed to be windows-1252 since that's where most of it comes from
+pub fn decode_caption_text(bytes: &[u8], charset: Option<&str>) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return encoding
+            .decode_without_bom_handling(&bytes[bom_length..])
+            .0
+            .into_owned();
+    }
+
+    if let Some(encoding) = charset.and_then(|c| Encoding::for_label(c.trim().as_bytes())) {
+        return encoding.decode_without_bom_handling(bytes).0.into_owned();
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => WINDOWS_1252
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+    }
+}
+
+/// `01:02:03,456` (srt), `01:02.456` (vtt) or `1:02:03.45` (ass) in milliseconds
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (clock, fraction) = value.rsplit_once([',', '.']).unwrap_or((value, ""));
+
+    let mut fields = clock.split(':').rev();
+    let mut next_field = || -> Option<Option<i64>> {
+        match fields.next() {
+            Some(field) => field.parse::<u32>().ok().map(|f| Some(f as i64)),
+            None => Some(None),
+        }
+    };
+    let seconds = next_field()??;
+    let minutes = next_field()?.unwrap_or(0);
+    let hours = next_field()?.unwrap_or(0);
+    if next_field()?.is_some() {
+        return None;
+    }
+
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    // ass has hundredths, some files have more than three digits
+    let millis: i64 = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse()
+        .ok()?;
+
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+pub fn format_timestamp(ms: i64) -> String {
+    let ms = ms.max(0);
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+fn filter_settings(settings: &str) -> Option<String> {
+    let kept: Vec<&str> = settings
+        .split_whitespace()
+        .filter(|setting| {
+            setting.split_once(':').is_some_and(|(key, value)| {
+                CUE_SETTINGS.contains(&key) && !value.is_empty() && !value.contains("-->")
+            })
+        })
+        .collect();
+
+    Some(kept.join(" ")).filter(|s| !s.is_empty())
+}
+
+// srt and vtt are both blank line separated blocks with a `start --> end` line, whatever comes
+// before it is a cue number or identifier
+fn parse_blocks(text: &str) -> Vec<Cue> {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut cues = Vec::new();
+
+    for block in text.split("\n\n") {
+        let lines: Vec<&str> = block
+            .lines()
+            .skip_while(|line| line.trim().is_empty())
+            .collect();
+        let Some(first) = lines.first() else {
+            continue;
+        };
+        if ["NOTE", "STYLE", "REGION"]
+            .iter()
+            .any(|kind| first.starts_with(kind))
+        {
+            continue;
+        }
+
+        let Some(timing) = lines.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+        let Some((start, rest)) = lines[timing].split_once("-->") else {
+            continue;
+        };
+        let rest = rest.trim();
+        let (end, settings) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+
+        let (Some(start_ms), Some(end_ms)) = (parse_timestamp(start), parse_timestamp(end)) else {
+            continue;
+        };
+        if end_ms <= start_ms {
+            continue;
+        }
+
+        cues.push(Cue {
+            start_ms,
+            end_ms,
+            settings: filter_settings(settings),
+            text: lines[timing + 1..].join("\n"),
+        });
+    }
+
+    cues
+}
+
+fn parse_ass(text: &str) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let mut in_events = false;
+    let mut format: Vec<String> = DEFAULT_ASS_FORMAT
+        .split(',')
+        .map(|f| f.trim().to_lowercase())
+        .collect();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+
+        if let Some(fields) = line.strip_prefix("Format:") {
+            format = fields.split(',').map(|f| f.trim().to_lowercase()).collect();
+            continue;
+        }
+        let Some(dialogue) = line.strip_prefix("Dialogue:") else {
+            continue;
+        };
+
+        // text is always last and the only field that can have commas in it
+        let values: Vec<&str> = dialogue.splitn(format.len(), ',').collect();
+        let field = |name: &str| {
+            format
+                .iter()
+                .position(|f| f == name)
+                .and_then(|i| values.get(i))
+                .copied()
+        };
+
+        let (Some(start_ms), Some(end_ms), Some(text)) = (
+            field("start").and_then(parse_timestamp),
+            field("end").and_then(parse_timestamp),
+            field("text"),
+        ) else {
+            continue;
+        };
+        if end_ms <= start_ms {
+            continue;
+        }
+
+        cues.push(Cue {
+            start_ms,
+            end_ms,
+            settings: None,
+            text: text
+                .replace("\\N", "\n")
+                .replace("\\n", "\n")
+                .replace("\\h", " "),
+        });
+    }
+
+    // events don't have to be in order but vtt cues do
+    cues.sort_by_key(|cue| cue.start_ms);
+    cues
+}
+
+// `{\i1}` style overrides, ass is full of them and srt files converted from ass often keep them
+fn strip_overrides(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{\\") {
+        stripped.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    stripped.push_str(rest);
+
+    stripped
+}
+
+// `b`, `c.yellow`, `v Bob`, `/i`. none when the tag isn't one a <track> renders
+fn sanitize_tag(tag: &str) -> Option<String> {
+    if let Some(name) = tag.strip_prefix('/') {
+        let name = name.trim().to_lowercase();
+        return CUE_TAGS
+            .contains(&name.as_str())
+            .then(|| format!("</{}>", name));
+    }
+
+    let (name, annotation) = tag.split_once(' ').unwrap_or((tag, ""));
+    let (name, classes) = name.split_once('.').unwrap_or((name, ""));
+    let name = name.to_lowercase();
+    if !CUE_TAGS.contains(&name.as_str()) {
+        return None;
+    }
+
+    let mut sanitized = format!("<{}", name);
+    for class in classes.split('.').filter(|c| {
+        !c.is_empty()
+            && c.chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_')
+    }) {
+        sanitized.push('.');
+        sanitized.push_str(class);
+    }
+
+    let annotation = annotation.trim();
+    if matches!(name.as_str(), "v" | "lang") && !annotation.is_empty() {
+        sanitized.push(' ');
+        sanitized.push_str(&escape_text(annotation));
+    }
+    sanitized.push('>');
+
+    Some(sanitized)
+}
+
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            // entities that are already there are left alone
+            '&' if is_entity(&text[i..]) => escaped.push('&'),
+            '&' => escaped.push_str("&amp;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn is_entity(text: &str) -> bool {
+    let Some(end) = text.find(';') else {
+        return false;
+    };
+
+    end > 1
+        && end <= 10
+        && text[1..end]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '#')
+}
+
+/// cue text reduced to what a <track> renders, stray `<`, `>` and `&` escaped. blank lines
+/// would end the cue early in vtt so they go too
+pub fn sanitize_cue_text(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(open) = rest.find('<') {
+        sanitized.push_str(&escape_text(&rest[..open]));
+        let after = &rest[open + 1..];
+
+        // a tag can't have another < in it, that one is just text
+        match after.find(['<', '>']) {
+            Some(close) if after.as_bytes()[close] == b'>' => {
+                let tag = after[..close].trim();
+                // timestamp tags are dropped, they'd need shifting along with the offset
+                if parse_timestamp(tag).is_none()
+                    && let Some(tag) = sanitize_tag(tag)
+                {
+                    sanitized.push_str(&tag);
+                }
+                rest = &after[close + 1..];
+            }
+            _ => {
+                sanitized.push_str("&lt;");
+                rest = after;
+            }
+        }
+    }
+    sanitized.push_str(&escape_text(rest));
+
+    sanitized
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// the cues of an srt, vtt or ass file with their text sanitized, none if it's none of those
+pub fn parse_captions(text: &str) -> Option<Vec<Cue>> {
+    let format = CaptionFormat::detect(text)?;
+    let cues = match format {
+        CaptionFormat::Vtt | CaptionFormat::Srt => parse_blocks(text),
+        CaptionFormat::Ass => parse_ass(text),
+    };
+
+    Some(
+        cues.into_iter()
+            .filter_map(|mut cue| {
+                if format != CaptionFormat::Vtt {
+                    cue.text = strip_overrides(&cue.text);
+                }
+                cue.text = sanitize_cue_text(&cue.text);
+
+                // nothing left to show once the markup is gone
+                (!cue.text.is_empty()).then_some(cue)
+            })
+            .collect(),
+    )
+}
+
+/// moves every cue by `offset_ms`, later when positive. cues that would end before the start
+/// are dropped and ones that straddle it start at zero
+pub fn shift_cues(cues: Vec<Cue>, offset_ms: i64) -> Vec<Cue> {
+    cues.into_iter()
+        .filter_map(|mut cue| {
+            cue.start_ms = cue.start_ms.saturating_add(offset_ms).max(0);
+            cue.end_ms = cue.end_ms.saturating_add(offset_ms);
+            (cue.end_ms > 0).then_some(cue)
+        })
+        .collect()
+}
+
+pub fn write_vtt(cues: &[Cue]) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+
+    for cue in cues {
+        vtt.push_str(&format!(
+            "\n{} --> {}",
+            format_timestamp(cue.start_ms),
+            format_timestamp(cue.end_ms)
+        ));
+        if let Some(settings) = &cue.settings {
+            vtt.push(' ');
+            vtt.push_str(settings);
+        }
+        vtt.push('\n');
+        vtt.push_str(&cue.text);
+        vtt.push('\n');
+    }
+
+    vtt
+}
+
+/// srt, vtt or ass as clean webvtt, none if it's none of those
+pub fn convert_to_vtt(text: &str) -> Option<String> {
+    parse_captions(text).map(|cues| write_vtt(&cues))
+}
+
+/// shifts vtt written by `convert_to_vtt`, it's already clean so it only gets reparsed
+pub fn offset_vtt(vtt: &str, offset_ms: i64) -> String {
+    if offset_ms == 0 {
+        return vtt.to_string();
+    }
+
+    write_vtt(&shift_cues(parse_blocks(vtt), offset_ms))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("00:01:02,345"), Some(62_345));
+        assert_eq!(parse_timestamp("01:02.345"), Some(62_345));
+        assert_eq!(parse_timestamp("1:00:00.5"), Some(3_600_500));
+        assert_eq!(parse_timestamp("0:00:01.25"), Some(1_250));
+        assert_eq!(parse_timestamp("00:00:01.2345"), Some(1_234));
+        assert_eq!(parse_timestamp("00:00:01"), Some(1_000));
+        assert_eq!(parse_timestamp("-00:01.000"), None);
+        assert_eq!(parse_timestamp("1:2:3:4.000"), None);
+        assert_eq!(parse_timestamp("soon"), None);
+
+        assert_eq!(format_timestamp(3_723_004), "01:02:03.004");
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            CaptionFormat::detect("\u{feff}WEBVTT\n\n00:01.000 --> 00:02.000\nhi"),
+            Some(CaptionFormat::Vtt)
+        );
+        assert_eq!(
+            CaptionFormat::detect("1\n00:00:01,000 --> 00:00:02,000\nhi"),
+            Some(CaptionFormat::Srt)
+        );
+        assert_eq!(
+            CaptionFormat::detect("[Script Info]\nTitle: x\n\n[Events]\n"),
+            Some(CaptionFormat::Ass)
+        );
+        assert_eq!(CaptionFormat::detect("<html></html>"), None);
+    }
+
+    #[test]
+    fn test_convert_srt() {
+        let srt = "1\r\n00:00:01,000 --> 00:00:02,500 X1:10 X2:20\r\n<i>Hello</i> <font color=\"red\">there</font>\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\n{\\an8}Tom & Jerry <3\r\n\r\n3\r\n00:00:05,000 --> 00:00:06,000\r\n<font></font>\r\n";
+
+        assert_eq!(
+            convert_to_vtt(srt).unwrap(),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.500\n<i>Hello</i> there\n\n00:00:03.000 --> 00:00:04.000\nTom &amp; Jerry &lt;3\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_vtt() {
+        let vtt = "WEBVTT\nKind: captions\n\nNOTE made by hand\n\nSTYLE\n::cue { color: red }\n\nintro\n00:01.000 --> 00:02.000 line:0 align:start region:top\n<v.loud Bob>Hi &amp; bye</v>\n<00:01.500><c.yellow>later</c>\n\n\n00:03.000 --> 00:04.000\n<script>alert(1)</script>\n";
+
+        assert_eq!(
+            convert_to_vtt(vtt).unwrap(),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.000 line:0 align:start\n<v.loud Bob>Hi &amp; bye</v>\n<c.yellow>later</c>\n\n00:00:03.000 --> 00:00:04.000\nalert(1)\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_ass() {
+        let ass = "[Script Info]\nScriptType: v4.00+\n\n[V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,Second, with a comma\nComment: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,not shown\nDialogue: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,{\\i1}First{\\i0}\\Nline\\htwo\n";
+
+        assert_eq!(
+            convert_to_vtt(ass).unwrap(),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.500\nFirst\nline two\n\n00:00:03.000 --> 00:00:04.000\nSecond, with a comma\n"
+        );
+    }
+
+    #[test]
+    fn test_offset_vtt() {
+        let vtt = convert_to_vtt(
+            "00:00:01,000 --> 00:00:02,000\none\n\n00:00:03,000 --> 00:00:05,000\ntwo\n",
+        )
+        .unwrap();
+
+        assert_eq!(offset_vtt(&vtt, 0), vtt);
+        assert_eq!(
+            offset_vtt(&vtt, 1500),
+            "WEBVTT\n\n00:00:02.500 --> 00:00:03.500\none\n\n00:00:04.500 --> 00:00:06.500\ntwo\n"
+        );
+        // the first cue is over before the start and the second gets cut
+        assert_eq!(
+            offset_vtt(&vtt, -4000),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.000\ntwo\n"
+        );
+    }
+
+    #[test]
+    fn test_decode_caption_text() {
+        // "café" in windows-1252
+        let latin = b"caf\xe9";
+        assert_eq!(decode_caption_text(latin, None), "café");
+        // "привет" in windows-1251
+        let cyrillic = b"\xef\xf0\xe8\xe2\xe5\xf2";
+        assert_eq!(
+            decode_caption_text(cyrillic, Some("windows-1251")),
+            "привет"
+        );
+        assert_eq!(decode_caption_text("café".as_bytes(), None), "café");
+        assert_eq!(
+            decode_caption_text(b"\xef\xbb\xbfcaf\xc3\xa9", Some("latin1")),
+            "café"
+        );
+        assert_eq!(decode_caption_text(b"\xff\xfeh\x00i\x00", None), "hi");
+    }
+}
@@ -0,0 +1,186 @@
+// folds whatever category names providers send into our own categories. a raw name goes through
+// the provider's aliases first, then gets matched against our names and slugs, and anything left
+// over keeps its raw name under a made up slug until an admin maps it
+use std::collections::HashMap;
+
+use crate::database::category::{Category, CategoryAlias};
+
+// unmapped categories go after everything we know about
+pub const UNMAPPED_SORT_ORDER: i32 = i32::MAX;
+
+/// "Ice Hockey" -> "ice-hockey", anything that isn't a letter or digit becomes one dash
+pub fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// where a raw category name ended up
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanonicalCategory {
+    pub slug: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub sort_order: i32,
+    /// false when nothing in the taxonomy matched and this is just the raw name
+    pub known: bool,
+}
+
+impl From<&Category> for CanonicalCategory {
+    fn from(category: &Category) -> Self {
+        Self {
+            slug: category.slug.clone(),
+            name: category.name.clone(),
+            icon: category.icon.clone(),
+            sort_order: category.sort_order,
+            known: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CategoryTaxonomy {
+    categories: Vec<Category>,
+    by_slug: HashMap<String, usize>,
+    // (provider, lowercased raw name) -> slug, unmapped aliases aren't in here
+    aliases: HashMap<(String, String), String>,
+}
+
+impl CategoryTaxonomy {
+    pub fn new(mut categories: Vec<Category>, aliases: &[CategoryAlias]) -> Self {
+        categories.sort_by(|a, b| a.sort_order.cmp(&b.sort_order).then(a.name.cmp(&b.name)));
+
+        let by_slug = categories
+            .iter()
+            .enumerate()
+            .map(|(i, category)| (category.slug.clone(), i))
+            .collect();
+
+        let aliases = aliases
+            .iter()
+            .filter_map(|alias| {
+                let slug = alias.category_slug.clone()?;
+                Some((
+                    (alias.provider.clone(), alias.raw_name.to_lowercase()),
+                    slug,
+                ))
+            })
+            .collect();
+
+        Self {
+            categories,
+            by_slug,
+            aliases,
+        }
+    }
+
+    /// in listing order
+    pub fn categories(&self) -> &[Category] {
+        &self.categories
+    }
+
+    pub fn get(&self, slug: &str) -> Option<&Category> {
+        self.by_slug.get(slug).map(|&i| &self.categories[i])
+    }
+
+    pub fn resolve(&self, provider: &str, raw_name: &str) -> CanonicalCategory {
+        let raw_name = raw_name.trim();
+
+        let aliased = self
+            .aliases
+            .get(&(provider.to_string(), raw_name.to_lowercase()))
+            .and_then(|slug| self.get(slug));
+        // a provider that already uses our names doesn't need aliases for them
+        let matched = aliased
+            .or_else(|| self.get(&slugify(raw_name)))
+            .or_else(|| {
+                self.categories
+                    .iter()
+                    .find(|c| c.name.eq_ignore_ascii_case(raw_name))
+            });
+
+        match matched {
+            Some(category) => category.into(),
+            None => CanonicalCategory {
+                slug: slugify(raw_name),
+                name: raw_name.to_string(),
+                icon: None,
+                sort_order: UNMAPPED_SORT_ORDER,
+                known: false,
+            },
+        }
+    }
+
+    /// what a listing group looks like for a slug games were stored under
+    pub fn for_slug(&self, slug: &str, fallback_name: &str) -> CanonicalCategory {
+        match self.get(slug) {
+            Some(category) => category.into(),
+            None => CanonicalCategory {
+                slug: slug.to_string(),
+                name: fallback_name.to_string(),
+                icon: None,
+                sort_order: UNMAPPED_SORT_ORDER,
+                known: false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::types::time::OffsetDateTime;
+
+    fn category(slug: &str, name: &str, sort_order: i32) -> Category {
+        Category {
+            slug: slug.to_string(),
+            name: name.to_string(),
+            icon: None,
+            sort_order,
+        }
+    }
+
+    fn alias(provider: &str, raw_name: &str, slug: Option<&str>) -> CategoryAlias {
+        CategoryAlias {
+            provider: provider.to_string(),
+            raw_name: raw_name.to_string(),
+            category_slug: slug.map(|s| s.to_string()),
+            first_seen_at: OffsetDateTime::UNIX_EPOCH,
+        }
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Ice Hockey"), "ice-hockey");
+        assert_eq!(slugify("  24/7 Streams "), "24-7-streams");
+        assert_eq!(slugify("Motorsports"), "motorsports");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let taxonomy = CategoryTaxonomy::new(
+            vec![
+                category("ice-hockey", "Ice Hockey", 40),
+                category("basketball", "Basketball", 10),
+            ],
+            &[
+                alias("ppvsu", "Hockey", Some("ice-hockey")),
+                alias("ppvsu", "Darts", None),
+            ],
+        );
+
+        assert_eq!(taxonomy.categories()[0].slug, "basketball");
+        assert_eq!(taxonomy.resolve("ppvsu", "hockey").slug, "ice-hockey");
+        assert_eq!(taxonomy.resolve("other", "Hockey").slug, "hockey");
+        assert_eq!(taxonomy.resolve("other", "ICE HOCKEY").name, "Ice Hockey");
+        assert_eq!(taxonomy.resolve("ppvsu", "basketball").slug, "basketball");
+
+        let darts = taxonomy.resolve("ppvsu", "Darts");
+        assert!(!darts.known);
+        assert_eq!(darts.slug, "darts");
+        assert_eq!(darts.name, "Darts");
+        assert_eq!(darts.sort_order, UNMAPPED_SORT_ORDER);
+    }
+}
@@ -0,0 +1,114 @@
+// just enough csv for watchlist imports and exports, quoted fields can hold commas, quotes ("")
+// and newlines. anything fancier than that (other delimiters, bom sniffing) isn't worth a crate
+/// one row of fields and the line of the file it starts on, quoted newlines make a row span
+/// more than one
+#[derive(Debug, PartialEq)]
+pub struct CsvRow {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// splits csv text into rows of fields, blank lines are dropped
+pub fn parse_csv(text: &str) -> Vec<CsvRow> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                end_row(&mut rows, &mut row, &mut field, row_line);
+                line += 1;
+                row_line = line;
+            }
+            '\n' => {
+                field.push(c);
+                line += 1;
+            }
+            c => field.push(c),
+        }
+    }
+    end_row(&mut rows, &mut row, &mut field, row_line);
+
+    rows
+}
+
+fn end_row(rows: &mut Vec<CsvRow>, row: &mut Vec<String>, field: &mut String, line: usize) {
+    row.push(std::mem::take(field));
+    let fields = std::mem::take(row);
+    if fields.iter().any(|f| !f.trim().is_empty()) {
+        rows.push(CsvRow { line, fields });
+    }
+}
+
+/// one csv line (without the newline), fields only get quoted when they need it
+pub fn write_csv_row(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|f| {
+            if f.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", f.replace('"', "\"\""))
+            } else {
+                f.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(rows: Vec<CsvRow>) -> Vec<Vec<String>> {
+        rows.into_iter().map(|row| row.fields).collect()
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        assert_eq!(
+            fields(parse_csv("id,kind\r\n1311031,movie\n\n1396\n")),
+            vec![vec!["id", "kind"], vec!["1311031", "movie"], vec!["1396"]]
+        );
+        assert_eq!(
+            fields(parse_csv("1396,tv,\"rewatch, \"\"later\"\"\nmaybe\"")),
+            vec![vec!["1396", "tv", "rewatch, \"later\"\nmaybe"]]
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_counts_file_lines() {
+        let lines: Vec<usize> = parse_csv("id\n\n1396,tv,\"two\nlines\"\r\n42\n")
+            .iter()
+            .map(|row| row.line)
+            .collect();
+
+        assert_eq!(lines, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn test_write_csv_row_round_trips() {
+        let row = write_csv_row(&["1396", "tv", "rewatch, \"later\""]);
+
+        assert_eq!(row, "1396,tv,\"rewatch, \"\"later\"\"\"");
+        assert_eq!(
+            fields(parse_csv(&row)),
+            vec![vec!["1396", "tv", "rewatch, \"later\""]]
+        );
+    }
+}
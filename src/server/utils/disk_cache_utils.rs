@@ -0,0 +1,221 @@
+// size capped cache of files on disk, least recently used goes first. what's been used when is
+// only tracked in memory, after a restart the file modified times stand in for it
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+use tracing::{error, info};
+
+struct CacheEntry {
+    size: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+    // bumped on every access, cheaper than timestamps and never ties
+    clock: u64,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, key: &str) -> bool {
+        self.clock += 1;
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_used = self.clock;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, key: String, size: u64) {
+        self.clock += 1;
+        if let Some(old) = self.entries.insert(
+            key,
+            CacheEntry {
+                size,
+                last_used: self.clock,
+            },
+        ) {
+            self.total_bytes -= old.size;
+        }
+        self.total_bytes += size;
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(old) = self.entries.remove(key) {
+            self.total_bytes -= old.size;
+        }
+    }
+
+    // oldest keys until whatever is left fits
+    fn evict_to(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.total_bytes > max_bytes {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+            evicted.push(oldest);
+        }
+        evicted
+    }
+}
+
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+}
+
+impl DiskCache {
+    /// creates the directory if needed and picks up whatever is already in it
+    pub fn open(dir: impl Into<PathBuf>, max_bytes: u64) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create cache dir {}", dir.display()))?;
+
+        let mut existing = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let Some(key) = entry.file_name().to_str().map(str::to_string) else {
+                continue;
+            };
+
+            // leftovers from a write that never finished
+            if key.ends_with(".tmp") {
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            }
+
+            if metadata.is_file() {
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                existing.push((modified, key, metadata.len()));
+            }
+        }
+
+        let mut index = CacheIndex::default();
+        existing.sort();
+        for (_, key, size) in existing {
+            index.insert(key, size);
+        }
+
+        info!(
+            "disk cache at {} has {} files ({} bytes)",
+            dir.display(),
+            index.entries.len(),
+            index.total_bytes
+        );
+
+        let cache = Self {
+            dir,
+            max_bytes,
+            index: Mutex::new(index),
+        };
+        // the limit might have been lowered since last time
+        cache.evict();
+
+        Ok(cache)
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        if !self.lock().touch(key) {
+            return None;
+        }
+
+        match tokio::fs::read(self.path(key)).await {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                // deleted behind our back, forget about it
+                error!("failed to read cached file {}: {}", key, e);
+                self.lock().remove(key);
+                None
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, bytes: &[u8]) -> anyhow::Result<()> {
+        // written to the side first so a reader never sees half a file
+        let tmp = self.dir.join(format!("{}.{}.tmp", key, nanoid::nanoid!(8)));
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(&tmp, self.path(key)).await?;
+
+        self.lock().insert(key.to_string(), bytes.len() as u64);
+        self.evict();
+
+        Ok(())
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.lock().total_bytes
+    }
+
+    fn evict(&self) {
+        let evicted = self.lock().evict_to(self.max_bytes);
+        for key in evicted {
+            if let Err(e) = std::fs::remove_file(self.path(&key)) {
+                error!("failed to evict cached file {}: {}", key, e);
+            }
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        // keys are only ever ours but don't let one walk out of the directory
+        self.dir
+            .join(Path::new(key).file_name().unwrap_or_default())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheIndex> {
+        // the index is only ever updated in one step so a poisoned lock still holds a valid one
+        self.index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("disk-cache-{}", nanoid::nanoid!(8)))
+    }
+
+    #[tokio::test]
+    async fn test_least_recently_used_is_evicted() {
+        let dir = temp_dir();
+        let cache = DiskCache::open(&dir, 10).unwrap();
+
+        cache.put("a", b"1234").await.unwrap();
+        cache.put("b", b"1234").await.unwrap();
+        // reading a makes b the oldest
+        assert!(cache.get("a").await.is_some());
+        cache.put("c", b"1234").await.unwrap();
+
+        assert!(cache.get("b").await.is_none());
+        assert_eq!(cache.get("a").await.unwrap(), b"1234");
+        assert_eq!(cache.get("c").await.unwrap(), b"1234");
+        assert_eq!(cache.total_bytes(), 8);
+
+        // picked back up after a restart
+        drop(cache);
+        let reopened = DiskCache::open(&dir, 10).unwrap();
+        assert_eq!(reopened.total_bytes(), 8);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
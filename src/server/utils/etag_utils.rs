@@ -0,0 +1,113 @@
+// conditional GETs. handlers work out an etag for what they're about to send, if the client
+// already has it they get a bodyless 304 instead
+use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+
+/// only the client should cache, but it can reuse the response for a bit without asking
+pub const CACHE_PRIVATE_SHORT: &str = "private, max-age=30";
+
+pub struct ETag {
+    value: String,
+    cache_control: &'static str,
+}
+
+impl ETag {
+    /// `version` has to change whenever the response body would, it gets quoted here
+    pub fn strong(version: impl AsRef<str>, cache_control: &'static str) -> Self {
+        Self {
+            value: format!("\"{}\"", version.as_ref()),
+            cache_control,
+        }
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// whether If-None-Match already covers this version. it's a weak comparison like the rfc
+    /// asks for so a W/ prefix added by a proxy still counts
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get_all(header::IF_NONE_MATCH)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == self.value)
+    }
+
+    /// a 304 if the client is up to date, otherwise `body` with the etag attached
+    pub fn respond(&self, headers: &HeaderMap, body: impl IntoResponse) -> Response {
+        if self.matches(headers) {
+            return self.not_modified();
+        }
+
+        let mut response = body.into_response();
+        self.apply(&mut response);
+        response
+    }
+
+    pub fn not_modified(&self) -> Response {
+        let mut response = StatusCode::NOT_MODIFIED.into_response();
+        self.apply(&mut response);
+        response
+    }
+
+    fn apply(&self, response: &mut Response) {
+        // error responses shouldn't get cached against this version
+        let status = response.status();
+        if !(status.is_success() || status == StatusCode::NOT_MODIFIED) {
+            return;
+        }
+
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.value) {
+            headers.insert(header::ETAG, etag);
+        }
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(self.cache_control),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_none_match(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn test_if_none_match() {
+        let etag = ETag::strong("streams-1760223600", CACHE_PRIVATE_SHORT);
+
+        assert!(etag.matches(&if_none_match("\"streams-1760223600\"")));
+        assert!(etag.matches(&if_none_match("\"old\", W/\"streams-1760223600\"")));
+        assert!(etag.matches(&if_none_match("*")));
+        assert!(!etag.matches(&if_none_match("\"streams-1760220000\"")));
+        assert!(!etag.matches(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_not_modified_response() {
+        let etag = ETag::strong("streams-1760223600", CACHE_PRIVATE_SHORT);
+
+        let fresh = etag.respond(&HeaderMap::new(), "{}");
+        assert_eq!(fresh.status(), StatusCode::OK);
+        assert_eq!(fresh.headers()[header::ETAG], etag.value());
+        assert_eq!(fresh.headers()[header::CACHE_CONTROL], CACHE_PRIVATE_SHORT);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::IF_NONE_MATCH,
+            HeaderValue::from_str(etag.value()).unwrap(),
+        );
+        let cached = etag.respond(&headers, "{}");
+        assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(cached.headers()[header::ETAG], etag.value());
+    }
+}
@@ -0,0 +1,436 @@
+// pulling the playable link out of an embed page. each strategy is one way upstream has hidden
+// the link before, they get tried in order and the first one that finds something wins
+use base64::Engine;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+use url::Url;
+
+lazy_static! {
+    static ref ATOB: Regex = Regex::new(r#"atob\(\s*["']([A-Za-z0-9+/=_-]+)["']\s*\)"#).unwrap();
+    static ref M3U8_URL: Regex =
+        Regex::new(r#"https?://[^\s"'<>\\]+?\.m3u8(?:\?[^\s"'<>\\]*)?"#).unwrap();
+    static ref SOURCE_TAG: Regex =
+        Regex::new(r#"(?is)<(?:source|video)\b[^>]*?\bsrc\s*=\s*["']([^"']+)["']"#).unwrap();
+    static ref SCRIPT_BODY: Regex = Regex::new(r"(?is)<script\b[^>]*>(.*?)</script>").unwrap();
+    static ref PACKED_JS: Regex = Regex::new(
+        r"(?s)\}\s*\(\s*'((?:[^'\\]|\\.)*)'\s*,\s*(\d+)\s*,\s*(\d+)\s*,\s*'((?:[^'\\]|\\.)*)'\.split\('\|'\)"
+    )
+    .unwrap();
+    static ref PACKED_WORD: Regex = Regex::new(r"\b\w+\b").unwrap();
+}
+
+// keys players usually keep the stream url under, only used when nothing looks like an m3u8
+const MEDIA_KEYS: [&str; 7] = [
+    "file",
+    "src",
+    "source",
+    "hls",
+    "url",
+    "stream",
+    "playbackUrl",
+];
+
+const PACKED_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// one entry of the rules file, `{"kind": "atob"}` or
+/// `{"kind": "regex", "pattern": "file:\\s*\"([^\"]+)\"", "base64": false}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StrategyConfig {
+    /// `atob("...")` anywhere in the page, what the embed used to do
+    Atob,
+    /// any absolute .m3u8 url in the page
+    M3u8Url,
+    /// `<source src>` or `<video src>`
+    SourceTag,
+    /// script tags holding json (or a json object assigned to a variable)
+    ScriptJson,
+    /// dean edwards' `eval(function(p,a,c,k,e,d)...)`, unpacked and searched with the other
+    /// strategies
+    PackedJs,
+    /// first capture group (or the whole match) of a custom pattern
+    Regex {
+        #[serde(default)]
+        name: Option<String>,
+        pattern: String,
+        #[serde(default)]
+        base64: bool,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    strategies: Vec<StrategyConfig>,
+}
+
+#[derive(Debug, Clone)]
+enum Strategy {
+    Atob,
+    M3u8Url,
+    SourceTag,
+    ScriptJson,
+    PackedJs,
+    Regex {
+        name: String,
+        pattern: Regex,
+        base64: bool,
+    },
+}
+
+impl Strategy {
+    fn name(&self) -> &str {
+        match self {
+            Strategy::Atob => "atob",
+            Strategy::M3u8Url => "m3u8_url",
+            Strategy::SourceTag => "source_tag",
+            Strategy::ScriptJson => "script_json",
+            Strategy::PackedJs => "packed_js",
+            Strategy::Regex { name, .. } => name,
+        }
+    }
+}
+
+/// the ordered strategy chain
+#[derive(Debug, Clone)]
+pub struct ExtractionRules {
+    strategies: Vec<Strategy>,
+}
+
+impl Default for ExtractionRules {
+    fn default() -> Self {
+        Self {
+            strategies: vec![
+                Strategy::Atob,
+                Strategy::M3u8Url,
+                Strategy::SourceTag,
+                Strategy::ScriptJson,
+                Strategy::PackedJs,
+            ],
+        }
+    }
+}
+
+impl ExtractionRules {
+    pub fn from_configs(configs: Vec<StrategyConfig>) -> anyhow::Result<Self> {
+        if configs.is_empty() {
+            return Err(anyhow::anyhow!("rules need at least one strategy"));
+        }
+
+        let strategies = configs
+            .into_iter()
+            .enumerate()
+            .map(|(i, config)| {
+                Ok(match config {
+                    StrategyConfig::Atob => Strategy::Atob,
+                    StrategyConfig::M3u8Url => Strategy::M3u8Url,
+                    StrategyConfig::SourceTag => Strategy::SourceTag,
+                    StrategyConfig::ScriptJson => Strategy::ScriptJson,
+                    StrategyConfig::PackedJs => Strategy::PackedJs,
+                    StrategyConfig::Regex {
+                        name,
+                        pattern,
+                        base64,
+                    } => Strategy::Regex {
+                        name: name.unwrap_or_else(|| format!("regex_{}", i)),
+                        pattern: Regex::new(&pattern)
+                            .map_err(|e| anyhow::anyhow!("bad pattern in strategy {}: {}", i, e))?,
+                        base64,
+                    },
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self { strategies })
+    }
+
+    /// rules file contents, `{"strategies": [{"kind": "atob"}, ...]}`
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let file: RulesFile = serde_json::from_str(json)?;
+        Self::from_configs(file.strategies)
+    }
+
+    pub fn strategy_names(&self) -> Vec<String> {
+        self.strategies
+            .iter()
+            .map(|s| s.name().to_string())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedLink {
+    pub link: String,
+    pub strategy: String,
+}
+
+/// every strategy came up empty, with why for each one
+#[derive(Debug, Clone)]
+pub struct ExtractionFailure {
+    pub attempts: Vec<(String, String)>,
+}
+
+impl fmt::Display for ExtractionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reasons: Vec<String> = self
+            .attempts
+            .iter()
+            .map(|(strategy, reason)| format!("{}: {}", strategy, reason))
+            .collect();
+        write!(f, "no strategy found a video link ({})", reasons.join("; "))
+    }
+}
+
+/// run the chain over an embed page, relative links are resolved against `page_url`
+pub fn extract_video_link(
+    rules: &ExtractionRules,
+    page_url: &str,
+    html: &str,
+) -> Result<ExtractedLink, ExtractionFailure> {
+    let base = Url::parse(page_url).ok();
+    let mut attempts = Vec::new();
+
+    for strategy in &rules.strategies {
+        match run_strategy(strategy, base.as_ref(), html) {
+            Ok(link) => {
+                return Ok(ExtractedLink {
+                    link,
+                    strategy: strategy.name().to_string(),
+                });
+            }
+            Err(reason) => attempts.push((strategy.name().to_string(), reason)),
+        }
+    }
+
+    Err(ExtractionFailure { attempts })
+}
+
+fn run_strategy(strategy: &Strategy, base: Option<&Url>, html: &str) -> Result<String, String> {
+    match strategy {
+        Strategy::Atob => ATOB
+            .captures_iter(html)
+            .filter_map(|caps| decode_base64(&caps[1]))
+            .find_map(|decoded| absolute_link(base, &decoded))
+            .ok_or_else(|| "no atob() call decoding to a url".to_string()),
+        Strategy::M3u8Url => M3U8_URL
+            .find(&html.replace("\\/", "/"))
+            .map(|m| m.as_str().to_string())
+            .ok_or_else(|| "no .m3u8 url".to_string()),
+        Strategy::SourceTag => SOURCE_TAG
+            .captures_iter(html)
+            .map(|caps| caps[1].trim().to_string())
+            .filter(|src| !src.starts_with("blob:"))
+            .find_map(|src| absolute_link(base, &src))
+            .ok_or_else(|| "no <source> or <video> with a src".to_string()),
+        Strategy::ScriptJson => SCRIPT_BODY
+            .captures_iter(html)
+            .filter_map(|caps| script_json(&caps[1]))
+            .find_map(|value| find_media_url(&value))
+            .and_then(|link| absolute_link(base, &link))
+            .ok_or_else(|| "no script json with a media url".to_string()),
+        Strategy::PackedJs => {
+            let unpacked: Vec<String> = PACKED_JS
+                .captures_iter(html)
+                .filter_map(|caps| unpack(&caps[1], &caps[2], &caps[3], &caps[4]))
+                .collect();
+
+            if unpacked.is_empty() {
+                return Err("no packed script".to_string());
+            }
+
+            // same search again on what came out, minus unpacking so it can't recurse
+            let inner = ExtractionRules::default();
+            unpacked
+                .iter()
+                .find_map(|code| {
+                    inner
+                        .strategies
+                        .iter()
+                        .filter(|s| !matches!(s, Strategy::PackedJs))
+                        .find_map(|s| run_strategy(s, base, code).ok())
+                })
+                .ok_or_else(|| "unpacked script has no video link".to_string())
+        }
+        Strategy::Regex {
+            pattern, base64, ..
+        } => {
+            let caps = pattern
+                .captures(html)
+                .ok_or_else(|| "pattern didn't match".to_string())?;
+            let found = caps.get(1).or_else(|| caps.get(0)).map(|m| m.as_str());
+            let found = match (found, base64) {
+                (Some(found), true) => {
+                    decode_base64(found).ok_or_else(|| "match isn't valid base64".to_string())?
+                }
+                (Some(found), false) => found.to_string(),
+                (None, _) => return Err("pattern didn't match".to_string()),
+            };
+            absolute_link(base, &found).ok_or_else(|| format!("match isn't a url: {}", found))
+        }
+    }
+}
+
+fn decode_base64(encoded: &str) -> Option<String> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .or_else(|_| base64::engine::general_purpose::URL_SAFE.decode(encoded))
+        .ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+// only http(s) counts as found, relative links are joined onto the embed's url
+fn absolute_link(base: Option<&Url>, link: &str) -> Option<String> {
+    let link = link.trim();
+    if link.is_empty() {
+        return None;
+    }
+
+    let url = match Url::parse(link) {
+        Ok(url) => url,
+        Err(_) => base?.join(link).ok()?,
+    };
+
+    matches!(url.scheme(), "http" | "https").then(|| url.to_string())
+}
+
+fn script_json(body: &str) -> Option<Value> {
+    let body = body.trim();
+    if let Ok(value) = serde_json::from_str::<Value>(body) {
+        return Some(value);
+    }
+
+    // `var config = {...};` style, try whatever sits between the outer braces
+    let start = body.find('{')?;
+    let end = body.rfind('}')?;
+    (start < end)
+        .then(|| serde_json::from_str(&body[start..=end]).ok())
+        .flatten()
+}
+
+// an m3u8 anywhere wins, otherwise the first url under a key players use for the stream
+fn find_media_url(value: &Value) -> Option<String> {
+    fn walk<'a>(value: &'a Value, key: Option<&str>, found: &mut Vec<(&'a str, bool)>) {
+        match value {
+            Value::String(s) if s.starts_with("http") || s.starts_with("//") => {
+                let media_key = key.is_some_and(|k| MEDIA_KEYS.contains(&k));
+                if s.contains(".m3u8") || media_key {
+                    found.push((s, s.contains(".m3u8")));
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|v| walk(v, key, found)),
+            Value::Object(map) => map.iter().for_each(|(k, v)| walk(v, Some(k), found)),
+            _ => {}
+        }
+    }
+
+    let mut found = Vec::new();
+    walk(value, None, &mut found);
+
+    found
+        .iter()
+        .find(|(_, m3u8)| *m3u8)
+        .or_else(|| found.first())
+        .map(|(link, _)| link.to_string())
+}
+
+// p,a,c,k from the packer's call, every word in the payload is an index (in base a) into k
+fn unpack(payload: &str, radix: &str, count: &str, words: &str) -> Option<String> {
+    let radix: u32 = radix.parse().ok()?;
+    let count: usize = count.parse().ok()?;
+    if !(2..=62).contains(&radix) {
+        return None;
+    }
+
+    let payload = payload.replace("\\'", "'").replace("\\\\", "\\");
+    let words: Vec<&str> = words.split('|').collect();
+    if words.len() < count {
+        return None;
+    }
+
+    let unpacked = PACKED_WORD.replace_all(&payload, |caps: &regex::Captures| {
+        let token = &caps[0];
+        parse_radix(token, radix)
+            .and_then(|i| words.get(i))
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_string())
+            .unwrap_or_else(|| token.to_string())
+    });
+
+    Some(unpacked.into_owned())
+}
+
+fn parse_radix(token: &str, radix: u32) -> Option<usize> {
+    token.bytes().try_fold(0usize, |acc, b| {
+        let digit = PACKED_ALPHABET.iter().position(|&c| c == b)? as u32;
+        (digit < radix).then(|| acc.checked_mul(radix as usize)?.checked_add(digit as usize))?
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = "https://embed.example.com/player/1";
+
+    fn extract(html: &str) -> Result<ExtractedLink, ExtractionFailure> {
+        extract_video_link(&ExtractionRules::default(), PAGE, html)
+    }
+
+    #[test]
+    fn test_each_builtin_strategy() {
+        let encoded = base64::engine::general_purpose::STANDARD
+            .encode("https://cdn.example.com/live/index.m3u8");
+        let atob = format!(r#"<script>const src = atob("{}");</script>"#, encoded);
+        assert_eq!(extract(&atob).unwrap().strategy, "atob");
+
+        let m3u8 = r#"<script>player.load("https:\/\/cdn.example.com\/a.m3u8?token=1")</script>"#;
+        let found = extract(m3u8).unwrap();
+        assert_eq!(found.strategy, "m3u8_url");
+        assert_eq!(found.link, "https://cdn.example.com/a.m3u8?token=1");
+
+        let source = r#"<video><source src="/hls/stream.mp4" type="video/mp4"></video>"#;
+        let found = extract(source).unwrap();
+        assert_eq!(found.strategy, "source_tag");
+        assert_eq!(found.link, "https://embed.example.com/hls/stream.mp4");
+
+        let json = r#"<script type="application/json">{"player": {"file": "https://cdn.example.com/s.mp4"}}</script>"#;
+        assert_eq!(extract(json).unwrap().strategy, "script_json");
+    }
+
+    #[test]
+    fn test_packed_js() {
+        // packer output for player.load("https://cdn.example.com/live/index.m3u8")
+        let html = r#"<script>eval(function(p,a,c,k,e,d){return p}('0.1("2://3.4.5/6/7.8")',9,9,'player|load|https|cdn|example|com|live|index|m3u8'.split('|'),0,{}))</script>"#;
+
+        let found = extract(html).unwrap();
+        assert_eq!(found.strategy, "packed_js");
+        assert_eq!(found.link, "https://cdn.example.com/live/index.m3u8");
+    }
+
+    #[test]
+    fn test_rules_file() {
+        let rules = ExtractionRules::from_json(
+            r#"{"strategies": [{"kind": "regex", "name": "jw", "pattern": "file:\\s*\"([^\"]+)\""}, {"kind": "atob"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(rules.strategy_names(), vec!["jw", "atob"]);
+
+        let found = extract_video_link(&rules, PAGE, r#"jwplayer().setup({file: "/live.mp4"})"#);
+        assert_eq!(found.unwrap().link, "https://embed.example.com/live.mp4");
+
+        assert!(ExtractionRules::from_json(r#"{"strategies": []}"#).is_err());
+        assert!(
+            ExtractionRules::from_json(r#"{"strategies": [{"kind": "regex", "pattern": "("}]}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_failure_lists_every_strategy() {
+        let failure = extract("<html>nothing here</html>").unwrap_err();
+        assert_eq!(failure.attempts.len(), 5);
+        assert!(failure.to_string().contains("packed_js: no packed script"));
+    }
+}
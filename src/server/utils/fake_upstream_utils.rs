@@ -0,0 +1,224 @@
+// stand-in for every site we scrape, served from fixture files on a local port so the stream and
+// movie pipelines can run with no network. requests map straight onto the fixture dir:
+//
+//   /ppvsu/api/streams        -> ppvsu/api/streams.json
+//   /vidlink/api/b/movie/xyz  -> vidlink/api/b/movie/_.json
+//
+// a file or dir named `_` matches any one path segment, and `.json`/`.html` can be left off the
+// last one. text fixtures get `{{base_url}}` swapped for where this is listening and `{{now}}`
+// (or `{{now+3600}}`, `{{now-600}}`) for the current unix time so fixture games are always live
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::Router;
+use axum::extract::State;
+use axum::http::{StatusCode, Uri, header};
+use axum::response::{IntoResponse, Response};
+use lazy_static::lazy_static;
+use regex::Regex;
+use tracing::{info, warn};
+
+lazy_static! {
+    static ref NOW_TOKEN: Regex = Regex::new(r"\{\{now([+-]\d+)?\}\}").unwrap();
+}
+
+struct Fixtures {
+    root: PathBuf,
+    base_url: String,
+}
+
+pub struct FakeUpstream {
+    addr: SocketAddr,
+}
+
+impl FakeUpstream {
+    /// serves `fixtures` on a random port on localhost until the runtime shuts down
+    pub async fn spawn(fixtures: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let root = fixtures.into();
+        if !root.is_dir() {
+            anyhow::bail!(
+                "fake upstream fixtures {} aren't a directory",
+                root.display()
+            );
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("couldn't bind the fake upstream")?;
+        let addr = listener.local_addr()?;
+
+        let app = fake_upstream_router(root.clone(), format!("http://{}", addr));
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                warn!("fake upstream stopped: {}", e);
+            }
+        });
+
+        info!("fake upstream serving {} on {}", root.display(), addr);
+
+        Ok(Self { addr })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn ppvsu_api_url(&self) -> String {
+        format!("{}/ppvsu", self.base_url())
+    }
+
+    pub fn ppvsu_site_url(&self) -> String {
+        format!("{}/ppvsu-site", self.base_url())
+    }
+
+    pub fn vidlink_url(&self) -> String {
+        format!("{}/vidlink", self.base_url())
+    }
+}
+
+pub fn fake_upstream_router(root: PathBuf, base_url: String) -> Router {
+    Router::new()
+        .fallback(serve_fixture)
+        .with_state(Arc::new(Fixtures { root, base_url }))
+}
+
+async fn serve_fixture(State(fixtures): State<Arc<Fixtures>>, uri: Uri) -> Response {
+    let Some(path) = resolve_fixture(&fixtures.root, uri.path()) else {
+        warn!("no fixture for {}", uri.path());
+        return (StatusCode::NOT_FOUND, "no fixture").into_response();
+    };
+
+    let bytes = match tokio::fs::read(&path).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("couldn't read fixture {}: {}", path.display(), e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let (content_type, text) = content_type(&path);
+    let body = match (text, String::from_utf8(bytes)) {
+        (true, Ok(body)) => render_template(&body, &fixtures.base_url).into_bytes(),
+        (_, Ok(body)) => body.into_bytes(),
+        (_, Err(e)) => e.into_bytes(),
+    };
+
+    ([(header::CONTENT_TYPE, content_type)], body).into_response()
+}
+
+/// the fixture file a request path maps to, literal names win over `_`
+pub fn resolve_fixture(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let segments: Vec<&str> = request_path.split('/').filter(|s| !s.is_empty()).collect();
+    let (last, dirs) = segments.split_last()?;
+
+    // nothing gets out of the fixture dir
+    if segments
+        .iter()
+        .any(|s| !matches!(Path::new(s).components().next(), Some(Component::Normal(_))))
+    {
+        return None;
+    }
+
+    let mut dir = root.to_path_buf();
+    for segment in dirs {
+        dir = [*segment, "_"]
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|candidate| candidate.is_dir())?;
+    }
+
+    [*last, "_"]
+        .iter()
+        .flat_map(|name| {
+            ["", ".json", ".html"]
+                .iter()
+                .map(move |ext| format!("{}{}", name, ext))
+        })
+        .map(|name| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+fn content_type(path: &Path) -> (&'static str, bool) {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+    {
+        "json" => ("application/json", true),
+        "html" => ("text/html; charset=utf-8", true),
+        "m3u8" => ("application/vnd.apple.mpegurl", true),
+        "vtt" => ("text/vtt", true),
+        "srt" => ("application/x-subrip", true),
+        "ass" => ("text/x-ssa", true),
+        "ts" => ("video/mp2t", false),
+        "png" => ("image/png", false),
+        "jpg" | "jpeg" => ("image/jpeg", false),
+        _ => ("application/octet-stream", false),
+    }
+}
+
+pub fn render_template(body: &str, base_url: &str) -> String {
+    let now = chrono::Utc::now().timestamp();
+    let body = body.replace("{{base_url}}", base_url);
+
+    NOW_TOKEN
+        .replace_all(&body, |caps: &regex::Captures| {
+            let offset: i64 = caps
+                .get(1)
+                .and_then(|m| m.as_str().trim_start_matches('+').parse().ok())
+                .unwrap_or(0);
+            (now + offset).to_string()
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_fixture() {
+        let root = std::env::temp_dir().join(format!("fake-upstream-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("api/streams")).unwrap();
+        std::fs::create_dir_all(root.join("movie/_")).unwrap();
+        std::fs::write(root.join("api/streams.json"), "{}").unwrap();
+        std::fs::write(root.join("api/streams/_.json"), "{}").unwrap();
+        std::fs::write(root.join("api/streams/7.json"), "{}").unwrap();
+        std::fs::write(root.join("movie/_/index.m3u8"), "").unwrap();
+
+        assert_eq!(
+            resolve_fixture(&root, "/api/streams"),
+            Some(root.join("api/streams.json"))
+        );
+        assert_eq!(
+            resolve_fixture(&root, "/api/streams/7"),
+            Some(root.join("api/streams/7.json"))
+        );
+        assert_eq!(
+            resolve_fixture(&root, "/api/streams/8"),
+            Some(root.join("api/streams/_.json"))
+        );
+        assert_eq!(
+            resolve_fixture(&root, "/movie/abc/index.m3u8"),
+            Some(root.join("movie/_/index.m3u8"))
+        );
+        assert_eq!(resolve_fixture(&root, "/api/../../etc/passwd"), None);
+        assert_eq!(resolve_fixture(&root, "/"), None);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_render_template() {
+        let rendered = render_template(
+            r#"{"iframe": "{{base_url}}/embed/1", "at": {{now+60}}}"#,
+            "http://127.0.0.1:1234",
+        );
+        let value: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+
+        assert_eq!(value["iframe"], "http://127.0.0.1:1234/embed/1");
+        assert!(value["at"].as_i64().unwrap() > chrono::Utc::now().timestamp());
+    }
+}
@@ -0,0 +1,261 @@
+// master playlist handling, a master playlist lists the same stream at different qualities as
+// #EXT-X-STREAM-INF tags each followed by the uri of a media playlist. media playlists (the ones
+// with the actual segments) have none of these so everything here is a no-op for them
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const STREAM_INF: &str = "#EXT-X-STREAM-INF:";
+
+const I_FRAME_STREAM_INF: &str = "#EXT-X-I-FRAME-STREAM-INF:";
+
+// the same link gets handed out over and over while a game is on, its variants don't change
+const PLAYLIST_TTL: Duration = Duration::from_secs(10 * 60);
+
+// a playlist that couldn't be fetched is retried sooner, it's usually the cdn having a moment
+const FAILED_PLAYLIST_TTL: Duration = Duration::from_secs(60);
+
+// expired playlists are only cleared out once there are this many
+const PLAYLIST_PRUNE_SIZE: usize = 1_000;
+
+/// one quality of a stream as the master playlist describes it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HlsVariant {
+    /// peak bits per second, the only attribute the spec requires
+    pub bandwidth: Option<u64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub codecs: Option<String>,
+    /// as written in the playlist, usually relative to it
+    pub uri: String,
+}
+
+// `KEY=value,KEY="quoted, value"` into pairs, quotes stripped
+fn parse_attributes(list: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = list.trim();
+
+    while let Some((key, after)) = rest.split_once('=') {
+        let (value, after) = if let Some(quoted) = after.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let after = quoted.get(end + 1..).unwrap_or_default();
+            (&quoted[..end], after)
+        } else {
+            after.split_once(',').map_or((after, ""), |(v, a)| (v, a))
+        };
+
+        attributes.push((key.trim().to_uppercase(), value.trim().to_string()));
+        rest = after.trim_start_matches([',', ' ']);
+    }
+
+    attributes
+}
+
+fn parse_variant(attribute_list: &str, uri: &str) -> HlsVariant {
+    let mut variant = HlsVariant {
+        uri: uri.to_string(),
+        ..Default::default()
+    };
+
+    for (key, value) in parse_attributes(attribute_list) {
+        match key.as_str() {
+            "BANDWIDTH" => variant.bandwidth = value.parse().ok(),
+            "RESOLUTION" => {
+                if let Some((width, height)) = value.to_lowercase().split_once('x') {
+                    variant.width = width.parse().ok();
+                    variant.height = height.parse().ok();
+                }
+            }
+            "CODECS" => variant.codecs = Some(value).filter(|c| !c.is_empty()),
+            _ => {}
+        }
+    }
+
+    variant
+}
+
+fn height_of(attribute_list: &str) -> Option<u32> {
+    parse_variant(attribute_list, "").height
+}
+
+/// the variants of a master playlist in the order they're listed, empty for media playlists
+pub fn parse_master_playlist(text: &str) -> Vec<HlsVariant> {
+    let mut variants = Vec::new();
+    let mut pending: Option<&str> = None;
+
+    for line in text.lines().map(str::trim) {
+        if let Some(attributes) = line.strip_prefix(STREAM_INF) {
+            pending = Some(attributes);
+        } else if !line.is_empty()
+            && !line.starts_with('#')
+            && let Some(attributes) = pending.take()
+        {
+            variants.push(parse_variant(attributes, line));
+        }
+    }
+
+    variants
+}
+
+/// drops the variants taller than `max_height`. variants without a resolution (audio only,
+/// mostly) stay, and if every video variant is too tall the shortest one is kept so the player
+/// always has something to play
+pub fn filter_master_playlist(text: &str, max_height: u32) -> String {
+    let variants = parse_master_playlist(text);
+    let keep_at_least = variants
+        .iter()
+        .filter_map(|v| v.height)
+        .min()
+        .filter(|shortest| *shortest > max_height);
+    let too_tall =
+        |height: Option<u32>| height.is_some_and(|h| h > max_height && Some(h) != keep_at_least);
+
+    let mut lines = Vec::new();
+    let mut skip_uri = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+
+        if let Some(attributes) = trimmed.strip_prefix(STREAM_INF) {
+            skip_uri = too_tall(height_of(attributes));
+            if skip_uri {
+                continue;
+            }
+        } else if let Some(attributes) = trimmed.strip_prefix(I_FRAME_STREAM_INF) {
+            // these carry their uri as an attribute, nothing follows them
+            if too_tall(height_of(attributes)) {
+                continue;
+            }
+        } else if !trimmed.is_empty() && !trimmed.starts_with('#') && skip_uri {
+            skip_uri = false;
+            continue;
+        }
+
+        lines.push(line);
+    }
+
+    lines.join("\n")
+}
+
+/// master playlists by url so qualities don't have to be fetched again for every signed link.
+/// a failed fetch is kept as None
+#[derive(Default)]
+pub struct PlaylistCache {
+    entries: Mutex<HashMap<String, (Instant, Option<String>)>>,
+}
+
+impl PlaylistCache {
+    /// None when `url` isn't cached, Some(None) when fetching it failed recently
+    pub fn get(&self, url: &str) -> Option<Option<String>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (expires_at, text) = entries.get(url)?;
+
+        (*expires_at > Instant::now()).then(|| text.clone())
+    }
+
+    pub fn put(&self, url: &str, text: Option<String>) {
+        let ttl = match text {
+            Some(_) => PLAYLIST_TTL,
+            None => FAILED_PLAYLIST_TTL,
+        };
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= PLAYLIST_PRUNE_SIZE {
+            let now = Instant::now();
+            entries.retain(|_, (expires_at, _)| *expires_at > now);
+        }
+        entries.insert(url.to_string(), (Instant::now() + ttl, text));
+    }
+}
+
+/// where a uri from a playlist points, relative ones are resolved against the playlist's own url
+pub fn resolve_playlist_uri(playlist_url: &str, uri: &str) -> Option<String> {
+    url::Url::parse(playlist_url)
+        .and_then(|base| base.join(uri))
+        .map(|resolved| resolved.to_string())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=5000000,RESOLUTION=1920x1080,CODECS=\"avc1.640028,mp4a.40.2\"
+1080/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=2800000,RESOLUTION=1280x720
+720/index.m3u8
+#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=90000,RESOLUTION=1920x1080,URI=\"1080/iframes.m3u8\"
+#EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"
+audio/index.m3u8";
+
+    #[test]
+    fn test_parse_master_playlist() {
+        let variants = parse_master_playlist(MASTER);
+
+        assert_eq!(variants.len(), 3);
+        assert_eq!(
+            variants[0],
+            HlsVariant {
+                bandwidth: Some(5000000),
+                width: Some(1920),
+                height: Some(1080),
+                codecs: Some(String::from("avc1.640028,mp4a.40.2")),
+                uri: String::from("1080/index.m3u8"),
+            }
+        );
+        assert_eq!(variants[1].height, Some(720));
+        assert_eq!(variants[2].height, None);
+        assert!(parse_master_playlist("#EXTM3U\n#EXTINF:2.0,\nsegment0.ts").is_empty());
+    }
+
+    #[test]
+    fn test_filter_master_playlist() {
+        let filtered = filter_master_playlist(MASTER, 720);
+
+        assert!(!filtered.contains("1080"));
+        assert!(filtered.contains("720/index.m3u8"));
+        assert!(filtered.contains("audio/index.m3u8"));
+
+        // nothing fits so the smallest video variant stays
+        let filtered = parse_master_playlist(&filter_master_playlist(MASTER, 360));
+        assert_eq!(
+            filtered.iter().map(|v| v.uri.as_str()).collect::<Vec<_>>(),
+            vec!["720/index.m3u8", "audio/index.m3u8"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_playlist_uri() {
+        let playlist = "https://cdn.example.com/hls/movie/index.m3u8?token=abc";
+
+        assert_eq!(
+            resolve_playlist_uri(playlist, "720p.m3u8").as_deref(),
+            Some("https://cdn.example.com/hls/movie/720p.m3u8")
+        );
+        assert_eq!(
+            resolve_playlist_uri(playlist, "/other/360p.m3u8").as_deref(),
+            Some("https://cdn.example.com/other/360p.m3u8")
+        );
+        assert_eq!(
+            resolve_playlist_uri(playlist, "https://other.example.com/a.m3u8").as_deref(),
+            Some("https://other.example.com/a.m3u8")
+        );
+    }
+
+    #[test]
+    fn test_playlist_cache() {
+        let cache = PlaylistCache::default();
+
+        assert_eq!(cache.get("https://cdn/master.m3u8"), None);
+
+        cache.put("https://cdn/master.m3u8", Some(MASTER.to_string()));
+        cache.put("https://cdn/dead.m3u8", None);
+
+        assert_eq!(
+            cache.get("https://cdn/master.m3u8"),
+            Some(Some(MASTER.to_string()))
+        );
+        assert_eq!(cache.get("https://cdn/dead.m3u8"), Some(None));
+    }
+}
@@ -0,0 +1,124 @@
+// decoding, resizing and re-encoding for the image proxy. all of it is cpu bound so callers
+// should run it on a blocking thread
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{ImageReader, Limits};
+
+/// the only widths the proxy hands out, anything else is rounded up to one of these so the cache
+/// doesn't fill with near duplicates
+pub const IMAGE_WIDTHS: [u32; 4] = [160, 320, 640, 960];
+
+pub const DEFAULT_IMAGE_WIDTH: u32 = 320;
+
+// anything bigger than this is either not a poster or an attempt at a decompression bomb
+const MAX_SOURCE_DIMENSION: u32 = 8192;
+
+const JPEG_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    WebP,
+    Jpeg,
+}
+
+impl ImageFormat {
+    /// webp if the client says it takes it, jpeg otherwise
+    pub fn from_accept(accept: Option<&str>) -> Self {
+        match accept {
+            Some(accept) if accept.contains("image/webp") => ImageFormat::WebP,
+            _ => ImageFormat::Jpeg,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::WebP => "webp",
+            ImageFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// smallest allowed width that's at least `requested`, capped at the largest
+pub fn snap_width(requested: Option<u32>) -> u32 {
+    let requested = requested.unwrap_or(DEFAULT_IMAGE_WIDTH);
+
+    IMAGE_WIDTHS
+        .iter()
+        .copied()
+        .find(|w| *w >= requested)
+        .unwrap_or(IMAGE_WIDTHS[IMAGE_WIDTHS.len() - 1])
+}
+
+/// scales `source` down to `width` keeping its aspect ratio, images that are already narrower
+/// are only re-encoded
+pub fn resize_image(source: &[u8], width: u32, format: ImageFormat) -> anyhow::Result<Vec<u8>> {
+    let mut reader = ImageReader::new(Cursor::new(source)).with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    reader.limits(limits);
+
+    let mut image = reader.decode()?;
+    if image.width() > width {
+        let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+        image = image.resize_exact(width, height, FilterType::Lanczos3);
+    }
+
+    let mut out = Vec::new();
+    match format {
+        // the webp encoder only does lossless, it wants 8 bit rgb(a)
+        ImageFormat::WebP => image
+            .to_rgba8()
+            .write_with_encoder(WebPEncoder::new_lossless(&mut out))?,
+        // no alpha in jpeg
+        ImageFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))?,
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Rgb};
+
+    #[test]
+    fn test_width_snapping() {
+        assert_eq!(snap_width(None), DEFAULT_IMAGE_WIDTH);
+        assert_eq!(snap_width(Some(1)), 160);
+        assert_eq!(snap_width(Some(320)), 320);
+        assert_eq!(snap_width(Some(321)), 640);
+        assert_eq!(snap_width(Some(5000)), 960);
+    }
+
+    #[test]
+    fn test_resize_keeps_aspect_ratio() {
+        let source = ImageBuffer::from_pixel(800, 400, Rgb([200u8, 30, 30]));
+        let mut png = Vec::new();
+        source
+            .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        for format in [ImageFormat::Jpeg, ImageFormat::WebP] {
+            let resized = resize_image(&png, 160, format).unwrap();
+            let decoded = image::load_from_memory(&resized).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (160, 80));
+        }
+
+        // never scaled up
+        let same = resize_image(&png, 960, ImageFormat::Jpeg).unwrap();
+        assert_eq!(image::load_from_memory(&same).unwrap().width(), 800);
+    }
+}
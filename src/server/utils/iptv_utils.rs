@@ -0,0 +1,150 @@
+// m3u playlist and xmltv guide for iptv players (vlc, kodi, tivimate). every game gets treated as
+// its own channel since that's the only way players will show one programme per stream
+use chrono::{DateTime, Utc};
+
+use crate::database::stream::Game;
+
+/// a game plus the provider it came from, together they make the channel id
+pub struct IptvEntry<'a> {
+    pub provider: &'a str,
+    pub game: &'a Game,
+}
+
+/// where the feed is served from and who it's for, players can't send headers so every url
+/// carries the user's feed key
+pub struct IptvFeed {
+    pub base_url: String,
+    pub user: String,
+    pub key: String,
+}
+
+impl IptvFeed {
+    fn auth_query(&self) -> String {
+        format!(
+            "user={}&key={}",
+            urlencoding::encode(&self.user),
+            urlencoding::encode(&self.key)
+        )
+    }
+
+    pub fn playlist_url(&self) -> String {
+        format!(
+            "{}/api/v1/streams/playlist.m3u?{}",
+            self.base_url,
+            self.auth_query()
+        )
+    }
+
+    pub fn guide_url(&self) -> String {
+        format!(
+            "{}/api/v1/streams/guide.xml?{}",
+            self.base_url,
+            self.auth_query()
+        )
+    }
+
+    // resolving happens when the player opens the entry, see the play endpoint
+    pub fn play_url(&self, provider: &str, game_id: i64) -> String {
+        format!(
+            "{}/api/v1/streams/{}/{}/play?{}",
+            self.base_url,
+            urlencoding::encode(provider),
+            game_id,
+            self.auth_query()
+        )
+    }
+}
+
+fn channel_id(entry: &IptvEntry) -> String {
+    format!("{}-{}", entry.provider, entry.game.id)
+}
+
+pub fn render_playlist(entries: &[IptvEntry], feed: &IptvFeed) -> String {
+    let mut out = format!("#EXTM3U url-tvg=\"{}\"\n", feed.guide_url());
+
+    for entry in entries {
+        let game = entry.game;
+        out.push_str(&format!(
+            "#EXTINF:-1 tvg-id=\"{}\" tvg-name=\"{}\" tvg-logo=\"{}\" group-title=\"{}\",{}\n",
+            m3u_attribute(&channel_id(entry)),
+            m3u_attribute(&game.name),
+            m3u_attribute(&game.poster),
+            m3u_attribute(&game.category),
+            single_line(&game.name)
+        ));
+        out.push_str(&feed.play_url(entry.provider, game.id));
+        out.push('\n');
+    }
+
+    out
+}
+
+pub fn render_guide(entries: &[IptvEntry]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE tv SYSTEM \"xmltv.dtd\">\n<tv generator-info-name=\"personalrestapi\">\n",
+    );
+
+    // the dtd wants every channel before the first programme
+    for entry in entries {
+        let game = entry.game;
+        out.push_str(&format!(
+            "  <channel id=\"{}\">\n    <display-name>{}</display-name>\n",
+            xml_escape(&channel_id(entry)),
+            xml_escape(&game.name)
+        ));
+        if !game.poster.is_empty() {
+            out.push_str(&format!(
+                "    <icon src=\"{}\"/>\n",
+                xml_escape(&game.poster)
+            ));
+        }
+        out.push_str("  </channel>\n");
+    }
+
+    for entry in entries {
+        let game = entry.game;
+        out.push_str(&format!(
+            "  <programme start=\"{}\" stop=\"{}\" channel=\"{}\">\n    <title>{}</title>\n",
+            format_time(game.start_time),
+            format_time(game.end_time.max(game.start_time)),
+            xml_escape(&channel_id(entry)),
+            xml_escape(&game.name)
+        ));
+        if !game.category.is_empty() {
+            out.push_str(&format!(
+                "    <category>{}</category>\n",
+                xml_escape(&game.category)
+            ));
+        }
+        out.push_str("  </programme>\n");
+    }
+
+    out.push_str("</tv>\n");
+
+    out
+}
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::<Utc>::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y%m%d%H%M%S +0000")
+        .to_string()
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+// there's no escaping in m3u attributes, players just stop at the next quote
+fn m3u_attribute(value: &str) -> String {
+    single_line(value).replace('"', "'")
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -1,3 +1,16 @@
 pub mod argon_utils;
+pub mod calendar_utils;
+pub mod caption_utils;
+pub mod category_utils;
+pub mod csv_utils;
+pub mod disk_cache_utils;
+pub mod etag_utils;
+pub mod extraction_utils;
+pub mod fake_upstream_utils;
+pub mod hls_utils;
+pub mod image_utils;
+pub mod iptv_utils;
 pub mod jwt_utils;
+pub mod network_utils;
 pub mod signature_utils;
+pub mod team_utils;
@@ -0,0 +1,143 @@
+// checks for urls users hand us that the server then calls itself (webhooks). without these
+// anyone could point one at localhost, the private network or a cloud metadata endpoint and have
+// the server make requests there for them
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use anyhow::{Context, bail};
+use url::{Host, Url};
+
+// hostnames that are internal without resolving to anything obviously private
+const BLOCKED_HOSTS: [&str; 3] = ["localhost", "metadata.google.internal", "metadata"];
+
+/// a url that was checked along with the addresses it resolved to, requests should go to these
+/// so the name can't resolve somewhere else by the time it's used
+#[derive(Debug, Clone)]
+pub struct PublicUrl {
+    pub url: Url,
+    pub host: String,
+    pub addrs: Vec<SocketAddr>,
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        // 169.254.169.254 is where the cloud metadata services live
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // 0.0.0.0/8, carrier grade nat and the reserved 240/4
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    if let Some(mapped) = ip.to_ipv4_mapped() {
+        return is_public_ipv4(mapped);
+    }
+
+    let first = ip.segments()[0];
+
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // unique local fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // link local fe80::/10
+        || (first & 0xffc0) == 0xfe80
+        // documentation 2001:db8::/32
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+/// parses `url` and resolves its host, failing unless it's http(s) and every address it
+/// resolves to is public
+pub async fn resolve_public_url(url: &str) -> anyhow::Result<PublicUrl> {
+    let url = Url::parse(url).context("not a valid url")?;
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("url must be http(s)");
+    }
+
+    let port = url.port_or_known_default().context("url has no port")?;
+    let (host, addrs) = match url.host() {
+        Some(Host::Ipv4(ip)) => (ip.to_string(), vec![SocketAddr::new(IpAddr::V4(ip), port)]),
+        Some(Host::Ipv6(ip)) => (ip.to_string(), vec![SocketAddr::new(IpAddr::V6(ip), port)]),
+        Some(Host::Domain(domain)) => {
+            let domain = domain.trim_end_matches('.').to_lowercase();
+            if BLOCKED_HOSTS.contains(&domain.as_str()) || domain.ends_with(".localhost") {
+                bail!("{} is not a public host", domain);
+            }
+
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain.as_str(), port))
+                .await
+                .with_context(|| format!("{} could not be resolved", domain))?
+                .collect();
+            (domain, addrs)
+        }
+        None => bail!("url has no host"),
+    };
+
+    if addrs.is_empty() {
+        bail!("{} could not be resolved", host);
+    }
+    if addrs.iter().any(|addr| !is_public_ip(addr.ip())) {
+        bail!("{} is not a public address", host);
+    }
+
+    Ok(PublicUrl { url, host, addrs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public_ip() {
+        for private in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(private.parse().unwrap()), "{}", private);
+        }
+
+        for public in ["1.1.1.1", "93.184.216.34", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(public.parse().unwrap()), "{}", public);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_public_url() {
+        for blocked in [
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost:6379",
+            "http://[::1]:8080/",
+            "http://metadata.google.internal/",
+            "ftp://1.1.1.1/",
+        ] {
+            assert!(resolve_public_url(blocked).await.is_err(), "{}", blocked);
+        }
+
+        let public = resolve_public_url("https://1.1.1.1/hook").await.unwrap();
+        assert_eq!(public.host, "1.1.1.1");
+        assert_eq!(public.addrs, vec!["1.1.1.1:443".parse().unwrap()]);
+    }
+}
@@ -1,3 +1,4 @@
+use base64::{Engine as _, engine::general_purpose::URL_SAFE};
 use hex;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
@@ -42,7 +43,7 @@ impl SignatureUtil {
         // see if we can regenerate the signature, if we can then it's valid
         let expected_signature = self.generate_signature(user_id, expiry, url);
 
-        signature.as_bytes().len() == expected_signature.as_bytes().len()
+        signature.len() == expected_signature.len()
             && signature
                 .as_bytes()
                 .iter()
@@ -51,6 +52,25 @@ impl SignatureUtil {
                 == 0
     }
 
+    /// long lived key for feeds that get subscribed to (calendar apps and the like), same hmac as
+    /// above but without an expiry. the feed name is mixed in so a key for one feed doesn't open
+    /// another, changing the secret revokes all of them
+    pub fn generate_feed_key(&self, user_id: &str, feed: &str) -> String {
+        self.generate_signature(user_id, 0, &format!("feed:{}", feed))
+    }
+
+    pub fn verify_feed_key(&self, user_id: &str, feed: &str, key: &str) -> bool {
+        let expected_key = self.generate_feed_key(user_id, feed);
+
+        key.len() == expected_key.len()
+            && key
+                .as_bytes()
+                .iter()
+                .zip(expected_key.as_bytes().iter())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
     pub fn generate_expiry(hours: i64) -> i64 {
         let current_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -59,6 +79,55 @@ impl SignatureUtil {
 
         current_time + (hours * 3600)
     }
+
+    /// (url, expiry) for fetching `url` through the proxy as `user_id` for the next 12 hours,
+    /// relative to the api root. only the encoded url is signed, `provider` just picks headers
+    pub fn signed_proxy_url(
+        &self,
+        user_id: &str,
+        url: &str,
+        schema: &str,
+        provider: Option<&str>,
+    ) -> (String, i64) {
+        let provider = provider
+            .map(|provider| format!("&provider={}", urlencoding::encode(provider)))
+            .unwrap_or_default();
+
+        self.signed_path(
+            "/api/v1/proxy",
+            user_id,
+            url,
+            &format!("&schema={}{}", schema, provider),
+        )
+    }
+
+    /// same as signed_proxy_url but for the captions proxy, `<track>` elements can't send auth
+    pub fn signed_captions_url(&self, user_id: &str, url: &str) -> String {
+        self.signed_path("/api/v1/proxy/captions", user_id, url, "")
+            .0
+    }
+
+    // `path?url=...` with the sig, exp and user params RequiredAuthentication checks
+    fn signed_path(&self, path: &str, user_id: &str, url: &str, params: &str) -> (String, i64) {
+        let encoded = URL_SAFE
+            .encode(url.as_bytes())
+            .trim_end_matches('=')
+            .to_string();
+        let expiry = Self::generate_expiry(12);
+        let signature = self.generate_signature(user_id, expiry, &encoded);
+
+        let signed_url = format!(
+            "{}?url={}{}&sig={}&exp={}&user={}",
+            path,
+            encoded,
+            params,
+            signature,
+            expiry,
+            urlencoding::encode(user_id)
+        );
+
+        (signed_url, expiry)
+    }
 }
 
 // again, i wrote these up trying to get it done fast and make sure everything works, these should
@@ -107,4 +176,53 @@ mod tests {
         // Expired signature should fail even if signature is correct
         assert!(!util.verify_signature(user_id, past_expiry, url, &signature));
     }
+
+    #[test]
+    fn test_signed_proxy_url_verifies() {
+        let util = SignatureUtil::new("test_secret".to_string());
+        let (signed_url, expiry) = util.signed_proxy_url(
+            "user 1",
+            "https://cdn.example.com/index.m3u8",
+            "sports",
+            Some("ppvsu"),
+        );
+
+        let query: std::collections::HashMap<String, String> =
+            url::Url::parse(&format!("http://localhost{}", signed_url))
+                .unwrap()
+                .query_pairs()
+                .into_owned()
+                .collect();
+
+        assert_eq!(query["provider"], "ppvsu");
+        assert_eq!(query["user"], "user 1");
+        assert_eq!(query["exp"], expiry.to_string());
+        assert!(util.verify_signature("user 1", expiry, &query["url"], &query["sig"]));
+    }
+
+    #[test]
+    fn test_signed_captions_url_verifies() {
+        let util = SignatureUtil::new("test_secret".to_string());
+        let signed_url = util.signed_captions_url("user123", "https://subs.example.com/en.srt");
+
+        let url = url::Url::parse(&format!("http://localhost{}", signed_url)).unwrap();
+        let query: std::collections::HashMap<String, String> =
+            url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.path(), "/api/v1/proxy/captions");
+        let expiry = query["exp"].parse().unwrap();
+        assert!(util.verify_signature("user123", expiry, &query["url"], &query["sig"]));
+    }
+
+    #[test]
+    fn test_feed_key_verification() {
+        let util = SignatureUtil::new("test_secret".to_string());
+        let key = util.generate_feed_key("user123", "calendar");
+
+        assert!(util.verify_feed_key("user123", "calendar", &key));
+
+        // keys are per user and per feed
+        assert!(!util.verify_feed_key("different_user", "calendar", &key));
+        assert!(!util.verify_feed_key("user123", "playlist", &key));
+    }
 }
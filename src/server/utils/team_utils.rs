@@ -0,0 +1,252 @@
+// pulls the teams and league out of game names like "NBA: Lakers vs. Celtics". providers only
+// give us one string so this is best effort, a name that doesn't look like a matchup just gets no
+// teams. "@" names put the away team first, every other separator puts the home team first
+use std::collections::HashMap;
+
+use crate::database::team::TeamAlias;
+
+// most specific first so "vs." isn't split on " v"
+const MATCHUP_SEPARATORS: [(&str, bool); 5] = [
+    (" vs. ", false),
+    (" vs ", false),
+    (" v. ", false),
+    (" v ", false),
+    (" @ ", true),
+];
+
+// the one both the prefix and hyphen matchups use, dashes get folded into it first
+const HYPHEN: &str = " - ";
+
+/// what could be read out of a game's name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Matchup {
+    /// league or event the name was prefixed with, "NBA" or "UFC 310"
+    pub league: Option<String>,
+    pub home_team: Option<String>,
+    pub away_team: Option<String>,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+// "Celtics, Game 7", "Celtics: Game 7" and "Celtics (Replay)" are all still the Celtics
+fn clean_team(team: &str) -> Option<String> {
+    let team = [", ", ": ", " ("].iter().fold(team, |team, suffix| {
+        team.split(suffix).next().unwrap_or_default()
+    });
+    non_empty(team.trim_end_matches(['.', ',', ':']))
+}
+
+// "[NBA] ..." or "NBA: ..." or "NBA | ..."
+fn split_league(name: &str) -> (Option<String>, &str) {
+    if let Some(rest) = name.strip_prefix('[')
+        && let Some((league, rest)) = rest.split_once(']')
+    {
+        return (non_empty(league), rest.trim());
+    }
+
+    for separator in [": ", " | "] {
+        // "Lakers vs Celtics: Game 7" has its matchup before the colon
+        if let Some((league, rest)) = name.split_once(separator)
+            && find_separator(league).is_none()
+        {
+            return (non_empty(league), rest.trim());
+        }
+    }
+
+    (None, name)
+}
+
+// position of the first separator that shows up, lowercased with ascii only so byte offsets
+// still line up with the original
+fn find_separator(name: &str) -> Option<(usize, &'static str, bool)> {
+    let lowered = name.to_ascii_lowercase();
+    MATCHUP_SEPARATORS
+        .iter()
+        .find_map(|&(separator, away_first)| {
+            lowered
+                .find(separator)
+                .map(|at| (at, separator, away_first))
+        })
+}
+
+pub fn parse_matchup(name: &str) -> Matchup {
+    let name = name
+        .replace('\u{a0}', " ")
+        .replace(" – ", HYPHEN)
+        .replace(" — ", HYPHEN);
+    let (mut league, rest) = split_league(name.trim());
+
+    let (first, second, away_first) = match find_separator(rest) {
+        Some((at, separator, away_first)) => {
+            let (mut first, second) = (&rest[..at], &rest[at + separator.len()..]);
+            // "Premier League - Arsenal vs Chelsea"
+            if let Some((prefix, team)) = first.rsplit_once(HYPHEN) {
+                league = league.or_else(|| non_empty(prefix));
+                first = team;
+            }
+            // "Arsenal vs Chelsea - Matchday 12"
+            let second = second.split(HYPHEN).next().unwrap_or_default();
+            (first, second, away_first)
+        }
+        None => {
+            let parts: Vec<&str> = rest.split(HYPHEN).collect();
+            match parts.as_slice() {
+                [first, second] => (*first, *second, false),
+                [prefix, first, second, ..] => {
+                    league = league.or_else(|| non_empty(prefix));
+                    (*first, *second, false)
+                }
+                _ => {
+                    return Matchup {
+                        league,
+                        ..Default::default()
+                    };
+                }
+            }
+        }
+    };
+
+    match (clean_team(first), clean_team(second)) {
+        (Some(first), Some(second)) => {
+            let (home_team, away_team) = if away_first {
+                (second, first)
+            } else {
+                (first, second)
+            };
+            Matchup {
+                league,
+                home_team: Some(home_team),
+                away_team: Some(away_team),
+            }
+        }
+        _ => Matchup {
+            league,
+            ..Default::default()
+        },
+    }
+}
+
+/// other names teams go by, so "Man Utd" and "Manchester United" end up the same team
+#[derive(Debug, Clone, Default)]
+pub struct TeamAliases {
+    // lowercased alias -> the team's name
+    aliases: HashMap<String, String>,
+}
+
+impl TeamAliases {
+    pub fn new(aliases: &[TeamAlias]) -> Self {
+        let aliases = aliases
+            .iter()
+            .map(|alias| (alias.alias.to_lowercase(), alias.team.clone()))
+            .collect();
+
+        Self { aliases }
+    }
+
+    /// the name a team goes by here, or the name as given if nobody aliased it
+    pub fn canonical(&self, team: &str) -> String {
+        let team = team.trim();
+        self.aliases
+            .get(&team.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| team.to_string())
+    }
+
+    pub fn parse(&self, name: &str) -> Matchup {
+        let matchup = parse_matchup(name);
+        Matchup {
+            home_team: matchup.home_team.map(|t| self.canonical(&t)),
+            away_team: matchup.away_team.map(|t| self.canonical(&t)),
+            ..matchup
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matchup(league: Option<&str>, home: &str, away: &str) -> Matchup {
+        Matchup {
+            league: league.map(String::from),
+            home_team: Some(String::from(home)),
+            away_team: Some(String::from(away)),
+        }
+    }
+
+    #[test]
+    fn test_parse_matchup() {
+        assert_eq!(
+            parse_matchup("Florida Gators vs. Texas A&M Aggies"),
+            matchup(None, "Florida Gators", "Texas A&M Aggies")
+        );
+        assert_eq!(
+            parse_matchup("NBA: Boston Celtics @ Los Angeles Lakers"),
+            matchup(Some("NBA"), "Los Angeles Lakers", "Boston Celtics")
+        );
+        assert_eq!(
+            parse_matchup("Premier League - Arsenal v Chelsea"),
+            matchup(Some("Premier League"), "Arsenal", "Chelsea")
+        );
+        assert_eq!(
+            parse_matchup("[NHL] Rangers VS Bruins"),
+            matchup(Some("NHL"), "Rangers", "Bruins")
+        );
+        assert_eq!(
+            parse_matchup("UFC 310: Pantoja vs Asakura"),
+            matchup(Some("UFC 310"), "Pantoja", "Asakura")
+        );
+        assert_eq!(
+            parse_matchup("Lakers vs Celtics, Game 7"),
+            matchup(None, "Lakers", "Celtics")
+        );
+        assert_eq!(
+            parse_matchup("Lakers vs Celtics: Game 7"),
+            matchup(None, "Lakers", "Celtics")
+        );
+        assert_eq!(
+            parse_matchup("Real Madrid - Barcelona"),
+            matchup(None, "Real Madrid", "Barcelona")
+        );
+        assert_eq!(
+            parse_matchup("La Liga – Real Madrid – Barcelona"),
+            matchup(Some("La Liga"), "Real Madrid", "Barcelona")
+        );
+        // hyphens inside names aren't separators
+        assert_eq!(
+            parse_matchup("Winston-Salem Dash vs Hickory Crawdads"),
+            matchup(None, "Winston-Salem Dash", "Hickory Crawdads")
+        );
+    }
+
+    #[test]
+    fn test_parse_matchup_without_teams() {
+        assert_eq!(parse_matchup("24/7 Highlights"), Matchup::default());
+        assert_eq!(
+            parse_matchup("WWE: Monday Night Raw"),
+            Matchup {
+                league: Some(String::from("WWE")),
+                ..Default::default()
+            }
+        );
+        assert_eq!(parse_matchup("Lakers vs "), Matchup::default());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let aliases = TeamAliases::new(&[TeamAlias {
+            alias: String::from("man utd"),
+            team: String::from("Manchester United"),
+        }]);
+
+        assert_eq!(aliases.canonical("Man Utd"), "Manchester United");
+        assert_eq!(aliases.canonical(" Chelsea "), "Chelsea");
+        assert_eq!(
+            aliases.parse("Man Utd vs Chelsea"),
+            matchup(None, "Manchester United", "Chelsea")
+        );
+    }
+}
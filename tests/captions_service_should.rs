@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+
+use api::server::{
+    error::Error,
+    services::caption_services::{CaptionsService, CaptionsServiceTrait},
+    utils::{disk_cache_utils::DiskCache, fake_upstream_utils::FakeUpstream},
+};
+
+async fn fake_upstream() -> FakeUpstream {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/upstream");
+    FakeUpstream::spawn(fixtures).await.unwrap()
+}
+
+fn captions_service(cache: Option<DiskCache>) -> CaptionsService {
+    // the fake upstream is on localhost
+    CaptionsService::new(cache, true)
+}
+
+#[tokio::test]
+async fn convert_srt_to_vtt() {
+    // arrange
+    let fake = fake_upstream().await;
+    let service = captions_service(None);
+
+    // act
+    let vtt = service
+        .get_captions(format!("{}/captions/en.srt", fake.base_url()), None, 0)
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(
+        vtt,
+        "WEBVTT\n\n00:00:01.000 --> 00:00:02.500\n<i>First</i> line\n\n00:00:03.000 --> 00:00:04.000\nSecond line\n"
+    );
+}
+
+#[tokio::test]
+async fn convert_ass_and_apply_the_offset() {
+    // arrange
+    let fake = fake_upstream().await;
+    let service = captions_service(None);
+
+    // act
+    let vtt = service
+        .get_captions(format!("{}/captions/en.ass", fake.base_url()), None, -500)
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(
+        vtt,
+        "WEBVTT\n\n00:00:00.500 --> 00:00:01.500\nFirst\nline\n"
+    );
+}
+
+#[tokio::test]
+async fn re_encode_legacy_captions_as_utf8() {
+    // arrange
+    let fake = fake_upstream().await;
+    let service = captions_service(None);
+
+    // act
+    let vtt = service
+        .get_captions(format!("{}/captions/fr.srt", fake.base_url()), None, 0)
+        .await
+        .unwrap();
+
+    // assert
+    assert!(vtt.ends_with("\nDéjà vu, ça va\n"));
+}
+
+#[tokio::test]
+async fn serve_cached_captions_with_a_new_offset() {
+    // arrange
+    let dir = std::env::temp_dir().join(format!("captions-{}", std::process::id()));
+    let fixtures = dir.join("fixtures");
+    std::fs::create_dir_all(fixtures.join("captions")).unwrap();
+    std::fs::write(
+        fixtures.join("captions/en.srt"),
+        "1\n00:00:01,000 --> 00:00:02,000\nHello\n",
+    )
+    .unwrap();
+    let fake = FakeUpstream::spawn(&fixtures).await.unwrap();
+    let service = captions_service(Some(
+        DiskCache::open(dir.join("cache"), 1024 * 1024).unwrap(),
+    ));
+    let url = format!("{}/captions/en.srt", fake.base_url());
+    service.get_captions(url.clone(), None, 0).await.unwrap();
+    // only the cache has it now
+    std::fs::remove_file(fixtures.join("captions/en.srt")).unwrap();
+
+    // act
+    let vtt = service.get_captions(url, None, 1000).await.unwrap();
+
+    // assert
+    assert_eq!(vtt, "WEBVTT\n\n00:00:02.000 --> 00:00:03.000\nHello\n");
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn reject_captions_in_an_unknown_format() {
+    // arrange
+    let fake = fake_upstream().await;
+    let service = captions_service(None);
+
+    // act
+    let result = service
+        .get_captions(format!("{}/posters/1001.png", fake.base_url()), None, 0)
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn refuse_to_fetch_captions_from_internal_addresses() {
+    // arrange
+    let fake = fake_upstream().await;
+    let service = CaptionsService::new(None, false);
+
+    // act
+    let result = service
+        .get_captions(format!("{}/captions/en.srt", fake.base_url()), None, 0)
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
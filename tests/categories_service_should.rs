@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use api::{
+    database::category::{
+        Category, CategoryAlias, DynCategoriesRepository, MockCategoriesRepository,
+    },
+    server::{
+        dtos::category_dto::{MapCategoryAliasDto, UpsertCategoryDto},
+        error::Error,
+        services::category_services::{CategoriesService, CategoriesServiceTrait},
+    },
+};
+use sqlx::types::time::OffsetDateTime;
+
+fn stub_category(slug: &str, name: &str, sort_order: i32) -> Category {
+    Category {
+        slug: String::from(slug),
+        name: String::from(name),
+        icon: None,
+        sort_order,
+    }
+}
+
+fn stub_alias(raw_name: &str, slug: Option<&str>) -> CategoryAlias {
+    CategoryAlias {
+        provider: String::from("ppvsu"),
+        raw_name: String::from(raw_name),
+        category_slug: slug.map(String::from),
+        first_seen_at: OffsetDateTime::UNIX_EPOCH,
+    }
+}
+
+fn service(repository: MockCategoriesRepository) -> CategoriesService {
+    CategoriesService::new(Arc::new(repository) as DynCategoriesRepository)
+}
+
+#[tokio::test]
+async fn list_categories_in_order_with_their_aliases() {
+    // arrange
+    let mut repository = MockCategoriesRepository::new();
+    repository.expect_get_categories().returning(|| {
+        Ok(vec![
+            stub_category("ice-hockey", "Ice Hockey", 40),
+            stub_category("basketball", "Basketball", 10),
+        ])
+    });
+    repository.expect_get_category_aliases().returning(|| {
+        Ok(vec![
+            stub_alias("NHL", Some("ice-hockey")),
+            stub_alias("Hockey", Some("ice-hockey")),
+            stub_alias("Curling", None),
+        ])
+    });
+
+    // act
+    let categories = service(repository).get_categories().await.unwrap();
+
+    // assert
+    assert_eq!(categories.len(), 2);
+    assert_eq!(categories[0].slug, "basketball");
+    assert!(categories[0].aliases.is_empty());
+    assert_eq!(categories[1].slug, "ice-hockey");
+
+    let aliases: Vec<&str> = categories[1]
+        .aliases
+        .iter()
+        .map(|a| a.raw_name.as_str())
+        .collect();
+    assert_eq!(aliases, vec!["NHL", "Hockey"]);
+}
+
+#[tokio::test]
+async fn only_list_aliases_nobody_has_mapped_as_unmapped() {
+    // arrange
+    let mut repository = MockCategoriesRepository::new();
+    repository.expect_get_category_aliases().returning(|| {
+        Ok(vec![
+            stub_alias("NHL", Some("ice-hockey")),
+            stub_alias("Curling", None),
+        ])
+    });
+
+    // act
+    let unmapped = service(repository).get_unmapped().await.unwrap();
+
+    // assert
+    assert_eq!(unmapped.len(), 1);
+    assert_eq!(unmapped[0].raw_name, "Curling");
+    assert_eq!(unmapped[0].category_slug, None);
+}
+
+#[tokio::test]
+async fn not_map_an_alias_to_a_category_that_does_not_exist() {
+    // arrange
+    let mut repository = MockCategoriesRepository::new();
+    repository
+        .expect_get_categories()
+        .returning(|| Ok(vec![stub_category("ice-hockey", "Ice Hockey", 40)]));
+    repository.expect_map_category_alias().times(0);
+
+    // act
+    let result = service(repository)
+        .map_alias(MapCategoryAliasDto {
+            provider: String::from("ppvsu"),
+            raw_name: String::from("Curling"),
+            category_slug: String::from("curling"),
+        })
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn reject_category_slugs_that_are_not_slugs() {
+    // arrange
+    let mut repository = MockCategoriesRepository::new();
+    repository.expect_upsert_category().times(0);
+
+    // act
+    let result = service(repository)
+        .upsert_category(
+            String::from("Ice Hockey"),
+            UpsertCategoryDto {
+                name: String::from("Ice Hockey"),
+                icon: None,
+                sort_order: 40,
+            },
+        )
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn reload_the_taxonomy_after_an_alias_is_mapped() {
+    // arrange
+    let mut repository = MockCategoriesRepository::new();
+    repository
+        .expect_get_categories()
+        .returning(|| Ok(vec![stub_category("ice-hockey", "Ice Hockey", 40)]));
+    // loaded once before the mapping and once after, the lookup in between uses the copy
+    repository
+        .expect_get_category_aliases()
+        .times(1)
+        .returning(|| Ok(vec![stub_alias("NHL", None)]));
+    repository
+        .expect_get_category_aliases()
+        .times(1)
+        .returning(|| Ok(vec![stub_alias("NHL", Some("ice-hockey"))]));
+    repository
+        .expect_map_category_alias()
+        .times(1)
+        .returning(|_, raw_name, slug| Ok(stub_alias(raw_name, Some(slug))));
+
+    let service = service(repository);
+
+    // act
+    let before = service.taxonomy().await.resolve("ppvsu", "NHL");
+    let cached = service.taxonomy().await.resolve("ppvsu", "NHL");
+    service
+        .map_alias(MapCategoryAliasDto {
+            provider: String::from("ppvsu"),
+            raw_name: String::from("NHL"),
+            category_slug: String::from("ice-hockey"),
+        })
+        .await
+        .unwrap();
+    let after = service.taxonomy().await.resolve("ppvsu", "NHL");
+
+    // assert
+    assert!(!before.known);
+    assert!(!cached.known);
+    assert_eq!(after.slug, "ice-hockey");
+    assert_eq!(after.name, "Ice Hockey");
+}
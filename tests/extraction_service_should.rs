@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use api::server::services::extraction_services::{ExtractionService, ExtractionServiceTrait};
+
+const PAGE: &str = "https://embed.example.com/player/1";
+
+// fresh dir per test so they can run side by side
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("extraction-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_rules(path: &Path, json: &str, modified: SystemTime) {
+    std::fs::write(path, json).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn pick_up_rules_file_changes_without_a_restart() {
+    // arrange
+    let dir = scratch_dir("reload");
+    let rules = dir.join("rules.json");
+    write_rules(
+        &rules,
+        r#"{"strategies": [{"kind": "source_tag"}]}"#,
+        SystemTime::now() - Duration::from_secs(60),
+    );
+    let service = ExtractionService::new(Some(rules.clone()), None);
+    let html = r#"<script>setup({file: "https://cdn.example.com/live.mp4"})</script>"#;
+
+    // act
+    let before = service.extract(PAGE, html).await;
+    write_rules(
+        &rules,
+        r#"{"strategies": [{"kind": "regex", "pattern": "file:\\s*\"([^\"]+)\""}]}"#,
+        SystemTime::now(),
+    );
+    let after = service.extract(PAGE, html).await;
+
+    // assert
+    assert!(before.is_err());
+    assert_eq!(after.unwrap(), "https://cdn.example.com/live.mp4");
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn keep_the_last_good_rules_when_the_file_breaks() {
+    // arrange
+    let dir = scratch_dir("broken");
+    let rules = dir.join("rules.json");
+    write_rules(
+        &rules,
+        r#"{"strategies": [{"kind": "m3u8_url"}]}"#,
+        SystemTime::now() - Duration::from_secs(60),
+    );
+    let service = ExtractionService::new(Some(rules.clone()), None);
+    let html = r#"<script>load("https://cdn.example.com/a.m3u8")</script>"#;
+    service.extract(PAGE, html).await.unwrap();
+
+    // act
+    write_rules(&rules, r#"{"strategies": ["#, SystemTime::now());
+    let result = service.extract(PAGE, html).await;
+
+    // assert
+    assert_eq!(result.unwrap(), "https://cdn.example.com/a.m3u8");
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn save_pages_no_strategy_could_read() {
+    // arrange
+    let dir = scratch_dir("failures");
+    let service = ExtractionService::new(None, Some(dir.clone()));
+
+    // act
+    let result = service
+        .extract(PAGE, "<html><p>new layout</p></html>")
+        .await;
+
+    // assert
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("atob"));
+    assert!(error.contains("packed_js"));
+
+    let saved: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(saved.len(), 1);
+    assert!(
+        saved[0]
+            .to_string_lossy()
+            .ends_with("-embed.example.com.html")
+    );
+
+    let contents = std::fs::read_to_string(&saved[0]).unwrap();
+    assert!(contents.contains(PAGE));
+    assert!(contents.contains("<p>new layout</p>"));
+
+    let _ = std::fs::remove_dir_all(dir);
+}
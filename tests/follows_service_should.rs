@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use api::{
+    database::follow::{DynFollowsRepository, Follow, MockFollowsRepository, NotificationSettings},
+    server::{
+        dtos::follow_dto::{CreateFollowDto, FollowKind, UpdateNotificationSettingsDto},
+        error::Error,
+        services::follow_services::{FollowsService, FollowsServiceTrait},
+    },
+};
+use mockall::predicate::*;
+
+#[tokio::test]
+async fn trim_the_value_when_creating_a_follow() {
+    // arrange
+    let mut repository = MockFollowsRepository::new();
+    repository.expect_get_follows().returning(|_| Ok(vec![]));
+    repository
+        .expect_create_follow()
+        .with(eq("IRFa~VaY2b"), eq("category"), eq("Basketball"))
+        .times(1)
+        .returning(|user_id, kind, value| {
+            Ok(Follow {
+                user_id: user_id.to_string(),
+                kind: kind.to_string(),
+                value: value.to_string(),
+                ..Default::default()
+            })
+        });
+
+    let service = FollowsService::new(Arc::new(repository) as DynFollowsRepository);
+
+    // act
+    let follow = service
+        .create_follow(
+            String::from("IRFa~VaY2b"),
+            CreateFollowDto {
+                kind: FollowKind::Category,
+                value: String::from("  Basketball "),
+            },
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(follow.kind, "category");
+    assert_eq!(follow.value, "Basketball");
+}
+
+#[tokio::test]
+async fn reject_a_follow_that_already_exists_in_any_case() {
+    // arrange
+    let mut repository = MockFollowsRepository::new();
+    repository.expect_get_follows().returning(|_| {
+        Ok(vec![Follow {
+            value: String::from("Lakers"),
+            ..Default::default()
+        }])
+    });
+    repository.expect_create_follow().times(0);
+
+    let service = FollowsService::new(Arc::new(repository) as DynFollowsRepository);
+
+    // act
+    let result = service
+        .create_follow(
+            String::from("IRFa~VaY2b"),
+            CreateFollowDto {
+                kind: FollowKind::Team,
+                value: String::from("lakers"),
+            },
+        )
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::ObjectConflict(_))));
+}
+
+#[tokio::test]
+async fn return_not_found_when_deleting_someone_elses_follow() {
+    // arrange
+    let mut repository = MockFollowsRepository::new();
+    repository
+        .expect_delete_follow()
+        .times(1)
+        .returning(|_, _| Ok(false));
+
+    let service = FollowsService::new(Arc::new(repository) as DynFollowsRepository);
+
+    // act
+    let result = service
+        .delete_follow(String::from("IRFa~VaY2b"), String::from("not-mine"))
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn reject_webhooks_pointing_at_internal_addresses() {
+    // arrange
+    let mut repository = MockFollowsRepository::new();
+    repository.expect_update_notification_settings().times(0);
+
+    let service = FollowsService::new(Arc::new(repository) as DynFollowsRepository);
+
+    for url in [
+        "http://169.254.169.254/latest/meta-data/",
+        "http://localhost:6379",
+        "http://127.0.0.1:8080/hook",
+        "http://10.0.0.5/hook",
+        "http://192.168.1.1/hook",
+        "http://[::1]/hook",
+        "http://[fd12:3456::1]/hook",
+        "http://metadata.google.internal/",
+    ] {
+        // act
+        let result = service
+            .update_notification_settings(
+                String::from("IRFa~VaY2b"),
+                UpdateNotificationSettingsDto {
+                    webhook_url: Some(String::from(url)),
+                    email: false,
+                },
+            )
+            .await;
+
+        // assert
+        assert!(matches!(result, Err(Error::BadRequest(_))), "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn save_a_webhook_on_a_public_address() {
+    // arrange
+    let mut repository = MockFollowsRepository::new();
+    repository
+        .expect_update_notification_settings()
+        .times(1)
+        .returning(|user_id, webhook_url, email| {
+            Ok(NotificationSettings {
+                user_id: user_id.to_string(),
+                webhook_url,
+                email,
+            })
+        });
+
+    let service = FollowsService::new(Arc::new(repository) as DynFollowsRepository);
+
+    // act
+    let settings = service
+        .update_notification_settings(
+            String::from("IRFa~VaY2b"),
+            UpdateNotificationSettingsDto {
+                webhook_url: Some(String::from("https://1.1.1.1/hook")),
+                email: true,
+            },
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(
+        settings.webhook_url.as_deref(),
+        Some("https://1.1.1.1/hook")
+    );
+}
@@ -0,0 +1,34 @@
+use api::server::dtos::stream_dto::{GameListQuery, GameSort, GameStatus};
+
+#[test]
+fn keep_only_the_listing_filters_in_feed_params() {
+    // arrange
+    let query = GameListQuery {
+        category: Some(String::from("ice hockey")),
+        status: Some(GameStatus::Upcoming),
+        from: Some(1760223600),
+        team: Some(String::from("Man Utd & Co")),
+        sort: GameSort::NameDesc,
+        limit: Some(20),
+        cursor: Some(String::from("abc")),
+        ..Default::default()
+    };
+
+    // act
+    let params = query.filter_params();
+
+    // assert
+    assert_eq!(
+        params,
+        "category=ice+hockey&status=upcoming&from=1760223600&team=Man+Utd+%26+Co&sort=-name"
+    );
+}
+
+#[test]
+fn leave_feed_params_empty_without_filters() {
+    // act
+    let params = GameListQuery::default().filter_params();
+
+    // assert
+    assert_eq!(params, "");
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use api::{
+    database::{
+        archive::{
+            ArchiveFilter, ArchivedCategoryCount, ArchivedGame, DynGamesArchiveRepository,
+            MockGamesArchiveRepository, merge_sources,
+        },
+        stream::GameSource,
+    },
+    server::{
+        dtos::history_dto::HistoryQuery,
+        error::Error,
+        services::history_services::{HistoryService, HistoryServiceTrait},
+    },
+};
+use mockall::predicate::*;
+
+fn archived_game(game_id: i64) -> ArchivedGame {
+    ArchivedGame {
+        provider: String::from("ppvsu"),
+        game_id,
+        name: format!("stub game {}", game_id),
+        poster: String::from("https://example.com/poster.png"),
+        category: String::from("Ice Hockey"),
+        category_slug: String::from("ice-hockey"),
+        start_time: 1760223600,
+        end_time: 1760236200,
+        sources: String::from(
+            r#"[{"label":"Main","priority":0,"link":"https://example.com/embed"}]"#,
+        ),
+        status: String::from("ended"),
+        first_seen_at: 1760220000,
+        last_seen_at: 1760236000,
+    }
+}
+
+fn service(repository: MockGamesArchiveRepository) -> HistoryService {
+    HistoryService::new(Arc::new(repository) as DynGamesArchiveRepository)
+}
+
+#[tokio::test]
+async fn look_up_archived_games_by_category_slug() {
+    // arrange
+    let expected = ArchiveFilter {
+        provider: None,
+        category_slug: Some(String::from("ice-hockey")),
+        from: Some(1760000000),
+        to: Some(1760300000),
+        limit: 100,
+        offset: 0,
+    };
+
+    let mut repository = MockGamesArchiveRepository::new();
+    repository
+        .expect_get_archived_games()
+        .with(eq(expected.clone()))
+        .times(1)
+        .returning(|_| Ok(vec![archived_game(1), archived_game(2)]));
+    repository
+        .expect_count_archived_games()
+        .with(eq(expected.clone()))
+        .returning(|_| Ok(7));
+    repository
+        .expect_count_archived_games_by_category()
+        .returning(|_| {
+            Ok(vec![ArchivedCategoryCount {
+                category_slug: String::from("ice-hockey"),
+                category: String::from("Ice Hockey"),
+                games: 7,
+            }])
+        });
+
+    // act
+    let history = service(repository)
+        .get_history(HistoryQuery {
+            category: Some(String::from("Ice Hockey")),
+            from: Some(1760000000),
+            to: Some(1760300000),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(history.total, 7);
+    assert_eq!(history.games.len(), 2);
+    assert_eq!(
+        history.games[0].sources,
+        vec![GameSource::primary("https://example.com/embed")]
+    );
+    assert_eq!(history.categories[0].slug, "ice-hockey");
+    assert_eq!(history.categories[0].games, 7);
+}
+
+#[tokio::test]
+async fn reject_a_range_that_ends_before_it_starts() {
+    // arrange
+    let mut repository = MockGamesArchiveRepository::new();
+    repository.expect_get_archived_games().times(0);
+
+    // act
+    let result = service(repository)
+        .get_history(HistoryQuery {
+            from: Some(1760300000),
+            to: Some(1760000000),
+            ..Default::default()
+        })
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn not_fail_when_the_archive_cant_be_written() {
+    // arrange
+    let mut repository = MockGamesArchiveRepository::new();
+    repository
+        .expect_archive_games()
+        .times(1)
+        .returning(|_, _, _| Err(anyhow::anyhow!("database is gone")));
+
+    // act + assert, nothing to check beyond it not panicking
+    service(repository).archive("ppvsu", &[], 1760223600).await;
+}
+
+#[test]
+fn keep_sources_upstream_stopped_listing() {
+    // arrange
+    let source = |label: &str, priority: i32, link: &str| GameSource {
+        label: String::from(label),
+        priority,
+        link: String::from(link),
+    };
+    let existing = vec![
+        source("Main", 0, "https://a.example/embed"),
+        source("Backup", 1, "https://b.example/embed"),
+    ];
+    let fresh = vec![source("Main", 0, "https://b.example/embed")];
+
+    // act
+    let merged = merge_sources(existing, &fresh);
+
+    // assert
+    assert_eq!(
+        merged,
+        vec![
+            source("Main", 0, "https://b.example/embed"),
+            source("Main", 1, "https://a.example/embed"),
+        ]
+    );
+}
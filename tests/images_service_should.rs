@@ -0,0 +1,64 @@
+use api::server::{
+    error::Error,
+    services::image_services::{ImagesService, ImagesServiceTrait},
+    utils::image_utils::ImageFormat,
+};
+
+fn allowlisted_service() -> ImagesService {
+    ImagesService::new(
+        vec![String::from("ppvs.su")],
+        Some(String::from("https://api.example.com")),
+        None,
+    )
+}
+
+#[test]
+fn point_allowlisted_posters_at_the_proxy() {
+    // arrange
+    let service = allowlisted_service();
+
+    // act
+    let proxied = service.proxied_url("https://ppvs.su/posters/1.png");
+
+    // assert
+    assert_eq!(
+        proxied,
+        "https://api.example.com/api/v1/images?src=https%3A%2F%2Fppvs.su%2Fposters%2F1.png&w=320"
+    );
+}
+
+#[test]
+fn leave_other_posters_alone() {
+    // arrange
+    let service = allowlisted_service();
+
+    // act / assert
+    for src in [
+        "https://evil.example.com/poster.png",
+        "https://notppvs.su/poster.png",
+        "https://api.ppvs.su/api/streams",
+        "https://cdn.ppvs.su/posters/1.png",
+        "ftp://ppvs.su/poster.png",
+        "",
+    ] {
+        assert_eq!(service.proxied_url(src), src);
+    }
+}
+
+#[tokio::test]
+async fn refuse_to_fetch_from_hosts_outside_the_allowlist() {
+    // arrange
+    let service = allowlisted_service();
+
+    // act
+    let result = service
+        .get_image(
+            String::from("http://169.254.169.254/latest/meta-data"),
+            Some(320),
+            ImageFormat::Jpeg,
+        )
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
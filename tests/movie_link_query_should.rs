@@ -0,0 +1,91 @@
+use api::server::{
+    dtos::movie_dto::{GetMovieLinkQuery, NextEpisodeQuery, TvEpisode},
+    error::Error,
+};
+use axum::{http::StatusCode, response::IntoResponse};
+
+fn query(season: Option<u32>, episode: Option<u32>, ep: Option<&str>) -> GetMovieLinkQuery {
+    GetMovieLinkQuery {
+        id: String::from("1396"),
+        season,
+        episode,
+        ep: ep.map(String::from),
+    }
+}
+
+fn invalid_fields(result: Result<Option<TvEpisode>, Error>) -> Vec<String> {
+    match result {
+        Err(Error::UnprocessableEntity { errors }) => {
+            let mut fields: Vec<String> = errors.keys().map(|k| k.to_string()).collect();
+            fields.sort();
+            fields
+        }
+        other => panic!("expected a 422, got {:?}", other),
+    }
+}
+
+#[test]
+fn read_season_and_episode_or_the_older_ep() {
+    // act
+    let typed = query(Some(1), Some(5), None).tv_episode().unwrap();
+    let legacy = query(None, None, Some("1/5")).tv_episode().unwrap();
+    let movie = query(None, None, None).tv_episode().unwrap();
+
+    // assert
+    let episode = TvEpisode {
+        season: 1,
+        episode: 5,
+    };
+    assert_eq!(typed, Some(episode));
+    assert_eq!(legacy, Some(episode));
+    assert_eq!(movie, None);
+    assert_eq!(episode.to_string(), "1/5");
+}
+
+#[test]
+fn reject_episodes_that_could_never_exist() {
+    // act
+    let half = query(Some(1), None, None).tv_episode();
+    let zero = query(Some(1), Some(0), None).tv_episode();
+    let too_far = query(Some(101), Some(1), None).tv_episode();
+    let garbled = query(None, None, Some("1/5/../../movie")).tv_episode();
+    let both = query(Some(1), Some(5), Some("1/5")).tv_episode();
+
+    // assert
+    assert_eq!(invalid_fields(half), vec!["episode"]);
+    assert_eq!(invalid_fields(zero), vec!["episode"]);
+    assert_eq!(invalid_fields(too_far), vec!["season"]);
+    assert_eq!(invalid_fields(garbled), vec!["ep"]);
+    assert_eq!(invalid_fields(both), vec!["ep"]);
+}
+
+#[test]
+fn need_both_halves_to_find_the_next_episode() {
+    // act
+    let result = NextEpisodeQuery {
+        season: None,
+        episode: None,
+    }
+    .tv_episode();
+
+    // assert
+    let response = result.unwrap_err().into_response();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn step_to_the_next_episode_then_the_next_season() {
+    // arrange
+    let current = TvEpisode {
+        season: 2,
+        episode: 13,
+    };
+
+    // act
+    let next = current.next();
+    let next_season = current.first_of_next_season();
+
+    // assert
+    assert_eq!(next.to_string(), "2/14");
+    assert_eq!(next_season.to_string(), "3/1");
+}
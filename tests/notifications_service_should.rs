@@ -0,0 +1,282 @@
+use std::sync::Arc;
+
+use api::{
+    database::{
+        follow::{DynFollowsRepository, Follow, MockFollowsRepository, NotificationTarget},
+        notification::{DynNotificationsRepository, MockNotificationsRepository},
+        stream::{DynStreamsRepository, Game, MockStreamsRepository},
+        team::TeamAlias,
+    },
+    server::services::{
+        notification_services::{
+            DynNotificationDispatcher, GoLiveNotification, MockNotificationDispatcher,
+            NotificationDispatcher, NotificationsService, NotificationsServiceTrait,
+            WebhookDispatcher,
+        },
+        stream_provider::{DynStreamProvider, MockStreamProvider, StreamProviderRegistry},
+        team_services::{DynTeamsService, MockTeamsServiceTrait},
+    },
+    server::utils::{category_utils::slugify, team_utils::TeamAliases},
+};
+use mockall::predicate::*;
+
+const NOW: i64 = 1760223600;
+
+fn starting_game(id: i64, name: &str, category: &str) -> Game {
+    Game {
+        id,
+        name: String::from(name),
+        poster: String::from("https://example.com/poster.png"),
+        start_time: NOW + 120,
+        end_time: NOW + 7320,
+        cache_time: NOW,
+        video_link: String::from("https://example.com/embed"),
+        category: String::from(category),
+        category_slug: slugify(category),
+        sources: vec![],
+        league: None,
+        home_team: None,
+        away_team: None,
+    }
+}
+
+fn follow(kind: &str, value: &str) -> Follow {
+    Follow {
+        kind: String::from(kind),
+        value: String::from(value),
+        ..Default::default()
+    }
+}
+
+fn follows_repository(follows: Vec<Follow>) -> MockFollowsRepository {
+    let mut repository = MockFollowsRepository::new();
+    repository
+        .expect_get_all_follows()
+        .returning(move || Ok(follows.clone()));
+    repository
+        .expect_get_notification_target()
+        .returning(|user_id| {
+            Ok(Some(NotificationTarget {
+                user_id: user_id.to_string(),
+                email: String::from("stub@example.com"),
+                webhook_url: Some(String::from("https://example.com/hook")),
+                email_enabled: false,
+            }))
+        });
+    repository
+}
+
+fn streams_repository(games: Vec<Game>) -> MockStreamsRepository {
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_games_in_window()
+        .returning(move |_, _, _| Ok(games.clone()));
+    repository
+}
+
+fn webhook(times: usize, succeed: bool) -> MockNotificationDispatcher {
+    let mut dispatcher = MockNotificationDispatcher::new();
+    dispatcher.expect_name().return_const("webhook");
+    dispatcher
+        .expect_enabled_for()
+        .returning(|target| target.webhook_url.is_some());
+    dispatcher
+        .expect_dispatch()
+        .times(times)
+        .returning(move |_, _| {
+            if succeed {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("webhook down"))
+            }
+        });
+    dispatcher
+}
+
+fn service(
+    follows: MockFollowsRepository,
+    streams: MockStreamsRepository,
+    notified: MockNotificationsRepository,
+    dispatcher: MockNotificationDispatcher,
+) -> NotificationsService {
+    service_with_aliases(
+        follows,
+        streams,
+        notified,
+        dispatcher,
+        TeamAliases::default(),
+    )
+}
+
+fn service_with_aliases(
+    follows: MockFollowsRepository,
+    streams: MockStreamsRepository,
+    notified: MockNotificationsRepository,
+    dispatcher: MockNotificationDispatcher,
+    aliases: TeamAliases,
+) -> NotificationsService {
+    let aliases = Arc::new(aliases);
+    let mut teams = MockTeamsServiceTrait::new();
+    teams.expect_aliases().returning(move || aliases.clone());
+
+    let mut provider = MockStreamProvider::new();
+    provider.expect_name().return_const("ppvsu");
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(provider) as DynStreamProvider);
+
+    NotificationsService::new(
+        Arc::new(follows) as DynFollowsRepository,
+        Arc::new(streams) as DynStreamsRepository,
+        Arc::new(notified) as DynNotificationsRepository,
+        Arc::new(providers),
+        Arc::new(teams) as DynTeamsService,
+        vec![Arc::new(dispatcher) as DynNotificationDispatcher],
+        300,
+        None,
+    )
+}
+
+#[tokio::test]
+async fn notify_once_per_user_and_game_when_several_follows_match() {
+    // arrange
+    let mut notified = MockNotificationsRepository::new();
+    notified
+        .expect_try_mark_notified()
+        .with(eq("IRFa~VaY2b"), eq("ppvsu"), eq(1), always())
+        .times(1)
+        .returning(|_, _, _, _| Ok(true));
+
+    let service = service(
+        follows_repository(vec![
+            follow("team", "lakers"),
+            follow("category", "basketball"),
+        ]),
+        streams_repository(vec![
+            starting_game(1, "Lakers vs. Celtics", "Basketball"),
+            starting_game(2, "Rangers vs. Devils", "Hockey"),
+        ]),
+        notified,
+        webhook(1, true),
+    );
+
+    // act
+    let sent = service.notify_due_games(NOW).await.unwrap();
+
+    // assert
+    assert_eq!(sent, 1);
+}
+
+#[tokio::test]
+async fn match_team_follows_through_their_aliases() {
+    // arrange
+    let mut notified = MockNotificationsRepository::new();
+    notified
+        .expect_try_mark_notified()
+        .with(always(), eq("ppvsu"), eq(1), always())
+        .times(1)
+        .returning(|_, _, _, _| Ok(true));
+
+    let game = Game {
+        home_team: Some(String::from("Manchester United")),
+        away_team: Some(String::from("Liverpool")),
+        ..starting_game(1, "Manchester United vs Liverpool", "Football")
+    };
+    let aliases = TeamAliases::new(&[TeamAlias {
+        alias: String::from("Man Utd"),
+        team: String::from("Manchester United"),
+    }]);
+
+    let service = service_with_aliases(
+        follows_repository(vec![follow("team", "man utd")]),
+        streams_repository(vec![game]),
+        notified,
+        webhook(1, true),
+        aliases,
+    );
+
+    // act
+    let sent = service.notify_due_games(NOW).await.unwrap();
+
+    // assert
+    assert_eq!(sent, 1);
+}
+
+#[tokio::test]
+async fn skip_games_that_were_already_notified() {
+    // arrange
+    let mut notified = MockNotificationsRepository::new();
+    notified
+        .expect_try_mark_notified()
+        .times(1)
+        .returning(|_, _, _, _| Ok(false));
+
+    let service = service(
+        follows_repository(vec![follow("team", "Lakers")]),
+        streams_repository(vec![starting_game(1, "Lakers vs. Celtics", "Basketball")]),
+        notified,
+        webhook(0, true),
+    );
+
+    // act
+    let sent = service.notify_due_games(NOW).await.unwrap();
+
+    // assert
+    assert_eq!(sent, 0);
+}
+
+#[tokio::test]
+async fn release_the_claim_when_every_dispatch_fails() {
+    // arrange
+    let mut notified = MockNotificationsRepository::new();
+    notified
+        .expect_try_mark_notified()
+        .times(1)
+        .returning(|_, _, _, _| Ok(true));
+    notified
+        .expect_clear_notified()
+        .with(eq("IRFa~VaY2b"), eq("ppvsu"), eq(1))
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let service = service(
+        follows_repository(vec![follow("team", "Lakers")]),
+        streams_repository(vec![starting_game(1, "Lakers vs. Celtics", "Basketball")]),
+        notified,
+        webhook(1, false),
+    );
+
+    // act
+    let sent = service.notify_due_games(NOW).await.unwrap();
+
+    // assert
+    assert_eq!(sent, 0);
+}
+
+#[tokio::test]
+async fn refuse_to_send_webhooks_to_internal_addresses() {
+    // arrange
+    let dispatcher = WebhookDispatcher::new();
+    let notification = GoLiveNotification {
+        provider: String::from("ppvsu"),
+        game: starting_game(1, "Lakers vs Celtics", "Basketball"),
+        matched: String::from("Lakers"),
+        link: None,
+        title: String::from("Lakers vs Celtics is starting"),
+        message: String::from("Basketball"),
+    };
+
+    for url in ["http://127.0.0.1:6379/", "http://169.254.169.254/"] {
+        let target = NotificationTarget {
+            user_id: String::from("IRFa~VaY2b"),
+            email: String::from("stub@example.com"),
+            webhook_url: Some(String::from(url)),
+            email_enabled: false,
+        };
+
+        // act
+        let result = dispatcher.dispatch(&target, &notification).await;
+
+        // assert
+        assert!(result.is_err(), "{}", url);
+    }
+}
@@ -1,15 +1,245 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use api::{
+    database::stream::{DynStreamsRepository, Game, MockStreamsRepository},
+    server::{
+        dtos::{movie_dto::VidLinkResponse, stream_dto::SourceSelection},
+        services::{
+            category_services::{DynCategoriesService, MockCategoriesServiceTrait},
+            event_services::{DynStreamEventsService, MockStreamEventsServiceTrait},
+            extraction_services::{DynExtractionService, ExtractionService},
+            history_services::{DynHistoryService, MockHistoryServiceTrait},
+            image_services::{DynImagesService, MockImagesServiceTrait},
+            ppvsu_services::{PpvsuService, PpvsuServiceTrait},
+            stream_provider::{DynStreamProvider, StreamProviderRegistry},
+            stream_services::{StreamsService, StreamsServiceTrait},
+            team_services::{DynTeamsService, MockTeamsServiceTrait},
+            upstream_services::{BreakerSettings, DynUpstreamsService, UpstreamsService},
+        },
+        utils::{
+            fake_upstream_utils::FakeUpstream,
+            hls_utils::{parse_master_playlist, resolve_playlist_uri},
+        },
+    },
+};
 use flate2::read::GzDecoder;
-use reqwest;
 use serde_json::Value;
 use std::io::Read;
 
+async fn fake_upstream() -> FakeUpstream {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/upstream");
+    FakeUpstream::spawn(fixtures).await.unwrap()
+}
+
+fn ppvsu_service(fake: &FakeUpstream, repository: MockStreamsRepository) -> PpvsuService {
+    PpvsuService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(ExtractionService::new(None, None)) as DynExtractionService,
+        Arc::new(UpstreamsService::new(
+            BreakerSettings::default(),
+            vec![String::from("127.0.0.1")],
+        )) as DynUpstreamsService,
+        &fake.ppvsu_api_url(),
+        &fake.ppvsu_site_url(),
+    )
+}
+
+#[tokio::test]
+async fn fetch_and_parse_the_listing() {
+    // arrange
+    let fake = fake_upstream().await;
+    let service = ppvsu_service(&fake, MockStreamsRepository::new());
+
+    // act
+    let games = service.fetch_games().await.unwrap();
+
+    // assert
+    assert_eq!(games.len(), 2);
+
+    let lakers = games.iter().find(|g| g.id == 1001).unwrap();
+    assert_eq!(lakers.category, "Basketball");
+    assert_eq!(
+        lakers.video_link,
+        format!("{}/embed/1001-dead", fake.base_url())
+    );
+    assert!(lakers.start_time < lakers.end_time);
+    assert_eq!(lakers.sources.len(), 1);
+}
+
+#[tokio::test]
+async fn resolve_a_playable_stream_end_to_end() {
+    // arrange
+    let fake = fake_upstream().await;
+    let listing: Vec<Game> = ppvsu_service(&fake, MockStreamsRepository::new())
+        .fetch_games()
+        .await
+        .unwrap();
+    let cached = listing.into_iter().find(|g| g.id == 1001).unwrap();
+
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_game()
+        .returning(move |_, _| Ok(Some(cached.clone())));
+    repository.expect_store_game().returning(|_, _| Ok(()));
+    repository
+        .expect_get_resolved_link()
+        .returning(|_, _| Ok(None));
+    repository
+        .expect_set_resolved_link()
+        .returning(|_, _, _, _| Ok(()));
+    let repository = Arc::new(repository) as DynStreamsRepository;
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(PpvsuService::new(
+        repository.clone(),
+        Arc::new(ExtractionService::new(None, None)) as DynExtractionService,
+        Arc::new(UpstreamsService::new(
+            BreakerSettings::default(),
+            vec![String::from("127.0.0.1")],
+        )) as DynUpstreamsService,
+        &fake.ppvsu_api_url(),
+        &fake.ppvsu_site_url(),
+    )) as DynStreamProvider);
+
+    let mut images = MockImagesServiceTrait::new();
+    images.expect_proxied_url().returning(|src| src.to_string());
+    let service = StreamsService::new(
+        repository,
+        Arc::new(providers),
+        Arc::new(MockStreamEventsServiceTrait::new()) as DynStreamEventsService,
+        Arc::new(images) as DynImagesService,
+        Arc::new(MockCategoriesServiceTrait::new()) as DynCategoriesService,
+        Arc::new(MockTeamsServiceTrait::new()) as DynTeamsService,
+        Arc::new(MockHistoryServiceTrait::new()) as DynHistoryService,
+    );
+
+    // act
+    // the listing only knows the dead embed, the backup comes from the detail endpoint
+    let resolved = service
+        .resolve_video_link(String::from("ppvsu"), 1001, SourceSelection::Auto)
+        .await
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let playlist = client
+        .get(&resolved.link)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let segment = client
+        .get(resolved.link.replace("index.m3u8", "segment0.ts"))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(resolved.index, 1);
+    assert_eq!(resolved.label, "Backup");
+    assert_eq!(
+        resolved.link,
+        format!("{}/hls/1001/index.m3u8", fake.base_url())
+    );
+    assert!(playlist.starts_with("#EXTM3U"));
+    assert!(playlist.contains("segment0.ts"));
+    assert_eq!(segment[0], 0x47);
+}
+
+#[tokio::test]
+async fn serve_vidlink_responses_for_any_movie() {
+    // arrange
+    let fake = fake_upstream().await;
+
+    // act
+    let response: VidLinkResponse = reqwest::get(format!(
+        "{}/api/b/movie/some-encrypted-id?multilang=0",
+        fake.vidlink_url()
+    ))
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+
+    // assert
+    let stream = response.stream.unwrap();
+    assert_eq!(
+        stream.playlist,
+        format!("{}/hls/movie/index.m3u8", fake.base_url())
+    );
+    assert_eq!(stream.captions[0].language, "English");
+}
+
+#[tokio::test]
+async fn serve_vidlink_responses_for_any_episode() {
+    // arrange
+    let fake = fake_upstream().await;
+
+    // act
+    let episode = reqwest::get(format!(
+        "{}/api/b/tv/some-encrypted-id/1/5?multilang=0",
+        fake.vidlink_url()
+    ))
+    .await
+    .unwrap();
+    let no_episode = reqwest::get(format!(
+        "{}/api/b/tv/some-encrypted-id/1?multilang=0",
+        fake.vidlink_url()
+    ))
+    .await
+    .unwrap();
+
+    // assert
+    let response: VidLinkResponse = episode.json().await.unwrap();
+    assert!(response.stream.is_some());
+    assert_eq!(no_episode.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn serve_movie_qualities_as_a_master_playlist() {
+    // arrange
+    let fake = fake_upstream().await;
+    let master_url = format!("{}/hls/movie/index.m3u8", fake.base_url());
+
+    // act
+    let master = reqwest::get(&master_url)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let variants = parse_master_playlist(&master);
+    let variant = reqwest::get(resolve_playlist_uri(&master_url, &variants[1].uri).unwrap())
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(
+        variants.iter().map(|v| v.height).collect::<Vec<_>>(),
+        vec![Some(720), Some(360)]
+    );
+    assert!(variant.contains("segment0.ts"));
+}
+
+// the real thing, only useful for checking upstream hasn't changed shape:
+// cargo test successfully_fetch_and_parse_ppvsu_api -- --ignored
 #[tokio::test]
+#[ignore]
 async fn successfully_fetch_and_parse_ppvsu_api() {
     let client = reqwest::Client::builder()
         .build()
         .expect("Failed to build HTTP client");
 
-    let response = match client
+    let response = client
         .get("https://api.ppvs.su/api/streams")
         .header(
             "User-Agent",
@@ -26,18 +256,7 @@ async fn successfully_fetch_and_parse_ppvsu_api() {
         .timeout(std::time::Duration::from_secs(10))
         .send()
         .await
-    {
-        Ok(resp) => resp,
-        Err(e) => {
-            eprintln!("Warning: Could not reach ppvs.su API");
-            eprintln!(" Error: {}", e);
-            eprintln!("  Run with: cargo test successfully_fetch_and_parse_ppvsu_api -- --ignored");
-            return;
-        }
-    };
-
-    println!("Response status: {}", response.status());
-    println!("Response headers: {:?}", response.headers());
+        .expect("Could not reach ppvs.su API");
 
     assert!(
         response.status().is_success(),
@@ -50,14 +269,7 @@ async fn successfully_fetch_and_parse_ppvsu_api() {
         .await
         .expect("Failed to read response body");
 
-    println!("Response body length: {} bytes", body_bytes.len());
-    println!(
-        "First two bytes (gzip magic): {:02x} {:02x}",
-        body_bytes[0], body_bytes[1]
-    );
-
     let decoded_text = if body_bytes.len() > 2 && body_bytes[0] == 0x1f && body_bytes[1] == 0x8b {
-        println!("Response is gzip compressed, decompressing...");
         let mut decoder = GzDecoder::new(&body_bytes[..]);
         let mut decompressed = String::new();
         decoder
@@ -68,40 +280,17 @@ async fn successfully_fetch_and_parse_ppvsu_api() {
         String::from_utf8(body_bytes.to_vec()).expect("Failed to convert to UTF-8")
     };
 
-    println!(
-        "Decoded response (first 200 chars): {}",
-        &decoded_text.chars().take(200).collect::<String>()
-    );
-
     let json: Value = serde_json::from_str(&decoded_text).expect("Failed to parse JSON response");
 
-    println!(
-        "Response body (JSON): {}",
-        serde_json::to_string_pretty(&json).unwrap()
-    );
-
-    assert!(
-        json.get("success").is_some(),
-        "Response missing 'success' field"
-    );
-
     assert_eq!(
         json["success"].as_bool(),
         Some(true),
         "Expected success to be true"
     );
 
-    assert!(
-        json.get("streams").is_some(),
-        "Response missing 'streams' field"
-    );
-
     let streams = json["streams"]
         .as_array()
         .expect("streams should be an array");
 
     assert!(!streams.is_empty(), "Expected at least one stream category");
-
-    println!("Successfully fetched {} stream categories", streams.len());
-    println!("API response structure validated");
 }
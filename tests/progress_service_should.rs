@@ -0,0 +1,242 @@
+use std::sync::Arc;
+
+use api::{
+    database::progress::{DynWatchProgressRepository, MockWatchProgressRepository, WatchProgress},
+    server::{
+        dtos::progress_dto::{MarkWatchedDto, UpdateProgressDto},
+        error::Error,
+        services::progress_services::{WatchProgressService, WatchProgressServiceTrait},
+    },
+};
+use mockall::predicate::*;
+use sqlx::types::time::OffsetDateTime;
+
+fn stub_progress(
+    media_id: &str,
+    episode: &str,
+    position_secs: f64,
+    watched: bool,
+) -> WatchProgress {
+    WatchProgress {
+        user_id: String::from("user"),
+        media_id: String::from(media_id),
+        episode: String::from(episode),
+        position_secs,
+        duration_secs: Some(3600.0),
+        watched,
+        updated_at: OffsetDateTime::UNIX_EPOCH,
+    }
+}
+
+fn progress_at(position: f64) -> UpdateProgressDto {
+    UpdateProgressDto {
+        id: String::from("1311031"),
+        ep: None,
+        position,
+        duration: Some(3600.0),
+    }
+}
+
+fn service(repository: MockWatchProgressRepository) -> WatchProgressService {
+    WatchProgressService::new(Arc::new(repository) as DynWatchProgressRepository)
+}
+
+#[tokio::test]
+async fn skip_writes_that_come_too_soon_after_the_last_one() {
+    // arrange
+    let mut repository = MockWatchProgressRepository::new();
+    repository
+        .expect_upsert_progress()
+        .times(1)
+        .returning(|progress| Ok(progress.clone()));
+
+    let service = service(repository);
+
+    // act
+    let first = service
+        .update_progress(String::from("user"), progress_at(100.0))
+        .await
+        .unwrap();
+    let second = service
+        .update_progress(String::from("user"), progress_at(105.0))
+        .await
+        .unwrap();
+
+    // assert
+    assert!(first.saved);
+    assert!(!second.saved);
+}
+
+#[tokio::test]
+async fn retry_straight_away_when_a_write_fails() {
+    // arrange
+    let mut repository = MockWatchProgressRepository::new();
+    repository
+        .expect_upsert_progress()
+        .times(1)
+        .returning(|_| Err(anyhow::anyhow!("database is down")));
+    repository
+        .expect_upsert_progress()
+        .times(1)
+        .returning(|progress| Ok(progress.clone()));
+
+    let service = service(repository);
+
+    // act
+    let first = service
+        .update_progress(String::from("user"), progress_at(100.0))
+        .await;
+    let second = service
+        .update_progress(String::from("user"), progress_at(105.0))
+        .await
+        .unwrap();
+
+    // assert
+    assert!(first.is_err());
+    assert!(second.saved);
+}
+
+#[tokio::test]
+async fn store_episodes_the_way_the_link_endpoints_read_them() {
+    // arrange
+    let mut repository = MockWatchProgressRepository::new();
+    repository
+        .expect_upsert_progress()
+        .withf(|progress| progress.episode == "1/5")
+        .times(1)
+        .returning(|progress| Ok(progress.clone()));
+
+    let service = service(repository);
+
+    // act
+    let saved = service
+        .update_progress(
+            String::from("user"),
+            UpdateProgressDto {
+                id: String::from("1396"),
+                ep: Some(String::from(" 1/05")),
+                ..progress_at(100.0)
+            },
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert!(saved.saved);
+}
+
+#[tokio::test]
+async fn reject_an_episode_that_is_not_one() {
+    // arrange
+    let mut repository = MockWatchProgressRepository::new();
+    repository.expect_upsert_progress().times(0);
+    repository.expect_set_watched().times(0);
+
+    let service = service(repository);
+
+    // act
+    let progress = service
+        .update_progress(
+            String::from("user"),
+            UpdateProgressDto {
+                ep: Some(String::from("season one")),
+                ..progress_at(100.0)
+            },
+        )
+        .await;
+    let watched = service
+        .mark_watched(
+            String::from("user"),
+            MarkWatchedDto {
+                id: String::from("1396"),
+                ep: Some(String::from("1/0")),
+                watched: true,
+            },
+        )
+        .await;
+
+    // assert
+    assert!(matches!(progress, Err(Error::UnprocessableEntity { .. })));
+    assert!(matches!(watched, Err(Error::UnprocessableEntity { .. })));
+}
+
+#[tokio::test]
+async fn always_write_and_mark_as_watched_near_the_end() {
+    // arrange
+    let mut repository = MockWatchProgressRepository::new();
+    repository
+        .expect_upsert_progress()
+        .withf(|progress| progress.watched == (progress.position_secs >= 3240.0))
+        .times(2)
+        .returning(|progress| Ok(progress.clone()));
+
+    let service = service(repository);
+
+    // act
+    service
+        .update_progress(String::from("user"), progress_at(3000.0))
+        .await
+        .unwrap();
+    let finished = service
+        .update_progress(String::from("user"), progress_at(3500.0))
+        .await
+        .unwrap();
+
+    // assert
+    assert!(finished.saved);
+    assert!(finished.watched);
+}
+
+#[tokio::test]
+async fn only_list_the_latest_episode_of_each_show() {
+    // arrange
+    let mut repository = MockWatchProgressRepository::new();
+    repository
+        .expect_get_unfinished()
+        .with(eq("user"), always())
+        .returning(|_, _| {
+            Ok(vec![
+                stub_progress("1396", "1/5", 600.0, false),
+                stub_progress("1311031", "", 1200.0, false),
+                stub_progress("1396", "1/4", 300.0, false),
+            ])
+        });
+
+    // act
+    let items = service(repository)
+        .continue_watching(String::from("user"))
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0].id, "1396");
+    assert_eq!(items[0].kind, "tv");
+    assert_eq!(items[0].ep.as_deref(), Some("1/5"));
+    assert_eq!(items[1].kind, "movie");
+    assert_eq!(items[1].ep, None);
+}
+
+#[tokio::test]
+async fn not_resume_something_already_watched() {
+    // arrange
+    let mut repository = MockWatchProgressRepository::new();
+    repository
+        .expect_get_progress()
+        .with(eq("user"), eq("1311031"), eq(""))
+        .returning(|_, _, _| Ok(Some(stub_progress("1311031", "", 3500.0, true))));
+    repository
+        .expect_get_progress()
+        .with(eq("user"), eq("1396"), eq("1/5"))
+        .returning(|_, _, _| Ok(Some(stub_progress("1396", "1/5", 600.0, false))));
+
+    let service = service(repository);
+
+    // act
+    let movie = service.resume_at("user", "1311031", "").await.unwrap();
+    let episode = service.resume_at("user", "1396", "1/5").await.unwrap();
+
+    // assert
+    assert_eq!(movie, None);
+    assert_eq!(episode, Some(600.0));
+}
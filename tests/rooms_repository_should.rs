@@ -0,0 +1,63 @@
+use api::database::{
+    RedisDatabase,
+    room::{Room, RoomsRepository},
+};
+
+// these need a real redis (see Running Tests in the README) and do nothing without REDIS_URL
+async fn redis() -> Option<RedisDatabase> {
+    let url = std::env::var("REDIS_URL").ok()?;
+    Some(RedisDatabase::connect(&url).await.unwrap())
+}
+
+fn room(position: f64) -> Room {
+    Room {
+        // every test gets its own room so they can run side by side
+        id: format!("test-{}", nanoid::nanoid!(8)),
+        host: String::from("host"),
+        media_url: String::from("https://example.com/embed"),
+        playing: false,
+        position,
+        updated_at: 0,
+        created_at: 0,
+    }
+}
+
+#[tokio::test]
+async fn only_replace_a_room_nobody_changed_in_between() {
+    // arrange
+    let Some(redis) = redis().await else { return };
+    let original = room(0.0);
+    redis.save_room(&original).await.unwrap();
+    let seeked = Room {
+        position: 30.0,
+        ..original.clone()
+    };
+    let played = Room {
+        playing: true,
+        ..original.clone()
+    };
+
+    // act
+    let first = redis.replace_room(&original, &seeked).await.unwrap();
+    let second = redis.replace_room(&original, &played).await.unwrap();
+
+    // assert
+    assert!(first);
+    assert!(!second);
+    let stored = redis.get_room(&original.id).await.unwrap();
+    assert_eq!(stored, Some(seeked));
+}
+
+#[tokio::test]
+async fn not_bring_back_a_room_that_expired() {
+    // arrange
+    let Some(redis) = redis().await else { return };
+    let gone = room(0.0);
+
+    // act
+    let replaced = redis.replace_room(&gone, &gone).await.unwrap();
+
+    // assert
+    assert!(!replaced);
+    assert_eq!(redis.get_room(&gone.id).await.unwrap(), None);
+}
@@ -0,0 +1,321 @@
+use std::sync::Arc;
+
+use api::{
+    database::room::{DynRoomsRepository, MockRoomsRepository, Room, RoomBroadcast, RoomEvent},
+    server::{
+        dtos::room_dto::{RoomCommand, RoomFrame, RoomSocketMessage},
+        error::Error,
+        services::room_services::{RoomsService, RoomsServiceTrait},
+    },
+};
+use futures::StreamExt;
+use futures::stream;
+use mockall::predicate::*;
+
+fn stub_room(playing: bool, position: f64, updated_at: i64) -> Room {
+    Room {
+        id: String::from("room1"),
+        host: String::from("host"),
+        media_url: String::from("https://example.com/embed"),
+        playing,
+        position,
+        updated_at,
+        created_at: 0,
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn service(repository: MockRoomsRepository) -> RoomsService {
+    RoomsService::new(Arc::new(repository) as DynRoomsRepository)
+}
+
+fn repository_with(room: Room) -> MockRoomsRepository {
+    let mut repository = MockRoomsRepository::new();
+    repository
+        .expect_get_room()
+        .with(eq("room1"))
+        .returning(move |_| Ok(Some(room.clone())));
+    repository
+}
+
+#[tokio::test]
+async fn only_let_the_host_control_playback() {
+    // arrange
+    let mut repository = repository_with(stub_room(false, 0.0, 0));
+    repository.expect_replace_room().times(0);
+    repository.expect_publish_room_event().times(0);
+
+    // act
+    let result = service(repository)
+        .handle_command("room1", "guest", RoomCommand::Play { position: None })
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::Forbidden)));
+}
+
+#[tokio::test]
+async fn play_from_where_the_host_is_and_broadcast_it() {
+    // arrange
+    let mut repository = repository_with(stub_room(false, 0.0, 0));
+    repository
+        .expect_replace_room()
+        .withf(|_, room| room.playing && room.position == 42.0 && room.updated_at > 0)
+        .times(1)
+        .returning(|_, _| Ok(true));
+    repository
+        .expect_publish_room_event()
+        .withf(|broadcast| {
+            broadcast.room_id == "room1"
+                && matches!(&broadcast.event, RoomEvent::State { room } if room.playing)
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    // act
+    let reply = service(repository)
+        .handle_command(
+            "room1",
+            "host",
+            RoomCommand::Play {
+                position: Some(42.0),
+            },
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(reply, None);
+}
+
+#[tokio::test]
+async fn pause_where_playback_got_to_when_no_position_is_given() {
+    // arrange
+    let mut repository = repository_with(stub_room(true, 10.0, now() - 5000));
+    repository
+        .expect_replace_room()
+        .withf(|_, room| !room.playing && room.position >= 15.0 && room.position < 16.0)
+        .times(1)
+        .returning(|_, _| Ok(true));
+    repository
+        .expect_publish_room_event()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    // act
+    let result = service(repository)
+        .handle_command("room1", "host", RoomCommand::Pause { position: None })
+        .await;
+
+    // assert
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn retry_against_the_fresh_room_when_another_command_got_there_first() {
+    // arrange
+    let mut repository = MockRoomsRepository::new();
+    let mut reads = 0;
+    repository.expect_get_room().times(2).returning(move |_| {
+        reads += 1;
+        // the second read sees the seek that beat us to it
+        let position = if reads == 1 { 0.0 } else { 30.0 };
+        Ok(Some(stub_room(false, position, 0)))
+    });
+    repository
+        .expect_replace_room()
+        .withf(|previous, _| previous.position == 0.0)
+        .times(1)
+        .returning(|_, _| Ok(false));
+    repository
+        .expect_replace_room()
+        .withf(|previous, room| previous.position == 30.0 && room.position == 30.0 && room.playing)
+        .times(1)
+        .returning(|_, _| Ok(true));
+    repository
+        .expect_publish_room_event()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    // act
+    let result = service(repository)
+        .handle_command("room1", "host", RoomCommand::Play { position: None })
+        .await;
+
+    // assert
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn reject_seeking_to_a_negative_position() {
+    // arrange
+    let mut repository = repository_with(stub_room(true, 10.0, 0));
+    repository.expect_replace_room().times(0);
+
+    // act
+    let result = service(repository)
+        .handle_command("room1", "host", RoomCommand::Seek { position: -1.0 })
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn let_anyone_chat_and_keep_the_message() {
+    // arrange
+    let mut repository = repository_with(stub_room(false, 0.0, 0));
+    repository
+        .expect_add_chat()
+        .withf(|room_id, message| {
+            room_id == "room1" && message.user_id == "guest" && message.message == "go lakers"
+        })
+        .times(1)
+        .returning(|_, _| Ok(()));
+    repository
+        .expect_publish_room_event()
+        .withf(|broadcast| matches!(&broadcast.event, RoomEvent::Chat(chat) if chat.message == "go lakers"))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    // act
+    let result = service(repository)
+        .handle_command(
+            "room1",
+            "guest",
+            RoomCommand::Chat {
+                message: String::from("  go lakers "),
+            },
+        )
+        .await;
+
+    // assert
+    assert!(result.is_ok());
+}
+
+#[tokio::test]
+async fn answer_pings_to_the_sender_only() {
+    // arrange
+    let mut repository = MockRoomsRepository::new();
+    repository.expect_publish_room_event().times(0);
+
+    // act
+    let reply = service(repository)
+        .handle_command("room1", "guest", RoomCommand::Ping { client_time: 123 })
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(reply, Some(RoomSocketMessage::Pong { client_time: 123 }));
+}
+
+#[tokio::test]
+async fn only_hand_sockets_events_for_their_room() {
+    // arrange
+    let mut repository = MockRoomsRepository::new();
+    repository
+        .expect_subscribe_room_events()
+        .times(1)
+        .returning(|| {
+            Ok(stream::iter(vec![
+                RoomBroadcast {
+                    room_id: String::from("other"),
+                    event: RoomEvent::Joined {
+                        user_id: String::from("someone"),
+                    },
+                },
+                RoomBroadcast {
+                    room_id: String::from("room1"),
+                    event: RoomEvent::Joined {
+                        user_id: String::from("guest"),
+                    },
+                },
+            ])
+            .chain(stream::pending())
+            .boxed())
+        });
+
+    let service = Arc::new(service(repository));
+    let mut events = service.subscribe("room1");
+
+    // act
+    let listener = service.clone();
+    tokio::spawn(async move { listener.listen().await });
+    let event = events.next().await;
+
+    // assert
+    assert_eq!(
+        event,
+        Some(RoomEvent::Joined {
+            user_id: String::from("guest")
+        })
+    );
+}
+
+#[tokio::test]
+async fn keep_sockets_connected_when_another_room_is_busy() {
+    // arrange
+    let busy = (0..1000).map(|i| RoomBroadcast {
+        room_id: String::from("busy"),
+        event: RoomEvent::Joined {
+            user_id: format!("spammer{}", i),
+        },
+    });
+    let quiet = RoomBroadcast {
+        room_id: String::from("room1"),
+        event: RoomEvent::Joined {
+            user_id: String::from("guest"),
+        },
+    };
+    let mut repository = MockRoomsRepository::new();
+    repository
+        .expect_subscribe_room_events()
+        .times(1)
+        .returning(move || {
+            Ok(
+                stream::iter(busy.clone().chain(std::iter::once(quiet.clone())))
+                    .chain(stream::pending())
+                    .boxed(),
+            )
+        });
+
+    let service = Arc::new(service(repository));
+    // nobody reads the busy room so it falls far behind
+    let _busy_events = service.subscribe("busy");
+    let mut events = service.subscribe("room1");
+
+    // act
+    let listener = service.clone();
+    tokio::spawn(async move { listener.listen().await });
+    let event = events.next().await;
+
+    // assert
+    assert_eq!(
+        event,
+        Some(RoomEvent::Joined {
+            user_id: String::from("guest")
+        })
+    );
+}
+
+#[test]
+fn send_events_flat_with_the_server_time() {
+    // arrange
+    let frame = RoomFrame {
+        message: RoomSocketMessage::Event(RoomEvent::State {
+            room: stub_room(true, 1.5, 1000),
+        }),
+        server_time: 2000,
+    };
+
+    // act
+    let json = serde_json::to_value(&frame).unwrap();
+
+    // assert
+    assert_eq!(json["type"], "state");
+    assert_eq!(json["room"]["position"], 1.5);
+    assert_eq!(json["server_time"], 2000);
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use api::{
+    database::event::{
+        DynStreamEventsRepository, MockStreamEventsRepository, StreamEvent, StreamEventKind,
+    },
+    server::services::event_services::{StreamEventsService, StreamEventsServiceTrait},
+};
+use futures::StreamExt;
+use mockall::predicate::*;
+use serde_json::json;
+
+fn stub_event(id: u64) -> StreamEvent {
+    StreamEvent {
+        id,
+        kind: StreamEventKind::CacheRefreshed,
+        data: json!({ "provider": "stub", "games": id }),
+    }
+}
+
+#[tokio::test]
+async fn replay_buffered_events_after_the_last_seen_id() {
+    // arrange
+    let mut repository = MockStreamEventsRepository::new();
+    repository
+        .expect_get_events_after()
+        .with(eq(3))
+        .times(1)
+        .returning(|_| Ok(vec![stub_event(4), stub_event(5)]));
+
+    let service = StreamEventsService::new(Arc::new(repository) as DynStreamEventsRepository);
+
+    // act
+    let events: Vec<u64> = service
+        .subscribe(Some(3))
+        .await
+        .unwrap()
+        .take(2)
+        .map(|e| e.id)
+        .collect()
+        .await;
+
+    // assert
+    assert_eq!(events, vec![4, 5]);
+}
+
+#[tokio::test]
+async fn forward_published_events_without_repeating_the_replay() {
+    // arrange
+    let mut repository = MockStreamEventsRepository::new();
+    repository
+        .expect_get_events_after()
+        .returning(|_| Ok(vec![stub_event(4), stub_event(5)]));
+    // 5 was already replayed so only 6 should come through live
+    repository.expect_subscribe_events().returning(|| {
+        Ok(futures::stream::iter(vec![stub_event(5), stub_event(6)])
+            .chain(futures::stream::pending())
+            .boxed())
+    });
+
+    let service = Arc::new(StreamEventsService::new(
+        Arc::new(repository) as DynStreamEventsRepository
+    ));
+    let subscription = service.subscribe(Some(3)).await.unwrap();
+
+    let listener = service.clone();
+    tokio::spawn(async move { listener.listen().await });
+
+    // act
+    let events: Vec<u64> = tokio::time::timeout(
+        Duration::from_secs(5),
+        subscription.take(3).map(|e| e.id).collect::<Vec<u64>>(),
+    )
+    .await
+    .unwrap();
+
+    // assert
+    assert_eq!(events, vec![4, 5, 6]);
+}
+
+#[tokio::test]
+async fn skip_the_replay_for_a_fresh_subscriber() {
+    // arrange
+    let mut repository = MockStreamEventsRepository::new();
+    repository.expect_get_events_after().times(0);
+
+    let service = StreamEventsService::new(Arc::new(repository) as DynStreamEventsRepository);
+
+    // act
+    let result = service.subscribe(None).await;
+
+    // assert
+    assert!(result.is_ok());
+}
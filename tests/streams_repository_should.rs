@@ -0,0 +1,174 @@
+use api::database::{
+    RedisDatabase,
+    stream::{Game, StreamsRepository},
+};
+use chrono::Utc;
+use redis::AsyncCommands;
+
+// these need a real redis (see Running Tests in the README) and do nothing without REDIS_URL
+async fn redis() -> Option<RedisDatabase> {
+    let url = std::env::var("REDIS_URL").ok()?;
+    Some(RedisDatabase::connect(&url).await.unwrap())
+}
+
+// every test gets its own provider so they can run side by side
+fn provider() -> String {
+    format!("test-{}", nanoid::nanoid!(8))
+}
+
+fn game(id: i64, category_slug: &str) -> Game {
+    let now = Utc::now().timestamp();
+
+    Game {
+        id,
+        name: format!("Game {}", id),
+        poster: String::new(),
+        start_time: now + id,
+        end_time: now + 3600,
+        cache_time: now,
+        video_link: "https://embed.example/1".to_string(),
+        category: category_slug.to_string(),
+        category_slug: category_slug.to_string(),
+        sources: vec![],
+        league: None,
+        home_team: None,
+        away_team: None,
+    }
+}
+
+#[tokio::test]
+async fn drop_games_missing_from_a_refresh_out_of_the_category_index() {
+    // arrange
+    let Some(redis) = redis().await else { return };
+    let provider = provider();
+    redis
+        .replace_games(&provider, &[game(1, "soccer"), game(2, "basketball")], 0)
+        .await
+        .unwrap();
+
+    // act
+    redis
+        .replace_games(&provider, &[game(1, "soccer")], 0)
+        .await
+        .unwrap();
+
+    // assert
+    let basketball = redis
+        .get_games_by_category(&provider, "basketball")
+        .await
+        .unwrap();
+    assert!(basketball.is_empty());
+    let mut conn = redis.connection.clone();
+    let exists: bool = conn
+        .exists(format!("stream:{}:category:basketball", provider))
+        .await
+        .unwrap();
+    assert!(!exists);
+    let soccer = redis
+        .get_games_by_category(&provider, "soccer")
+        .await
+        .unwrap();
+    assert_eq!(soccer.len(), 1);
+
+    redis.clear_cache(&provider).await.unwrap();
+}
+
+#[tokio::test]
+async fn move_a_stored_game_to_its_new_category() {
+    // arrange
+    let Some(redis) = redis().await else { return };
+    let provider = provider();
+    redis
+        .store_game(&provider, &game(1, "soccer"))
+        .await
+        .unwrap();
+
+    // act
+    redis
+        .store_game(&provider, &game(1, "football"))
+        .await
+        .unwrap();
+
+    // assert
+    let soccer = redis
+        .get_games_by_category(&provider, "soccer")
+        .await
+        .unwrap();
+    assert!(soccer.is_empty());
+    let football = redis
+        .get_games_by_category(&provider, "football")
+        .await
+        .unwrap();
+    assert_eq!(football.len(), 1);
+
+    redis.clear_cache(&provider).await.unwrap();
+}
+
+#[tokio::test]
+async fn expire_indexes_with_their_last_game() {
+    // arrange
+    let Some(redis) = redis().await else { return };
+    let provider = provider();
+    let short = game(1, "soccer");
+    let mut long = game(2, "soccer");
+    long.end_time += 3600;
+
+    // act
+    redis
+        .replace_games(&provider, &[long.clone(), short], 0)
+        .await
+        .unwrap();
+
+    // assert
+    let mut conn = redis.connection.clone();
+    let game_ttl: i64 = conn
+        .ttl(format!("stream:{}:game:2", provider))
+        .await
+        .unwrap();
+    for index in ["category:soccer", "categories", "by_start"] {
+        let ttl: i64 = conn
+            .ttl(format!("stream:{}:{}", provider, index))
+            .await
+            .unwrap();
+        assert!((game_ttl - 1..=game_ttl + 1).contains(&ttl), "{}", index);
+    }
+
+    redis.clear_cache(&provider).await.unwrap();
+}
+
+#[tokio::test]
+async fn list_games_in_start_order() {
+    // arrange
+    let Some(redis) = redis().await else { return };
+    let provider = provider();
+
+    // act
+    redis
+        .replace_games(
+            &provider,
+            &[game(3, "soccer"), game(1, "soccer"), game(2, "tennis")],
+            0,
+        )
+        .await
+        .unwrap();
+
+    // assert
+    let ids: Vec<i64> = redis
+        .get_games(&provider)
+        .await
+        .unwrap()
+        .iter()
+        .map(|g| g.id)
+        .collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+    let soccer: Vec<i64> = redis
+        .get_games_by_category(&provider, "soccer")
+        .await
+        .unwrap()
+        .iter()
+        .map(|g| g.id)
+        .collect();
+    assert_eq!(soccer, vec![1, 3]);
+
+    redis.clear_cache(&provider).await.unwrap();
+}
@@ -0,0 +1,1550 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use api::{
+    database::{
+        category::{Category, CategoryAlias},
+        event::StreamEventKind,
+        stream::{DynStreamsRepository, Game, GameSource, MockStreamsRepository, ResolvedLink},
+        team::TeamAlias,
+    },
+    server::{
+        dtos::stream_dto::{GameListQuery, GameSort, GameStatus, SourceSelection},
+        error::{Error, UpstreamUnavailable},
+        services::{
+            category_services::{DynCategoriesService, MockCategoriesServiceTrait},
+            event_services::{DynStreamEventsService, MockStreamEventsServiceTrait},
+            history_services::{DynHistoryService, MockHistoryServiceTrait},
+            image_services::{DynImagesService, MockImagesServiceTrait},
+            stream_provider::{DynStreamProvider, MockStreamProvider, StreamProviderRegistry},
+            stream_services::{StreamsService, StreamsServiceTrait, listing_clock},
+            team_services::{DynTeamsService, MockTeamsServiceTrait},
+        },
+        utils::{
+            category_utils::{CategoryTaxonomy, slugify},
+            iptv_utils::IptvFeed,
+            team_utils::TeamAliases,
+        },
+    },
+};
+use mockall::predicate::*;
+
+fn stub_game(id: i64, category: &str) -> Game {
+    Game {
+        id,
+        name: format!("stub game {}", id),
+        poster: String::from("https://example.com/poster.png"),
+        start_time: 1760223600,
+        end_time: 1760236200,
+        cache_time: 1760234070,
+        video_link: String::from("https://example.com/embed"),
+        category: String::from(category),
+        category_slug: slugify(category),
+        sources: vec![],
+        league: None,
+        home_team: None,
+        away_team: None,
+    }
+}
+
+fn stub_provider(name: &'static str) -> MockStreamProvider {
+    let mut provider = MockStreamProvider::new();
+    provider.expect_name().return_const(name);
+    provider
+}
+
+// game starting `starts_in` seconds from now and running for two hours
+fn game_starting_in(id: i64, name: &str, starts_in: i64) -> Game {
+    let start_time = chrono::Utc::now().timestamp() + starts_in;
+    Game {
+        name: String::from(name),
+        start_time,
+        end_time: start_time + 2 * 60 * 60,
+        ..stub_game(id, "Football")
+    }
+}
+
+fn game_with_sources(id: i64, links: &[&str]) -> Game {
+    Game {
+        video_link: links[0].to_string(),
+        sources: links
+            .iter()
+            .enumerate()
+            .map(|(i, link)| GameSource {
+                label: format!("Source {}", i + 1),
+                priority: i as i32,
+                link: link.to_string(),
+            })
+            .collect(),
+        ..stub_game(id, "Football")
+    }
+}
+
+// repository with nothing in the resolved link cache that takes whatever gets cached
+fn uncached_links_repository() -> MockStreamsRepository {
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_resolved_link()
+        .returning(|_, _| Ok(None));
+    repository
+        .expect_set_resolved_link()
+        .returning(|_, _, _, _| Ok(()));
+    repository
+}
+
+// service around a single provider
+fn provider_service(provider: MockStreamProvider) -> StreamsService {
+    provider_service_with(provider, uncached_links_repository())
+}
+
+fn provider_service_with(
+    provider: MockStreamProvider,
+    repository: MockStreamsRepository,
+) -> StreamsService {
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(provider) as DynStreamProvider);
+
+    StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    )
+}
+
+// service with a single "cached" provider that is only ever read from
+fn cached_games_service(repository: MockStreamsRepository) -> StreamsService {
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(stub_provider("cached")) as DynStreamProvider);
+
+    StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    )
+}
+
+// events service that accepts whatever gets published
+fn quiet_events() -> DynStreamEventsService {
+    let mut events = MockStreamEventsServiceTrait::new();
+    events.expect_publish().returning(|_| Ok(()));
+    Arc::new(events) as DynStreamEventsService
+}
+
+// no aliases, teams keep whatever name the game gave them
+fn no_team_aliases() -> DynTeamsService {
+    teams_with(TeamAliases::default())
+}
+
+fn teams_with(aliases: TeamAliases) -> DynTeamsService {
+    let aliases = Arc::new(aliases);
+    let mut teams = MockTeamsServiceTrait::new();
+    teams.expect_aliases().returning(move || aliases.clone());
+    Arc::new(teams) as DynTeamsService
+}
+
+// archive that takes whatever it's given
+fn quiet_history() -> DynHistoryService {
+    let mut history = MockHistoryServiceTrait::new();
+    history.expect_archive().returning(|_, _, _| ());
+    Arc::new(history) as DynHistoryService
+}
+
+// taxonomy that doesn't know any categories, so games keep whatever they came in with
+fn default_categories() -> DynCategoriesService {
+    categories_with(CategoryTaxonomy::default())
+}
+
+fn categories_with(taxonomy: CategoryTaxonomy) -> DynCategoriesService {
+    let taxonomy = Arc::new(taxonomy);
+    let mut categories = MockCategoriesServiceTrait::new();
+    categories
+        .expect_taxonomy()
+        .returning(move || taxonomy.clone());
+    categories.expect_record_unmapped().returning(|_, _| ());
+    Arc::new(categories) as DynCategoriesService
+}
+
+fn stub_category(slug: &str, name: &str, sort_order: i32) -> Category {
+    Category {
+        slug: String::from(slug),
+        name: String::from(name),
+        icon: Some(String::from("*")),
+        sort_order,
+    }
+}
+
+fn stub_alias(provider: &str, raw_name: &str, slug: &str) -> CategoryAlias {
+    CategoryAlias {
+        provider: String::from(provider),
+        raw_name: String::from(raw_name),
+        category_slug: Some(String::from(slug)),
+        first_seen_at: sqlx::types::time::OffsetDateTime::UNIX_EPOCH,
+    }
+}
+
+// image proxy that leaves every poster alone
+fn passthrough_images() -> DynImagesService {
+    let mut images = MockImagesServiceTrait::new();
+    images.expect_proxied_url().returning(|src| src.to_string());
+    Arc::new(images) as DynImagesService
+}
+
+// repository where this instance always wins the refresh lock and nothing was cached before
+fn locked_repository() -> MockStreamsRepository {
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| Ok(vec![]));
+    repository
+        .expect_get_last_fetch_time()
+        .returning(|_| Ok(None));
+    repository
+        .expect_try_acquire_refresh_lock()
+        .returning(|_, _, _| Ok(true));
+    repository
+        .expect_release_refresh_lock()
+        .returning(|_, _| Ok(()));
+    repository
+}
+
+#[tokio::test]
+async fn merge_games_from_every_registered_provider() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_games()
+        .with(eq("first"))
+        .times(1)
+        .returning(|_| Ok(vec![stub_game(1, "Football"), stub_game(2, "Hockey")]));
+    repository
+        .expect_get_games()
+        .with(eq("second"))
+        .times(1)
+        .returning(|_| Ok(vec![stub_game(3, "Football")]));
+
+    let mut first = stub_provider("first");
+    first.expect_fetch_games().times(0);
+    let mut second = stub_provider("second");
+    second.expect_fetch_games().times(0);
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(first) as DynStreamProvider);
+    providers.register(Arc::new(second) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let categories = service
+        .get_all_games(GameListQuery::default())
+        .await
+        .unwrap()
+        .categories;
+
+    // assert
+    assert_eq!(categories.len(), 2);
+    assert_eq!(categories[0].category, "Football");
+    assert_eq!(categories[0].games.len(), 2);
+    assert_eq!(categories[1].category, "Hockey");
+}
+
+#[tokio::test]
+async fn swap_in_fetched_games_on_refresh() {
+    // arrange
+    let mut repository = locked_repository();
+    repository
+        .expect_replace_games()
+        .withf(|provider, games, _| provider == "healthy" && games.len() == 2)
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut healthy = stub_provider("healthy");
+    healthy
+        .expect_fetch_games()
+        .times(1)
+        .returning(|| Ok(vec![stub_game(1, "Football"), stub_game(2, "Hockey")]));
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(healthy) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("healthy")).await;
+
+    // assert
+    assert_eq!(result.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn publish_what_changed_on_refresh() {
+    // arrange
+    let now = chrono::Utc::now().timestamp();
+
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_try_acquire_refresh_lock()
+        .returning(|_, _, _| Ok(true));
+    repository
+        .expect_release_refresh_lock()
+        .returning(|_, _| Ok(()));
+    repository
+        .expect_get_games()
+        .with(eq("changing"))
+        .times(1)
+        .returning(|_| Ok(vec![stub_game(1, "Football"), stub_game(2, "Hockey")]));
+    repository
+        .expect_get_last_fetch_time()
+        .returning(move |_| Ok(Some(now - 120)));
+    repository
+        .expect_replace_games()
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut changing = stub_provider("changing");
+    changing.expect_fetch_games().times(1).returning(move || {
+        Ok(vec![
+            stub_game(2, "Hockey"),
+            game_starting_in(3, "Kicked Off", -60),
+        ])
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(changing) as DynStreamProvider);
+
+    let mut events = MockStreamEventsServiceTrait::new();
+    events
+        .expect_publish()
+        .withf(|events| {
+            let kinds: Vec<StreamEventKind> = events.iter().map(|(kind, _)| *kind).collect();
+            kinds
+                == vec![
+                    StreamEventKind::GameAdded,
+                    StreamEventKind::GameRemoved,
+                    StreamEventKind::GameLive,
+                    StreamEventKind::CacheRefreshed,
+                ]
+                && events[0].1["game"]["id"] == 3
+                && events[1].1["id"] == 1
+                && events[2].1["game"]["id"] == 3
+                && events[3].1["games"] == 2
+        })
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        Arc::new(events) as DynStreamEventsService,
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("changing")).await;
+
+    // assert
+    assert_eq!(result.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn leave_cached_games_in_place_when_upstream_fails() {
+    // arrange
+    let mut repository = locked_repository();
+    repository.expect_replace_games().times(0);
+    repository.expect_clear_cache().times(0);
+
+    let mut broken = stub_provider("broken");
+    broken.expect_fetch_games().times(1).returning(|| {
+        Err(Error::InternalServerErrorWithContext(
+            "upstream down".into(),
+        ))
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(broken) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("broken")).await;
+
+    // assert
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn return_not_found_for_unknown_provider() {
+    // arrange
+    let service = StreamsService::new(
+        Arc::new(MockStreamsRepository::new()) as DynStreamsRepository,
+        Arc::new(StreamProviderRegistry::new()),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let result = service.get_game(String::from("nope"), 1).await;
+
+    // assert
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn coalesce_concurrent_refreshes_into_one_upstream_fetch() {
+    // arrange
+    let mut repository = locked_repository();
+    repository
+        .expect_replace_games()
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut slow = stub_provider("slow");
+    slow.expect_fetch_games().times(1).returning(|| {
+        // hold the refresh open long enough for the other callers to pile up on it
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        Ok(vec![stub_game(1, "Football")])
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(slow) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let results =
+        futures::future::join_all((0..5).map(|_| service.refresh_provider(String::from("slow"))))
+            .await;
+
+    // assert
+    for result in results {
+        assert_eq!(result.unwrap(), 1);
+    }
+}
+
+#[tokio::test]
+async fn serve_cached_games_when_another_instance_holds_the_lock() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_try_acquire_refresh_lock()
+        .times(1)
+        .returning(|_, _, _| Ok(false));
+    repository.expect_release_refresh_lock().times(0);
+    repository.expect_replace_games().times(0);
+    repository
+        .expect_get_games()
+        .with(eq("busy"))
+        .times(1)
+        .returning(|_| Ok(vec![stub_game(1, "Football"), stub_game(2, "Hockey")]));
+
+    let mut busy = stub_provider("busy");
+    busy.expect_fetch_games().times(0);
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(busy) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("busy")).await;
+
+    // assert
+    assert_eq!(result.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn filter_games_by_status_and_search() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        // countdowns are worked out against the listing clock, starting off it keeps them exact
+        let started = listing_clock() - 600;
+        Ok(vec![
+            Game {
+                start_time: started,
+                end_time: started + 2 * 60 * 60,
+                ..game_starting_in(1, "Lakers vs Celtics", -600)
+            },
+            game_starting_in(2, "Lakers vs Bulls", 3600),
+            game_starting_in(3, "Knicks vs Heat", -300),
+            game_starting_in(4, "Lakers vs Heat", -3 * 60 * 60),
+        ])
+    });
+
+    let service = cached_games_service(repository);
+
+    // act
+    let response = service
+        .get_all_games(GameListQuery {
+            status: Some(GameStatus::Live),
+            q: Some(String::from("lakers")),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.categories.len(), 1);
+    let games = &response.categories[0].games;
+    assert_eq!(games.len(), 1);
+    assert_eq!(games[0].id, 1);
+    assert_eq!(games[0].status, GameStatus::Live);
+    assert!(games[0].starts_in_seconds <= -600);
+    assert!(response.next_cursor.is_none());
+}
+
+#[tokio::test]
+async fn serve_posters_through_the_image_proxy() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_games()
+        .returning(|_| Ok(vec![stub_game(1, "Football")]));
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(stub_provider("cached")) as DynStreamProvider);
+
+    let mut images = MockImagesServiceTrait::new();
+    images
+        .expect_proxied_url()
+        .with(eq("https://example.com/poster.png"))
+        .returning(|_| String::from("/api/v1/images?src=poster"));
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        Arc::new(images) as DynImagesService,
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let response = service
+        .get_all_games(GameListQuery::default())
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(
+        response.categories[0].games[0].poster,
+        "/api/v1/images?src=poster"
+    );
+}
+
+#[tokio::test]
+async fn page_through_games_with_cursor() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        Ok(vec![
+            game_starting_in(1, "a", 500),
+            game_starting_in(2, "b", 100),
+            game_starting_in(3, "c", 400),
+            game_starting_in(4, "d", 200),
+            game_starting_in(5, "e", 300),
+        ])
+    });
+
+    let service = cached_games_service(repository);
+
+    // act
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    loop {
+        let response = service
+            .get_all_games(GameListQuery {
+                sort: GameSort::StartDesc,
+                limit: Some(2),
+                cursor,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        seen.extend(response.categories[0].games.iter().map(|g| g.id));
+        cursor = response.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    // assert
+    assert_eq!(seen, vec![1, 3, 5, 4, 2]);
+}
+
+#[tokio::test]
+async fn page_through_games_sharing_an_id_across_providers() {
+    // arrange
+    let game = game_starting_in(1, "a", 100);
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(move |provider| {
+        Ok(vec![Game {
+            name: format!("{} game", provider),
+            ..game.clone()
+        }])
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(stub_provider("alpha")) as DynStreamProvider);
+    providers.register(Arc::new(stub_provider("beta")) as DynStreamProvider);
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let mut seen = Vec::new();
+    let mut cursor = None;
+    loop {
+        let response = service
+            .get_all_games(GameListQuery {
+                limit: Some(1),
+                cursor,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        seen.extend(
+            response
+                .categories
+                .iter()
+                .flat_map(|c| c.games.iter().map(|g| g.name.clone())),
+        );
+        cursor = response.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    // assert
+    assert_eq!(seen, vec!["alpha game", "beta game"]);
+}
+
+#[tokio::test]
+async fn reject_a_cursor_made_for_another_sort() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        Ok(vec![
+            game_starting_in(1, "a", 100),
+            game_starting_in(2, "b", 200),
+        ])
+    });
+
+    let service = cached_games_service(repository);
+    let first_page = service
+        .get_all_games(GameListQuery {
+            sort: GameSort::Name,
+            limit: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // act
+    let result = service
+        .get_all_games(GameListQuery {
+            sort: GameSort::Start,
+            limit: Some(1),
+            cursor: first_page.next_cursor,
+            ..Default::default()
+        })
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn use_the_start_time_index_for_time_windows() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().times(0);
+    repository
+        .expect_get_games_in_window()
+        .with(eq("cached"), eq(1000), eq(i64::MAX))
+        .times(1)
+        .returning(|_, _, _| Ok(vec![stub_game(1, "Football")]));
+
+    let service = cached_games_service(repository);
+
+    // act
+    let response = service
+        .get_all_games(GameListQuery {
+            from: Some(1000),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(response.categories[0].games.len(), 1);
+}
+
+#[tokio::test]
+async fn reject_a_window_that_ends_before_it_starts() {
+    // arrange
+    let service = cached_games_service(MockStreamsRepository::new());
+
+    // act
+    let result = service
+        .get_all_games(GameListQuery {
+            from: Some(2000),
+            to: Some(1000),
+            ..Default::default()
+        })
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn render_cached_games_as_calendar_events() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        Ok(vec![Game {
+            name: String::from("Lakers vs. Celtics, Game 7"),
+            ..stub_game(42, "Basketball")
+        }])
+    });
+
+    let service = cached_games_service(repository);
+
+    // act
+    let calendar = service
+        .get_calendar(
+            GameListQuery::default(),
+            Some(String::from("https://example.com/")),
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert!(calendar.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    assert!(calendar.contains("UID:cached-42@streams\r\n"));
+    assert!(calendar.contains("DTSTART:20251011T230000Z\r\n"));
+    assert!(calendar.contains("SUMMARY:Lakers vs. Celtics\\, Game 7\r\n"));
+    assert!(calendar.contains("CATEGORIES:Basketball\r\n"));
+    assert!(calendar.contains("URL:https://example.com/streams/cached/42\r\n"));
+}
+
+#[tokio::test]
+async fn list_only_current_games_in_the_playlist() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        Ok(vec![
+            game_starting_in(1, "Live \"Game\"", -600),
+            game_starting_in(2, "Finished Game", -5 * 60 * 60),
+        ])
+    });
+
+    let service = cached_games_service(repository);
+    let feed = IptvFeed {
+        base_url: String::from("https://api.example.com"),
+        user: String::from("user123"),
+        key: String::from("feedkey"),
+    };
+
+    // act
+    let playlist = service
+        .get_playlist(GameListQuery::default(), feed)
+        .await
+        .unwrap();
+
+    // assert
+    let lines: Vec<&str> = playlist.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(
+        lines[0].starts_with("#EXTM3U url-tvg=\"https://api.example.com/api/v1/streams/guide.xml?")
+    );
+    assert!(lines[1].contains("tvg-id=\"cached-1\""));
+    assert!(lines[1].contains("tvg-logo=\"https://example.com/poster.png\""));
+    assert!(lines[1].contains("group-title=\"Football\""));
+    assert!(lines[1].contains("tvg-name=\"Live 'Game'\""));
+    assert_eq!(
+        lines[2],
+        "https://api.example.com/api/v1/streams/cached/1/play?user=user123&key=feedkey"
+    );
+}
+
+#[tokio::test]
+async fn build_an_xmltv_guide_from_game_times() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        Ok(vec![Game {
+            name: String::from("Cats & Dogs"),
+            start_time: 4102444800,
+            end_time: 4102452000,
+            ..stub_game(7, "Hockey")
+        }])
+    });
+
+    let service = cached_games_service(repository);
+
+    // act
+    let guide = service.get_guide(GameListQuery::default()).await.unwrap();
+
+    // assert
+    assert!(guide.contains("<channel id=\"cached-7\">"));
+    assert!(guide.contains("<display-name>Cats &amp; Dogs</display-name>"));
+    assert!(guide.contains(
+        "<programme start=\"21000101000000 +0000\" stop=\"21000101020000 +0000\" channel=\"cached-7\">"
+    ));
+    assert!(guide.contains("<category>Hockey</category>"));
+}
+
+#[tokio::test]
+async fn version_the_listing_by_each_providers_last_fetch() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_last_fetch_time()
+        .with(eq("fetched"))
+        .returning(|_| Ok(Some(1760223600)));
+    repository
+        .expect_get_last_fetch_time()
+        .with(eq("never"))
+        .returning(|_| Ok(None));
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(stub_provider("fetched")) as DynStreamProvider);
+    providers.register(Arc::new(stub_provider("never")) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let version = service.get_listing_version().await.unwrap();
+
+    // assert
+    assert!(version.starts_with("streams-1760223600-0-"));
+}
+
+#[tokio::test]
+async fn fall_back_to_the_next_source_when_one_is_dead() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider.expect_get_game_by_id().returning(|id| {
+        Ok(game_with_sources(
+            id,
+            &["https://dead/embed", "https://alive/embed"],
+        ))
+    });
+    provider
+        .expect_fetch_video_link()
+        .with(eq("https://dead/embed"))
+        .times(1)
+        .returning(|_| Err(anyhow::anyhow!("404")));
+    provider
+        .expect_fetch_video_link()
+        .with(eq("https://alive/embed"))
+        .times(1)
+        .returning(|_| Ok(String::from("https://alive/index.m3u8")));
+    provider.expect_fetch_game().times(0);
+
+    let service = provider_service(provider);
+
+    // act
+    let resolved = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Auto)
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(resolved.index, 1);
+    assert_eq!(resolved.label, "Source 2");
+    assert_eq!(resolved.link, "https://alive/index.m3u8");
+}
+
+#[tokio::test]
+async fn ask_upstream_for_more_sources_when_every_cached_one_is_dead() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider
+        .expect_get_game_by_id()
+        .returning(|id| Ok(game_with_sources(id, &["https://dead/embed"])));
+    provider.expect_fetch_game().times(1).returning(|id| {
+        Ok(game_with_sources(
+            id,
+            &["https://dead/embed", "https://backup/embed"],
+        ))
+    });
+    // the dead one isn't tried a second time
+    provider
+        .expect_fetch_video_link()
+        .with(eq("https://dead/embed"))
+        .times(1)
+        .returning(|_| Err(anyhow::anyhow!("404")));
+    provider
+        .expect_fetch_video_link()
+        .with(eq("https://backup/embed"))
+        .times(1)
+        .returning(|_| Ok(String::from("https://backup/index.m3u8")));
+
+    let service = provider_service(provider);
+
+    // act
+    let resolved = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Auto)
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(resolved.index, 1);
+    assert_eq!(resolved.link, "https://backup/index.m3u8");
+}
+
+#[tokio::test]
+async fn remember_games_where_no_source_resolved() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider
+        .expect_get_game_by_id()
+        .returning(|id| Ok(game_with_sources(id, &["https://dead/embed"])));
+    provider
+        .expect_fetch_game()
+        .times(1)
+        .returning(|id| Ok(game_with_sources(id, &["https://dead/embed"])));
+    provider
+        .expect_fetch_video_link()
+        .times(1)
+        .returning(|_| Err(anyhow::anyhow!("404")));
+
+    // a tiny resolved link cache so the second request sees what the first one stored
+    let cached: Arc<Mutex<HashMap<String, ResolvedLink>>> = Arc::default();
+    let mut repository = MockStreamsRepository::new();
+    let reads = cached.clone();
+    repository
+        .expect_get_resolved_link()
+        .returning(move |_, key| Ok(reads.lock().unwrap().get(key).cloned()));
+    let writes = cached.clone();
+    repository
+        .expect_set_resolved_link()
+        .returning(move |_, key, resolved, _| {
+            writes
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), resolved.clone());
+            Ok(())
+        });
+
+    let service = provider_service_with(provider, repository);
+
+    // act
+    let first = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Auto)
+        .await;
+    let second = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Auto)
+        .await;
+
+    // assert
+    assert!(matches!(first, Err(Error::NotFound(_))));
+    assert!(matches!(second, Err(Error::NotFound(_))));
+    assert!(matches!(
+        cached.lock().unwrap().get("game:1"),
+        Some(ResolvedLink::Failed(_))
+    ));
+}
+
+#[tokio::test]
+async fn return_not_found_for_a_source_the_game_does_not_have() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider
+        .expect_get_game_by_id()
+        .returning(|id| Ok(game_with_sources(id, &["https://only/embed"])));
+    provider
+        .expect_fetch_game()
+        .times(1)
+        .returning(|id| Ok(game_with_sources(id, &["https://only/embed"])));
+    provider.expect_fetch_video_link().times(0);
+
+    let service = provider_service(provider);
+
+    // act
+    let result = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Index(3))
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn reuse_a_cached_resolved_link_without_scraping() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider
+        .expect_get_game_by_id()
+        .returning(|id| Ok(game_with_sources(id, &["https://cached/embed"])));
+    provider.expect_fetch_video_link().times(0);
+
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_resolved_link()
+        .with(eq("sources"), eq("https://cached/embed"))
+        .times(1)
+        .returning(|_, _| {
+            Ok(Some(ResolvedLink::Playable(String::from(
+                "https://cached/index.m3u8",
+            ))))
+        });
+    repository.expect_set_resolved_link().times(0);
+
+    let service = provider_service_with(provider, repository);
+
+    // act
+    let resolved = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Auto)
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(resolved.link, "https://cached/index.m3u8");
+}
+
+#[tokio::test]
+async fn remember_sources_that_failed_to_resolve() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider
+        .expect_get_game_by_id()
+        .returning(|id| Ok(game_with_sources(id, &["https://dead/embed"])));
+    provider
+        .expect_fetch_video_link()
+        .times(1)
+        .returning(|_| Err(anyhow::anyhow!("video link not found in iframe")));
+
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_resolved_link()
+        .returning(|_, _| Ok(None));
+    repository
+        .expect_set_resolved_link()
+        .withf(|_, link, resolved, ttl| {
+            link == "https://dead/embed"
+                && matches!(resolved, ResolvedLink::Failed(_))
+                && *ttl <= 60
+        })
+        .times(1)
+        .returning(|_, _, _, _| Ok(()));
+
+    let service = provider_service_with(provider, repository);
+
+    // act
+    let result = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Index(0))
+        .await;
+
+    // assert
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn invalidate_the_resolved_link_of_every_source() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider.expect_get_game_by_id().returning(|id| {
+        Ok(game_with_sources(
+            id,
+            &["https://a/embed", "https://b/embed"],
+        ))
+    });
+
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_delete_resolved_links()
+        .withf(|provider, links| {
+            provider == "sources"
+                && links
+                    == [
+                        String::from("https://a/embed"),
+                        String::from("https://b/embed"),
+                        String::from("game:1"),
+                    ]
+        })
+        .times(1)
+        .returning(|_, _| Ok(1));
+
+    let service = provider_service_with(provider, repository);
+
+    // act
+    let invalidated = service
+        .invalidate_resolved_links(String::from("sources"), 1)
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(invalidated, 1);
+}
+
+#[tokio::test]
+async fn return_unavailable_without_caching_when_the_embed_host_is_down() {
+    // arrange
+    let mut provider = stub_provider("sources");
+    provider
+        .expect_get_game_by_id()
+        .returning(|id| Ok(game_with_sources(id, &["https://down/embed"])));
+    provider
+        .expect_fetch_game()
+        .returning(|id| Ok(game_with_sources(id, &["https://down/embed"])));
+    provider.expect_fetch_video_link().times(1).returning(|_| {
+        Err(UpstreamUnavailable {
+            host: String::from("down"),
+            retry_in_secs: 30,
+        }
+        .into())
+    });
+
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_resolved_link()
+        .returning(|_, _| Ok(None));
+    repository.expect_set_resolved_link().times(0);
+
+    let service = provider_service_with(provider, repository);
+
+    // act
+    let result = service
+        .resolve_video_link(String::from("sources"), 1, SourceSelection::Auto)
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::ServiceUnavailable(_))));
+}
+
+// ice hockey under two provider names plus basketball, which sorts first
+fn hockey_taxonomy(provider: &str) -> CategoryTaxonomy {
+    CategoryTaxonomy::new(
+        vec![
+            stub_category("ice-hockey", "Ice Hockey", 40),
+            stub_category("basketball", "Basketball", 10),
+        ],
+        &[
+            stub_alias(provider, "Hockey", "ice-hockey"),
+            stub_alias(provider, "NHL", "ice-hockey"),
+        ],
+    )
+}
+
+#[tokio::test]
+async fn group_the_listing_by_canonical_category() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        Ok(vec![
+            stub_game(1, "Curling"),
+            stub_game(2, "Hockey"),
+            stub_game(3, "NHL"),
+            stub_game(4, "Basketball"),
+        ])
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(stub_provider("cached")) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        categories_with(hockey_taxonomy("cached")),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let categories = service
+        .get_all_games(GameListQuery::default())
+        .await
+        .unwrap()
+        .categories;
+
+    // assert
+    let slugs: Vec<&str> = categories.iter().map(|c| c.slug.as_str()).collect();
+    assert_eq!(slugs, vec!["basketball", "ice-hockey", "curling"]);
+
+    assert_eq!(categories[1].category, "Ice Hockey");
+    assert_eq!(categories[1].icon.as_deref(), Some("*"));
+    assert_eq!(categories[1].games.len(), 2);
+    assert!(
+        categories[1]
+            .games
+            .iter()
+            .all(|g| g.category == "Ice Hockey" && g.category_slug == "ice-hockey")
+    );
+
+    // nobody has mapped it yet so it keeps the provider's name
+    assert_eq!(categories[2].category, "Curling");
+    assert_eq!(categories[2].icon, None);
+}
+
+#[tokio::test]
+async fn filter_by_category_name_through_the_slug_index() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository
+        .expect_get_games_by_category()
+        .with(eq("cached"), eq("ice-hockey"))
+        .times(1)
+        .returning(|_, _| {
+            Ok(vec![Game {
+                category_slug: String::from("ice-hockey"),
+                ..stub_game(1, "Ice Hockey")
+            }])
+        });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(stub_provider("cached")) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        categories_with(hockey_taxonomy("cached")),
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let categories = service
+        .get_all_games(GameListQuery {
+            category: Some(String::from("Ice Hockey")),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .categories;
+
+    // assert
+    assert_eq!(categories.len(), 1);
+    assert_eq!(categories[0].slug, "ice-hockey");
+}
+
+#[tokio::test]
+async fn normalise_categories_on_refresh_and_record_unknown_ones() {
+    // arrange
+    let mut repository = locked_repository();
+    repository
+        .expect_replace_games()
+        .withf(|_, games, _| {
+            games[0].category == "Ice Hockey"
+                && games[0].category_slug == "ice-hockey"
+                && games[1].category == "Curling"
+                && games[1].category_slug == "curling"
+        })
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut healthy = stub_provider("healthy");
+    healthy.expect_fetch_games().times(1).returning(|| {
+        Ok(vec![
+            stub_game(1, "NHL"),
+            stub_game(2, "Curling"),
+            stub_game(3, "Curling"),
+        ])
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(healthy) as DynStreamProvider);
+
+    let taxonomy = Arc::new(hockey_taxonomy("healthy"));
+    let mut categories = MockCategoriesServiceTrait::new();
+    categories
+        .expect_taxonomy()
+        .returning(move || taxonomy.clone());
+    categories
+        .expect_record_unmapped()
+        .with(eq("healthy"), eq(vec![String::from("Curling")]))
+        .times(1)
+        .returning(|_, _| ());
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        Arc::new(categories) as DynCategoriesService,
+        no_team_aliases(),
+        quiet_history(),
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("healthy")).await;
+
+    // assert
+    assert_eq!(result.unwrap(), 3);
+}
+
+fn united_aliases() -> TeamAliases {
+    TeamAliases::new(&[TeamAlias {
+        alias: String::from("man utd"),
+        team: String::from("Manchester United"),
+    }])
+}
+
+#[tokio::test]
+async fn read_teams_out_of_names_on_refresh() {
+    // arrange
+    let mut repository = locked_repository();
+    repository
+        .expect_replace_games()
+        .withf(|_, games, _| {
+            games[0].league.as_deref() == Some("Premier League")
+                && games[0].home_team.as_deref() == Some("Manchester United")
+                && games[0].away_team.as_deref() == Some("Chelsea")
+                && games[1].home_team.is_none()
+                && games[1].away_team.is_none()
+        })
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+
+    let mut healthy = stub_provider("healthy");
+    healthy.expect_fetch_games().times(1).returning(|| {
+        Ok(vec![
+            Game {
+                name: String::from("Premier League - Man Utd vs. Chelsea"),
+                ..stub_game(1, "Soccer")
+            },
+            stub_game(2, "Soccer"),
+        ])
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(healthy) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        teams_with(united_aliases()),
+        quiet_history(),
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("healthy")).await;
+
+    // assert
+    assert_eq!(result.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn filter_by_team_through_the_aliases() {
+    // arrange
+    let mut repository = MockStreamsRepository::new();
+    repository.expect_get_games().returning(|_| {
+        Ok(vec![
+            Game {
+                name: String::from("Man Utd vs Chelsea"),
+                ..stub_game(1, "Soccer")
+            },
+            Game {
+                name: String::from("Arsenal @ Manchester City"),
+                ..stub_game(2, "Soccer")
+            },
+            Game {
+                name: String::from("WWE: Monday Night Raw"),
+                ..stub_game(3, "Wrestling")
+            },
+        ])
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(stub_provider("cached")) as DynStreamProvider);
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        teams_with(united_aliases()),
+        quiet_history(),
+    );
+
+    let ids_for = |team: &str| {
+        let service = service.clone();
+        let query = GameListQuery {
+            team: Some(String::from(team)),
+            ..Default::default()
+        };
+        async move {
+            service
+                .get_all_games(query)
+                .await
+                .unwrap()
+                .categories
+                .into_iter()
+                .flat_map(|c| c.games)
+                .map(|g| g.id)
+                .collect::<Vec<i64>>()
+        }
+    };
+
+    // act
+    let united = ids_for("MAN UTD").await;
+    let manchester = ids_for("manchester").await;
+    let arsenal = ids_for("arsenal").await;
+    let raw = ids_for("raw").await;
+
+    // assert
+    assert_eq!(united, vec![1]);
+    assert_eq!(manchester, vec![1, 2]);
+    assert_eq!(arsenal, vec![2]);
+    // no teams in the name so the name itself is matched
+    assert_eq!(raw, vec![3]);
+}
+
+#[tokio::test]
+async fn archive_what_a_refresh_cached() {
+    // arrange
+    let mut repository = locked_repository();
+    repository
+        .expect_replace_games()
+        .returning(|_, _, _| Ok(()));
+
+    let mut healthy = stub_provider("healthy");
+    healthy
+        .expect_fetch_games()
+        .times(1)
+        .returning(|| Ok(vec![stub_game(1, "NHL"), stub_game(2, "Basketball")]));
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(healthy) as DynStreamProvider);
+
+    let mut history = MockHistoryServiceTrait::new();
+    history
+        .expect_archive()
+        .withf(|provider, games, seen_at| {
+            provider == "healthy"
+                && games.len() == 2
+                && games[0].category_slug == "ice-hockey"
+                && *seen_at > 0
+        })
+        .times(1)
+        .returning(|_, _, _| ());
+
+    let service = StreamsService::new(
+        Arc::new(repository) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        categories_with(hockey_taxonomy("healthy")),
+        no_team_aliases(),
+        Arc::new(history) as DynHistoryService,
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("healthy")).await;
+
+    // assert
+    assert_eq!(result.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn not_archive_anything_when_the_fetch_fails() {
+    // arrange
+    let mut dead = stub_provider("dead");
+    dead.expect_fetch_games().times(1).returning(|| {
+        Err(Error::InternalServerErrorWithContext(
+            "upstream down".into(),
+        ))
+    });
+
+    let mut providers = StreamProviderRegistry::new();
+    providers.register(Arc::new(dead) as DynStreamProvider);
+
+    let mut history = MockHistoryServiceTrait::new();
+    history.expect_archive().times(0);
+
+    let service = StreamsService::new(
+        Arc::new(locked_repository()) as DynStreamsRepository,
+        Arc::new(providers),
+        quiet_events(),
+        passthrough_images(),
+        default_categories(),
+        no_team_aliases(),
+        Arc::new(history) as DynHistoryService,
+    );
+
+    // act
+    let result = service.refresh_provider(String::from("dead")).await;
+
+    // assert
+    assert!(result.is_err());
+}
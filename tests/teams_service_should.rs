@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use api::{
+    database::team::{DynTeamsRepository, MockTeamsRepository, TeamAlias},
+    server::{
+        dtos::team_dto::UpsertTeamAliasDto,
+        error::Error,
+        services::team_services::{TeamsService, TeamsServiceTrait},
+    },
+};
+use mockall::predicate::*;
+
+fn stub_alias(alias: &str, team: &str) -> TeamAlias {
+    TeamAlias {
+        alias: String::from(alias),
+        team: String::from(team),
+    }
+}
+
+fn service(repository: MockTeamsRepository) -> TeamsService {
+    TeamsService::new(Arc::new(repository) as DynTeamsRepository)
+}
+
+#[tokio::test]
+async fn store_aliases_lowercased() {
+    // arrange
+    let mut repository = MockTeamsRepository::new();
+    repository
+        .expect_upsert_team_alias()
+        .with(eq(stub_alias("man utd", "Manchester United")))
+        .times(1)
+        .returning(|alias| Ok(alias.clone()));
+
+    // act
+    let alias = service(repository)
+        .upsert_alias(
+            String::from(" Man Utd "),
+            UpsertTeamAliasDto {
+                team: String::from("Manchester United"),
+            },
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(alias.alias, "man utd");
+    assert_eq!(alias.team, "Manchester United");
+}
+
+#[tokio::test]
+async fn not_alias_a_team_to_itself() {
+    // arrange
+    let mut repository = MockTeamsRepository::new();
+    repository.expect_upsert_team_alias().times(0);
+
+    // act
+    let result = service(repository)
+        .upsert_alias(
+            String::from("chelsea"),
+            UpsertTeamAliasDto {
+                team: String::from("Chelsea"),
+            },
+        )
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::BadRequest(_))));
+}
+
+#[tokio::test]
+async fn return_not_found_deleting_an_alias_that_does_not_exist() {
+    // arrange
+    let mut repository = MockTeamsRepository::new();
+    repository
+        .expect_delete_team_alias()
+        .with(eq("spurs"))
+        .times(1)
+        .returning(|_| Ok(false));
+
+    // act
+    let result = service(repository)
+        .delete_alias(String::from("Spurs"))
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::NotFound(_))));
+}
+
+#[tokio::test]
+async fn reload_the_aliases_after_one_is_saved() {
+    // arrange
+    let mut repository = MockTeamsRepository::new();
+    // loaded once before the save and once after, the lookup in between uses the copy
+    repository
+        .expect_get_team_aliases()
+        .times(1)
+        .returning(|| Ok(vec![]));
+    repository
+        .expect_get_team_aliases()
+        .times(1)
+        .returning(|| Ok(vec![stub_alias("man utd", "Manchester United")]));
+    repository
+        .expect_upsert_team_alias()
+        .times(1)
+        .returning(|alias| Ok(alias.clone()));
+
+    let service = service(repository);
+
+    // act
+    let before = service.aliases().await.canonical("Man Utd");
+    let cached = service.aliases().await.canonical("Man Utd");
+    service
+        .upsert_alias(
+            String::from("man utd"),
+            UpsertTeamAliasDto {
+                team: String::from("Manchester United"),
+            },
+        )
+        .await
+        .unwrap();
+    let after = service.aliases().await.canonical("Man Utd");
+
+    // assert
+    assert_eq!(before, "Man Utd");
+    assert_eq!(cached, "Man Utd");
+    assert_eq!(after, "Manchester United");
+}
@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+use api::server::{
+    dtos::health_dto::{BreakerState, HealthStatus},
+    services::upstream_services::{BreakerSettings, UpstreamsService, UpstreamsServiceTrait},
+};
+
+const HOST: &str = "api.ppvs.su";
+
+fn quick_breaker() -> UpstreamsService {
+    UpstreamsService::new(
+        BreakerSettings {
+            window: 10,
+            min_requests: 4,
+            error_rate: 0.5,
+            open_for: Duration::from_millis(50),
+            slow_call: Duration::from_secs(5),
+        },
+        vec![String::from(HOST)],
+    )
+}
+
+fn fail(service: &UpstreamsService, times: usize) {
+    for _ in 0..times {
+        service.acquire(HOST).unwrap();
+        service.record(HOST, false, Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn stay_closed_below_the_minimum_number_of_calls() {
+    // arrange
+    let service = quick_breaker();
+
+    // act
+    fail(&service, 3);
+
+    // assert
+    assert!(service.acquire(HOST).is_ok());
+    assert_eq!(service.health()[HOST].state, BreakerState::Closed);
+}
+
+#[test]
+fn open_and_reject_once_the_error_rate_is_too_high() {
+    // arrange
+    let service = quick_breaker();
+    service.record(HOST, true, Duration::from_millis(20));
+    service.record(HOST, true, Duration::from_millis(20));
+
+    // act
+    fail(&service, 2);
+    let rejected = service.acquire(HOST);
+
+    // assert
+    let unavailable = rejected.unwrap_err();
+    assert_eq!(unavailable.host, HOST);
+    assert!(unavailable.retry_in_secs >= 1);
+
+    let health = &service.health()[HOST];
+    assert_eq!(health.state, BreakerState::Open);
+    assert_eq!(health.status, HealthStatus::Unhealthy);
+    assert_eq!(health.error_rate, 0.5);
+    assert_eq!(health.requests, 4);
+
+    // other hosts aren't affected
+    assert!(service.acquire("vidlink.pro").is_ok());
+}
+
+#[test]
+fn count_slow_calls_as_failures() {
+    // arrange
+    let service = quick_breaker();
+
+    // act
+    for _ in 0..4 {
+        service.record(HOST, true, Duration::from_secs(6));
+    }
+
+    // assert
+    assert!(service.acquire(HOST).is_err());
+}
+
+#[tokio::test]
+async fn let_one_probe_through_and_close_when_it_works() {
+    // arrange
+    let service = quick_breaker();
+    fail(&service, 4);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    // act
+    let probe = service.acquire(HOST);
+    let during_probe = service.acquire(HOST);
+    service.record(HOST, true, Duration::from_millis(20));
+
+    // assert
+    assert!(probe.is_ok());
+    assert!(during_probe.is_err());
+    assert!(service.acquire(HOST).is_ok());
+
+    let health = &service.health()[HOST];
+    assert_eq!(health.state, BreakerState::Closed);
+    assert_eq!(health.requests, 0);
+}
+
+#[tokio::test]
+async fn reopen_when_the_probe_fails() {
+    // arrange
+    let service = quick_breaker();
+    fail(&service, 4);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+
+    // act
+    service.acquire(HOST).unwrap();
+    service.record(HOST, false, Duration::from_millis(20));
+
+    // assert
+    assert!(service.acquire(HOST).is_err());
+    assert_eq!(service.health()[HOST].state, BreakerState::Open);
+}
+
+#[test]
+fn leave_hosts_without_a_breaker_alone() {
+    // arrange
+    let service = quick_breaker();
+
+    // act
+    for _ in 0..10 {
+        service.acquire("anything.example.com").unwrap();
+        service.record("anything.example.com", false, Duration::from_millis(20));
+    }
+
+    // assert
+    assert!(!service.tracks("anything.example.com"));
+    assert!(service.acquire("anything.example.com").is_ok());
+    assert!(service.health().is_empty());
+}
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+
+use api::{
+    database::watchlist::{DynWatchlistRepository, MockWatchlistRepository, WatchlistItem},
+    server::{
+        dtos::watchlist_dto::{AddWatchlistItemDto, UpdateWatchlistItemDto, WatchlistKind},
+        error::Error,
+        services::watchlist_services::{WatchlistService, WatchlistServiceTrait},
+    },
+};
+use mockall::predicate::*;
+
+fn stub_item(kind: &str, media_id: &str, position: i64, note: Option<&str>) -> WatchlistItem {
+    WatchlistItem {
+        id: format!("{}-{}", kind, media_id),
+        kind: String::from(kind),
+        media_id: String::from(media_id),
+        note: note.map(String::from),
+        position,
+        ..Default::default()
+    }
+}
+
+fn service(repository: MockWatchlistRepository) -> WatchlistService {
+    WatchlistService::new(Arc::new(repository) as DynWatchlistRepository)
+}
+
+#[tokio::test]
+async fn add_to_the_end_of_the_list_when_no_position_is_given() {
+    // arrange
+    let mut repository = MockWatchlistRepository::new();
+    repository.expect_get_watchlist().returning(|_| {
+        Ok(vec![
+            stub_item("movie", "1311031", 0, None),
+            stub_item("tv", "1396", 4, None),
+        ])
+    });
+    repository
+        .expect_add_watchlist_item()
+        .with(
+            eq("IRFa~VaY2b"),
+            eq("tv"),
+            eq("94605"),
+            eq(Some(String::from("after breaking bad"))),
+            eq(5),
+        )
+        .times(1)
+        .returning(|_, kind, media_id, note, position| {
+            Ok(Some(WatchlistItem {
+                kind: kind.to_string(),
+                media_id: media_id.to_string(),
+                note,
+                position,
+                ..Default::default()
+            }))
+        });
+
+    // act
+    let item = service(repository)
+        .add_item(
+            String::from("IRFa~VaY2b"),
+            AddWatchlistItemDto {
+                kind: WatchlistKind::Tv,
+                media_id: String::from(" 94605 "),
+                note: Some(String::from(" after breaking bad ")),
+                position: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(item.kind, "tv");
+    assert_eq!(item.position, 5);
+}
+
+#[tokio::test]
+async fn reject_something_already_on_the_list() {
+    // arrange
+    let mut repository = MockWatchlistRepository::new();
+    repository.expect_get_watchlist().returning(|_| Ok(vec![]));
+    repository
+        .expect_add_watchlist_item()
+        .returning(|_, _, _, _, _| Ok(None));
+
+    // act
+    let result = service(repository)
+        .add_item(
+            String::from("IRFa~VaY2b"),
+            AddWatchlistItemDto {
+                kind: WatchlistKind::Movie,
+                media_id: String::from("1311031"),
+                note: None,
+                position: None,
+            },
+        )
+        .await;
+
+    // assert
+    assert!(matches!(result, Err(Error::ObjectConflict(_))));
+}
+
+#[tokio::test]
+async fn clear_the_note_with_an_empty_one_and_keep_the_position() {
+    // arrange
+    let mut repository = MockWatchlistRepository::new();
+    repository
+        .expect_get_watchlist_item()
+        .with(eq("IRFa~VaY2b"), eq("movie-1311031"))
+        .returning(|_, _| Ok(Some(stub_item("movie", "1311031", 3, Some("maybe")))));
+    repository
+        .expect_update_watchlist_item()
+        .withf(|item| item.note.is_none() && item.position == 3)
+        .times(1)
+        .returning(|item| Ok(item.clone()));
+
+    // act
+    let item = service(repository)
+        .update_item(
+            String::from("IRFa~VaY2b"),
+            String::from("movie-1311031"),
+            UpdateWatchlistItemDto {
+                note: Some(String::from("  ")),
+                position: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(item.note, None);
+}
+
+#[tokio::test]
+async fn import_a_plain_list_of_ids_skipping_what_is_already_there() {
+    // arrange
+    let mut repository = MockWatchlistRepository::new();
+    repository
+        .expect_get_watchlist()
+        .returning(|_| Ok(vec![stub_item("movie", "1311031", 0, None)]));
+    repository
+        .expect_add_watchlist_item()
+        .with(eq("IRFa~VaY2b"), eq("movie"), eq("550"), eq(None), eq(1))
+        .times(1)
+        .returning(|_, _, _, _, _| Ok(Some(stub_item("movie", "550", 1, None))));
+    repository
+        .expect_add_watchlist_item()
+        .with(
+            eq("IRFa~VaY2b"),
+            eq("tv"),
+            eq("1396"),
+            eq(Some(String::from("from the start, again"))),
+            eq(2),
+        )
+        .times(1)
+        .returning(|_, _, _, _, _| Ok(Some(stub_item("tv", "1396", 2, None))));
+
+    // act
+    let imported = service(repository)
+        .import_csv(
+            String::from("IRFa~VaY2b"),
+            String::from(
+                "id,kind,note\n1311031\n550,movie\n1396,tv,\"from the start, again\"\n550\n42,show\n",
+            ),
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(imported.added, 2);
+    assert_eq!(imported.skipped, 2);
+    assert_eq!(imported.errors, vec!["line 6: unknown kind \"show\""]);
+}
+
+#[tokio::test]
+async fn report_errors_on_the_line_they_are_in_the_file() {
+    // arrange
+    let mut repository = MockWatchlistRepository::new();
+    repository.expect_get_watchlist().returning(|_| Ok(vec![]));
+    repository
+        .expect_add_watchlist_item()
+        .times(1)
+        .returning(|_, _, _, _, _| Ok(Some(stub_item("tv", "1396", 0, None))));
+
+    // act
+    let imported = service(repository)
+        .import_csv(
+            String::from("IRFa~VaY2b"),
+            String::from("id,kind,note\n\n1396,tv,\"watch it\nagain\"\n42,show\n"),
+        )
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(imported.added, 1);
+    assert_eq!(imported.errors, vec!["line 5: unknown kind \"show\""]);
+}
+
+#[tokio::test]
+async fn export_in_the_format_imports_take() {
+    // arrange
+    let mut repository = MockWatchlistRepository::new();
+    repository.expect_get_watchlist().returning(|_| {
+        Ok(vec![
+            stub_item("movie", "1311031", 0, None),
+            stub_item("tv", "1396", 1, Some("from the start, again")),
+        ])
+    });
+
+    // act
+    let csv = service(repository)
+        .export_csv(String::from("IRFa~VaY2b"))
+        .await
+        .unwrap();
+
+    // assert
+    assert_eq!(
+        csv,
+        "id,kind,note\n1311031,movie,\n1396,tv,\"from the start, again\"\n"
+    );
+}